@@ -0,0 +1,166 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// `automation` config section: a transpose lane played back alongside a backing track
+#[derive(Debug, serde::Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct AutomationConfig {
+    /// Lane file used by `automation start` and the MQTT switch
+    pub file: Option<String>,
+    /// Post each key change to the VRChat chatbox
+    pub chatbox: bool,
+}
+
+/// One key change of a lane: at `at` after the start, set the transpose to `transpose`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LaneEvent {
+    pub at: Duration,
+    pub transpose: i32,
+}
+
+struct Playback {
+    file: String,
+    events: Vec<LaneEvent>,
+    started: Instant,
+    next: usize,
+}
+
+static PLAYBACK: Mutex<Option<Playback>> = Mutex::new(None);
+// Bumped on start/stop and every applied key change so MQTT can publish the progress
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+pub fn is_active() -> bool {
+    PLAYBACK.lock().map(|p| p.is_some()).unwrap_or(false)
+}
+
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+/// Parse a time like `95`, `1:35`, `1:35.5` or `0:01:35` into a duration
+pub fn parse_time(text: &str) -> Option<Duration> {
+    let mut secs = 0.0;
+    for part in text.split(':') {
+        let value: f64 = part.parse().ok()?;
+        if !value.is_finite() || value < 0.0 {
+            return None;
+        }
+        secs = secs * 60.0 + value;
+    }
+    Some(Duration::from_secs_f64(secs))
+}
+
+/// Parse a lane file: one `<time> <transpose>` pair per line, `#` starts a comment.
+/// Events are returned sorted by time.
+pub fn parse_lane(text: &str) -> Result<Vec<LaneEvent>, String> {
+    let mut events = Vec::new();
+    for (idx, raw) in text.lines().enumerate() {
+        let line = raw.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let (Some(time), Some(value), None) = (fields.next(), fields.next(), fields.next()) else {
+            return Err(format!("line {}: expected '<time> <transpose>'", idx + 1));
+        };
+        let at = parse_time(time).ok_or_else(|| format!("line {}: invalid time '{}'", idx + 1, time))?;
+        let transpose = value
+            .trim_start_matches('+')
+            .parse::<i32>()
+            .map_err(|_| format!("line {}: invalid transpose '{}'", idx + 1, value))?;
+        events.push(LaneEvent { at, transpose });
+    }
+    events.sort_by_key(|e| e.at);
+    Ok(events)
+}
+
+/// Load a lane file and start playing it back from time 0 (restarts a running playback)
+pub fn start(file: &str) -> Result<usize, String> {
+    let text = std::fs::read_to_string(file).map_err(|e| format!("{}: {}", file, e))?;
+    let events = parse_lane(&text).map_err(|e| format!("{}: {}", file, e))?;
+    if events.is_empty() {
+        return Err(format!("{}: no key changes", file));
+    }
+    let count = events.len();
+    if let Ok(mut guard) = PLAYBACK.lock() {
+        *guard = Some(Playback { file: file.to_string(), events, started: Instant::now(), next: 0 });
+    }
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    log::info!(target: "app", "Automation started: {} ({} key changes)", file, count);
+    crate::general::now_playing::set(&crate::general::now_playing::lane_title(file), true);
+    Ok(count)
+}
+
+/// Start the lane configured in `automation.file`
+pub fn start_configured() -> Result<usize, String> {
+    match crate::get_config().automation.file.as_deref() {
+        Some(file) => start(file),
+        None => Err("no automation.file configured".to_string()),
+    }
+}
+
+/// Stop playback (the current transpose is kept)
+pub fn stop() {
+    let was_active = PLAYBACK.lock().map(|mut p| p.take().is_some()).unwrap_or(false);
+    if was_active {
+        GENERATION.fetch_add(1, Ordering::SeqCst);
+        log::info!(target: "app", "Automation stopped");
+    }
+}
+
+/// Playback position for `status`, e.g. `song.lane 1:05 (3/7)` or `off`
+pub fn progress() -> String {
+    let Ok(guard) = PLAYBACK.lock() else { return "off".to_string() };
+    match guard.as_ref() {
+        Some(p) => {
+            let secs = p.started.elapsed().as_secs();
+            format!("{} {}:{:02} ({}/{})", p.file, secs / 60, secs % 60, p.next, p.events.len())
+        }
+        None => "off".to_string(),
+    }
+}
+
+/// Apply all events that are due; returns false once the lane has finished
+fn apply_due(playback: &mut Playback) -> bool {
+    let elapsed = playback.started.elapsed();
+    while let Some(event) = playback.events.get(playback.next).copied() {
+        if event.at > elapsed {
+            break;
+        }
+        playback.next += 1;
+        GENERATION.fetch_add(1, Ordering::SeqCst);
+        let value = crate::set_transpose_semitones(event.transpose);
+        let secs = event.at.as_secs();
+        let text = format!("Automation {}:{:02}: transpose {:+}", secs / 60, secs % 60, value);
+        log::info!(target: "app", "{}", text);
+        if crate::get_config().automation.chatbox {
+            crate::remote::chatbox::send_chatbox(&text);
+        }
+    }
+    playback.next < playback.events.len()
+}
+
+/// Spawn the player thread that applies the key changes of a running lane on time
+pub fn spawn_automation_player() -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        while !crate::state().exit.load(Ordering::SeqCst) {
+            let finished = match PLAYBACK.lock() {
+                Ok(mut guard) => {
+                    let done = guard.as_mut().is_some_and(|playback| !apply_due(playback));
+                    if done {
+                        *guard = None;
+                    }
+                    done
+                }
+                Err(_) => false,
+            };
+            if finished {
+                GENERATION.fetch_add(1, Ordering::SeqCst);
+                log::info!(target: "app", "Automation finished");
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+    })
+}
@@ -1,11 +1,135 @@
-use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
-use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
-use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering};
+use std::sync::Mutex;
+use termcolor::Color;
+use crate::io::console::print_colored;
 
 // Connection status flags
-pub static OSC_LISTENER_RUNNING: AtomicBool = AtomicBool::new(false);
 static OSC_SENDER_COUNT: AtomicI32 = AtomicI32::new(0);
 static BANNER_PRINTED: AtomicBool = AtomicBool::new(false);
+// Where the OSC listener is bound, or why it couldn't bind (shown by `status` and MQTT)
+static OSC_LISTENER_STATUS: Mutex<String> = Mutex::new(String::new());
+static OSC_LISTENER_GENERATION: AtomicU64 = AtomicU64::new(0);
+// Bumped on every MIDI in/out state change (device connected, lost or back; published over MQTT)
+static MIDI_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Readiness of one subsystem in the startup report
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ComponentState {
+    Up,
+    /// Running, but not as configured (e.g. OSC listener on a fallback port)
+    Degraded,
+    Broken,
+    /// Disabled by config
+    Off,
+}
+
+impl ComponentState {
+    pub fn label(self) -> &'static str {
+        match self {
+            ComponentState::Up => "up",
+            ComponentState::Degraded => "degraded",
+            ComponentState::Broken => "broken",
+            ComponentState::Off => "off",
+        }
+    }
+
+    pub fn color(self) -> Option<Color> {
+        match self {
+            ComponentState::Up => Some(Color::Green),
+            ComponentState::Degraded => Some(Color::Yellow),
+            ComponentState::Broken => Some(Color::Red),
+            ComponentState::Off => None,
+        }
+    }
+}
+
+// Components in report order; each reports its state once it has started (or failed to)
+pub const MIDI_IN: &str = "MIDI in";
+pub const MIDI_OUT: &str = "MIDI out";
+pub const OSC_LISTENER: &str = "OSC listener";
+pub const OSC_SENDER_ORIGINAL: &str = "OSC sender (original)";
+pub const OSC_SENDER_TRANSPOSED: &str = "OSC sender (transposed)";
+pub const MQTT: &str = "MQTT";
+const COMPONENTS: [&str; 6] = [MIDI_IN, MIDI_OUT, OSC_LISTENER, OSC_SENDER_ORIGINAL, OSC_SENDER_TRANSPOSED, MQTT];
+
+static COMPONENT_STATES: Mutex<Vec<(&'static str, ComponentState, String)>> = Mutex::new(Vec::new());
+
+/// Register or update a component's state for the readiness report, e.g.
+/// `report(MIDI_IN, ComponentState::Up, "Keystation 49")`
+pub fn report(component: &'static str, state: ComponentState, detail: impl Into<String>) {
+    let detail = detail.into();
+    if let Ok(mut states) = COMPONENT_STATES.lock() {
+        match states.iter_mut().find(|(name, _, _)| *name == component) {
+            Some(entry) => *entry = (component, state, detail),
+            None => states.push((component, state, detail)),
+        }
+    }
+    if component == MIDI_IN || component == MIDI_OUT {
+        MIDI_GENERATION.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+pub fn midi_generation() -> u64 {
+    MIDI_GENERATION.load(Ordering::SeqCst)
+}
+
+/// MIDI in/out state for MQTT, e.g. `in: up (Keystation 49); out: degraded (MRCC; lost 'loopMIDI')`
+pub fn midi_status() -> String {
+    component_report()
+        .into_iter()
+        .filter(|(name, _)| *name == MIDI_IN || *name == MIDI_OUT)
+        .map(|(name, entry)| {
+            let short = if name == MIDI_IN { "in" } else { "out" };
+            match entry {
+                Some((state, detail)) => format!("{}: {} ({})", short, state.label(), detail),
+                None => format!("{}: starting", short),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Every component with its state and detail, for the web dashboard
+pub fn components_json() -> serde_json::Value {
+    component_report()
+        .into_iter()
+        .map(|(name, entry)| {
+            let (state, detail) = entry.map_or(("starting", String::new()), |(state, detail)| (state.label(), detail));
+            serde_json::json!({ "name": name, "state": state, "detail": detail })
+        })
+        .collect()
+}
+
+/// Hot-plug: a MIDI device (`kind` = "input"/"output") disappeared
+pub fn print_device_lost(kind: &str, name: &str) {
+    print_colored(&[(Some(Color::Red), &format!("MIDI {} lost: {} | Waiting for it to come back", kind, name))]);
+}
+
+/// Hot-plug: a lost MIDI device is connected again
+pub fn print_device_restored(kind: &str, name: &str) {
+    print_colored(&[(Some(Color::Green), &format!("MIDI {} reconnected: {}", kind, name))]);
+}
+
+/// One line per component (`None` = not reported yet), in report order
+pub fn component_report() -> Vec<(&'static str, Option<(ComponentState, String)>)> {
+    let states = COMPONENT_STATES.lock().map(|s| s.clone()).unwrap_or_default();
+    COMPONENTS
+        .iter()
+        .map(|name| (*name, states.iter().find(|(n, _, _)| n == name).map(|(_, state, detail)| (*state, detail.clone()))))
+        .collect()
+}
+
+/// Print the readiness of every component, colored by state (also the `check` console command)
+pub fn print_component_report() {
+    for (name, entry) in component_report() {
+        let (state, detail) = match entry {
+            Some((state, detail)) => (Some(state), detail),
+            None => (None, String::new()),
+        };
+        let label = format!("  {:<24} {:<8}", name, state.map_or("starting", ComponentState::label));
+        print_colored(&[(state.and_then(ComponentState::color), &label), (None, &format!(" {}", detail))]);
+    }
+}
 
 pub fn mark_osc_sender_started() {
     OSC_SENDER_COUNT.fetch_add(1, Ordering::SeqCst);
@@ -19,12 +143,29 @@ pub fn is_osc_sender_running() -> bool {
     OSC_SENDER_COUNT.load(Ordering::SeqCst) > 0
 }
 
+/// Record the OSC listener bind result, e.g. `listening on 127.0.0.1:9070 (9069 in use)`
+pub fn set_osc_listener_status(status: String, state: ComponentState) {
+    report(OSC_LISTENER, state, status.clone());
+    if let Ok(mut current) = OSC_LISTENER_STATUS.lock() {
+        *current = status;
+    }
+    OSC_LISTENER_GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+pub fn osc_listener_status() -> String {
+    match OSC_LISTENER_STATUS.lock() {
+        Ok(status) if !status.is_empty() => status.clone(),
+        _ => "starting".to_string(),
+    }
+}
+
+pub fn osc_listener_generation() -> u64 {
+    OSC_LISTENER_GENERATION.load(Ordering::SeqCst)
+}
+
 // Print the quick help line in blue (works on Windows CMD via termcolor)
 pub fn print_quick_help() {
-    let mut stdout = StandardStream::stdout(ColorChoice::Always);
-    let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Blue)).set_intense(true));
-    let _ = writeln!(&mut stdout, "Type 'help' for commands, 'exit' to quit");
-    let _ = stdout.reset();
+    print_colored(&[(Some(Color::Blue), "Type 'help' for commands, 'exit' to quit")]);
 }
 
 pub fn print_connections_active() {
@@ -35,10 +176,8 @@ pub fn print_connections_active() {
     {
         return;
     }
-    let mut stdout = StandardStream::stdout(ColorChoice::Always);
-    let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)).set_intense(true));
-    let _ = writeln!(&mut stdout, "Connections active | Program started");
-    let _ = stdout.reset();
+    print_colored(&[(Some(Color::Green), "Connections active | Program started")]);
+    print_component_report();
     print_quick_help();
 }
 
@@ -49,10 +188,8 @@ pub fn print_connections_broken() {
     {
         return;
     }
-    let mut stdout = StandardStream::stdout(ColorChoice::Always);
-    let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Red)).set_intense(true));
-    let _ = writeln!(&mut stdout, "Connections broken | Program tries reconnecting");
-    let _ = stdout.reset();
+    print_colored(&[(Some(Color::Red), "Connections broken | Program tries reconnecting")]);
+    print_component_report();
 }
 
 /// Call once after startup to print a single final status line after other debug logs.
@@ -60,20 +197,102 @@ pub fn print_final_status_after_startup() {
     // Small delay so other services can print their initial logs first
     std::thread::sleep(std::time::Duration::from_millis(300));
 
-    let mqtt_enabled = crate::MQTT_ENABLED.load(Ordering::SeqCst);
-    let osc_listener = OSC_LISTENER_RUNNING.load(Ordering::SeqCst);
+    let mqtt_enabled = crate::state().mqtt_enabled.load(Ordering::SeqCst);
+    let osc_listener = crate::state().osc_listener_running.load(Ordering::SeqCst);
     let osc_sender = is_osc_sender_running();
 
+    // With MQTT enabled, do not print here at all to avoid double banners.
+    // mqtt_listener will print the final banner after setup (or on error).
     if mqtt_enabled {
-        // With MQTT enabled, do not print here at all to avoid double banners.
-        // mqtt_listener will print the final banner after setup (or on error).
         return;
+    }
+
+    // No MQTT involvement: decide based on OSC only
+    if osc_listener && osc_sender {
+        print_connections_active();
     } else {
-        // No MQTT involvement: decide based on OSC only
-        if osc_listener && osc_sender {
-            print_connections_active();
-        } else {
-            print_connections_broken();
+        print_connections_broken();
+    }
+}
+
+/// The current runtime state, one line per value (`status` command)
+pub fn status_lines() -> Vec<String> {
+    let mut lines = Vec::new();
+    let on_off = |b: bool| if b { "on" } else { "off" };
+    lines.push("Status:".to_string());
+    lines.push(format!("  Transpose:        {}", crate::state().transpose.load(Ordering::SeqCst)));
+    lines.push(format!("  Octave shift:     {:+}", crate::state().octave.load(Ordering::SeqCst)));
+    lines.push(format!("  Glide:            {} (target {})", on_off(crate::general::glide::is_enabled()), crate::general::glide::target()));
+    if !crate::general::zones::zones().is_empty() {
+        lines.push(format!("  Zones:            {}", crate::general::zones::describe()));
+    }
+    if crate::state().observer.load(Ordering::SeqCst) {
+        lines.push("  MIDI output:      none (observer mode)".to_string());
+    }
+    if crate::get_config().midi.lean_cc.is_some() {
+        lines.push(format!("  Lean offset:      {:+}", crate::transpose::lean_offset()));
+    }
+    if crate::get_config().midi.hold_cc.is_some() || crate::get_config().midi.hold_note.is_some() {
+        lines.push(format!("  Hold offset:      {:+}", crate::transpose::hold_offset(&crate::get_config().midi)));
+    }
+    lines.push(format!("  OSC sending:      {}", on_off(crate::state().osc_sending.load(Ordering::SeqCst))));
+    if crate::osc_sender::targets().len() > 1 {
+        lines.push(format!("  OSC targets:      {}", crate::osc_sender::describe_targets().join(", ")));
+    }
+    if crate::osc_sender::encoding() == crate::osc_sender::OscEncoding::Notes {
+        lines.push(format!("  Note velocity:    {}", on_off(crate::osc_sender::note_velocity())));
+    }
+    lines.push(format!("  Aftertouch->CC:   {} (CC{})", on_off(crate::state().aftertouch_to_cc.load(Ordering::SeqCst)), crate::get_config().midi.aftertouch_cc));
+    lines.push(format!("  Pitch bend mode:  {} (range {})", crate::transpose::pitch_bend_mode().name(), crate::get_config().midi.pitch_bend_range));
+    if let Some((channel, program)) = crate::general::program::current() {
+        lines.push(format!("  Program:          {} (channel {})", program, channel));
+    }
+    lines.push(format!("  Practice:         {}", crate::general::practice::phase()));
+    lines.push(format!("  Automation:       {}", crate::general::automation::progress()));
+    lines.push(format!("  Sequence:         {}", crate::general::sequence::progress()));
+    lines.push(format!("  LFO:              {}", crate::general::lfo::describe()));
+    lines.push(format!("  OSC auto:         {}", on_off(crate::state().osc_sending_auto.load(Ordering::SeqCst))));
+    lines.push(format!("  VRChat:           {}", crate::vrchat::detection_state()));
+    lines.push(format!("  VRChat OSC:       {}", crate::vrchat::last_check_summary().unwrap_or_else(|| "not checked".to_string())));
+    lines.push(format!("  OSC original:     {}", on_off(crate::state().osc_original.load(Ordering::SeqCst))));
+    lines.push(format!("  OSC transposed:   {}", on_off(crate::state().osc_transposed.load(Ordering::SeqCst))));
+    lines.push(format!("  OSC encoding:     {}", crate::osc_sender::encoding().name()));
+    if crate::get_config().osc.note_value_mode == crate::osc_sender::NoteValueMode::Velocity {
+        lines.push("  OSC note type:    velocity (float)".to_string());
+    } else {
+        lines.push(format!("  OSC note type:    {}", crate::osc_sender::note_arg_type_for(&crate::osc_sender::current_target()).name()));
+    }
+    lines.push(format!(
+        "  OSC listener:     {} ({})",
+        if crate::state().osc_listener_running.load(Ordering::SeqCst) { "running" } else { "stopped" },
+        osc_listener_status()
+    ));
+    lines.push(format!(
+        "  MQTT:             {} ({})",
+        on_off(crate::state().mqtt_enabled.load(Ordering::SeqCst)),
+        if crate::state().mqtt_connected.load(Ordering::SeqCst) { "connected" } else { "disconnected" }
+    ));
+    lines.push(format!("  Debug:            {}", on_off(crate::is_debug_enabled())));
+    let categories: Vec<String> = crate::DebugCategory::ALL
+        .iter()
+        .map(|c| format!("{} {}", c.name(), on_off(crate::is_debug_enabled_for(*c))))
+        .collect();
+    lines.push(format!("  Debug categories: {}", categories.join(", ")));
+    let history = crate::general::history::describe(5);
+    if !history.is_empty() {
+        lines.push(format!("  Transpose history (last {}, see 'history'):", history.len()));
+        for line in history {
+            lines.push(format!("    {}", line));
         }
     }
+    lines.push(format!("  Profile:          {}", crate::general::profile::active().unwrap_or_else(|| "-".to_string())));
+    match crate::general::setlist::current() {
+        Some((idx, song)) => lines.push(format!("  Song:             {}/{} {}", idx + 1, crate::get_config().setlist.len(), song.name)),
+        None => lines.push("  Song:             -".to_string()),
+    }
+    lines.push("  Queues:".to_string());
+    for q in crate::general::queue::all_stats() {
+        lines.push(format!("    {:<16} {:>5}/{:<5} dropped {}", q.name, q.queued(), q.capacity, q.dropped()));
+    }
+    lines
 }
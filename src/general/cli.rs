@@ -0,0 +1,296 @@
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+// How long `--list-ports` waits for the MQTT broker to accept a TCP connection
+const MQTT_CONNECT_TIMEOUT_SECS: u64 = 3;
+
+/// Command line options. They override the config file for this run only.
+#[derive(Debug, Default, Clone)]
+pub struct CliOptions {
+    /// Config file instead of `config.json` / `config.<instance>.json` (JSON, TOML or YAML by extension)
+    pub config: Option<PathBuf>,
+    /// Input port name substrings; several are merged like `midi.input_port_name_substrs`
+    pub inputs: Vec<String>,
+    /// Output port name substrings; several fan out like `midi.output_ports`
+    pub outputs: Vec<String>,
+    pub list_ports: bool,
+    /// Validate the config file, list every problem and exit
+    pub check_config: bool,
+    pub no_mqtt: bool,
+    /// Print every MIDI message in and out from the start (`monitor on`)
+    pub monitor: bool,
+    /// Transpose at startup, instead of the configured one (or, for a named instance, the one its last run saved)
+    pub transpose: Option<i32>,
+    /// No prompts, no splash logo, and a closed stdin doesn't quit
+    pub headless: bool,
+    /// Full-screen terminal UI instead of the scrolling console
+    pub tui: bool,
+    /// Windows tray icon instead of a console window (implies `headless`)
+    pub tray: bool,
+    /// Run under the Windows service manager or systemd: no stdin, clean stop (implies `headless`)
+    pub service: bool,
+    pub help: bool,
+}
+
+pub const USAGE: &str = "\
+Usage: VRC-Midi-Transposer [options]
+
+Options:
+  --config <path>        Config file to load, .json/.toml/.yaml (default: config.json)
+  --input <name>         Input port name substring (repeat to merge several inputs)
+  --output <name>        Output port name substring (repeat to send to several outputs)
+  --list-ports           Print MIDI ports, OSC and MQTT checks as JSON and exit
+  --check-config         Validate the config file, list every problem and exit
+  --no-mqtt              Start with MQTT disabled
+  --monitor              Print every incoming and outgoing MIDI message
+  --transpose <n>        Transpose at startup in semitones
+  --headless             No prompts (best port is picked), no logo; keeps running without stdin
+  --tui                  Full-screen terminal UI: live state, channel meters, log and command line
+  --tray                 Windows: hide the console, control it from a tray icon (implies --headless)
+  --service              Run as a Windows service or systemd daemon: no stdin, stops cleanly (implies --headless)
+  --listen-debug <port>  Print received OSC messages instead of transposing
+  --send-test [host:port]  Test the control surface of a running instance
+  --help                 Show this help";
+
+static OPTIONS: OnceLock<CliOptions> = OnceLock::new();
+
+/// Options of this run (defaults before `set`)
+pub fn options() -> &'static CliOptions {
+    OPTIONS.get_or_init(CliOptions::default)
+}
+
+pub fn set(options: CliOptions) {
+    let _ = OPTIONS.set(options);
+}
+
+/// Parse the program arguments (without the program name). Accepts `--flag value` and
+/// `--flag=value` (switches take no value); the debug tools' flags are skipped here, they are
+/// handled separately.
+pub fn parse(args: &[String]) -> Result<CliOptions, String> {
+    let mut options = CliOptions::default();
+    let mut args = args.iter().peekable();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        if inline.is_some() && !matches!(flag, "--config" | "--input" | "--output" | "--transpose") {
+            return Err(format!("Unknown argument '{}' (see --help)", arg));
+        }
+        let mut value = |name: &str| inline.clone().or_else(|| args.next().cloned()).ok_or_else(|| format!("{} needs a {}", flag, name));
+        match flag {
+            "--config" => options.config = Some(PathBuf::from(value("path")?)),
+            "--input" => options.inputs.push(value("port name")?),
+            "--output" => options.outputs.push(value("port name")?),
+            "--transpose" => {
+                let text = value("number of semitones")?;
+                options.transpose = Some(text.parse().map_err(|_| format!("--transpose: invalid number '{}'", text))?);
+            }
+            "--list-ports" => options.list_ports = true,
+            "--check-config" => options.check_config = true,
+            "--no-mqtt" => options.no_mqtt = true,
+            "--monitor" => options.monitor = true,
+            "--headless" => options.headless = true,
+            "--tui" => options.tui = true,
+            "--tray" => {
+                options.tray = true;
+                options.headless = true;
+            }
+            "--service" => {
+                options.service = true;
+                options.headless = true;
+            }
+            "--help" | "-h" => options.help = true,
+            crate::remote::osc_debug::LISTEN_DEBUG_FLAG => {
+                args.next();
+            }
+            crate::remote::self_test::SEND_TEST_FLAG => {
+                args.next_if(|next| !next.starts_with("--"));
+            }
+            _ => return Err(format!("Unknown argument '{}' (see --help)", arg)),
+        }
+    }
+    if [options.tui, options.tray, options.service].iter().filter(|on| **on).count() > 1 {
+        return Err("--tui, --tray and --service can't be combined".to_string());
+    }
+    Ok(options)
+}
+
+/// Apply the port and MQTT options on top of the loaded config
+pub fn apply(options: &CliOptions, config: &mut crate::Config) {
+    match options.inputs.as_slice() {
+        [] => {}
+        [input] => {
+            config.midi.input_port_name_substr = input.clone();
+            config.midi.input_port_name_substrs.clear();
+        }
+        inputs => config.midi.input_port_name_substrs = inputs.to_vec(),
+    }
+    match options.outputs.as_slice() {
+        [] => {}
+        [output] => {
+            config.midi.output_port_name_substr = output.clone();
+            config.midi.output_ports.clear();
+        }
+        outputs => {
+            config.midi.output_ports = outputs
+                .iter()
+                .map(|name| crate::output::OutputPortConfig { name: name.clone(), channels: Vec::new() })
+                .collect();
+        }
+    }
+    if options.no_mqtt {
+        config.mqtt.enabled = false;
+    }
+}
+
+/// Port list for the report; a port matches when the config would pick it
+fn midi_ports(names: Result<Vec<String>, String>, patterns: &[String], exclude: &[String]) -> serde_json::Value {
+    match names {
+        Ok(names) => names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| {
+                let matches = !crate::input::is_excluded(name, exclude) && patterns.iter().any(|p| name.contains(p.as_str()));
+                serde_json::json!({ "index": index, "name": name, "matches_config": matches })
+            })
+            .collect(),
+        Err(e) => serde_json::json!({ "error": e }),
+    }
+}
+
+/// Whether the OSC listener could bind: every configured port with its availability
+fn osc_listener_check(osc: &crate::OscConfig) -> serde_json::Value {
+    let mut ports = vec![osc.listening_port];
+    ports.extend(osc.listening_port_fallbacks.iter().filter(|p| **p != osc.listening_port));
+    let checked: Vec<serde_json::Value> = ports
+        .iter()
+        .map(|port| {
+            let addr = crate::osc_sender::join_host_port(&osc.listening_host, *port);
+            match UdpSocket::bind(&addr) {
+                Ok(_) => serde_json::json!({ "address": addr, "available": true }),
+                Err(e) => serde_json::json!({ "address": addr, "available": false, "error": e.to_string() }),
+            }
+        })
+        .collect();
+    let ok = checked.iter().any(|port| port["available"] == true);
+    serde_json::json!({ "ok": ok, "ports": checked })
+}
+
+/// Whether `host:port` resolves and (for TCP) accepts a connection within `timeout`
+fn reachability(target: &str, tcp: bool, timeout: Duration) -> serde_json::Value {
+    let addrs: Vec<SocketAddr> = match target.to_socket_addrs() {
+        Ok(addrs) => addrs.collect(),
+        Err(e) => return serde_json::json!({ "ok": false, "target": target, "error": format!("cannot resolve: {}", e) }),
+    };
+    let resolved: Vec<String> = addrs.iter().map(SocketAddr::to_string).collect();
+    if !tcp {
+        return serde_json::json!({ "ok": !addrs.is_empty(), "target": target, "resolved": resolved });
+    }
+    let mut error = "no address".to_string();
+    for addr in &addrs {
+        match TcpStream::connect_timeout(addr, timeout) {
+            Ok(_) => return serde_json::json!({ "ok": true, "target": target, "resolved": resolved }),
+            Err(e) => error = e.to_string(),
+        }
+    }
+    serde_json::json!({ "ok": false, "target": target, "resolved": resolved, "error": error })
+}
+
+/// `--list-ports`: MIDI ports (marking the ones the config would pick), the OSC listener bind
+/// check, the OSC targets and the MQTT broker reachability, as JSON for setup scripts
+pub fn port_report() -> serde_json::Value {
+    let config = crate::get_config();
+    let midi = &config.midi;
+    let input_patterns = if midi.input_port_name_substrs.is_empty() { vec![midi.input_port_name_substr.clone()] } else { midi.input_port_name_substrs.clone() };
+    let output_patterns: Vec<String> = if midi.output_ports.is_empty() {
+        vec![midi.output_port_name_substr.clone()]
+    } else {
+        midi.output_ports.iter().map(|p| p.name.clone()).collect()
+    };
+    let inputs = midir::MidiInput::new("midir port list")
+        .map_err(|e| e.to_string())
+        .map(|m| m.ports().iter().map(|p| m.port_name(p).unwrap_or_default()).collect());
+    let outputs = midir::MidiOutput::new("midir port list")
+        .map_err(|e| e.to_string())
+        .map(|m| m.ports().iter().map(|p| m.port_name(p).unwrap_or_default()).collect());
+    let osc_targets: Vec<serde_json::Value> = crate::osc_sender::targets()
+        .iter()
+        .map(|target| {
+            let mut check = reachability(&target.addr, false, Duration::ZERO);
+            check["name"] = target.name.clone().into();
+            check
+        })
+        .collect();
+    let broker = crate::osc_sender::join_host_port(&config.mqtt.broker_host, config.mqtt.broker_port);
+    let mut mqtt = reachability(&broker, true, Duration::from_secs(MQTT_CONNECT_TIMEOUT_SECS));
+    mqtt["enabled"] = config.mqtt.enabled.into();
+    serde_json::json!({
+        "config": crate::config_path().map(|p| p.display().to_string()),
+        "config_loaded": crate::state().config_from_file.load(Ordering::SeqCst),
+        "midi": {
+            "inputs": midi_ports(inputs, &input_patterns, &midi.input_port_name_exclude),
+            "outputs": midi_ports(outputs, &output_patterns, &midi.output_port_name_exclude),
+        },
+        "osc": {
+            "listener": osc_listener_check(&config.osc),
+            "targets": osc_targets,
+        },
+        "mqtt": mqtt,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Result<CliOptions, String> {
+        parse(&args.iter().map(|a| a.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn flags_take_separate_or_inline_values() {
+        let options = parse_args(&["--config=live.toml", "--input", "KeyLab", "--output=Synth", "--output", "Drums"]).unwrap();
+        assert_eq!(options.config, Some(PathBuf::from("live.toml")));
+        assert_eq!(options.inputs, ["KeyLab"]);
+        assert_eq!(options.outputs, ["Synth", "Drums"]);
+        assert_eq!(parse_args(&["--input=a=b"]).unwrap().inputs, ["a=b"]);
+    }
+
+    #[test]
+    fn negative_transpose_is_a_value_not_a_flag() {
+        assert_eq!(parse_args(&["--transpose", "-3"]).unwrap().transpose, Some(-3));
+        assert_eq!(parse_args(&["--transpose=-3"]).unwrap().transpose, Some(-3));
+        assert!(parse_args(&["--transpose", "up"]).is_err());
+        assert!(parse_args(&["--transpose"]).is_err());
+    }
+
+    #[test]
+    fn ui_modes_are_exclusive() {
+        assert!(parse_args(&["--tui", "--tray"]).is_err());
+        assert!(parse_args(&["--tray", "--service"]).is_err());
+        assert!(parse_args(&["--tui", "--service"]).is_err());
+        let options = parse_args(&["--service"]).unwrap();
+        assert!(options.service && options.headless);
+        assert!(parse_args(&["--tui", "--headless"]).is_ok());
+    }
+
+    #[test]
+    fn unknown_flags_are_rejected() {
+        assert!(parse_args(&["--verbose"]).unwrap_err().contains("--verbose"));
+        assert!(parse_args(&["--no-mqtt=1"]).is_err());
+        assert!(parse_args(&["config.json"]).is_err());
+    }
+
+    #[test]
+    fn debug_tool_flags_are_skipped_with_their_values() {
+        let flag = crate::remote::osc_debug::LISTEN_DEBUG_FLAG;
+        assert!(parse_args(&[flag, "9001", "--monitor"]).unwrap().monitor);
+        let send_test = crate::remote::self_test::SEND_TEST_FLAG;
+        assert!(parse_args(&[send_test, "--monitor"]).unwrap().monitor);
+        assert!(parse_args(&[send_test, "127.0.0.1:9000"]).is_ok());
+    }
+}
@@ -0,0 +1,657 @@
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+
+/// A runtime control action, independent of where it came from (console, OSC or MQTT).
+/// Every front end parses its own input into a `Command` and runs it through `execute`,
+/// so clamping, validation, permissions and side effects are the same everywhere.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Absolute transpose in semitones
+    Transpose(i32),
+    /// Relative transpose (+1 = up, -1 = down)
+    TransposeStep(i32),
+    /// Position in -1..1 mapped linearly onto the configured min..max range (radial puppets)
+    TransposeNormalized(f32),
+    /// Absolute octave shift, on top of the semitone transpose
+    Octave(i32),
+    /// Relative octave shift (+1 = up, -1 = down)
+    OctaveStep(i32),
+    OscSending(bool),
+    OscAuto(bool),
+    /// Send the original (true) or the transposed (false) stream
+    OscSendOriginal(bool),
+    /// Enable/disable a single stream
+    OscStream { original: bool, enabled: bool },
+    /// Enable/disable one send target (name, slug or 1-based number)
+    OscTarget(String, bool),
+    NoteArgType(crate::osc_sender::NoteArgType),
+    /// Per-note velocity parameters (`<note>_v`)
+    NoteVelocity(bool),
+    AftertouchToCc(bool),
+    /// Transpose status posts to the VRChat chatbox
+    ChatboxStatus(bool),
+    /// Transpose changes in 1-semitone steps (`transpose.glide`)
+    Glide(bool),
+    /// Transpose of one keyboard zone (name, slug or 1-based number)
+    Zone(String, i32),
+    PitchBend(crate::transpose::PitchBendMode),
+    /// Argument of `debug ...`: on/off, a duration or `<category> on/off`
+    Debug(String),
+    DebugCategory(crate::DebugCategory, bool),
+    /// Argument of `log ...`: `<subsystem|all> <level>`, a level for all, or empty for the levels
+    Log(String),
+    SetlistNext,
+    SetlistPrev,
+    /// Setlist song, 1-based
+    Song(usize),
+    Practice(bool),
+    /// Lane file, or the configured lane
+    AutomationStart(Option<String>),
+    AutomationStop,
+    SequenceStart,
+    SequenceStop,
+    Lfo(bool),
+    LfoDepth(f32),
+    LfoRate(f32),
+    Profile(String),
+    /// Custom control key (`<slug>` or `<slug>/<arg>`) and one value, or one per argument
+    Custom(String, Vec<f32>),
+    /// `at <HH:MM> <command>` or `in <duration> <command>`
+    Schedule(String),
+    /// All notes off on every MIDI output channel
+    Panic,
+    /// MIDI thru latency statistics, or reset them (true)
+    Stats(bool),
+    /// Print every MIDI message in and out
+    Monitor(bool),
+    /// Start (or stop publishing through) the MQTT listener; console only
+    Mqtt(bool),
+    /// Save the current settings as a profile file
+    ExportProfile(String),
+    /// Load and apply a profile file
+    ImportProfile(String),
+    /// Write a TouchOSC or Open Stage Control layout file
+    ExportLayout(String),
+    /// Readiness of every component
+    Check,
+    /// ICMP and OSCQuery check of the VRChat OSC connection
+    CheckVrchat,
+    Status,
+    /// Transpose changes of this session
+    History,
+    OscTargets,
+    /// Reset the note parameters and custom controls on the OSC target
+    OscInit,
+    /// Search the network for OSC targets and pick one on the console; console only
+    Discover,
+    ScheduleList,
+    /// Cancel one scheduled action by id, or all with `None`
+    ScheduleCancel(Option<u32>),
+    /// Quit the program; console only
+    Exit,
+}
+
+/// Where a command came from. Remote sources can't run console-only commands and only
+/// read or write bare file names in the working directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// Console, TUI or tray menu
+    Console,
+    Osc,
+    Mqtt,
+    /// Web dashboard, REST and event API
+    Web,
+}
+
+impl Source {
+    pub fn name(self) -> &'static str {
+        match self {
+            Source::Console => "console",
+            Source::Osc => "OSC",
+            Source::Mqtt => "MQTT",
+            Source::Web => "web",
+        }
+    }
+}
+
+impl Command {
+    /// Commands that only the console may run: they quit, touch the connections or prompt on stdin
+    pub fn console_only(&self) -> bool {
+        matches!(self, Command::Mqtt(_) | Command::Discover | Command::Exit)
+    }
+}
+
+/// Result of a successful command
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// Transpose changed; `requested` differs from `applied` when it was clamped
+    Transpose { requested: i32, applied: i32 },
+    /// Done, with a confirmation for the console
+    Done(String),
+    /// Done; the component already printed its own confirmation
+    Silent,
+}
+
+/// Syntax of every command `parse` accepts that is not console only, for help texts and the capability document
+pub const SYNTAX: &[&str] = &[
+    "<semitones>",
+    "transpose <semitones>",
+    "up",
+    "down",
+    "octave up|down",
+    "octave <octaves>",
+    "osc on|off",
+    "osc auto on|off",
+    "osc original",
+    "osc transposed",
+    "osc original on|off",
+    "osc transposed on|off",
+    "osc target <name|number> on|off",
+    "osc notetype int|bool|float",
+    "osc velocity on|off",
+    "aftertouch cc on|off",
+    "chatbox status on|off",
+    "glide on|off",
+    "zone <name|number> <semitones>",
+    "pitchbend off|center|bend",
+    "debug on|off",
+    "debug on <duration>",
+    "debug <category> on|off",
+    "log [<subsystem|all>] <level>",
+    "next",
+    "prev",
+    "song <number>",
+    "practice on|off",
+    "automation start [<lane file>]",
+    "automation stop",
+    "sequence start|stop",
+    "lfo on|off",
+    "lfo depth <0-1>",
+    "lfo rate <beats per step>",
+    "profile <name>",
+    "custom <slug>[/<arg>] <value> [<value> ...]",
+    "at <HH:MM> <command>",
+    "in <duration> <command>",
+    "panic",
+    "stats [reset]",
+    "monitor on|off",
+    "export profile <file>",
+    "import profile <file>",
+    "export layout <file>",
+    "check",
+    "check vrchat",
+    "status",
+    "history",
+    "osc targets",
+    "osc init",
+    "schedule",
+    "schedule cancel <id|all>",
+];
+
+/// On/off words accepted by all switch commands
+pub fn parse_switch(text: &str) -> Option<bool> {
+    match text.trim().to_ascii_lowercase().as_str() {
+        "on" | "enable" | "1" | "true" => Some(true),
+        "off" | "disable" | "0" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// Case-insensitive `strip_prefix`
+fn strip_prefix_ci<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = text.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix).then(|| &text[prefix.len()..])
+}
+
+fn switch_or(text: &str, usage: &str) -> Result<bool, String> {
+    parse_switch(text).ok_or_else(|| format!("Usage: {}", usage))
+}
+
+fn number_or<T: std::str::FromStr>(text: &str, usage: &str) -> Result<T, String> {
+    text.trim().parse().map_err(|_| format!("Usage: {}", usage))
+}
+
+/// Parse a console command line (also used for the OSC `command_path` and MQTT `command` topic).
+/// `None` means the text is not a control command; `Some(Err)` carries a usage hint.
+pub fn parse(text: &str) -> Option<Result<Command, String>> {
+    let text = text.trim();
+    let lower = text.to_ascii_lowercase();
+    let result = match lower.as_str() {
+        "1" | "osc on" | "osc enable" => Ok(Command::OscSending(true)),
+        "0" | "osc off" | "osc disable" => Ok(Command::OscSending(false)),
+        "osc auto" | "osc auto on" => Ok(Command::OscAuto(true)),
+        "osc auto off" => Ok(Command::OscAuto(false)),
+        "osc original" | "osc input" | "osc_original" => Ok(Command::OscSendOriginal(true)),
+        "osc transposed" | "osc output" | "osc_transposed" => Ok(Command::OscSendOriginal(false)),
+        "next" => Ok(Command::SetlistNext),
+        "prev" => Ok(Command::SetlistPrev),
+        "up" | "transpose up" => Ok(Command::TransposeStep(1)),
+        "down" | "transpose down" => Ok(Command::TransposeStep(-1)),
+        "automation stop" => Ok(Command::AutomationStop),
+        "sequence start" => Ok(Command::SequenceStart),
+        "sequence stop" => Ok(Command::SequenceStop),
+        "panic" => Ok(Command::Panic),
+        "stats" => Ok(Command::Stats(false)),
+        "stats reset" => Ok(Command::Stats(true)),
+        "log" => Ok(Command::Log(String::new())),
+        "mqtt on" | "mqtt enable" => Ok(Command::Mqtt(true)),
+        "mqtt off" | "mqtt disable" => Ok(Command::Mqtt(false)),
+        "check" => Ok(Command::Check),
+        "check vrchat" => Ok(Command::CheckVrchat),
+        "status" => Ok(Command::Status),
+        "history" => Ok(Command::History),
+        "osc targets" => Ok(Command::OscTargets),
+        "osc init" => Ok(Command::OscInit),
+        "discover" => Ok(Command::Discover),
+        "schedule" => Ok(Command::ScheduleList),
+        "exit" | "quit" | "q" => Ok(Command::Exit),
+        _ => return parse_with_argument(text, &lower),
+    };
+    Some(result)
+}
+
+fn parse_with_argument(text: &str, lower: &str) -> Option<Result<Command, String>> {
+    // Legacy forms 'osc_original 1' and 'osc_original:1'
+    if let Some(arg) = lower.strip_prefix("osc_original ").or_else(|| lower.strip_prefix("osc_original:")) {
+        return Some(switch_or(arg, "osc_original 1|0").map(Command::OscSendOriginal));
+    }
+    if let Some(name) = lower.strip_prefix("osc notetype ") {
+        return Some(crate::osc_sender::NoteArgType::from_name(name.trim()).map(Command::NoteArgType).ok_or_else(|| "Usage: osc notetype int|bool|float".to_string()));
+    }
+    if let Some(state) = lower.strip_prefix("osc velocity ") {
+        return Some(switch_or(state, "osc velocity on|off").map(Command::NoteVelocity));
+    }
+    // Per-target switch: 'osc target <name|number> on/off' (names may contain spaces)
+    if let Some(rest) = strip_prefix_ci(text, "osc target ") {
+        const USAGE: &str = "osc target <name|number> on|off";
+        return Some(match rest.trim().rsplit_once(' ') {
+            Some((key, state)) => switch_or(state, USAGE).map(|on| Command::OscTarget(key.trim().to_string(), on)),
+            None => Err(format!("Usage: {}", USAGE)),
+        });
+    }
+    // Per-stream switches: 'osc original on/off', 'osc transposed on/off'
+    if let Some((stream, state)) = lower.strip_prefix("osc ").and_then(|rest| rest.trim().split_once(' ')) {
+        let original = match stream {
+            "original" => true,
+            "transposed" => false,
+            _ => return None,
+        };
+        return Some(switch_or(state, "osc original|transposed on|off").map(|enabled| Command::OscStream { original, enabled }));
+    }
+    if let Some(arg) = lower.strip_prefix("octave ") {
+        return Some(match arg.trim() {
+            "up" => Ok(Command::OctaveStep(1)),
+            "down" => Ok(Command::OctaveStep(-1)),
+            n => number_or(n, "octave up|down|<octaves>").map(Command::Octave),
+        });
+    }
+    if let Some(state) = lower.strip_prefix("aftertouch cc ") {
+        return Some(switch_or(state, "aftertouch cc on|off").map(Command::AftertouchToCc));
+    }
+    if let Some(name) = lower.strip_prefix("pitchbend ") {
+        return Some(crate::transpose::PitchBendMode::from_name(name.trim()).map(Command::PitchBend).ok_or_else(|| "Usage: pitchbend off|center|bend".to_string()));
+    }
+    if let Some(arg) = lower.strip_prefix("debug ") {
+        return Some(Ok(Command::Debug(arg.trim().to_string())));
+    }
+    if let Some(arg) = lower.strip_prefix("log ") {
+        return Some(Ok(Command::Log(arg.trim().to_string())));
+    }
+    if let Some(state) = lower.strip_prefix("monitor ") {
+        return Some(switch_or(state, "monitor on|off").map(Command::Monitor));
+    }
+    if let Some(state) = lower.strip_prefix("glide ") {
+        return Some(switch_or(state, "glide on|off").map(Command::Glide));
+    }
+    // 'zone <name|number> <semitones>' (names may contain spaces)
+    if let Some(rest) = strip_prefix_ci(text, "zone ") {
+        const USAGE: &str = "zone <name|number> <semitones>";
+        return Some(match rest.trim().rsplit_once(' ') {
+            Some((key, value)) => number_or(value.trim_start_matches('+'), USAGE).map(|value| Command::Zone(key.trim().to_string(), value)),
+            None => Err(format!("Usage: {}", USAGE)),
+        });
+    }
+    if let Some(state) = lower.strip_prefix("chatbox status ") {
+        return Some(switch_or(state, "chatbox status on|off").map(Command::ChatboxStatus));
+    }
+    if let Some(state) = lower.strip_prefix("practice ") {
+        return Some(switch_or(state, "practice on|off").map(Command::Practice));
+    }
+    if let Some(arg) = lower.strip_prefix("lfo ") {
+        const USAGE: &str = "lfo on|off, lfo depth <0-1>, lfo rate <beats per step>";
+        let arg = arg.trim();
+        return Some(if let Some(depth) = arg.strip_prefix("depth ") {
+            number_or(depth, USAGE).map(Command::LfoDepth)
+        } else if let Some(beats) = arg.strip_prefix("rate ") {
+            number_or(beats, USAGE).map(Command::LfoRate)
+        } else {
+            switch_or(arg, USAGE).map(Command::Lfo)
+        });
+    }
+    if let Some(file) = strip_prefix_ci(text, "automation start") {
+        let file = file.trim();
+        return Some(Ok(Command::AutomationStart((!file.is_empty()).then(|| file.to_string()))));
+    }
+    if let Some(n) = lower.strip_prefix("song ") {
+        return Some(match n.trim().parse::<usize>() {
+            Ok(n) if n >= 1 => Ok(Command::Song(n)),
+            _ => Err(format!("Invalid song number '{}'", n.trim())),
+        });
+    }
+    if let Some(name) = strip_prefix_ci(text, "profile ") {
+        return Some(Ok(Command::Profile(name.trim().to_string())));
+    }
+    // 'custom <slug> <value>' or 'custom <slug> <value> <value> ...' (one per argument)
+    if let Some(rest) = strip_prefix_ci(text, "custom ") {
+        const USAGE: &str = "custom <slug>[/<arg>] <value> [<value> ...]";
+        let mut parts = rest.split_whitespace();
+        let Some(key) = parts.next() else { return Some(Err(format!("Usage: {}", USAGE))) };
+        let values: Result<Vec<f32>, String> = parts
+            .map(|v| number_or(v, USAGE).or_else(|e| parse_switch(v).map(|on| if on { 1.0 } else { 0.0 }).ok_or(e)))
+            .collect();
+        return Some(match values {
+            Ok(values) if !values.is_empty() => Ok(Command::Custom(key.to_string(), values)),
+            Ok(_) => Err(format!("Usage: {}", USAGE)),
+            Err(e) => Err(e),
+        });
+    }
+    if let Some(file) = strip_prefix_ci(text, "export profile ") {
+        return Some(Ok(Command::ExportProfile(file.trim().to_string())));
+    }
+    if let Some(file) = strip_prefix_ci(text, "import profile ") {
+        return Some(Ok(Command::ImportProfile(file.trim().to_string())));
+    }
+    if let Some(file) = strip_prefix_ci(text, "export layout ") {
+        return Some(Ok(Command::ExportLayout(file.trim().to_string())));
+    }
+    if let Some(arg) = lower.strip_prefix("schedule cancel ") {
+        return Some(match arg.trim().trim_start_matches('#') {
+            "all" => Ok(Command::ScheduleCancel(None)),
+            id => number_or(id, "schedule cancel <id|all>").map(|id| Command::ScheduleCancel(Some(id))),
+        });
+    }
+    if lower.starts_with("at ") || lower.starts_with("in ") {
+        return Some(Ok(Command::Schedule(text.to_string())));
+    }
+    let value = lower.strip_prefix("transpose ").unwrap_or(lower).trim();
+    value.parse::<i32>().ok().map(|v| Ok(Command::Transpose(v)))
+}
+
+fn enabled(on: bool) -> &'static str {
+    if on { "enabled" } else { "disabled" }
+}
+
+fn transpose_to(requested: i32) -> Outcome {
+    Outcome::Transpose { requested, applied: crate::set_transpose_semitones(requested) }
+}
+
+fn octave_to(requested: i32) -> Outcome {
+    let applied = crate::set_octave_shift(requested);
+    let range = &crate::get_config().transpose;
+    if applied != requested {
+        Outcome::Done(format!("Octave shift {:+} (limited to {}..{})", applied, range.octave_min, range.octave_max))
+    } else {
+        Outcome::Done(format!("Octave shift {:+}", applied))
+    }
+}
+
+/// One indented line per entry, or `empty` when there are none
+fn listing(lines: Vec<String>, empty: &str) -> Outcome {
+    if lines.is_empty() {
+        return Outcome::Done(empty.to_string());
+    }
+    Outcome::Done(lines.iter().map(|line| format!("  {}", line)).collect::<Vec<_>>().join("\n"))
+}
+
+/// Path of a file command: as typed on the console, a bare file name in the working directory otherwise
+fn file_path(file: &str, source: Source) -> Result<PathBuf, String> {
+    if source == Source::Console {
+        return Ok(PathBuf::from(file.trim()));
+    }
+    crate::general::profile::remote_file_path(file).ok_or_else(|| format!("Expected a plain file name over {} (got '{}')", source.name(), file.trim()))
+}
+
+/// Run a command from `source`. `Err` describes why it was rejected (console only, unknown
+/// profile, empty setlist, ...).
+pub fn execute(command: Command, source: Source) -> Result<Outcome, String> {
+    if source != Source::Console && command.console_only() {
+        return Err(format!("This command is only available on the console, not over {}", source.name()));
+    }
+    let outcome = match command {
+        Command::Transpose(value) => transpose_to(value),
+        Command::TransposeStep(delta) => transpose_to(crate::general::glide::target() + delta),
+        Command::TransposeNormalized(position) => {
+            let range = &crate::get_config().transpose;
+            let (min, max) = (range.min as f32, range.max as f32);
+            transpose_to((min + (position.clamp(-1.0, 1.0) + 1.0) / 2.0 * (max - min)).round() as i32)
+        }
+        Command::Octave(octaves) => octave_to(octaves),
+        Command::OctaveStep(delta) => octave_to(crate::state().octave.load(Ordering::SeqCst) + delta),
+        Command::OscSending(on) => {
+            crate::state().osc_sending.store(on, Ordering::SeqCst);
+            Outcome::Done(format!("OSC sending {}", enabled(on)))
+        }
+        Command::OscAuto(on) => {
+            crate::state().osc_sending_auto.store(on, Ordering::SeqCst);
+            if on {
+                Outcome::Done(format!("OSC auto mode enabled (VRChat: {})", crate::vrchat::detection_state()))
+            } else {
+                Outcome::Done("OSC auto mode disabled".to_string())
+            }
+        }
+        Command::OscSendOriginal(original) => {
+            crate::set_osc_send_original(original);
+            Outcome::Done(if original { "OSC sending original input MIDI" } else { "OSC sending transposed MIDI" }.to_string())
+        }
+        Command::OscStream { original, enabled: on } => {
+            let (flag, name) = if original {
+                (&crate::state().osc_original, "original")
+            } else {
+                (&crate::state().osc_transposed, "transposed")
+            };
+            flag.store(on, Ordering::SeqCst);
+            Outcome::Done(format!("OSC {} stream {}", name, enabled(on)))
+        }
+        Command::OscTarget(key, on) => {
+            let target = crate::osc_sender::find_target(&key).ok_or_else(|| format!("Unknown OSC target '{}' (see 'osc targets')", key))?;
+            crate::osc_sender::set_target_enabled(target, on);
+            Outcome::Done(format!("OSC target {} {}", target.name, enabled(on)))
+        }
+        Command::NoteArgType(arg_type) => {
+            crate::osc_sender::set_note_arg_type(arg_type);
+            let target = crate::osc_sender::current_target();
+            if crate::osc_sender::note_arg_type_for(&target) != arg_type {
+                Outcome::Done(format!("OSC note type: {} (the current target {} keeps its osc.note_arg_types entry)", arg_type.name(), target))
+            } else {
+                Outcome::Done(format!("OSC note type: {}", arg_type.name()))
+            }
+        }
+        Command::NoteVelocity(on) => {
+            crate::osc_sender::set_note_velocity(on);
+            Outcome::Done(format!("OSC note velocity parameters {}", enabled(on)))
+        }
+        Command::AftertouchToCc(on) => {
+            crate::state().aftertouch_to_cc.store(on, Ordering::SeqCst);
+            Outcome::Done(format!("Aftertouch to CC{} {}", crate::get_config().midi.aftertouch_cc, enabled(on)))
+        }
+        Command::Glide(on) => {
+            crate::general::glide::set_enabled(on);
+            Outcome::Silent
+        }
+        Command::Zone(key, value) => {
+            let index = crate::general::zones::find(&key).ok_or_else(|| format!("Unknown zone '{}'", key))?;
+            let value = crate::general::zones::set_transpose(index, value).ok_or_else(|| format!("Unknown zone '{}'", key))?;
+            Outcome::Done(format!("Zone {} transpose {:+}", crate::general::zones::zones()[index].name, value))
+        }
+        Command::ChatboxStatus(on) => {
+            crate::remote::chatbox::set_status_enabled(on);
+            Outcome::Done(format!("Chatbox status {}", enabled(on)))
+        }
+        Command::PitchBend(mode) => {
+            crate::transpose::set_pitch_bend_mode(mode);
+            Outcome::Done(format!("Pitch bend mode: {} (range {} semitones)", mode.name(), crate::get_config().midi.pitch_bend_range))
+        }
+        Command::Debug(arg) => Outcome::Done(
+            crate::general::debug::apply_command(&arg).ok_or("Usage: debug on|off, debug on <duration>, debug <category> on|off")?,
+        ),
+        Command::DebugCategory(category, on) => {
+            crate::general::debug::set_category(category, on);
+            Outcome::Done(format!("Debug {} {}", category.name(), enabled(on)))
+        }
+        Command::Log(arg) => Outcome::Done(crate::general::logging::apply_command(&arg)?),
+        Command::SetlistNext => {
+            crate::general::setlist::next().ok_or("Setlist is empty")?;
+            Outcome::Silent
+        }
+        Command::SetlistPrev => {
+            crate::general::setlist::prev().ok_or("Setlist is empty")?;
+            Outcome::Silent
+        }
+        Command::Song(n) => {
+            n.checked_sub(1).and_then(crate::general::setlist::goto).ok_or_else(|| format!("Invalid song number '{}'", n))?;
+            Outcome::Silent
+        }
+        Command::Practice(on) => {
+            crate::general::practice::set_active(on);
+            Outcome::Silent
+        }
+        Command::AutomationStart(file) => {
+            match file {
+                Some(file) => crate::general::automation::start(&file),
+                None => crate::general::automation::start_configured(),
+            }
+            .map_err(|e| format!("Automation not started: {}", e))?;
+            Outcome::Silent
+        }
+        Command::AutomationStop => {
+            crate::general::automation::stop();
+            Outcome::Silent
+        }
+        Command::SequenceStart => {
+            crate::general::sequence::start()?;
+            Outcome::Silent
+        }
+        Command::SequenceStop => {
+            crate::general::sequence::stop();
+            Outcome::Silent
+        }
+        Command::Lfo(on) => {
+            crate::general::lfo::set_enabled(on);
+            Outcome::Silent
+        }
+        Command::LfoDepth(depth) => Outcome::Done(format!("LFO depth {:.2}", crate::general::lfo::set_depth(depth))),
+        Command::LfoRate(beats) => Outcome::Done(format!("LFO rate {} beats per step", crate::general::lfo::set_beats_per_step(beats))),
+        Command::Profile(name) => {
+            if !crate::general::profile::apply(&name) {
+                return Err(format!("Unknown profile '{}'", name));
+            }
+            Outcome::Done(format!("Profile '{}' applied", name))
+        }
+        Command::Custom(key, values) => {
+            let known = match values.as_slice() {
+                [value] => crate::custom_controls::set_value(&key, *value).is_some(),
+                values => crate::custom_controls::set_values(&key, values),
+            };
+            if !known {
+                return Err(format!("Unknown custom control '{}'", key));
+            }
+            Outcome::Done(format!("Custom control {} set", key))
+        }
+        Command::Schedule(spec) => Outcome::Done(crate::general::scheduler::schedule(&spec, source).map_err(|e| format!("Not scheduled: {}", e))?),
+        Command::Monitor(on) => {
+            crate::general::monitor::set_enabled(on);
+            Outcome::Done(format!("MIDI monitor {}", enabled(on)))
+        }
+        Command::Panic => {
+            crate::general::takeover::all_notes_off();
+            Outcome::Done("All notes off sent on all channels".to_string())
+        }
+        Command::Stats(true) => {
+            crate::general::latency::reset();
+            Outcome::Done("MIDI thru latency statistics reset".to_string())
+        }
+        Command::Stats(false) => Outcome::Done(crate::general::latency::describe()),
+        Command::Mqtt(true) => {
+            crate::state().mqtt_enabled.store(true, Ordering::SeqCst);
+            crate::mqtt_listener::ensure_started();
+            Outcome::Done("MQTT enabled".to_string())
+        }
+        Command::Mqtt(false) => {
+            crate::state().mqtt_enabled.store(false, Ordering::SeqCst);
+            crate::general::check::report(crate::general::check::MQTT, crate::general::check::ComponentState::Off, "disabled via console");
+            Outcome::Done("MQTT disabled (listener will stop on next reconnect/exit)".to_string())
+        }
+        Command::ExportProfile(file) => {
+            let path = file_path(&file, source)?;
+            crate::general::profile::export(&path).map_err(|e| format!("Profile export failed: {}", e))?;
+            Outcome::Done(format!("Profile exported to {}", path.display()))
+        }
+        Command::ImportProfile(file) => {
+            let path = file_path(&file, source)?;
+            let name = crate::general::profile::import(&path).map_err(|e| format!("Profile import failed: {}", e))?;
+            Outcome::Done(format!("Profile '{}' imported and applied", name))
+        }
+        Command::ExportLayout(file) => {
+            let path = file_path(&file, source)?;
+            let count = crate::remote::layout::export(&path).map_err(|e| format!("Layout export failed: {}", e))?;
+            Outcome::Done(format!("Controller layout with {} controls written to {}", count, path.display()))
+        }
+        Command::Check => {
+            crate::general::check::print_component_report();
+            Outcome::Silent
+        }
+        Command::CheckVrchat => {
+            log::info!(target: "app", "Checking VRChat connectivity...");
+            crate::vrchat::print_connectivity_banner(&crate::vrchat::check_connectivity());
+            Outcome::Silent
+        }
+        Command::Status => Outcome::Done(crate::general::check::status_lines().join("\n")),
+        Command::History => listing(crate::general::history::describe(usize::MAX), "No transpose changes yet"),
+        Command::OscTargets => listing(crate::osc_sender::describe_targets(), "No OSC targets"),
+        Command::OscInit => Outcome::Done(format!("Sent {} initial OSC parameter values", crate::osc_sender::initialize_parameters())),
+        Command::Discover => {
+            let timeout = std::time::Duration::from_millis(crate::get_config().osc.discovery_timeout_ms);
+            let target = crate::discovery::choose_target_interactive(timeout).ok_or("No OSC target selected")?;
+            crate::osc_sender::set_target(target.target_addr());
+            Outcome::Done(format!("OSC send target set to {} ({})", target.name, target.target_addr()))
+        }
+        Command::ScheduleList => listing(crate::general::scheduler::pending(), "No scheduled actions"),
+        Command::ScheduleCancel(id) => Outcome::Done(format!("Cancelled {} scheduled action(s)", crate::general::scheduler::cancel(id))),
+        Command::Exit => {
+            // Same path as Ctrl+C: the console handler ends the program
+            crate::io::console::inject("exit");
+            Outcome::Silent
+        }
+    };
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn console_commands_parse_into_commands() {
+        assert_eq!(parse("export profile live.json"), Some(Ok(Command::ExportProfile("live.json".to_string()))));
+        assert_eq!(parse("schedule cancel #3"), Some(Ok(Command::ScheduleCancel(Some(3)))));
+        assert_eq!(parse("schedule cancel all"), Some(Ok(Command::ScheduleCancel(None))));
+        assert_eq!(parse("MQTT off"), Some(Ok(Command::Mqtt(false))));
+        assert_eq!(parse("quit"), Some(Ok(Command::Exit)));
+    }
+
+    #[test]
+    fn remote_sources_cannot_run_console_only_commands() {
+        for source in [Source::Osc, Source::Mqtt, Source::Web] {
+            assert!(execute(Command::Exit, source).is_err());
+            assert!(execute(Command::Mqtt(false), source).is_err());
+        }
+    }
+
+    #[test]
+    fn remote_file_commands_take_bare_names_only() {
+        assert!(file_path("/etc/passwd", Source::Mqtt).is_err());
+        assert!(file_path("..\\config.toml", Source::Web).is_err());
+        assert_eq!(file_path("live.json", Source::Osc), Ok(PathBuf::from("live.json")));
+        assert_eq!(file_path("/tmp/live.json", Source::Console), Ok(PathBuf::from("/tmp/live.json")));
+    }
+}
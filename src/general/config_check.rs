@@ -0,0 +1,527 @@
+use std::fmt;
+use std::path::Path;
+use crate::Config;
+use crate::general::config_format::{self, ConfigFormat};
+
+// Fields reset to their default before giving up on the file
+const MAX_REPAIRS: usize = 100;
+
+/// Step of a path into the config: an object key or an array index
+#[derive(Debug, Clone, PartialEq)]
+enum Key {
+    Field(String),
+    Index(usize),
+}
+
+/// One problem found in the config file
+#[derive(Debug)]
+pub struct Problem {
+    /// Line of the field in the file (1-based), if it could be found
+    pub line: Option<usize>,
+    /// Dotted field name, e.g. `osc.sending_targets[1].port`; empty for the whole file
+    pub field: String,
+    pub message: String,
+    /// Warnings (unknown keys) don't stop `--check-config` from passing
+    pub warning: bool,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {}: ", line)?;
+        }
+        if !self.field.is_empty() {
+            write!(f, "{}: ", self.field)?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+fn field_name(keys: &[Key]) -> String {
+    let mut name = String::new();
+    for key in keys {
+        match key {
+            Key::Field(field) if name.is_empty() => name.push_str(field),
+            Key::Field(field) => {
+                name.push('.');
+                name.push_str(field);
+            }
+            Key::Index(index) => name.push_str(&format!("[{}]", index)),
+        }
+    }
+    name
+}
+
+fn ignored_keys(path: &serde_ignored::Path, keys: &mut Vec<Key>) {
+    use serde_ignored::Path;
+    match path {
+        Path::Root => {}
+        Path::Seq { parent, index } => {
+            ignored_keys(parent, keys);
+            keys.push(Key::Index(*index));
+        }
+        Path::Map { parent, key } => {
+            ignored_keys(parent, keys);
+            keys.push(Key::Field(key.clone()));
+        }
+        Path::Some { parent } | Path::NewtypeStruct { parent } | Path::NewtypeVariant { parent } => ignored_keys(parent, keys),
+    }
+}
+
+/// Path of a type error; `None` when it can't be followed back into the JSON
+fn error_keys(path: &serde_path_to_error::Path) -> Option<Vec<Key>> {
+    use serde_path_to_error::Segment;
+    path.iter()
+        .filter(|segment| !matches!(segment, Segment::Enum { .. }))
+        .map(|segment| match segment {
+            Segment::Seq { index } => Some(Key::Index(*index)),
+            Segment::Map { key } => Some(Key::Field(key.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Drop the value at `keys`, so the field falls back to its default on the next attempt
+fn remove(value: &mut serde_json::Value, keys: &[Key]) -> bool {
+    let Some((last, parents)) = keys.split_last() else { return false };
+    let mut parent = value;
+    for key in parents {
+        let child = match key {
+            Key::Field(field) => parent.get_mut(field.as_str()),
+            Key::Index(index) => parent.get_mut(*index),
+        };
+        let Some(child) = child else { return false };
+        parent = child;
+    }
+    match (last, parent) {
+        (Key::Field(field), serde_json::Value::Object(map)) => map.shift_remove(field).is_some(),
+        (Key::Index(index), serde_json::Value::Array(items)) if *index < items.len() => {
+            items.remove(*index);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Minimal JSON walker that finds where a value starts in the text, for line numbers
+struct Scanner<'a> {
+    text: &'a [u8],
+    position: usize,
+}
+
+impl Scanner<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.text.get(self.position).is_some_and(u8::is_ascii_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        self.skip_whitespace();
+        let found = self.text.get(self.position) == Some(&byte);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    /// The string starting at the current position (its raw text, escapes kept)
+    fn string(&mut self) -> Option<&str> {
+        if !self.eat(b'"') {
+            return None;
+        }
+        let start = self.position;
+        while let Some(&byte) = self.text.get(self.position) {
+            self.position += 1;
+            match byte {
+                b'\\' => self.position += 1,
+                b'"' => return std::str::from_utf8(&self.text[start..self.position - 1]).ok(),
+                _ => {}
+            }
+        }
+        None
+    }
+
+    fn skip_value(&mut self) -> Option<()> {
+        self.skip_whitespace();
+        match self.text.get(self.position)? {
+            b'"' => self.string().map(|_| ()),
+            b'{' | b'[' => {
+                let mut depth = 0;
+                while let Some(&byte) = self.text.get(self.position) {
+                    match byte {
+                        b'"' => {
+                            self.string()?;
+                            continue;
+                        }
+                        b'{' | b'[' => depth += 1,
+                        b'}' | b']' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                self.position += 1;
+                                return Some(());
+                            }
+                        }
+                        _ => {}
+                    }
+                    self.position += 1;
+                }
+                None
+            }
+            _ => {
+                while self.text.get(self.position).is_some_and(|b| !matches!(b, b',' | b'}' | b']') && !b.is_ascii_whitespace()) {
+                    self.position += 1;
+                }
+                Some(())
+            }
+        }
+    }
+
+    /// Move to the value of `key` in the object or array at the current position
+    fn enter(&mut self, key: &Key) -> Option<()> {
+        match key {
+            Key::Field(field) => {
+                if !self.eat(b'{') {
+                    return None;
+                }
+                loop {
+                    let name = self.string()?;
+                    let found = serde_json::from_str::<String>(&format!("\"{}\"", name)).is_ok_and(|name| name == *field);
+                    if !self.eat(b':') {
+                        return None;
+                    }
+                    if found {
+                        self.skip_whitespace();
+                        return Some(());
+                    }
+                    self.skip_value()?;
+                    if !self.eat(b',') {
+                        return None;
+                    }
+                }
+            }
+            Key::Index(index) => {
+                if !self.eat(b'[') {
+                    return None;
+                }
+                for _ in 0..*index {
+                    self.skip_value()?;
+                    if !self.eat(b',') {
+                        return None;
+                    }
+                }
+                self.skip_whitespace();
+                Some(())
+            }
+        }
+    }
+}
+
+/// JSON: line of the value at `keys` in the config text
+fn line_of(text: &str, keys: &[Key]) -> Option<usize> {
+    let mut scanner = Scanner { text: text.as_bytes(), position: 0 };
+    for key in keys {
+        scanner.enter(key)?;
+    }
+    scanner.skip_whitespace();
+    Some(text.as_bytes()[..scanner.position].iter().filter(|b| **b == b'\n').count() + 1)
+}
+
+/// Whether a TOML or YAML line declares `field`: `field = `, `field:`, `- field:`, `[section.field]`
+fn declares(line: &str, field: &str) -> bool {
+    let line = line.trim_start().trim_start_matches("- ").trim_start_matches('[');
+    let head = line.split(['=', ':', ']']).next().unwrap_or_default();
+    head.split('.').any(|part| part.trim().trim_matches(['"', '\'']) == field)
+}
+
+/// TOML and YAML: line of a field, found by searching for its keys one after another
+fn line_of_key(text: &str, keys: &[Key]) -> Option<usize> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut line = 0;
+    for key in keys {
+        if let Key::Field(field) = key {
+            line += lines[line..].iter().position(|l| declares(l, field))?;
+        }
+    }
+    Some(line + 1)
+}
+
+fn keys(field: &str) -> Vec<Key> {
+    field.split('.').map(|f| Key::Field(f.to_string())).collect()
+}
+
+/// Whether `address` is usable as an OSC address: `/` first, no spaces or pattern characters
+fn osc_address_problem(address: &str) -> Option<String> {
+    if !address.starts_with('/') {
+        return Some(format!("OSC address '{}' must start with '/'", address));
+    }
+    address
+        .chars()
+        .find(|c| c.is_whitespace() || c.is_control() || "#*,?[]{}".contains(*c))
+        .map(|c| format!("OSC address '{}' must not contain '{}'", address, c.escape_default()))
+}
+
+fn host_port_problem(value: &str) -> Option<String> {
+    match value.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() => match port.parse::<u16>() {
+            Ok(0) | Err(_) => Some(format!("'{}' needs a port from 1 to 65535", value)),
+            Ok(_) => None,
+        },
+        _ => Some(format!("'{}' must be host:port", value)),
+    }
+}
+
+/// Checks serde can't do: OSC address syntax, ports, host:port values and min <= max
+fn check_values(config: &mut Config) -> Vec<(Vec<Key>, String)> {
+    let mut problems = Vec::new();
+    let osc = &config.osc;
+    let addresses = [
+        ("osc.transpose_path", &osc.transpose_path),
+        ("osc.transpose_up_path", &osc.transpose_up_path),
+        ("osc.transpose_down_path", &osc.transpose_down_path),
+        ("osc.octave_up_path", &osc.octave_up_path),
+        ("osc.octave_down_path", &osc.octave_down_path),
+        ("osc.transpose_norm_path", &osc.transpose_norm_path),
+        ("osc.custom_control_path", &osc.custom_control_path),
+        ("osc.command_path", &osc.command_path),
+        ("osc.setlist_next_path", &osc.setlist_next_path),
+        ("osc.setlist_prev_path", &osc.setlist_prev_path),
+        ("osc.sequence_start_path", &osc.sequence_start_path),
+        ("osc.sequence_stop_path", &osc.sequence_stop_path),
+        ("osc.pressure_parameter", &osc.pressure_parameter),
+        ("osc.program_parameter", &osc.program_parameter),
+        ("osc.sequence_parameter", &osc.sequence_parameter),
+        ("osc.raw_mirror_path", &osc.raw_mirror_path),
+    ];
+    for (field, address) in addresses {
+        if let Some(problem) = osc_address_problem(address) {
+            problems.push((keys(field), problem));
+        }
+    }
+    if let Some(address) = &osc.transpose_state_path {
+        if let Some(problem) = osc_address_problem(address) {
+            problems.push((keys("osc.transpose_state_path"), problem));
+        }
+    }
+    if !osc.note_path_template.contains("{note}") {
+        problems.push((keys("osc.note_path_template"), "must contain {note}".to_string()));
+    } else if let Some(problem) = osc_address_problem(&osc.note_path_template.replace("{note}", "C4")) {
+        problems.push((keys("osc.note_path_template"), problem));
+    }
+    for (cc, mapping) in &osc.cc_map {
+        if let Some(problem) = osc_address_problem(&mapping.address) {
+            problems.push((vec![Key::Field("osc".into()), Key::Field("cc_map".into()), Key::Field(cc.to_string()), Key::Field("address".into())], problem));
+        }
+    }
+    for (index, path) in osc.proxy_drop_paths.iter().enumerate() {
+        if let Some(problem) = osc_address_problem(path) {
+            problems.push((vec![Key::Field("osc".into()), Key::Field("proxy_drop_paths".into()), Key::Index(index)], problem));
+        }
+    }
+
+    let zero_port = "port 0 is not a valid destination".to_string();
+    if osc.listening_port == 0 {
+        problems.push((keys("osc.listening_port"), "port 0 would listen on a random port".to_string()));
+    }
+    if osc.sending_port == 0 {
+        problems.push((keys("osc.sending_port"), zero_port.clone()));
+    }
+    for (index, target) in osc.sending_targets.iter().enumerate() {
+        if target.port == 0 {
+            problems.push((vec![Key::Field("osc".into()), Key::Field("sending_targets".into()), Key::Index(index), Key::Field("port".into())], zero_port.clone()));
+        }
+    }
+    if config.mqtt.enabled && config.mqtt.broker_port == 0 {
+        problems.push((keys("mqtt.broker_port"), zero_port));
+    }
+    let host_ports = [
+        ("osc.proxy_target", Some(&osc.proxy_target).filter(|_| osc.proxy_enabled)),
+        ("osc.raw_mirror_target", osc.raw_mirror_target.as_ref()),
+        ("capabilities_http", config.capabilities_http.as_ref()),
+        ("metrics_http", config.metrics_http.as_ref()),
+        ("web_ui", config.web_ui.as_ref()),
+    ];
+    for (field, value) in host_ports {
+        if let Some(problem) = value.and_then(|value| host_port_problem(value)) {
+            problems.push((keys(field), problem));
+        }
+    }
+
+    // Swapped, since clamping to a range with min above max would panic
+    let transpose = &mut config.transpose;
+    if transpose.min > transpose.max {
+        problems.push((keys("transpose.min"), format!("{} is above transpose.max ({}); the two are swapped", transpose.min, transpose.max)));
+        std::mem::swap(&mut transpose.min, &mut transpose.max);
+    }
+    if transpose.octave_min > transpose.octave_max {
+        problems.push((keys("transpose.octave_min"), format!("{} is above transpose.octave_max ({}); the two are swapped", transpose.octave_min, transpose.octave_max)));
+        std::mem::swap(&mut transpose.octave_min, &mut transpose.octave_max);
+    }
+    if let Err(problem) = crate::general::sequence::check(&config.sequence) {
+        problems.push((keys("sequence.steps"), problem));
+    }
+    problems
+}
+
+/// Parse the config file text, collecting every problem instead of stopping at the first:
+/// unknown keys, fields of the wrong type (dropped, so they fall back to their default
+/// and the rest still loads) and invalid values. `None` when the file can't be used at all.
+pub fn parse(text: &str, format: ConfigFormat) -> (Option<Config>, Vec<Problem>) {
+    let mut problems = Vec::new();
+    let mut value = match config_format::to_value(text, format) {
+        Ok(value) => value,
+        Err((line, message)) => {
+            problems.push(Problem { line, field: String::new(), message, warning: false });
+            return (None, problems);
+        }
+    };
+    let line_of = |keys: &[Key]| if format == ConfigFormat::Json { line_of(text, keys) } else { line_of_key(text, keys) };
+    let mut unknown: Vec<Vec<Key>> = Vec::new();
+    let mut config = None;
+    for _ in 0..MAX_REPAIRS {
+        let mut on_ignored = |path: serde_ignored::Path| {
+            let mut keys = Vec::new();
+            ignored_keys(&path, &mut keys);
+            if !unknown.contains(&keys) {
+                unknown.push(keys);
+            }
+        };
+        let deserializer = serde_ignored::Deserializer::new(&value, &mut on_ignored);
+        let error = match serde_path_to_error::deserialize::<_, Config>(deserializer) {
+            Ok(parsed) => {
+                config = Some(parsed);
+                break;
+            }
+            Err(error) => error,
+        };
+        let keys = error_keys(error.path()).unwrap_or_default();
+        let message = error.into_inner().to_string();
+        // A missing required field can't be defaulted, only a list entry without it dropped;
+        // neither can an error outside any field
+        let missing = message.starts_with("missing field");
+        let repaired = (!missing || matches!(keys.last(), Some(Key::Index(_)))) && remove(&mut value, &keys);
+        let message = match (repaired, missing) {
+            (true, true) => format!("{} (entry ignored)", message),
+            (true, false) => format!("{} (ignored)", message),
+            (false, _) => message,
+        };
+        problems.push(Problem { line: line_of(&keys), field: field_name(&keys), message, warning: false });
+        if !repaired {
+            break;
+        }
+    }
+    for keys in unknown {
+        problems.push(Problem { line: line_of(&keys), field: field_name(&keys), message: "unknown key (ignored)".to_string(), warning: true });
+    }
+    if let Some(config) = &mut config {
+        for (keys, message) in check_values(config) {
+            problems.push(Problem { line: line_of(&keys), field: field_name(&keys), message, warning: false });
+        }
+    }
+    problems.sort_by_key(|problem| problem.line);
+    (config, problems)
+}
+
+/// Log the problems of a config file at startup
+pub fn report(path: &Path, problems: &[Problem]) {
+    for problem in problems {
+        if problem.warning {
+            log::warn!(target: "config", "[CONFIG] {} {}", path.display(), problem);
+        } else {
+            log::error!(target: "config", "[CONFIG] {} {}", path.display(), problem);
+        }
+    }
+}
+
+/// `--check-config`: validate the config file, list every problem and fail if there are errors
+pub fn run_check_config(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    let (_, problems) = parse(&text, ConfigFormat::of(path));
+    for problem in &problems {
+        crate::io::console::print_line(format!("{}: {} {}", if problem.warning { "warning" } else { "error" }, path.display(), problem));
+    }
+    let errors = problems.iter().filter(|problem| !problem.warning).count();
+    let warnings = problems.len() - errors;
+    if errors > 0 {
+        return Err(format!("{}: {} error(s), {} warning(s)", path.display(), errors, warnings).into());
+    }
+    crate::io::console::print_line(format!("{}: OK ({} warning(s))", path.display(), warnings));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Sections with required fields, appended after the fields under test
+    const REQUIRED_JSON: &str = r#""midi": { "input_port_name_substr": "", "output_port_name_substr": "" },
+  "mqtt": { "broker_host": "localhost", "broker_port": 1883, "base_topic": "t", "username": "", "password": "" },
+  "transpose": { "min": -12, "max": 12 }
+}"#;
+    const REQUIRED_TOML: &str = "[midi]\ninput_port_name_substr = \"\"\noutput_port_name_substr = \"\"\n\n[mqtt]\nbroker_host = \"localhost\"\nbroker_port = 1883\nbase_topic = \"t\"\nusername = \"\"\npassword = \"\"\n";
+
+    /// (line, field, warning) of every problem, in order
+    fn found(problems: &[Problem]) -> Vec<(Option<usize>, &str, bool)> {
+        problems.iter().map(|p| (p.line, p.field.as_str(), p.warning)).collect()
+    }
+
+    #[test]
+    fn unknown_keys_are_warnings_at_their_path() {
+        let text = format!("{{\n  \"osc\": {{\n    \"listening_port\": 9001,\n    \"listenig_port\": 9002\n  }},\n  \"colour\": true,\n  {}", REQUIRED_JSON);
+        let (config, problems) = parse(&text, ConfigFormat::Json);
+        assert_eq!(found(&problems), vec![(Some(4), "osc.listenig_port", true), (Some(6), "colour", true)]);
+        assert_eq!(config.unwrap().osc.listening_port, 9001);
+    }
+
+    #[test]
+    fn type_errors_fall_back_to_the_default() {
+        let text = format!(
+            "{{\n  \"osc\": {{\n    \"listening_port\": \"high\",\n    \"sending_targets\": [\n      {{ \"addr\": \"127.0.0.1\", \"port\": 9000 }},\n      {{ \"addr\": \"127.0.0.1\", \"port\": true }}\n    ]\n  }},\n  {}",
+            REQUIRED_JSON
+        );
+        let (config, problems) = parse(&text, ConfigFormat::Json);
+        // The port is required, so after dropping it the whole target is dropped
+        assert_eq!(
+            found(&problems),
+            vec![(Some(3), "osc.listening_port", false), (Some(6), "osc.sending_targets[1].port", false), (Some(6), "osc.sending_targets[1]", false)]
+        );
+        assert!(problems[1].message.ends_with("(ignored)"));
+        assert!(problems[2].message.ends_with("(entry ignored)"));
+        let config = config.unwrap();
+        assert_eq!(config.osc.listening_port, Config::default().osc.listening_port);
+        assert_eq!(config.osc.sending_targets.len(), 1);
+    }
+
+    #[test]
+    fn missing_required_fields_make_the_file_unusable() {
+        let (config, problems) = parse("{\n  \"osc\": {}\n}", ConfigFormat::Json);
+        assert!(config.is_none());
+        assert_eq!(found(&problems), vec![(Some(1), "", false)]);
+        assert!(problems[0].message.contains("missing field `midi`"));
+    }
+
+    #[test]
+    fn out_of_range_values_are_errors() {
+        let text = format!("[osc]\nlistening_port = 0\nsending_port = 70000\n\n[transpose]\nmin = 12\nmax = -12\n\n{}", REQUIRED_TOML);
+        let (config, problems) = parse(&text, ConfigFormat::Toml);
+        assert_eq!(found(&problems), vec![(Some(2), "osc.listening_port", false), (Some(3), "osc.sending_port", false), (Some(6), "transpose.min", false)]);
+        let transpose = config.unwrap().transpose;
+        assert_eq!((transpose.min, transpose.max), (-12, 12));
+    }
+
+    #[test]
+    fn invalid_osc_addresses_report_the_field() {
+        let text = "osc:\n  command_path: command\n  proxy_drop_paths:\n    - /ok\n    - /bad path\nmidi:\n  input_port_name_substr: ''\n  output_port_name_substr: ''\nmqtt:\n  broker_host: localhost\n  broker_port: 1883\n  base_topic: t\n  username: ''\n  password: ''\ntranspose:\n  min: -12\n  max: 12\n";
+        let problems = parse(text, ConfigFormat::Yaml).1;
+        assert_eq!(found(&problems), vec![(Some(2), "osc.command_path", false), (Some(3), "osc.proxy_drop_paths[1]", false)]);
+        assert!(problems[0].message.contains("must start with '/'"));
+    }
+
+    #[test]
+    fn syntax_errors_make_the_file_unusable() {
+        let (config, problems) = parse("{\n  \"osc\": {\n}", ConfigFormat::Json);
+        assert!(config.is_none());
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].field, "");
+    }
+}
@@ -0,0 +1,145 @@
+use std::path::{Path, PathBuf};
+
+/// Syntax of a config file, chosen by its extension
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+// Looked for in this order when no `--config` is given
+const EXTENSIONS: [&str; 4] = ["json", "toml", "yaml", "yml"];
+
+impl ConfigFormat {
+    /// `.toml` and `.yaml`/`.yml`; anything else is read as JSON
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("toml") => ConfigFormat::Toml,
+            Some("yaml" | "yml") => ConfigFormat::Yaml,
+            _ => ConfigFormat::Json,
+        }
+    }
+}
+
+/// `<stem>.json`, `.toml`, `.yaml` or `.yml`, whichever exists first (`<stem>.json` if none does)
+pub fn find(stem: &str) -> PathBuf {
+    EXTENSIONS
+        .iter()
+        .map(|extension| PathBuf::from(format!("{}.{}", stem, extension)))
+        .find(|path| path.exists())
+        .unwrap_or_else(|| PathBuf::from(format!("{}.json", stem)))
+}
+
+fn line_at(text: &str, offset: usize) -> usize {
+    text.as_bytes()[..offset.min(text.len())].iter().filter(|b| **b == b'\n').count() + 1
+}
+
+/// The config text as JSON, so all formats share the `Config` structs and their checks;
+/// a syntax error comes with its line
+pub fn to_value(text: &str, format: ConfigFormat) -> Result<serde_json::Value, (Option<usize>, String)> {
+    // The libraries append the position to their messages; it is reported separately
+    let message = |e: &dyn std::fmt::Display| e.to_string().split(" at line ").next().unwrap_or_default().to_string();
+    match format {
+        ConfigFormat::Json => serde_json::from_str(text).map_err(|e| (Some(e.line()), message(&e))),
+        ConfigFormat::Toml => toml::from_str(text).map_err(|e| (e.span().map(|span| line_at(text, span.start)), e.message().trim().replace('\n', ": "))),
+        ConfigFormat::Yaml => serde_yaml::from_str(text).map_err(|e| (e.location().map(|l| l.line()), message(&e))),
+    }
+}
+
+fn toml_value(value: &serde_json::Value) -> Result<toml_edit::Item, String> {
+    match value {
+        serde_json::Value::String(text) => Ok(toml_edit::value(text.as_str())),
+        serde_json::Value::Bool(flag) => Ok(toml_edit::value(*flag)),
+        serde_json::Value::Number(number) => match number.as_i64() {
+            Some(integer) => Ok(toml_edit::value(integer)),
+            None => number.as_f64().map(toml_edit::value).ok_or_else(|| format!("{} can't be written to TOML", number)),
+        },
+        _ => Err(format!("{} can't be written to TOML", value)),
+    }
+}
+
+/// `text` with `section.key` set to `value`, in the file's own format. TOML keeps its
+/// comments; YAML is refused, since rewriting it would drop them.
+pub fn set_value(text: &str, format: ConfigFormat, section: &str, key: &str, value: serde_json::Value) -> Result<String, String> {
+    match format {
+        ConfigFormat::Json => {
+            let mut root: serde_json::Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+            let object = root.as_object_mut().ok_or("config root is not an object")?;
+            let section_value = object.entry(section.to_string()).or_insert_with(|| serde_json::json!({}));
+            section_value
+                .as_object_mut()
+                .ok_or_else(|| format!("config section '{}' is not an object", section))?
+                .insert(key.to_string(), value);
+            serde_json::to_string_pretty(&root).map_err(|e| e.to_string())
+        }
+        ConfigFormat::Toml => {
+            let mut document: toml_edit::DocumentMut = text.parse().map_err(|e: toml_edit::TomlError| e.to_string())?;
+            document
+                .entry(section)
+                .or_insert_with(toml_edit::table)
+                .as_table_like_mut()
+                .ok_or_else(|| format!("config section '{}' is not a table", section))?
+                .insert(key, toml_value(&value)?);
+            Ok(document.to_string())
+        }
+        ConfigFormat::Yaml => Err(format!("YAML configs are not edited automatically; set {}.{}: {} yourself", section, key, value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn format_follows_extension() {
+        assert_eq!(ConfigFormat::of(Path::new("config.TOML")), ConfigFormat::Toml);
+        assert_eq!(ConfigFormat::of(Path::new("config.yml")), ConfigFormat::Yaml);
+        assert_eq!(ConfigFormat::of(Path::new("config.piano.json")), ConfigFormat::Json);
+        assert_eq!(ConfigFormat::of(Path::new("config")), ConfigFormat::Json);
+    }
+
+    #[test]
+    fn all_formats_give_the_same_value() {
+        let expected = json!({"osc": {"listening_port": 9069, "sending_enabled": true}, "transpose": {"min": -12}});
+        let json = r#"{"osc": {"listening_port": 9069, "sending_enabled": true}, "transpose": {"min": -12}}"#;
+        let toml = "[osc]\nlistening_port = 9069\nsending_enabled = true\n\n[transpose]\nmin = -12\n";
+        let yaml = "osc:\n  listening_port: 9069\n  sending_enabled: true\ntranspose:\n  min: -12\n";
+        assert_eq!(to_value(json, ConfigFormat::Json).unwrap(), expected);
+        assert_eq!(to_value(toml, ConfigFormat::Toml).unwrap(), expected);
+        assert_eq!(to_value(yaml, ConfigFormat::Yaml).unwrap(), expected);
+    }
+
+    #[test]
+    fn syntax_errors_report_their_line() {
+        assert_eq!(to_value("{\n  \"osc\": {,\n}", ConfigFormat::Json).unwrap_err().0, Some(2));
+        assert_eq!(to_value("[osc]\nlistening_port = \n", ConfigFormat::Toml).unwrap_err().0, Some(2));
+        assert_eq!(to_value("osc:\n  a: 1\n b: 2\n", ConfigFormat::Yaml).unwrap_err().0, Some(3));
+    }
+
+    #[test]
+    fn set_value_in_json() {
+        let text = set_value(r#"{"osc": {"sending_port": 9000}}"#, ConfigFormat::Json, "osc", "sending_enabled", json!(true)).unwrap();
+        assert_eq!(to_value(&text, ConfigFormat::Json).unwrap(), json!({"osc": {"sending_port": 9000, "sending_enabled": true}}));
+        let text = set_value("{}", ConfigFormat::Json, "transpose", "min", json!(-12)).unwrap();
+        assert_eq!(to_value(&text, ConfigFormat::Json).unwrap(), json!({"transpose": {"min": -12}}));
+        assert!(set_value(r#"{"osc": 1}"#, ConfigFormat::Json, "osc", "sending_enabled", json!(true)).is_err());
+    }
+
+    #[test]
+    fn set_value_in_toml_keeps_comments() {
+        let text = "# my rig\n[osc]\nsending_port = 9000 # VRChat\n";
+        let text = set_value(text, ConfigFormat::Toml, "osc", "sending_enabled", json!(true)).unwrap();
+        assert!(text.contains("# my rig") && text.contains("# VRChat"));
+        assert_eq!(to_value(&text, ConfigFormat::Toml).unwrap(), json!({"osc": {"sending_port": 9000, "sending_enabled": true}}));
+        let text = set_value(&text, ConfigFormat::Toml, "transpose", "min", json!(-12)).unwrap();
+        assert_eq!(to_value(&text, ConfigFormat::Toml).unwrap()["transpose"], json!({"min": -12}));
+        assert!(set_value("", ConfigFormat::Toml, "osc", "sending_addresses", json!([])).is_err());
+    }
+
+    #[test]
+    fn yaml_is_not_rewritten() {
+        assert!(set_value("osc: {}\n", ConfigFormat::Yaml, "osc", "sending_enabled", json!(true)).is_err());
+    }
+}
@@ -0,0 +1,174 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::general::logging::{self, LogLevel};
+
+/// Debug log categories that can be switched individually (`debug osc on`); each is the
+/// log subsystem of the same name
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugCategory {
+    Midi,
+    Osc,
+    Mqtt,
+    Config,
+}
+
+impl DebugCategory {
+    pub const ALL: [DebugCategory; 4] = [DebugCategory::Midi, DebugCategory::Osc, DebugCategory::Mqtt, DebugCategory::Config];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DebugCategory::Midi => "midi",
+            DebugCategory::Osc => "osc",
+            DebugCategory::Mqtt => "mqtt",
+            DebugCategory::Config => "config",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+// Bumped on every debug change so a running timer knows it was superseded
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Log level of a subsystem with debug on or off: its configured level, raised to `debug`
+/// while debugging and capped at `info` afterwards
+fn debug_level(subsystem: &str, enabled: bool) -> LogLevel {
+    let configured = crate::get_config().logging.level_of(subsystem);
+    if enabled { configured.max(LogLevel::Debug) } else { configured.min(LogLevel::Info) }
+}
+
+/// Whether debug logging of one category is enabled
+pub fn category_enabled(category: DebugCategory) -> bool {
+    logging::enabled(category.name(), LogLevel::Debug)
+}
+
+/// Switch a single category; general debug output (`AppState::debug`) is left alone
+pub fn set_category(category: DebugCategory, enabled: bool) {
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    logging::set_level(category.name(), debug_level(category.name(), enabled));
+}
+
+// Set general debug output and every category at once
+fn store_all(enabled: bool) {
+    crate::state().debug.store(enabled, Ordering::SeqCst);
+    for subsystem in logging::SUBSYSTEMS {
+        logging::set_level(subsystem, debug_level(subsystem, enabled));
+    }
+}
+
+/// Switch all debug logging on or off; cancels a pending timed debug
+pub fn set_enabled(enabled: bool) {
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    store_all(enabled);
+}
+
+/// Enable debug logging for `duration`, then switch it off again
+/// (unless debug was changed in the meantime)
+pub fn enable_for(duration: Duration) {
+    let generation = GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    store_all(true);
+    thread::spawn(move || {
+        let deadline = Instant::now() + duration;
+        while Instant::now() < deadline {
+            if crate::state().exit.load(Ordering::SeqCst) || GENERATION.load(Ordering::SeqCst) != generation {
+                return;
+            }
+            thread::sleep(Duration::from_millis(200));
+        }
+        if GENERATION.compare_exchange(generation, generation + 1, Ordering::SeqCst, Ordering::SeqCst).is_ok() {
+            store_all(false);
+            log::info!(target: "app", "Debug disabled (timer expired)");
+        }
+    });
+}
+
+fn parse_state(text: &str) -> Option<bool> {
+    match text.trim() {
+        "on" | "enable" | "1" | "true" => Some(true),
+        "off" | "disable" | "0" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parse a duration like `90`, `30s`, `5m` or `1h` (plain numbers are seconds)
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let text = text.trim().to_ascii_lowercase();
+    let (number, unit) = match text.find(|c: char| !c.is_ascii_digit()) {
+        Some(pos) => text.split_at(pos),
+        None => (text.as_str(), "s"),
+    };
+    let value: u64 = number.parse().ok()?;
+    let secs = match unit.trim() {
+        "s" | "sec" => value,
+        "m" | "min" => value.checked_mul(60)?,
+        "h" => value.checked_mul(3600)?,
+        _ => return None,
+    };
+    if secs == 0 { None } else { Some(Duration::from_secs(secs)) }
+}
+
+/// Apply a debug command argument: `on`/`off` (and aliases), optionally followed by a
+/// duration (`on 5m`), a bare duration (`5m`), or a category switch (`osc on`).
+/// Returns a description of the change.
+pub fn apply_command(arg: &str) -> Option<String> {
+    let arg = arg.trim().to_ascii_lowercase();
+    if let Some((name, state)) = arg.split_once(' ') {
+        if let Some(category) = DebugCategory::from_name(name) {
+            let enabled = parse_state(state)?;
+            set_category(category, enabled);
+            return Some(format!("Debug {} {}", category.name(), if enabled { "enabled" } else { "disabled" }));
+        }
+    }
+    let (state, rest) = match arg.split_once(' ') {
+        Some((state, rest)) => (state, rest.trim()),
+        None => (arg.as_str(), ""),
+    };
+    match state {
+        "on" | "enable" | "1" | "true" if rest.is_empty() => {
+            set_enabled(true);
+            Some("Debug enabled".to_string())
+        }
+        "off" | "disable" | "0" | "false" if rest.is_empty() => {
+            set_enabled(false);
+            Some("Debug disabled".to_string())
+        }
+        "on" | "enable" | "1" | "true" => {
+            let duration = parse_duration(rest)?;
+            enable_for(duration);
+            Some(format!("Debug enabled for {}s", duration.as_secs()))
+        }
+        _ if rest.is_empty() => {
+            let duration = parse_duration(state)?;
+            enable_for(duration);
+            Some(format!("Debug enabled for {}s", duration.as_secs()))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("30s"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_duration(" 5M "), Some(Duration::from_secs(300)));
+        assert_eq!(parse_duration("2 min"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1h"), Some(Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn invalid_durations() {
+        assert_eq!(parse_duration("0"), None);
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("5d"), None);
+        assert_eq!(parse_duration("-5"), None);
+        assert_eq!(parse_duration("m"), None);
+        assert_eq!(parse_duration("18446744073709551615h"), None);
+    }
+}
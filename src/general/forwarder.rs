@@ -1,40 +1,250 @@
-use std::sync::mpsc::{Receiver, Sender};
+use std::collections::HashMap;
 use std::thread;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::{Duration, Instant};
+use crate::general::output_buffer::PacedOutput;
+use crate::general::queue::{QueueReceiver, QueueSender};
 use std::sync::atomic::Ordering;
 
-/// Spawn a forwarding thread that owns the provided `conn_out` and listens on `rx`.
+/// Base transpose and octave shift plus the momentary offsets (lean pedal, hold, LFO)
+fn effective_transpose(midi: &crate::MidiConfig) -> i32 {
+    crate::state().transpose.load(Ordering::Relaxed)
+        + 12 * crate::state().octave.load(Ordering::Relaxed)
+        + crate::transpose::lean_offset()
+        + crate::transpose::hold_offset(midi)
+        + crate::general::lfo::offset()
+}
+
+/// Output note of `note` sent on `channel` (after a zone's remap): the part of transpose `t`
+/// realized with notes (see `note_shift`) plus the zone offset, fitted into
+/// `midi.note_min`..`midi.note_max` (None = dropped)
+fn landing_note(note: u8, channel: u8, t: i32, zone: Option<(i32, Option<u8>)>, midi: &crate::MidiConfig) -> Option<u8> {
+    crate::transpose::fit_note(note as i32 + crate::transpose::note_shift(t, channel, midi) + zone.map_or(0, |(offset, _)| offset), midi)
+}
+
+/// The note the forwarder currently turns `note` on input `channel` into (None = dropped)
+pub fn transposed_note(note: u8, channel: u8) -> Option<u8> {
+    let config = crate::get_config();
+    let zone = crate::general::zones::lookup(&config.midi.zones, note);
+    let channel = zone.and_then(|(_, zone_channel)| zone_channel).unwrap_or(channel);
+    landing_note(note, channel, effective_transpose(&config.midi), zone, &config.midi)
+}
+
+/// Apply `midi.channel_map` to a channel message: remap its channel in place, or return false
+/// when the channel is dropped. System messages have no channel and always pass.
+fn map_channel(msg: &mut [u8], map: &HashMap<u8, Option<u8>>) -> bool {
+    if map.is_empty() || !matches!(msg.first(), Some(0x80..=0xEF)) {
+        return true;
+    }
+    match map.get(&((msg[0] & 0x0F) + 1)) {
+        Some(Some(channel)) => {
+            msg[0] = (msg[0] & 0xF0) | (channel.clamp(&1, &16) - 1);
+            true
+        }
+        Some(None) => false,
+        None => true,
+    }
+}
+
+/// Spawn a forwarding thread that owns the provided output `targets` and listens on `rx`.
 /// Each incoming raw MIDI message is transposed (using the global
-/// `crate::TRANSPOSE_SEMITONES`) and forwarded to the output port.
-/// Also sends transposed MIDI to OSC if enabled and configured.
+/// `crate::state().transpose` plus the octave shift and the lean pedal, hold and LFO offsets) and forwarded to the output port.
+/// Messages are tagged with their input source; note-offs are sent for the note their note-on
+/// (from the same source) was sent as, so changing the transpose while notes are held never
+/// leaves notes hanging, even when two inputs play the same key. Poly aftertouch of a held note
+/// follows it the same way.
+/// With `midi.pitch_bend_mode` part or all of the transpose is realized via pitch bend.
+/// Notes inside a `midi.zones` range get the zone's extra transpose and output channel.
+/// Notes that would land outside `midi.note_min`..`midi.note_max` are dropped, folded or
+/// clamped (`midi.out_of_range`).
+/// Also sends transposed MIDI to OSC if enabled and configured; without any target
+/// (observer mode) that is the only output.
+/// Output is paced through a `PacedOutput` buffer when `midi.output_bytes_per_sec` is set.
 pub fn spawn_forwarder(
-    mut conn_out: midir::MidiOutputConnection, 
-    rx: Receiver<Vec<u8>>,
-    osc_transposed_tx: Option<Sender<Vec<u8>>>
+    targets: Vec<crate::output::MidiTarget>,
+    rx: QueueReceiver<(u8, Vec<u8>, Instant)>,
+    osc_transposed_tx: Vec<QueueSender<Vec<u8>>>
 ) -> thread::JoinHandle<()> {
     thread::spawn(move || {
-        for msg in rx {
-            if crate::EXIT_FLAG.load(std::sync::atomic::Ordering::SeqCst) {
+        let mut output = PacedOutput::new(targets);
+        // Output channel and note of every sounding (input source, channel, input note)
+        let mut sounding: HashMap<(u8, u8, u8), (u8, u8)> = HashMap::new();
+        let mut bend = crate::transpose::PitchBendState::default();
+        let mut takeover = crate::general::takeover::SoftTakeover::default();
+        loop {
+            // Outputs unplugged or plugged back in
+            output.update_targets(crate::output::take_changes());
+            // CC values set by a profile
+            for msg in crate::general::takeover::take_pending() {
+                output.send(msg);
+            }
+            // Custom control values held back by the OSC rate limit
+            takeover.flush_due();
+            // Wake up in time for the next paced message or rate limited value
+            let timeout = [output.flush_due(), takeover.next_due_in()].into_iter().flatten().min().unwrap_or(Duration::from_millis(100));
+            let (source, msg, received) = match rx.recv_timeout(timeout) {
+                Ok(tagged) => tagged,
+                Err(RecvTimeoutError::Timeout) => {
+                    // Transpose changed while idle: move the bend of channels realized via pitch bend
+                    let midi = &crate::get_config().midi;
+                    for msg in bend.refresh(effective_transpose(midi), midi) {
+                        output.send(msg);
+                    }
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            if crate::state().exit.load(std::sync::atomic::Ordering::SeqCst) {
                 break;
             }
             if msg.is_empty() {
                 continue;
             }
+            // One config snapshot per message, so a reload never mixes two configs in one message
+            let config = crate::get_config();
+            let midi = &config.midi;
+            // Drop/remap channels first, so everything below sees the output channel
+            let mut msg = msg;
+            if !map_channel(&mut msg, &midi.channel_map) {
+                continue;
+            }
+            // Active Sensing is regenerated by some interfaces; optionally keep it off the output
+            if msg[0] == 0xFE && midi.strip_active_sensing {
+                continue;
+            }
+            // MIDI clock drives the transpose LFO and sequence bars (clock itself is forwarded)
+            crate::general::lfo::on_midi(&msg);
+            crate::general::sequence::on_midi(&msg);
+            // Lean pedal: consumed unless configured to pass through
+            if crate::transpose::update_lean(&msg, midi) && !midi.lean_pass_through {
+                continue;
+            }
+            // Hold pedal/note: momentary offset, the trigger itself is never forwarded
+            if crate::transpose::update_hold(&msg, midi) {
+                continue;
+            }
+            // Mapped custom controls; a CC not yet picked up after a profile set it is held back
+            if !takeover.process(&msg, &config) {
+                continue;
+            }
             let mut out_msg = msg;
-            let t = crate::TRANSPOSE_SEMITONES.load(Ordering::Relaxed);
-            crate::transpose::apply_transpose(&mut out_msg, t as i32);
-            
-            // Send MIDI output
-            if let Err(err) = conn_out.send(&out_msg) {
-                eprintln!("Error sending MIDI message to output: {}", err);
+            // Destinations that ignore aftertouch can get it as a CC (e.g. expression)
+            if crate::state().aftertouch_to_cc.load(Ordering::Relaxed) {
+                crate::transpose::channel_pressure_to_cc(&mut out_msg, midi.aftertouch_cc);
             }
-            
-            // Send transposed MIDI to OSC if enabled and configured for transposed
-            if let Some(ref osc_tx) = osc_transposed_tx {
-                if crate::OSC_SENDING_ENABLED.load(Ordering::SeqCst) && !crate::OSC_SEND_ORIGINAL.load(Ordering::SeqCst) {
-                    let _ = osc_tx.send(out_msg.clone());
+            let t = effective_transpose(midi);
+            let input_note = (out_msg.len() > 2).then(|| (source, out_msg[0] & 0x0F, out_msg[1]));
+            // Keyboard zones: the notes in a zone's range get its extra transpose and channel
+            let zone = input_note.filter(|_| matches!(out_msg[0] & 0xF0, 0x80 | 0x90 | 0xA0)).and_then(|(_, _, note)| crate::general::zones::lookup(&midi.zones, note));
+            if let Some((_, Some(channel))) = zone {
+                out_msg[0] = (out_msg[0] & 0xF0) | channel;
+            }
+            // Where a note lands, fitted into midi.note_min/note_max (None = dropped)
+            let landing = input_note.and_then(|(_, _, note)| landing_note(note, out_msg[0] & 0x0F, t, zone, midi));
+            for msg in bend.apply(&mut out_msg, t, midi) {
+                output.send(msg);
+            }
+            if let Some((offset, _)) = zone {
+                crate::transpose::apply_transpose(&mut out_msg, offset);
+            }
+            if let Some(key) = input_note {
+                match out_msg[0] & 0xF0 {
+                    // Notes transposed outside midi.note_min/note_max follow midi.out_of_range
+                    0x90 if out_msg[2] > 0 => {
+                        let Some(note) = landing else {
+                            log::debug!(target: "midi", "[MIDI] Note {} transposed outside the note range, dropped", key.2);
+                            continue;
+                        };
+                        out_msg[1] = note;
+                        // Key struck again without a note-off in between: release what it sounded
+                        // as before, which may be another note after a transpose change
+                        if let Some((channel, held)) = sounding.insert(key, (out_msg[0] & 0x0F, note)) {
+                            output.send(vec![0x80 | channel, held, 0]);
+                        }
+                    }
+                    0x80 | 0x90 => {
+                        if let Some((channel, note)) = sounding.remove(&key) {
+                            out_msg[0] = (out_msg[0] & 0xF0) | channel;
+                            out_msg[1] = note;
+                        } else {
+                            let Some(note) = landing else { continue };
+                            out_msg[1] = note;
+                        }
+                    }
+                    // Poly aftertouch goes to the channel and pitch the held note sounds at
+                    0xA0 => {
+                        if let Some(&(channel, note)) = sounding.get(&key) {
+                            out_msg[0] = 0xA0 | channel;
+                            out_msg[1] = note;
+                        } else {
+                            let Some(note) = landing else { continue };
+                            out_msg[1] = note;
+                        }
+                    }
+                    _ => {}
                 }
             }
+            
+            // Send MIDI output (buffered when paced)
+            output.send(out_msg.clone());
+            crate::general::latency::record(received);
+            
+            // Send transposed MIDI to OSC if sending and the transposed stream are enabled
+            if crate::state().osc_sending.load(Ordering::SeqCst) && crate::state().osc_transposed.load(Ordering::SeqCst) {
+                crate::osc_sender::send_to_targets(&osc_transposed_tx, &out_msg);
+            }
+        }
+        // Receiver closed -> release the notes still sounding (plus All Notes Off for
+        // anything missed), flush what is left, then the thread exits
+        for (channel, note) in sounding.into_values() {
+            output.send(vec![0x80 | channel, note, 0]);
+        }
+        crate::general::takeover::all_notes_off();
+        for msg in crate::general::takeover::take_pending() {
+            output.send(msg);
         }
-        // Receiver closed -> thread exits
+        output.drain();
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel_map(entries: &[(u8, Option<u8>)]) -> HashMap<u8, Option<u8>> {
+        entries.iter().copied().collect()
+    }
+
+    #[test]
+    fn channel_map_remaps_and_drops() {
+        let map = channel_map(&[(1, Some(10)), (2, None)]);
+        let mut note_on = [0x90, 60, 100];
+        assert!(map_channel(&mut note_on, &map));
+        assert_eq!(note_on, [0x99, 60, 100]);
+        assert!(!map_channel(&mut [0xB1, 7, 100], &map));
+        let mut unlisted = [0xE2, 0, 64];
+        assert!(map_channel(&mut unlisted, &map));
+        assert_eq!(unlisted, [0xE2, 0, 64]);
+    }
+
+    #[test]
+    fn channel_map_clamps_targets_and_ignores_system_messages() {
+        let map = channel_map(&[(1, Some(0)), (2, Some(40))]);
+        let mut low = [0x80, 60, 0];
+        assert!(map_channel(&mut low, &map));
+        assert_eq!(low, [0x80, 60, 0]);
+        let mut high = [0x81, 60, 0];
+        assert!(map_channel(&mut high, &map));
+        assert_eq!(high, [0x8F, 60, 0]);
+        let mut clock = [0xF8];
+        assert!(map_channel(&mut clock, &channel_map(&[(9, None)])));
+        assert_eq!(clock, [0xF8]);
+    }
+
+    #[test]
+    fn empty_channel_map_passes_everything() {
+        let mut msg = [0x95, 60, 100];
+        assert!(map_channel(&mut msg, &HashMap::new()));
+        assert_eq!(msg, [0x95, 60, 100]);
+    }
+}
@@ -0,0 +1,83 @@
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+use rosc::OscType;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static STEP_MS: AtomicU64 = AtomicU64::new(0);
+// Transpose the glide is heading for, while GLIDING
+static TARGET: AtomicI32 = AtomicI32::new(0);
+static GLIDING: AtomicBool = AtomicBool::new(false);
+
+/// Take the initial state from the config
+pub fn init(config: &crate::TransposeConfig) {
+    ENABLED.store(config.glide, Ordering::SeqCst);
+    STEP_MS.store(config.glide_step_ms.max(1), Ordering::SeqCst);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// Switch glide on or off; switching it off lands a running glide on its target at once
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::SeqCst);
+    if !enabled && GLIDING.swap(false, Ordering::SeqCst) {
+        crate::apply_transpose(TARGET.load(Ordering::SeqCst));
+    }
+    log::info!(target: "app", "Transpose glide {}", if enabled { "enabled" } else { "disabled" });
+}
+
+/// Hand a (clamped) transpose change to the glide. Returns true when it is taken over and
+/// applied in 1-semitone steps; false when it should be applied directly (glide off, or a
+/// change of one semitone or less, which also ends a running glide).
+pub fn begin(target: i32) -> bool {
+    TARGET.store(target, Ordering::SeqCst);
+    let current = crate::state().transpose.load(Ordering::SeqCst);
+    let glide = is_enabled() && (target - current).abs() > 1;
+    GLIDING.store(glide, Ordering::SeqCst);
+    glide
+}
+
+/// Where the transpose is heading: the glide target while gliding, else the current value
+pub fn target() -> i32 {
+    if GLIDING.load(Ordering::SeqCst) {
+        TARGET.load(Ordering::SeqCst)
+    } else {
+        crate::state().transpose.load(Ordering::SeqCst)
+    }
+}
+
+fn send_state(value: i32) {
+    if let Some(path) = &crate::get_config().osc.transpose_state_path {
+        let target = crate::osc_sender::current_target();
+        if let Err(e) = crate::osc_sender::send_single_osc_message(&target, path, vec![OscType::Int(value)]) {
+            log::error!(target: "osc", "[OSC] Failed to send transpose state: {}", e);
+        }
+    }
+}
+
+/// Spawn the thread stepping a running glide towards its target every `transpose.glide_step_ms`,
+/// and sending every change of the effective transpose to `osc.transpose_state_path`
+pub fn spawn_glide_thread() -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut last_sent = None;
+        while !crate::state().exit.load(Ordering::SeqCst) {
+            if GLIDING.load(Ordering::SeqCst) {
+                let current = crate::state().transpose.load(Ordering::SeqCst);
+                let target = TARGET.load(Ordering::SeqCst);
+                if current == target {
+                    GLIDING.store(false, Ordering::SeqCst);
+                } else {
+                    crate::apply_transpose(current + (target - current).signum());
+                }
+            }
+            let effective = crate::state().transpose.load(Ordering::SeqCst);
+            if last_sent != Some(effective) {
+                send_state(effective);
+                last_sent = Some(effective);
+            }
+            thread::sleep(Duration::from_millis(STEP_MS.load(Ordering::SeqCst).max(1)));
+        }
+    })
+}
@@ -0,0 +1,97 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// One transpose change
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Change {
+    /// Running number of the change in this session (1-based)
+    pub seq: u64,
+    /// Wall clock time in milliseconds since the Unix epoch
+    pub time_ms: u64,
+    pub transpose: i32,
+    pub previous: i32,
+    #[serde(skip)]
+    pub at: Instant,
+}
+
+struct History {
+    started: Instant,
+    changes: VecDeque<Change>,
+}
+
+static HISTORY: Mutex<Option<History>> = Mutex::new(None);
+// Sequence number of the latest change; MQTT publishes every change after the one it saw last
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+fn with_history<R>(f: impl FnOnce(&mut History) -> R) -> Option<R> {
+    let mut guard = HISTORY.lock().ok()?;
+    let history = guard.get_or_insert_with(|| History { started: Instant::now(), changes: VecDeque::new() });
+    Some(f(history))
+}
+
+/// Start the session clock the console times are relative to
+pub fn start() {
+    with_history(|_| ());
+}
+
+/// Record a transpose change; the oldest entries are dropped beyond `transpose.history_size`
+pub fn record(previous: i32, transpose: i32) {
+    if previous == transpose {
+        return;
+    }
+    let capacity = crate::get_config().transpose.history_size;
+    if capacity == 0 {
+        return;
+    }
+    with_history(|h| {
+        let change = Change {
+            seq: GENERATION.load(Ordering::SeqCst) + 1,
+            time_ms: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0),
+            transpose,
+            previous,
+            at: Instant::now(),
+        };
+        while h.changes.len() >= capacity {
+            h.changes.pop_front();
+        }
+        h.changes.push_back(change);
+    });
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Buffered changes, oldest first
+pub fn changes() -> Vec<Change> {
+    with_history(|h| h.changes.iter().cloned().collect()).unwrap_or_default()
+}
+
+/// Buffered changes with a sequence number above `seq`
+pub fn changes_since(seq: u64) -> Vec<Change> {
+    with_history(|h| h.changes.iter().filter(|c| c.seq > seq).cloned().collect()).unwrap_or_default()
+}
+
+/// The whole buffer as a JSON array (trend graph data)
+pub fn json() -> String {
+    serde_json::to_string(&changes()).unwrap_or_default()
+}
+
+/// Console lines for the last `limit` changes, with the time into the session
+pub fn describe(limit: usize) -> Vec<String> {
+    with_history(|h| {
+        let skip = h.changes.len().saturating_sub(limit);
+        h.changes
+            .iter()
+            .skip(skip)
+            .map(|c| {
+                let secs = c.at.duration_since(h.started).as_secs();
+                format!("{}:{:02}:{:02}  {:+} -> {:+}", secs / 3600, secs / 60 % 60, secs % 60, c.previous, c.transpose)
+            })
+            .collect()
+    })
+    .unwrap_or_default()
+}
@@ -0,0 +1,125 @@
+use std::sync::atomic::Ordering;
+
+/// Environment variable selecting the instance name (overrides `instance_name` in config)
+pub const INSTANCE_ENV_VAR: &str = "TRANSPOSER_INSTANCE";
+
+/// Normalize an instance name to a topic/ID-safe slug (lowercase ASCII, digits, `_`).
+/// Returns `None` for names that are empty after normalization.
+pub fn sanitize_instance_name(name: &str) -> Option<String> {
+    let slug: String = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    let slug = slug.trim_matches('_').to_string();
+    if slug.is_empty() { None } else { Some(slug) }
+}
+
+/// Instance name requested via environment, if any
+pub fn instance_from_env() -> Option<String> {
+    std::env::var(INSTANCE_ENV_VAR).ok().and_then(|v| sanitize_instance_name(&v))
+}
+
+/// Active instance name from the loaded configuration
+pub fn instance_name() -> Option<String> {
+    crate::get_config().instance_name.clone()
+}
+
+/// Append `_<instance>` to `base` when running as a named instance
+pub fn with_suffix(base: &str) -> String {
+    match instance_name() {
+        Some(name) => format!("{}_{}", base, name),
+        None => base.to_string(),
+    }
+}
+
+/// Per-instance config file (`config.<instance>.json`, `.toml` or `.yaml`), used when present
+pub fn config_path_for(instance: Option<&str>) -> std::path::PathBuf {
+    match instance {
+        Some(name) => crate::general::config_format::find(&format!("config.{}", name)),
+        None => crate::general::config_format::find("config"),
+    }
+}
+
+/// Runtime state file of a named instance (`state.<instance>.json`); instances never share one.
+/// Without an instance name nothing is persisted, so a plain start always begins from the config.
+pub fn state_file_path() -> Option<std::path::PathBuf> {
+    instance_name().map(|name| std::path::PathBuf::from(format!("state.{}.json", name)))
+}
+
+/// Config files of other instances in the working directory (`config.json`, `config.<name>.toml`,
+/// ...) whose `osc.listening_port` is `port`
+pub fn configs_listening_on(port: u16) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(".") else { return Vec::new() };
+    let own = crate::config_path().and_then(|path| path.file_name()).map(|name| name.to_os_string());
+    let mut files: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let file_name = path.file_name()?.to_str()?.to_string();
+            let format = crate::general::config_format::ConfigFormat::of(&path);
+            let is_config = file_name.starts_with("config.") && ["json", "toml", "yaml", "yml"].iter().any(|ext| file_name.ends_with(&format!(".{}", ext)));
+            if !is_config || own.as_deref() == path.file_name() {
+                return None;
+            }
+            let value = crate::general::config_format::to_value(&std::fs::read_to_string(&path).ok()?, format).ok()?;
+            (value.pointer("/osc/listening_port")?.as_u64()? == port as u64).then_some(file_name)
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Runtime values persisted between launches of the same instance
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct InstanceState {
+    pub transpose: i32,
+}
+
+/// Load the persisted state of this named instance, if a readable state file exists
+pub fn load_state() -> Option<InstanceState> {
+    let path = state_file_path()?;
+    let text = std::fs::read_to_string(&path).ok()?;
+    match serde_json::from_str::<InstanceState>(&text) {
+        Ok(state) => {
+            log::debug!(target: "app", "[STATE] Restored state from {}", path.display());
+            Some(state)
+        }
+        Err(err) => {
+            log::warn!(target: "app", "[STATE] Ignoring invalid {}: {}", path.display(), err);
+            None
+        }
+    }
+}
+
+/// Write the current runtime state of this named instance to its state file
+pub fn save_state() {
+    let Some(path) = state_file_path() else { return };
+    let state = InstanceState {
+        transpose: crate::state().transpose.load(Ordering::SeqCst),
+    };
+    match serde_json::to_string_pretty(&state) {
+        Ok(text) => {
+            if let Err(err) = std::fs::write(&path, text) {
+                log::error!(target: "app", "[STATE] Failed to write {}: {}", path.display(), err);
+            } else if crate::is_debug_enabled() {
+                log::debug!(target: "app", "[STATE] Saved state to {}", path.display());
+            }
+        }
+        Err(err) => log::error!(target: "app", "[STATE] Failed to serialize state: {}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instance_names() {
+        assert_eq!(sanitize_instance_name("Piano"), Some("piano".to_string()));
+        assert_eq!(sanitize_instance_name(" Drum Pads 2 "), Some("drum_pads_2".to_string()));
+        assert_eq!(sanitize_instance_name("../keys"), Some("keys".to_string()));
+        assert_eq!(sanitize_instance_name(" - "), None);
+        assert_eq!(sanitize_instance_name(""), None);
+    }
+}
@@ -0,0 +1,96 @@
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Running statistics of the MIDI thru delay: from the input callback to the forwarder
+/// handing the message to the outputs (queueing and processing; not the
+/// `midi.output_bytes_per_sec` pacing delay)
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct LatencyStats {
+    pub count: u64,
+    /// Microseconds
+    pub last_us: u64,
+    pub min_us: u64,
+    pub max_us: u64,
+    pub sum_us: u64,
+    /// Smoothed variation between consecutive messages (RFC 3550 interarrival jitter)
+    pub jitter_us: f64,
+}
+
+impl LatencyStats {
+    const EMPTY: LatencyStats = LatencyStats { count: 0, last_us: 0, min_us: 0, max_us: 0, sum_us: 0, jitter_us: 0.0 };
+
+    pub fn avg_us(&self) -> f64 {
+        if self.count == 0 { 0.0 } else { self.sum_us as f64 / self.count as f64 }
+    }
+}
+
+static STATS: Mutex<LatencyStats> = Mutex::new(LatencyStats::EMPTY);
+
+/// Account one message received at `received` that is being sent now
+pub fn record(received: Instant) {
+    let micros = received.elapsed().as_micros().min(u64::MAX as u128) as u64;
+    let Ok(mut s) = STATS.lock() else { return };
+    if s.count == 0 {
+        s.min_us = micros;
+    } else {
+        let delta = (micros as f64 - s.last_us as f64).abs();
+        s.jitter_us += (delta - s.jitter_us) / 16.0;
+    }
+    s.count += 1;
+    s.last_us = micros;
+    s.min_us = s.min_us.min(micros);
+    s.max_us = s.max_us.max(micros);
+    s.sum_us = s.sum_us.saturating_add(micros);
+}
+
+pub fn snapshot() -> LatencyStats {
+    STATS.lock().map(|s| *s).unwrap_or(LatencyStats::EMPTY)
+}
+
+/// Start over, e.g. after changing the setup
+pub fn reset() {
+    if let Ok(mut s) = STATS.lock() {
+        *s = LatencyStats::EMPTY;
+    }
+}
+
+fn ms(micros: f64) -> String {
+    format!("{:.3} ms", micros / 1000.0)
+}
+
+/// Summary for the `stats` command
+pub fn describe() -> String {
+    let s = snapshot();
+    if s.count == 0 {
+        return "MIDI thru latency: no messages forwarded yet".to_string();
+    }
+    format!(
+        "MIDI thru latency over {} messages: avg {}, min {}, max {}, last {}, jitter {}",
+        s.count,
+        ms(s.avg_us()),
+        ms(s.min_us as f64),
+        ms(s.max_us as f64),
+        ms(s.last_us as f64),
+        ms(s.jitter_us)
+    )
+}
+
+/// Metrics in the Prometheus text format
+pub fn prometheus() -> String {
+    let s = snapshot();
+    let secs = |micros: f64| micros / 1_000_000.0;
+    let mut text = format!(
+        "# HELP midi_thru_latency_seconds Input-to-output delay of forwarded MIDI messages\n# TYPE midi_thru_latency_seconds summary\nmidi_thru_latency_seconds_sum {}\nmidi_thru_latency_seconds_count {}\n",
+        secs(s.sum_us as f64),
+        s.count
+    );
+    let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+        text.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n{} {}\n", name, help, name, kind, name, value));
+    };
+    metric("midi_thru_latency_min_seconds", "gauge", "Lowest input-to-output delay", secs(s.min_us as f64).to_string());
+    metric("midi_thru_latency_max_seconds", "gauge", "Highest input-to-output delay", secs(s.max_us as f64).to_string());
+    metric("midi_thru_latency_last_seconds", "gauge", "Input-to-output delay of the last message", secs(s.last_us as f64).to_string());
+    metric("midi_thru_jitter_seconds", "gauge", "Smoothed delay variation between consecutive messages", secs(s.jitter_us).to_string());
+    metric("midi_dropped_messages_total", "counter", "Messages dropped by full queues", crate::general::queue::total_dropped().to_string());
+    text
+}
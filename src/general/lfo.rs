@@ -0,0 +1,142 @@
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, AtomicU64, Ordering};
+
+// MIDI clock resolution
+const TICKS_PER_BEAT: u64 = 24;
+
+/// `lfo` config section: step through a transpose pattern in time with the incoming MIDI clock
+#[derive(Debug, serde::Deserialize, Clone)]
+#[serde(default)]
+pub struct LfoConfig {
+    /// Start with the LFO running
+    pub enabled: bool,
+    /// Offsets in semitones, one per step (default: alternate octaves)
+    pub pattern: Vec<i32>,
+    /// Initial depth: multiplier for the pattern offsets (0.0 - 1.0, results are rounded)
+    pub depth: f32,
+    /// Initial rate: beats per step (4 = one step per 4/4 bar)
+    pub beats_per_step: f32,
+}
+
+impl Default for LfoConfig {
+    fn default() -> Self {
+        LfoConfig { enabled: false, pattern: vec![0, 12], depth: 1.0, beats_per_step: 4.0 }
+    }
+}
+
+pub const MIN_BEATS_PER_STEP: f32 = 0.25;
+pub const MAX_BEATS_PER_STEP: f32 = 16.0;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+// f32 bits
+static DEPTH: AtomicU32 = AtomicU32::new(0);
+static BEATS_PER_STEP: AtomicU32 = AtomicU32::new(0);
+// Clock ticks since the last Start, whether the clock is running and whether it was stopped
+// (a clock that runs without a Start, e.g. a sequencer already playing, counts as running)
+static TICKS: AtomicU64 = AtomicU64::new(0);
+static RUNNING: AtomicBool = AtomicBool::new(false);
+static STOPPED: AtomicBool = AtomicBool::new(false);
+static OFFSET: AtomicI32 = AtomicI32::new(0);
+// Bumped when enabled/depth/rate change so MQTT can publish them
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Take the initial state from the config
+pub fn init(config: &LfoConfig) {
+    ENABLED.store(config.enabled, Ordering::SeqCst);
+    DEPTH.store(config.depth.clamp(0.0, 1.0).to_bits(), Ordering::SeqCst);
+    BEATS_PER_STEP.store(config.beats_per_step.clamp(MIN_BEATS_PER_STEP, MAX_BEATS_PER_STEP).to_bits(), Ordering::SeqCst);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+pub fn depth() -> f32 {
+    f32::from_bits(DEPTH.load(Ordering::SeqCst))
+}
+
+pub fn beats_per_step() -> f32 {
+    f32::from_bits(BEATS_PER_STEP.load(Ordering::SeqCst))
+}
+
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+/// Current LFO offset in semitones (0 while disabled or the clock is stopped)
+pub fn offset() -> i32 {
+    OFFSET.load(Ordering::Relaxed)
+}
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::SeqCst);
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    update_offset();
+    log::info!(target: "app", "Transpose LFO {}", if enabled { "enabled" } else { "disabled" });
+}
+
+pub fn set_depth(depth: f32) -> f32 {
+    let depth = depth.clamp(0.0, 1.0);
+    DEPTH.store(depth.to_bits(), Ordering::SeqCst);
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    update_offset();
+    depth
+}
+
+pub fn set_beats_per_step(beats: f32) -> f32 {
+    let beats = beats.clamp(MIN_BEATS_PER_STEP, MAX_BEATS_PER_STEP);
+    BEATS_PER_STEP.store(beats.to_bits(), Ordering::SeqCst);
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    update_offset();
+    beats
+}
+
+/// Recompute the offset from the clock position
+fn update_offset() {
+    let pattern = &crate::get_config().lfo.pattern;
+    let offset = if is_enabled() && RUNNING.load(Ordering::Relaxed) && !pattern.is_empty() {
+        let ticks_per_step = ((beats_per_step() * TICKS_PER_BEAT as f32).round() as u64).max(1);
+        let step = (TICKS.load(Ordering::Relaxed) / ticks_per_step) as usize % pattern.len();
+        (pattern[step] as f32 * depth()).round() as i32
+    } else {
+        0
+    };
+    if OFFSET.swap(offset, Ordering::Relaxed) != offset && crate::is_debug_enabled_for(crate::DebugCategory::Midi) {
+        log::debug!(target: "midi", "[LFO] Offset {:+}", offset);
+    }
+}
+
+/// Follow MIDI clock and transport messages (Clock, Start, Continue, Stop).
+/// The pattern restarts on Start and pauses on Stop.
+pub fn on_midi(msg: &[u8]) {
+    match msg.first() {
+        Some(0xF8) => {
+            if STOPPED.load(Ordering::Relaxed) {
+                return;
+            }
+            RUNNING.store(true, Ordering::Relaxed);
+            TICKS.fetch_add(1, Ordering::Relaxed);
+        }
+        Some(0xFA) | Some(0xFB) => {
+            if msg[0] == 0xFA {
+                TICKS.store(0, Ordering::Relaxed);
+            }
+            STOPPED.store(false, Ordering::Relaxed);
+            RUNNING.store(true, Ordering::Relaxed);
+        }
+        Some(0xFC) => {
+            STOPPED.store(true, Ordering::Relaxed);
+            RUNNING.store(false, Ordering::Relaxed);
+        }
+        _ => return,
+    }
+    update_offset();
+}
+
+/// LFO state for `status`
+pub fn describe() -> String {
+    if !is_enabled() {
+        return "off".to_string();
+    }
+    let clock = if RUNNING.load(Ordering::Relaxed) { "clock running" } else { "waiting for MIDI clock" };
+    format!("offset {:+}, depth {:.2}, {} beats/step ({})", offset(), depth(), beats_per_step(), clock)
+}
@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Log subsystems with their own level; also the `target` of the log macros.
+/// `app` covers everything that isn't MIDI, OSC, MQTT or config handling.
+pub const SUBSYSTEMS: [&str; 5] = ["app", "midi", "osc", "mqtt", "config"];
+
+#[derive(Debug, serde::Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 6] = [LogLevel::Off, LogLevel::Error, LogLevel::Warn, LogLevel::Info, LogLevel::Debug, LogLevel::Trace];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LogLevel::Off => "off",
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim();
+        if name.eq_ignore_ascii_case("warning") {
+            return Some(LogLevel::Warn);
+        }
+        Self::ALL.into_iter().find(|l| l.name().eq_ignore_ascii_case(name))
+    }
+
+    fn from_u8(value: u8) -> Self {
+        Self::ALL.get(value as usize).copied().unwrap_or(LogLevel::Trace)
+    }
+
+    // Off is 0 in both; the `log` crate counts Error = 1 .. Trace = 5 like this enum
+    fn allows(self, level: log::Level) -> bool {
+        self as usize >= level as usize
+    }
+}
+
+/// `logging` section of the config
+#[derive(Debug, serde::Deserialize, Clone)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// Level of every subsystem without an entry in `levels`
+    pub level: LogLevel,
+    /// Per-subsystem levels, e.g. `{ "osc": "debug", "mqtt": "warn" }`
+    pub levels: HashMap<String, LogLevel>,
+    /// Also append log messages (with timestamp, level and subsystem) to this file
+    pub file: Option<String>,
+    /// Rotate the log file once it is larger than this (KiB); `0` = never
+    pub max_file_kb: u64,
+    /// Rotated files to keep (`<file>.1` is the newest)
+    pub max_files: u32,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig { level: LogLevel::Info, levels: HashMap::new(), file: None, max_file_kb: 1024, max_files: 3 }
+    }
+}
+
+impl LoggingConfig {
+    /// Configured level of one subsystem
+    pub fn level_of(&self, subsystem: &str) -> LogLevel {
+        self.levels.get(subsystem).copied().unwrap_or(self.level)
+    }
+}
+
+// Current levels, indexed like `SUBSYSTEMS`
+static LEVELS: [AtomicU8; 5] = [
+    AtomicU8::new(LogLevel::Info as u8),
+    AtomicU8::new(LogLevel::Info as u8),
+    AtomicU8::new(LogLevel::Info as u8),
+    AtomicU8::new(LogLevel::Info as u8),
+    AtomicU8::new(LogLevel::Info as u8),
+];
+// Bumped on every level change so MQTT can publish the levels
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+static FILE: Mutex<Option<LogFile>> = Mutex::new(None);
+
+struct LogFile {
+    path: String,
+    file: File,
+    size: u64,
+    max_size: u64,
+    max_files: u32,
+}
+
+impl LogFile {
+    fn open(config: &LoggingConfig, path: &str) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(LogFile { path: path.to_string(), file, size, max_size: config.max_file_kb * 1024, max_files: config.max_files })
+    }
+
+    fn write_line(&mut self, line: &str) {
+        if self.max_size > 0 && self.size + line.len() as u64 > self.max_size && self.size > 0 {
+            self.rotate();
+        }
+        if self.file.write_all(line.as_bytes()).is_ok() {
+            self.size += line.len() as u64;
+        }
+    }
+
+    // <file> -> <file>.1 -> <file>.2 ...; the oldest is dropped
+    fn rotate(&mut self) {
+        for index in (1..self.max_files).rev() {
+            let _ = fs::rename(format!("{}.{}", self.path, index), format!("{}.{}", self.path, index + 1));
+        }
+        if self.max_files > 0 {
+            let _ = fs::rename(&self.path, format!("{}.1", self.path));
+        }
+        if let Ok(file) = OpenOptions::new().create(true).write(true).truncate(true).open(&self.path) {
+            self.file = file;
+            self.size = 0;
+        }
+    }
+}
+
+fn index_of(subsystem: &str) -> Option<usize> {
+    SUBSYSTEMS.iter().position(|s| s.eq_ignore_ascii_case(subsystem.trim()))
+}
+
+/// Current level of a subsystem (unknown names report the `app` level)
+pub fn level(subsystem: &str) -> LogLevel {
+    LogLevel::from_u8(LEVELS[index_of(subsystem).unwrap_or(0)].load(Ordering::SeqCst))
+}
+
+/// Change the level of one subsystem at runtime. Returns false for an unknown subsystem.
+pub fn set_level(subsystem: &str, level: LogLevel) -> bool {
+    let Some(index) = index_of(subsystem) else { return false };
+    LEVELS[index].store(level as u8, Ordering::SeqCst);
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    true
+}
+
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+/// Whether messages of `level` for `subsystem` are currently logged
+pub fn enabled(subsystem: &str, level: LogLevel) -> bool {
+    level != LogLevel::Off && self::level(subsystem) >= level
+}
+
+/// UTC timestamp `YYYY-MM-DDTHH:MM:SS.mmmZ` for the log file
+fn timestamp() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = now.as_secs() as i64;
+    let (days, secs_of_day) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+    // Civil date from days since 1970-01-01 (proleptic Gregorian calendar)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        now.subsec_millis()
+    )
+}
+
+struct Logger;
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        match index_of(metadata.target()) {
+            Some(index) => LogLevel::from_u8(LEVELS[index].load(Ordering::Relaxed)).allows(metadata.level()),
+            // Libraries (MQTT client, mDNS) only get through with warnings, or everything at `app` trace
+            None => metadata.level() <= log::Level::Warn || level("app") == LogLevel::Trace,
+        }
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        // Console output stays as before: the message itself, errors and warnings on stderr
+        match record.level() {
+            log::Level::Error | log::Level::Warn => crate::io::console::print_error_line(record.args().to_string()),
+            _ => crate::io::console::print_line(record.args().to_string()),
+        }
+        if let Ok(mut file) = FILE.lock() {
+            if let Some(file) = file.as_mut() {
+                file.write_line(&format!("{} {:<5} {:<6} {}\n", timestamp(), record.level(), record.target(), record.args()));
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = FILE.lock() {
+            if let Some(file) = file.as_mut() {
+                let _ = file.file.flush();
+            }
+        }
+    }
+}
+
+static LOGGER: Logger = Logger;
+
+/// Install the logger; messages use the default levels until `configure` is called
+pub fn install() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(log::LevelFilter::Trace);
+    }
+}
+
+/// Apply the `logging` config: subsystem levels and the log file
+pub fn configure(config: &LoggingConfig) {
+    for subsystem in SUBSYSTEMS {
+        set_level(subsystem, config.level_of(subsystem));
+    }
+    for name in config.levels.keys().filter(|name| index_of(name).is_none()) {
+        log::warn!(target: "config", "[CONFIG] Unknown log subsystem '{}' (use {})", name, SUBSYSTEMS.join(", "));
+    }
+    let file = match &config.file {
+        Some(path) if !path.trim().is_empty() => match LogFile::open(config, path.trim()) {
+            Ok(file) => Some(file),
+            Err(e) => {
+                log::error!(target: "config", "[CONFIG] Cannot open log file '{}': {}", path, e);
+                None
+            }
+        },
+        _ => None,
+    };
+    if let Ok(mut current) = FILE.lock() {
+        *current = file;
+    }
+}
+
+/// Apply a `log` command argument: `<subsystem|all> <level>`, or `<level>` for all subsystems.
+/// Returns a description of the change.
+pub fn apply_command(arg: &str) -> Result<String, String> {
+    let usage = || format!("Usage: log [<{}|all>] <off|error|warn|info|debug|trace>", SUBSYSTEMS.join("|"));
+    let arg = arg.trim().to_ascii_lowercase();
+    let (subsystem, level) = match arg.split_once(' ') {
+        Some((subsystem, level)) => (subsystem, level.trim()),
+        None if arg.is_empty() => return Ok(status()),
+        None => ("all", arg.as_str()),
+    };
+    let level = LogLevel::from_name(level).ok_or_else(usage)?;
+    if subsystem == "all" {
+        for subsystem in SUBSYSTEMS {
+            set_level(subsystem, level);
+        }
+        return Ok(format!("Log level of all subsystems: {}", level.name()));
+    }
+    if !set_level(subsystem, level) {
+        return Err(usage());
+    }
+    Ok(format!("Log level of {}: {}", subsystem, level.name()))
+}
+
+/// Current levels, e.g. `app=info midi=info osc=debug mqtt=info config=info`
+pub fn status() -> String {
+    SUBSYSTEMS.iter().map(|s| format!("{}={}", s, level(s).name())).collect::<Vec<_>>().join(" ")
+}
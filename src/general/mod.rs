@@ -1,4 +1,32 @@
 pub mod stdin_handler;
+pub mod commands;
 pub mod transpose;
 pub mod forwarder;
+pub mod state;
+pub mod cli;
+pub mod takeover;
 pub mod check;
+pub mod instance;
+pub mod queue;
+pub mod profile;
+pub mod setlist;
+pub mod debug;
+pub mod stats;
+pub mod history;
+pub mod output_buffer;
+pub mod practice;
+pub mod automation;
+pub mod lfo;
+pub mod scheduler;
+pub mod repeat;
+pub mod notes;
+pub mod now_playing;
+pub mod program;
+pub mod monitor;
+pub mod logging;
+pub mod sequence;
+pub mod glide;
+pub mod zones;
+pub mod latency;
+pub mod config_check;
+pub mod config_format;
@@ -0,0 +1,103 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::Instant;
+use termcolor::Color;
+
+// Print every MIDI message in and out (`monitor on`, `--monitor`)
+static ENABLED: AtomicBool = AtomicBool::new(false);
+// Timestamps are relative to the first use
+static START: OnceLock<Instant> = OnceLock::new();
+
+pub fn set_enabled(enabled: bool) {
+    START.get_or_init(Instant::now);
+    ENABLED.store(enabled, Ordering::SeqCst);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Common controller names
+fn cc_name(controller: u8) -> Option<&'static str> {
+    Some(match controller {
+        0 => "Bank Select",
+        1 => "Mod Wheel",
+        2 => "Breath",
+        4 => "Foot",
+        7 => "Volume",
+        10 => "Pan",
+        11 => "Expression",
+        32 => "Bank Select LSB",
+        64 => "Sustain",
+        65 => "Portamento",
+        66 => "Sostenuto",
+        67 => "Soft Pedal",
+        120 => "All Sound Off",
+        121 => "Reset All Controllers",
+        123 => "All Notes Off",
+        _ => return None,
+    })
+}
+
+fn note(note: u8) -> String {
+    format!("{} ({})", crate::general::notes::display_name(note & 0x7F), note & 0x7F)
+}
+
+/// Human-readable form of a MIDI message and its display color
+pub fn describe(msg: &[u8]) -> (String, Color) {
+    let Some(&status) = msg.first() else { return ("(empty)".to_string(), Color::White) };
+    let data = |i: usize| msg.get(i).copied().unwrap_or(0);
+    let channel = (status & 0x0F) + 1;
+    match status & 0xF0 {
+        0x90 if data(2) > 0 => (format!("ch {:<2} Note On         {} vel {}", channel, note(data(1)), data(2)), Color::Green),
+        0x80 | 0x90 => (format!("ch {:<2} Note Off        {} vel {}", channel, note(data(1)), data(2)), Color::Cyan),
+        0xA0 => (format!("ch {:<2} Poly Pressure   {} {}", channel, note(data(1)), data(2)), Color::Magenta),
+        0xB0 => {
+            let name = cc_name(data(1)).map(|n| format!(" {}", n)).unwrap_or_default();
+            (format!("ch {:<2} CC {:<3}{} = {}", channel, data(1), name, data(2)), Color::Yellow)
+        }
+        0xC0 => (format!("ch {:<2} Program Change  {}", channel, data(1)), Color::Blue),
+        0xD0 => (format!("ch {:<2} Channel Pressure {}", channel, data(1)), Color::Magenta),
+        0xE0 => {
+            let value = (data(1) as i32 | (data(2) as i32) << 7) - 8192;
+            (format!("ch {:<2} Pitch Bend      {}", channel, value), Color::Magenta)
+        }
+        _ => {
+            let text = match status {
+                0xF0 => format!("SysEx ({} bytes)", msg.len()),
+                0xF8 => "Clock".to_string(),
+                0xFA => "Start".to_string(),
+                0xFB => "Continue".to_string(),
+                0xFC => "Stop".to_string(),
+                0xFE => "Active Sensing".to_string(),
+                0xFF => "Reset".to_string(),
+                _ => msg.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" "),
+            };
+            (text, Color::White)
+        }
+    }
+}
+
+fn print(direction: &str, msg: &[u8]) {
+    // Clock and Active Sensing arrive many times per second and would bury everything else
+    if matches!(msg.first(), Some(0xF8) | Some(0xFE)) {
+        return;
+    }
+    let elapsed = START.get_or_init(Instant::now).elapsed().as_secs_f64();
+    let (text, color) = describe(msg);
+    crate::io::console::print_colored(&[(Some(color), &format!("[{:>9.3}s] {:<5} {}", elapsed, direction, text))]);
+}
+
+/// Log a message received from input `source` (0-based), while the monitor is on
+pub fn log_in(source: u8, msg: &[u8]) {
+    if enabled() {
+        print(&format!("IN {}", source + 1), msg);
+    }
+}
+
+/// Log a message sent to the MIDI outputs, while the monitor is on
+pub fn log_out(msg: &[u8]) {
+    if enabled() {
+        print("OUT", msg);
+    }
+}
@@ -0,0 +1,107 @@
+/// Spelling of the note names
+#[derive(Debug, serde::Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NoteNameStyle {
+    /// C, C#, D, ... B
+    #[default]
+    Letters,
+    /// Do, Do#, Re, ... Si
+    Solfege,
+}
+
+/// `note_names` config section: how notes are named in console output, MQTT and optionally OSC
+#[derive(Debug, serde::Deserialize, Clone)]
+#[serde(default)]
+pub struct NoteNamesConfig {
+    pub style: NoteNameStyle,
+    /// Octave number of middle C (MIDI note 60): 4 = scientific (C4), 3 = Yamaha (C3)
+    pub middle_c_octave: i32,
+    /// Also use this naming for the per-note and per-octave OSC parameters
+    pub apply_to_osc: bool,
+}
+
+impl Default for NoteNamesConfig {
+    fn default() -> Self {
+        NoteNamesConfig { style: NoteNameStyle::Letters, middle_c_octave: 4, apply_to_osc: false }
+    }
+}
+
+const LETTER_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+const SOLFEGE_NAMES: [&str; 12] = ["Do", "Do#", "Re", "Re#", "Mi", "Fa", "Fa#", "Sol", "Sol#", "La", "La#", "Si"];
+const LETTER_FLAT_NAMES: [&str; 12] = ["C", "Db", "D", "Eb", "E", "F", "Gb", "G", "Ab", "A", "Bb", "B"];
+const SOLFEGE_FLAT_NAMES: [&str; 12] = ["Do", "Reb", "Re", "Mib", "Mi", "Fa", "Solb", "Sol", "Lab", "La", "Sib", "Si"];
+
+/// Octave number of a MIDI note when middle C is in octave `middle_c_octave`
+pub fn octave_of(note: u8, middle_c_octave: i32) -> i32 {
+    (note / 12) as i32 - 5 + middle_c_octave
+}
+
+/// Name of a MIDI note with octave in the given convention (e.g. "C4", "Sol#3")
+pub fn format_note(note: u8, style: NoteNameStyle, middle_c_octave: i32) -> String {
+    if note > 127 {
+        return "INVALID".to_string();
+    }
+    let names = match style {
+        NoteNameStyle::Letters => &LETTER_NAMES,
+        NoteNameStyle::Solfege => &SOLFEGE_NAMES,
+    };
+    format!("{}{}", names[(note % 12) as usize], octave_of(note, middle_c_octave))
+}
+
+/// Key a C major part sounds in after transposing by `semitones` (e.g. "D" for +2), in the configured convention
+pub fn key_name(semitones: i32) -> String {
+    let names = match crate::get_config().note_names.style {
+        NoteNameStyle::Letters => &LETTER_NAMES,
+        NoteNameStyle::Solfege => &SOLFEGE_NAMES,
+    };
+    names[semitones.rem_euclid(12) as usize].to_string()
+}
+
+/// Note name for console output and MQTT, using the configured convention
+pub fn display_name(note: u8) -> String {
+    let config = &crate::get_config().note_names;
+    format_note(note, config.style, config.middle_c_octave)
+}
+
+/// Note name used for OSC parameters: the configured convention with `apply_to_osc`,
+/// otherwise the default scientific letter names (C4 = 60); spelled with flats with
+/// `osc.note_accidentals` and shifted by `osc.note_octave_offset`
+pub fn osc_name(note: u8) -> String {
+    let config = &crate::get_config().note_names;
+    let style = if config.apply_to_osc { config.style } else { NoteNameStyle::Letters };
+    let names = match (style, crate::get_config().osc.note_accidentals) {
+        (NoteNameStyle::Letters, crate::osc_sender::NoteAccidentals::Sharp) => &LETTER_NAMES,
+        (NoteNameStyle::Solfege, crate::osc_sender::NoteAccidentals::Sharp) => &SOLFEGE_NAMES,
+        (NoteNameStyle::Letters, crate::osc_sender::NoteAccidentals::Flat) => &LETTER_FLAT_NAMES,
+        (NoteNameStyle::Solfege, crate::osc_sender::NoteAccidentals::Flat) => &SOLFEGE_FLAT_NAMES,
+    };
+    format!("{}{}", names[(note % 12) as usize], osc_octave(note.min(127)))
+}
+
+/// Parse a note name ("C2", "F#5", "Bb3", "Sol4") into a MIDI note, with middle C in octave
+/// `middle_c_octave` (`note_names.middle_c_octave`)
+pub fn parse_note(name: &str, middle_c_octave: i32) -> Option<u8> {
+    let name = name.trim();
+    let split = name.find(|c: char| c.is_ascii_digit() || c == '-')?;
+    let (pitch, octave) = name.split_at(split);
+    let octave: i32 = octave.parse().ok()?;
+    let (base, shift) = match pitch.strip_suffix('#') {
+        Some(base) => (base, 1),
+        None => match pitch.strip_suffix('b').filter(|base| !base.is_empty()) {
+            Some(base) => (base, -1),
+            None => (pitch, 0),
+        },
+    };
+    let index = LETTER_NAMES
+        .iter()
+        .position(|n| n.eq_ignore_ascii_case(base))
+        .or_else(|| SOLFEGE_NAMES.iter().position(|n| n.eq_ignore_ascii_case(base)))? as i32;
+    let note = (octave - middle_c_octave + 5) * 12 + index + shift;
+    u8::try_from(note).ok().filter(|n| *n <= 127)
+}
+
+/// Octave number used in the OSC note names and the per-octave OSC parameters
+pub fn osc_octave(note: u8) -> i32 {
+    let config = &crate::get_config().note_names;
+    octave_of(note, if config.apply_to_osc { config.middle_c_octave } else { 4 }) + crate::get_config().osc.note_octave_offset
+}
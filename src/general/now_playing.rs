@@ -0,0 +1,118 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// `now_playing` config section: announce song titles from the setlist and automation lanes
+#[derive(Debug, serde::Deserialize, Clone)]
+#[serde(default)]
+pub struct NowPlayingConfig {
+    /// Post the title to the VRChat chatbox
+    pub chatbox: bool,
+    /// Chatbox text; `{title}` is replaced by the song title
+    pub format: String,
+}
+
+impl Default for NowPlayingConfig {
+    fn default() -> Self {
+        NowPlayingConfig { chatbox: true, format: "Now playing: {title}".to_string() }
+    }
+}
+
+static TITLE: Mutex<Option<String>> = Mutex::new(None);
+// Bumped on every title change so MQTT can publish it
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+/// Current title (empty when nothing was announced yet)
+pub fn title() -> String {
+    TITLE.lock().ok().and_then(|t| t.clone()).unwrap_or_default()
+}
+
+/// Announce a new title. `chatbox` false skips the chatbox post (e.g. the song has its own text).
+pub fn set(title: &str, chatbox: bool) {
+    if let Ok(mut current) = TITLE.lock() {
+        *current = Some(title.to_string());
+    }
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    let config = &crate::get_config().now_playing;
+    if chatbox && config.chatbox {
+        crate::remote::chatbox::send_chatbox(&config.format.replace("{title}", title));
+    }
+}
+
+/// Read a variable-length quantity; returns (value, bytes used)
+fn read_vlq(data: &[u8]) -> Option<(usize, usize)> {
+    let mut value = 0usize;
+    for (i, byte) in data.iter().take(4).enumerate() {
+        value = (value << 7) | (byte & 0x7F) as usize;
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// Title of a Standard MIDI File: the first track name meta event (FF 03) of the first track,
+/// else its first text event (FF 01). Stops at the first channel message that can't be skipped.
+pub fn smf_title(data: &[u8]) -> Option<String> {
+    if data.get(..4)? != b"MThd" {
+        return None;
+    }
+    let header_len = u32::from_be_bytes(data.get(4..8)?.try_into().ok()?) as usize;
+    let track = data.get(8 + header_len..)?;
+    if track.get(..4)? != b"MTrk" {
+        return None;
+    }
+    let track_len = u32::from_be_bytes(track.get(4..8)?.try_into().ok()?) as usize;
+    let events = track.get(8..8 + track_len).unwrap_or(&track[8..]);
+    let mut text = None;
+    let mut pos = 0;
+    let mut running = 0u8;
+    while pos < events.len() {
+        let (_, delta_len) = read_vlq(&events[pos..])?;
+        pos += delta_len;
+        let mut status = *events.get(pos)?;
+        if status & 0x80 != 0 {
+            pos += 1;
+        } else {
+            status = running;
+        }
+        match status {
+            0xFF => {
+                let kind = *events.get(pos)?;
+                let (len, len_bytes) = read_vlq(events.get(pos + 1..)?)?;
+                let start = pos + 1 + len_bytes;
+                let body = String::from_utf8_lossy(events.get(start..start + len)?).trim().to_string();
+                match kind {
+                    0x03 if !body.is_empty() => return Some(body),
+                    0x01 if text.is_none() && !body.is_empty() => text = Some(body),
+                    0x2F => break,
+                    _ => {}
+                }
+                pos = start + len;
+            }
+            0xF0 | 0xF7 => {
+                let (len, len_bytes) = read_vlq(events.get(pos..)?)?;
+                pos += len_bytes + len;
+            }
+            0x80..=0xEF => {
+                running = status;
+                pos += if matches!(status & 0xF0, 0xC0 | 0xD0) { 1 } else { 2 };
+            }
+            _ => break,
+        }
+    }
+    text
+}
+
+/// Title for an automation lane: from a MIDI backing track next to it with the same name
+/// (`medley.lane` + `medley.mid`), else the lane's file name
+pub fn lane_title(lane_file: &str) -> String {
+    let path = std::path::Path::new(lane_file);
+    ["mid", "midi"]
+        .iter()
+        .find_map(|ext| std::fs::read(path.with_extension(ext)).ok().and_then(|data| smf_title(&data)))
+        .unwrap_or_else(|| path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| lane_file.to_string()))
+}
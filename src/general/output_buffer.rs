@@ -0,0 +1,186 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use crate::general::queue::{self, QueueStats};
+
+/// MIDI output with an optional pacing buffer, so bursts don't overrun slow DIN interfaces.
+/// Without pacing (`output_bytes_per_sec` = 0) messages go straight to the ports.
+/// Every message goes to all targets whose channel filter passes it; without any target
+/// (observer mode) messages are discarded.
+pub struct PacedOutput {
+    targets: Vec<crate::output::MidiTarget>,
+    buffer: VecDeque<Vec<u8>>,
+    capacity: usize,
+    // Time needed per byte on the wire; zero disables pacing
+    byte_time: Duration,
+    next_send: Instant,
+    stats: Arc<QueueStats>,
+}
+
+impl PacedOutput {
+    pub fn new(targets: Vec<crate::output::MidiTarget>) -> Self {
+        let config = &crate::get_config().midi;
+        let capacity = config.output_buffer_capacity.max(1);
+        let byte_time = if config.output_bytes_per_sec > 0 {
+            Duration::from_secs_f64(1.0 / config.output_bytes_per_sec as f64)
+        } else {
+            Duration::ZERO
+        };
+        PacedOutput {
+            targets,
+            buffer: VecDeque::with_capacity(capacity),
+            capacity,
+            byte_time,
+            next_send: Instant::now(),
+            stats: queue::register("midi_out", capacity),
+        }
+    }
+
+    /// Apply hot-plug changes: stop sending to lost ports, add reopened ones
+    pub fn update_targets(&mut self, changes: Vec<crate::output::TargetChange>) {
+        for change in changes {
+            match change {
+                crate::output::TargetChange::Lost(name) => self.targets.retain(|t| t.name != name),
+                crate::output::TargetChange::Restored(target) => {
+                    self.targets.retain(|t| t.name != target.name);
+                    self.targets.push(target);
+                }
+            }
+        }
+    }
+
+    /// Queue a message (or send it directly when pacing is off), then send what is due
+    pub fn send(&mut self, msg: Vec<u8>) {
+        if self.byte_time.is_zero() {
+            self.write(&msg);
+            return;
+        }
+        if self.buffer.len() >= self.capacity {
+            self.drop_least_important(&msg);
+        }
+        self.buffer.push_back(msg);
+        self.stats.set_queued(self.buffer.len());
+        self.flush_due();
+    }
+
+    /// Send all buffered messages whose slot has come. Returns how long until the next one is due.
+    pub fn flush_due(&mut self) -> Option<Duration> {
+        while !self.buffer.is_empty() {
+            let now = Instant::now();
+            if now < self.next_send {
+                return Some(self.next_send - now);
+            }
+            let Some(msg) = self.buffer.pop_front() else { break };
+            self.write(&msg);
+            self.next_send = self.next_send.max(now) + self.byte_time * msg.len() as u32;
+            self.stats.set_queued(self.buffer.len());
+        }
+        None
+    }
+
+    /// Send everything left without pacing (shutdown)
+    pub fn drain(&mut self) {
+        while let Some(msg) = self.buffer.pop_front() {
+            self.write(&msg);
+        }
+        self.stats.set_queued(0);
+    }
+
+    fn write(&mut self, msg: &[u8]) {
+        if !self.targets.is_empty() {
+            crate::general::monitor::log_out(msg);
+        }
+        for target in &mut self.targets {
+            target.send(msg);
+        }
+    }
+
+    /// Make room for `incoming`: drop a CC superseded by a later one for the same
+    /// controller first, then other non-note channel messages, then the oldest non-note-off
+    fn drop_least_important(&mut self, incoming: &[u8]) {
+        let is_cc = |m: &[u8]| m.len() >= 2 && m[0] & 0xF0 == 0xB0;
+        let same_cc = |a: &[u8], b: &[u8]| is_cc(a) && is_cc(b) && a[0] == b[0] && a[1] == b[1];
+        let is_note = |m: &[u8]| matches!(m.first().map(|s| s & 0xF0), Some(0x80) | Some(0x90));
+        let is_note_off = |m: &[u8]| match m.first().map(|s| s & 0xF0) {
+            Some(0x80) => true,
+            Some(0x90) => m.get(2) == Some(&0),
+            _ => false,
+        };
+
+        let superseded = self.buffer.iter().enumerate().position(|(i, m)| {
+            same_cc(m, incoming) || self.buffer.iter().skip(i + 1).any(|later| same_cc(m, later))
+        });
+        let index = superseded
+            .or_else(|| self.buffer.iter().position(|m| !is_note(m)))
+            .or_else(|| self.buffer.iter().position(|m| !is_note_off(m)))
+            .unwrap_or(0);
+        self.buffer.remove(index);
+        self.stats.record_drop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Observer-mode output (no targets) buffering up to `capacity` messages
+    fn paced(capacity: usize, byte_time: Duration) -> PacedOutput {
+        PacedOutput {
+            targets: Vec::new(),
+            buffer: VecDeque::new(),
+            capacity,
+            byte_time,
+            next_send: Instant::now() + Duration::from_secs(60),
+            stats: queue::register("midi_out_test", capacity),
+        }
+    }
+
+    fn buffered(output: &PacedOutput) -> Vec<Vec<u8>> {
+        output.buffer.iter().cloned().collect()
+    }
+
+    #[test]
+    fn full_buffer_drops_superseded_cc_first() {
+        let mut output = paced(3, Duration::from_secs(1));
+        output.send(vec![0x90, 60, 100]);
+        output.send(vec![0xB0, 7, 10]);
+        output.send(vec![0xE0, 0, 64]);
+        output.send(vec![0xB0, 7, 20]);
+        assert_eq!(buffered(&output), [vec![0x90, 60, 100], vec![0xE0, 0, 64], vec![0xB0, 7, 20]]);
+        assert_eq!(output.stats.dropped(), 1);
+    }
+
+    #[test]
+    fn full_buffer_drops_non_note_messages_before_notes() {
+        let mut output = paced(2, Duration::from_secs(1));
+        output.send(vec![0x90, 60, 100]);
+        output.send(vec![0xD0, 50]);
+        output.send(vec![0x80, 60, 0]);
+        assert_eq!(buffered(&output), [vec![0x90, 60, 100], vec![0x80, 60, 0]]);
+    }
+
+    #[test]
+    fn full_buffer_spares_note_offs() {
+        let mut output = paced(3, Duration::from_secs(1));
+        output.send(vec![0x80, 60, 0]);
+        output.send(vec![0x90, 62, 0]);
+        output.send(vec![0x90, 64, 100]);
+        output.send(vec![0x90, 65, 100]);
+        assert_eq!(buffered(&output), [vec![0x80, 60, 0], vec![0x90, 62, 0], vec![0x90, 65, 100]]);
+    }
+
+    #[test]
+    fn paced_messages_wait_for_their_slot() {
+        let mut output = paced(8, Duration::from_millis(100));
+        output.next_send = Instant::now();
+        output.send(vec![0x90, 60, 100]);
+        output.send(vec![0x90, 62, 100]);
+        // The first one went out, the second one is due after its three bytes on the wire
+        assert_eq!(buffered(&output), [vec![0x90, 62, 100]]);
+        let wait = output.flush_due().unwrap();
+        assert!(wait > Duration::from_millis(200) && wait <= Duration::from_millis(300));
+        output.drain();
+        assert!(output.buffer.is_empty());
+        assert_eq!(output.stats.queued(), 0);
+    }
+}
@@ -0,0 +1,118 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// `practice` config section: alternate between two transposes to learn a song in two keys
+#[derive(Debug, serde::Deserialize, Clone)]
+#[serde(default)]
+pub struct PracticeConfig {
+    pub transpose_a: i32,
+    pub transpose_b: i32,
+    /// Switch interval in minutes; used when `interval_bars` is 0
+    pub interval_minutes: f64,
+    /// Switch interval in bars at `bpm` / `beats_per_bar` (0 = use `interval_minutes`)
+    pub interval_bars: u32,
+    pub bpm: f64,
+    pub beats_per_bar: u32,
+    /// Post each switch to the VRChat chatbox
+    pub chatbox: bool,
+}
+
+impl Default for PracticeConfig {
+    fn default() -> Self {
+        PracticeConfig {
+            transpose_a: 0,
+            transpose_b: 5,
+            interval_minutes: 2.0,
+            interval_bars: 0,
+            bpm: 120.0,
+            beats_per_bar: 4,
+            chatbox: true,
+        }
+    }
+}
+
+impl PracticeConfig {
+    /// Time between two switches
+    pub fn interval(&self) -> Duration {
+        let secs = if self.interval_bars > 0 && self.bpm > 0.0 {
+            self.interval_bars as f64 * self.beats_per_bar.max(1) as f64 * 60.0 / self.bpm
+        } else {
+            self.interval_minutes * 60.0
+        };
+        Duration::from_secs_f64(secs.max(1.0))
+    }
+}
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+// false = A, true = B
+static ON_B: AtomicBool = AtomicBool::new(false);
+// Bumped on start/stop/switch so MQTT can publish the new phase
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::SeqCst)
+}
+
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+/// Human-readable phase for `status` and MQTT, e.g. `B (+5)` or `off`
+pub fn phase() -> String {
+    if !is_active() {
+        return "off".to_string();
+    }
+    let config = &crate::get_config().practice;
+    if ON_B.load(Ordering::SeqCst) {
+        format!("B ({:+})", config.transpose_b)
+    } else {
+        format!("A ({:+})", config.transpose_a)
+    }
+}
+
+/// Start practice mode at transpose A, or stop it (the current transpose is kept)
+pub fn set_active(active: bool) {
+    if ACTIVE.swap(active, Ordering::SeqCst) == active {
+        return;
+    }
+    if active {
+        apply_phase(false);
+    } else {
+        GENERATION.fetch_add(1, Ordering::SeqCst);
+        log::info!(target: "app", "Practice mode stopped");
+    }
+}
+
+fn apply_phase(on_b: bool) {
+    let config = &crate::get_config().practice;
+    ON_B.store(on_b, Ordering::SeqCst);
+    let value = crate::set_transpose_semitones(if on_b { config.transpose_b } else { config.transpose_a });
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    let text = format!("Practice: now in key {} (transpose {:+})", if on_b { "B" } else { "A" }, value);
+    log::info!(target: "app", "{}", text);
+    if config.chatbox {
+        crate::remote::chatbox::send_chatbox(&text);
+    }
+}
+
+/// Spawn the timer thread that switches between A and B while practice mode is active
+pub fn spawn_practice_timer() -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut last_switch = Instant::now();
+        let mut last_generation = generation();
+        while !crate::state().exit.load(Ordering::SeqCst) {
+            // Restart the interval whenever practice is (re)started
+            if generation() != last_generation {
+                last_generation = generation();
+                last_switch = Instant::now();
+            }
+            if is_active() && last_switch.elapsed() >= crate::get_config().practice.interval() {
+                apply_phase(!ON_B.load(Ordering::SeqCst));
+                last_generation = generation();
+                last_switch = Instant::now();
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+    })
+}
@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Named set of runtime overrides (`profiles` in config, or a file written by `export profile`).
+/// Unset fields leave the current value alone.
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, Default)]
+#[serde(default)]
+pub struct Profile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transpose: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub osc_sending_enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub osc_send_original: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub osc_original_enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub osc_transposed_enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub osc_encoding: Option<crate::osc_sender::OscEncoding>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub osc_note_arg_type: Option<crate::osc_sender::NoteArgType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub osc_note_velocity: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aftertouch_to_cc: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pitch_bend_mode: Option<crate::transpose::PitchBendMode>,
+    /// Values sent to the synth by controller number, on `midi.cc_channel`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub midi_cc: BTreeMap<u8, u8>,
+    /// Custom control values by slug
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub custom_controls: BTreeMap<String, f32>,
+}
+
+/// Complete runtime state for MQTT snapshots: the current settings plus the active profile
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, Default)]
+#[serde(default)]
+pub struct Snapshot {
+    #[serde(flatten)]
+    pub settings: Profile,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+}
+
+static ACTIVE_PROFILE: Mutex<Option<String>> = Mutex::new(None);
+// Bumped on every profile switch so MQTT can publish the change
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Name of the active profile, if one was applied
+pub fn active() -> Option<String> {
+    ACTIVE_PROFILE.lock().ok().and_then(|g| g.clone())
+}
+
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+/// Apply a configured profile by name. Returns false if no such profile exists.
+pub fn apply(name: &str) -> bool {
+    match crate::get_config().profiles.get(name) {
+        Some(profile) => {
+            apply_profile(name, profile);
+            true
+        }
+        None => false,
+    }
+}
+
+fn apply_profile(name: &str, profile: &Profile) {
+    apply_settings(name, profile);
+    if let Ok(mut guard) = ACTIVE_PROFILE.lock() {
+        *guard = Some(name.to_string());
+    }
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    log::debug!(target: "app", "[PROFILE] Applied '{}'", name);
+}
+
+fn apply_settings(name: &str, profile: &Profile) {
+    if let Some(t) = profile.transpose {
+        crate::set_transpose_semitones(t);
+    }
+    if let Some(enabled) = profile.osc_sending_enabled {
+        crate::state().osc_sending.store(enabled, Ordering::SeqCst);
+    }
+    if let Some(original) = profile.osc_send_original {
+        crate::set_osc_send_original(original);
+    }
+    if let Some(enabled) = profile.osc_original_enabled {
+        crate::state().osc_original.store(enabled, Ordering::SeqCst);
+    }
+    if let Some(enabled) = profile.osc_transposed_enabled {
+        crate::state().osc_transposed.store(enabled, Ordering::SeqCst);
+    }
+    if let Some(encoding) = profile.osc_encoding {
+        crate::osc_sender::set_encoding(encoding);
+    }
+    if let Some(arg_type) = profile.osc_note_arg_type {
+        crate::osc_sender::set_note_arg_type(arg_type);
+    }
+    if let Some(enabled) = profile.osc_note_velocity {
+        crate::osc_sender::set_note_velocity(enabled);
+    }
+    if let Some(enabled) = profile.aftertouch_to_cc {
+        crate::state().aftertouch_to_cc.store(enabled, Ordering::SeqCst);
+    }
+    if let Some(mode) = profile.pitch_bend_mode {
+        crate::transpose::set_pitch_bend_mode(mode);
+    }
+    for (controller, value) in &profile.midi_cc {
+        crate::general::takeover::set_synth_cc(crate::get_config().midi.cc_channel, *controller, *value);
+    }
+    for (slug, value) in &profile.custom_controls {
+        if crate::custom_controls::set_value(slug, *value).is_none() {
+            log::warn!(target: "app", "[PROFILE] Unknown custom control '{}' in profile '{}'", slug, name);
+        }
+    }
+}
+
+/// Snapshot of the current runtime state as a profile
+pub fn current() -> Profile {
+    Profile {
+        transpose: Some(crate::state().transpose.load(Ordering::SeqCst)),
+        osc_sending_enabled: Some(crate::state().osc_sending.load(Ordering::SeqCst)),
+        osc_send_original: None,
+        osc_original_enabled: Some(crate::state().osc_original.load(Ordering::SeqCst)),
+        osc_transposed_enabled: Some(crate::state().osc_transposed.load(Ordering::SeqCst)),
+        osc_encoding: Some(crate::osc_sender::encoding()),
+        osc_note_arg_type: Some(crate::osc_sender::note_arg_type()),
+        osc_note_velocity: Some(crate::osc_sender::note_velocity()),
+        aftertouch_to_cc: Some(crate::state().aftertouch_to_cc.load(Ordering::SeqCst)),
+        pitch_bend_mode: Some(crate::transpose::pitch_bend_mode()),
+        midi_cc: BTreeMap::new(),
+        custom_controls: crate::custom_controls::controls()
+            .iter()
+            .flat_map(|c| c.value_keys())
+            .map(|key| {
+                let value = crate::custom_controls::value(&key);
+                (key, value)
+            })
+            .collect(),
+    }
+}
+
+/// Snapshot of the current runtime state including the active profile name, as JSON
+pub fn snapshot_json() -> String {
+    let snapshot = Snapshot { settings: current(), profile: active() };
+    serde_json::to_string(&snapshot).unwrap_or_default()
+}
+
+/// Restore a snapshot produced by `snapshot_json`: its settings are applied and its profile
+/// becomes the active one again (without re-applying that profile's own values)
+pub fn restore(json: &str) -> Result<(), String> {
+    let snapshot: Snapshot = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    apply_settings("snapshot", &snapshot.settings);
+    if let Ok(mut guard) = ACTIVE_PROFILE.lock() {
+        *guard = snapshot.profile;
+    }
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Write the current runtime state to a shareable profile file
+pub fn export(path: &Path) -> Result<(), Box<dyn Error>> {
+    std::fs::write(path, serde_json::to_string_pretty(&current())?)?;
+    Ok(())
+}
+
+/// Read a profile file and apply it; it becomes the active profile under its file name.
+/// Returns that name.
+pub fn import(path: &Path) -> Result<String, Box<dyn Error>> {
+    let profile: Profile = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    let name = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| "imported".to_string());
+    apply_profile(&name, &profile);
+    Ok(name)
+}
+
+/// Profile file for a remote import/export request: only bare file names in the working
+/// directory are accepted, so MQTT can't read or write arbitrary paths
+pub fn remote_file_path(name: &str) -> Option<std::path::PathBuf> {
+    let name = name.trim();
+    let bare = !name.is_empty() && !name.contains(['/', '\\', ':']) && !name.starts_with('.');
+    bare.then(|| std::path::PathBuf::from(name))
+}
@@ -0,0 +1,41 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+// Last program change from the input: (channel 0-15, program 0-127)
+static CURRENT: Mutex<Option<(u8, u8)>> = Mutex::new(None);
+// Bumped on every program change so MQTT can publish it
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+/// Remember a program change (0xC0); called from the MIDI input callback with every message
+pub fn record(message: &[u8]) {
+    if message.len() < 2 || message[0] & 0xF0 != 0xC0 {
+        return;
+    }
+    if let Ok(mut current) = CURRENT.lock() {
+        *current = Some((message[0] & 0x0F, message[1] & 0x7F));
+    }
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    log::debug!(target: "midi", "[MIDI] Program change: {} (channel {})", message[1] & 0x7F, (message[0] & 0x0F) + 1);
+}
+
+/// Last program and its channel (1-16), if any program change was received
+pub fn current() -> Option<(u8, u8)> {
+    CURRENT.lock().ok().and_then(|c| *c).map(|(channel, program)| (channel + 1, program))
+}
+
+/// Program number for the MQTT state (empty before the first program change)
+pub fn state() -> String {
+    current().map(|(_, program)| program.to_string()).unwrap_or_default()
+}
+
+/// `{"channel": 1-16, "program": 0-127}` for the MQTT attributes
+pub fn attributes_json() -> String {
+    match current() {
+        Some((channel, program)) => serde_json::json!({ "channel": channel, "program": program }).to_string(),
+        None => "{}".to_string(),
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Default capacity for MIDI/OSC message queues
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+/// Counters of one named queue, shown by `status` and published as MQTT diagnostics
+pub struct QueueStats {
+    pub name: String,
+    pub capacity: usize,
+    len: AtomicUsize,
+    dropped: AtomicU64,
+}
+
+impl QueueStats {
+    pub fn queued(&self) -> usize {
+        self.len.load(Ordering::SeqCst)
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::SeqCst)
+    }
+
+    /// Count a message dropped outside of a `bounded` queue (e.g. a full MQTT client queue)
+    pub fn record_drop(&self) {
+        self.dropped.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Report the fill level of a queue not created by `bounded`
+    pub fn set_queued(&self, len: usize) {
+        self.len.store(len, Ordering::SeqCst);
+    }
+}
+
+// All queues created so far, for reporting
+static REGISTRY: Mutex<Vec<Arc<QueueStats>>> = Mutex::new(Vec::new());
+
+/// Register a named counter set; used by `bounded` and by queues owned by libraries
+pub fn register(name: &str, capacity: usize) -> Arc<QueueStats> {
+    let stats = Arc::new(QueueStats { name: name.to_string(), capacity, len: AtomicUsize::new(0), dropped: AtomicU64::new(0) });
+    if let Ok(mut registry) = REGISTRY.lock() {
+        registry.push(stats.clone());
+    }
+    stats
+}
+
+/// Snapshot of all registered queues
+pub fn all_stats() -> Vec<Arc<QueueStats>> {
+    REGISTRY.lock().map(|r| r.clone()).unwrap_or_default()
+}
+
+/// Total number of dropped messages over all queues
+pub fn total_dropped() -> u64 {
+    all_stats().iter().map(|s| s.dropped()).sum()
+}
+
+struct Shared<T> {
+    items: Mutex<VecDeque<T>>,
+    available: Condvar,
+    senders: AtomicUsize,
+    stats: Arc<QueueStats>,
+}
+
+/// Sending half of a bounded queue
+pub struct QueueSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// Receiving half of a bounded queue
+pub struct QueueReceiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// Create a bounded queue: when full, `send` discards a queued message and counts the drop, so a
+/// stalled consumer never grows memory and the newest data always gets through. Note-offs and
+/// All Notes Off are spared, controller and aftertouch data goes first (see `drop_index`).
+pub fn bounded<T>(name: &str, capacity: usize) -> (QueueSender<T>, QueueReceiver<T>) {
+    let capacity = capacity.max(1);
+    let shared = Arc::new(Shared {
+        items: Mutex::new(VecDeque::with_capacity(capacity)),
+        available: Condvar::new(),
+        senders: AtomicUsize::new(1),
+        stats: register(name, capacity),
+    });
+    (QueueSender { shared: shared.clone() }, QueueReceiver { shared })
+}
+
+/// A queued message carrying raw MIDI, so a full queue can tell which messages it may drop
+pub trait MidiItem {
+    fn midi(&self) -> &[u8];
+}
+
+impl MidiItem for Vec<u8> {
+    fn midi(&self) -> &[u8] {
+        self
+    }
+}
+
+/// (input source, message, receive time) as queued for the forwarder
+impl MidiItem for (u8, Vec<u8>, Instant) {
+    fn midi(&self) -> &[u8] {
+        &self.1
+    }
+}
+
+/// Index of the message to drop from a full queue: the oldest one that is neither a note nor a
+/// channel mode message (All Notes Off etc.), then the oldest note-on, then the oldest message.
+/// Dropping a note-off or All Notes Off would leave notes hanging.
+fn drop_index<T: MidiItem>(items: &VecDeque<T>) -> usize {
+    let status = |m: &[u8]| m.first().map(|s| s & 0xF0);
+    let is_note = |m: &[u8]| matches!(status(m), Some(0x80 | 0x90));
+    let is_channel_mode = |m: &[u8]| status(m) == Some(0xB0) && m.get(1).is_some_and(|cc| *cc >= 120);
+    let is_note_on = |m: &[u8]| status(m) == Some(0x90) && m.get(2).is_some_and(|velocity| *velocity > 0);
+    items
+        .iter()
+        .position(|item| !is_note(item.midi()) && !is_channel_mode(item.midi()))
+        .or_else(|| items.iter().position(|item| is_note_on(item.midi())))
+        .unwrap_or(0)
+}
+
+impl<T: MidiItem> QueueSender<T> {
+    pub fn send(&self, item: T) {
+        let mut items = match self.shared.items.lock() {
+            Ok(i) => i,
+            Err(_) => return,
+        };
+        if items.len() >= self.shared.stats.capacity {
+            let index = drop_index(&items);
+            items.remove(index);
+            self.shared.stats.dropped.fetch_add(1, Ordering::SeqCst);
+        }
+        items.push_back(item);
+        self.shared.stats.len.store(items.len(), Ordering::SeqCst);
+        self.shared.available.notify_one();
+    }
+}
+
+impl<T> Clone for QueueSender<T> {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::SeqCst);
+        QueueSender { shared: self.shared.clone() }
+    }
+}
+
+impl<T> Drop for QueueSender<T> {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::SeqCst) == 1 {
+            // Last sender gone: wake the receiver so it can observe the disconnect
+            let _guard = self.shared.items.lock();
+            self.shared.available.notify_all();
+        }
+    }
+}
+
+impl<T> QueueReceiver<T> {
+    /// Block until a message arrives; `None` once all senders are dropped and the queue is drained
+    pub fn recv(&self) -> Option<T> {
+        let mut items = self.shared.items.lock().ok()?;
+        loop {
+            if let Some(item) = items.pop_front() {
+                self.shared.stats.len.store(items.len(), Ordering::SeqCst);
+                return Some(item);
+            }
+            if self.shared.senders.load(Ordering::SeqCst) == 0 {
+                return None;
+            }
+            items = self.shared.available.wait(items).ok()?;
+        }
+    }
+
+    /// Like `recv`, but gives up after `timeout`
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut items = self.shared.items.lock().map_err(|_| RecvTimeoutError::Disconnected)?;
+        loop {
+            if let Some(item) = items.pop_front() {
+                self.shared.stats.len.store(items.len(), Ordering::SeqCst);
+                return Ok(item);
+            }
+            if self.shared.senders.load(Ordering::SeqCst) == 0 {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            items = self
+                .shared
+                .available
+                .wait_timeout(items, deadline - now)
+                .map_err(|_| RecvTimeoutError::Disconnected)?
+                .0;
+        }
+    }
+}
+
+impl<T> Iterator for QueueReceiver<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.recv()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(rx: &QueueReceiver<Vec<u8>>) -> Vec<Vec<u8>> {
+        std::iter::from_fn(|| rx.recv_timeout(Duration::ZERO).ok()).collect()
+    }
+
+    #[test]
+    fn overflow_drops_controllers_before_notes() {
+        let (tx, rx) = bounded("test_controllers", 3);
+        tx.send(vec![0x90, 60, 100]);
+        tx.send(vec![0xB0, 1, 10]);
+        tx.send(vec![0x80, 60, 0]);
+        tx.send(vec![0xD0, 50]);
+        assert_eq!(drain(&rx), vec![vec![0x90, 60, 100], vec![0x80, 60, 0], vec![0xD0, 50]]);
+        assert_eq!(rx.shared.stats.dropped(), 1);
+    }
+
+    #[test]
+    fn overflow_spares_note_offs_and_all_notes_off() {
+        let (tx, rx) = bounded("test_note_offs", 3);
+        tx.send(vec![0x80, 60, 0]);
+        tx.send(vec![0xB0, 123, 0]);
+        tx.send(vec![0x90, 62, 100]);
+        tx.send(vec![0x90, 64, 0]);
+        assert_eq!(drain(&rx), vec![vec![0x80, 60, 0], vec![0xB0, 123, 0], vec![0x90, 64, 0]]);
+    }
+
+    #[test]
+    fn overflow_keeps_order() {
+        let (tx, rx) = bounded("test_order", 4);
+        for value in 0..10 {
+            tx.send(vec![0xB0, 7, value]);
+        }
+        assert_eq!(drain(&rx), (6..10).map(|value| vec![0xB0, 7, value]).collect::<Vec<_>>());
+        assert_eq!(rx.shared.stats.dropped(), 6);
+    }
+
+    #[test]
+    fn full_of_note_offs_drops_the_oldest() {
+        let (tx, rx) = bounded("test_note_offs_only", 2);
+        for note in 60..63 {
+            tx.send(vec![0x80, note, 0]);
+        }
+        assert_eq!(drain(&rx), vec![vec![0x80, 61, 0], vec![0x80, 62, 0]]);
+    }
+}
@@ -0,0 +1,60 @@
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How often the repeat thread checks for a due step
+const POLL_MS: u64 = 10;
+
+/// Held transpose button: direction (+1 up, -1 down) and when its next repeat step is due
+static HELD: Mutex<Option<(i32, Instant)>> = Mutex::new(None);
+
+/// A transpose up/down button went down and its first step was applied. With
+/// `transpose.auto_repeat` the step repeats after `repeat_delay_ms` until the button is released.
+pub fn press(direction: i32) {
+    let config = &crate::get_config().transpose;
+    if !config.auto_repeat {
+        return;
+    }
+    if let Ok(mut held) = HELD.lock() {
+        *held = Some((direction, Instant::now() + Duration::from_millis(config.repeat_delay_ms)));
+    }
+}
+
+/// The button was released (value 0); stops the repeat if it was for this direction
+pub fn release(direction: i32) {
+    if let Ok(mut held) = HELD.lock() {
+        if held.is_some_and(|(d, _)| d == direction) {
+            *held = None;
+        }
+    }
+}
+
+/// Spawn the thread stepping the transpose while a button is held. Repeating also stops
+/// at the end of the transpose range, so a lost release message can't scroll forever.
+pub fn spawn_repeater() -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        while !crate::state().exit.load(Ordering::SeqCst) {
+            let interval = Duration::from_millis(crate::get_config().transpose.repeat_interval_ms.max(POLL_MS));
+            let due = match HELD.lock() {
+                Ok(mut held) => match *held {
+                    Some((direction, next)) if Instant::now() >= next => {
+                        *held = Some((direction, Instant::now() + interval));
+                        Some(direction)
+                    }
+                    _ => None,
+                },
+                Err(_) => None,
+            };
+            if let Some(direction) = due {
+                let current = crate::general::glide::target();
+                let new_value = crate::set_transpose_semitones(current + direction);
+                log::debug!(target: "app", "[REPEAT] Transpose {}: {} -> {}", if direction > 0 { "UP" } else { "DOWN" }, current, new_value);
+                if new_value == current {
+                    release(direction);
+                }
+            }
+            thread::sleep(Duration::from_millis(POLL_MS));
+        }
+    })
+}
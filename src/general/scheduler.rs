@@ -0,0 +1,159 @@
+use crate::general::commands::{self, Command, Outcome, Source};
+use chrono::{Local, NaiveTime, TimeDelta};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A command waiting to be run at `due`, with the permissions of the source that scheduled it
+struct ScheduledAction {
+    id: u32,
+    due: Instant,
+    when: String,
+    text: String,
+    command: Command,
+    source: Source,
+}
+
+static ACTIONS: Mutex<Vec<ScheduledAction>> = Mutex::new(Vec::new());
+static NEXT_ID: AtomicU32 = AtomicU32::new(1);
+// Bumped when actions are added, run or cancelled so MQTT can publish the pending list
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+/// Parse a wall clock time `HH:MM` or `HH:MM:SS`
+fn parse_clock_time(text: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(text, "%H:%M:%S").or_else(|_| NaiveTime::parse_from_str(text, "%H:%M")).ok()
+}
+
+/// Time until the next occurrence of `time` in local time (today or tomorrow). The time zone
+/// offset of that day is used, so a DST change in between doesn't shift the action.
+fn until_local_time(time: NaiveTime) -> Duration {
+    let now = Local::now();
+    let mut day = now.date_naive();
+    for _ in 0..3 {
+        // `earliest` picks the first of a repeated hour; a skipped hour has no such time that day
+        if let Some(due) = day.and_time(time).and_local_timezone(Local).earliest().filter(|due| *due > now) {
+            return (due - now).to_std().unwrap_or_default();
+        }
+        day += TimeDelta::days(1);
+    }
+    Duration::ZERO
+}
+
+/// Schedule a command from `source`: `at 22:30 transpose 0` (next 22:30 local time) or
+/// `in 5m osc off` (s/m/h). The command is checked now and runs later with the same
+/// permissions, so remote sources can't schedule console-only commands. Returns a confirmation text.
+pub fn schedule(spec: &str, source: Source) -> Result<String, String> {
+    let spec = spec.trim();
+    let (mode, rest) = spec.split_once(' ').ok_or("expected 'at <HH:MM> <command>' or 'in <duration> <command>'")?;
+    let (time, text) = rest.trim().split_once(' ').ok_or("missing command")?;
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("missing command".to_string());
+    }
+    let command = commands::parse(text).unwrap_or_else(|| Err(format!("unknown command '{}'", text)))?;
+    if source != Source::Console && command.console_only() {
+        return Err(format!("'{}' is only available on the console, not over {}", text, source.name()));
+    }
+    let delay = match mode.to_ascii_lowercase().as_str() {
+        "at" => until_local_time(parse_clock_time(time).ok_or_else(|| format!("invalid time '{}' (HH:MM or HH:MM:SS)", time))?),
+        "in" => crate::general::debug::parse_duration(time).ok_or_else(|| format!("invalid duration '{}' (e.g. 90s, 5m, 2h)", time))?,
+        other => return Err(format!("unknown schedule '{}' (use 'at' or 'in')", other)),
+    };
+    let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+    let when = format!("{} {}", mode.to_ascii_lowercase(), time);
+    if let Ok(mut actions) = ACTIONS.lock() {
+        actions.push(ScheduledAction { id, due: Instant::now() + delay, when: when.clone(), text: text.to_string(), command, source });
+    }
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    let secs = delay.as_secs();
+    Ok(format!("Scheduled #{} '{}' {} (in {}:{:02}:{:02})", id, text, when, secs / 3600, secs / 60 % 60, secs % 60))
+}
+
+/// Cancel one action by id, or all with `None`. Returns the number of cancelled actions.
+pub fn cancel(id: Option<u32>) -> usize {
+    let Ok(mut actions) = ACTIONS.lock() else { return 0 };
+    let before = actions.len();
+    actions.retain(|a| id.is_some_and(|id| a.id != id));
+    let cancelled = before - actions.len();
+    if cancelled > 0 {
+        GENERATION.fetch_add(1, Ordering::SeqCst);
+    }
+    cancelled
+}
+
+/// Pending actions, soonest first, e.g. `#2 in 13:05 osc off (at 22:30)`
+pub fn pending() -> Vec<String> {
+    let Ok(actions) = ACTIONS.lock() else { return Vec::new() };
+    let mut list: Vec<&ScheduledAction> = actions.iter().collect();
+    list.sort_by_key(|a| a.due);
+    list.iter()
+        .map(|a| {
+            let secs = a.due.saturating_duration_since(Instant::now()).as_secs();
+            format!("#{} in {}:{:02}:{:02} {} ({})", a.id, secs / 3600, secs / 60 % 60, secs % 60, a.text, a.when)
+        })
+        .collect()
+}
+
+/// Spawn the thread that runs due actions through the command executor
+pub fn spawn_scheduler() -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        while !crate::state().exit.load(Ordering::SeqCst) {
+            let due: Vec<ScheduledAction> = match ACTIONS.lock() {
+                Ok(mut actions) => {
+                    let now = Instant::now();
+                    let (due, waiting) = actions.drain(..).partition(|a| a.due <= now);
+                    *actions = waiting;
+                    due
+                }
+                Err(_) => Vec::new(),
+            };
+            for action in &due {
+                log::info!(target: "app", "[SCHEDULE] Running #{}: {}", action.id, action.text);
+                match commands::execute(action.command.clone(), action.source) {
+                    Ok(Outcome::Transpose { applied, .. }) => log::info!(target: "app", "[SCHEDULE] Transpose set to {}", applied),
+                    Ok(Outcome::Done(message)) => log::info!(target: "app", "[SCHEDULE] {}", message),
+                    Ok(Outcome::Silent) => {}
+                    Err(e) => log::warn!(target: "app", "[SCHEDULE] #{}: {}", action.id, e),
+                }
+            }
+            if !due.is_empty() {
+                GENERATION.fetch_add(1, Ordering::SeqCst);
+            }
+            thread::sleep(Duration::from_millis(200));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_times_with_and_without_seconds() {
+        assert_eq!(parse_clock_time("22:30"), NaiveTime::from_hms_opt(22, 30, 0));
+        assert_eq!(parse_clock_time("07:05:09"), NaiveTime::from_hms_opt(7, 5, 9));
+        assert_eq!(parse_clock_time("24:00"), None);
+        assert_eq!(parse_clock_time("noon"), None);
+    }
+
+    #[test]
+    fn next_local_time_is_within_a_day() {
+        let time = (Local::now() + TimeDelta::minutes(1)).time();
+        assert!(until_local_time(time) <= Duration::from_secs(61));
+        assert!(until_local_time(Local::now().time()) <= Duration::from_secs(25 * 3600));
+    }
+
+    #[test]
+    fn remote_sources_cannot_schedule_console_only_commands() {
+        assert!(schedule("in 1s exit", Source::Mqtt).is_err());
+        assert!(schedule("at 22:30 mqtt off", Source::Osc).is_err());
+        assert!(schedule("in 1s discover", Source::Web).is_err());
+        assert!(schedule("in 1s no such command", Source::Console).is_err());
+        assert!(pending().is_empty());
+    }
+}
@@ -0,0 +1,229 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// MIDI clock resolution
+const TICKS_PER_BEAT: f64 = 24.0;
+
+/// `sequence` config section: planned key changes started over OSC, MQTT or the console
+#[derive(Debug, serde::Deserialize, Clone)]
+#[serde(default)]
+pub struct SequenceConfig {
+    /// Steps like `at bar 16 to +2` or `after 30s down 1`, applied in order
+    pub steps: Vec<String>,
+    /// Tempo for bar positions; `0` = count bars from the MIDI clock on the input
+    pub bpm: f64,
+    pub beats_per_bar: u32,
+    /// Post each key change to the VRChat chatbox
+    pub chatbox: bool,
+}
+
+impl Default for SequenceConfig {
+    fn default() -> Self {
+        SequenceConfig { steps: Vec::new(), bpm: 0.0, beats_per_bar: 4, chatbox: false }
+    }
+}
+
+/// When a step is due: since the sequence start (`at`) or since the previous step (`after`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum When {
+    AtTime(Duration),
+    AtBar(u32),
+    After(Duration),
+    AfterBars(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Change {
+    To(i32),
+    Up(i32),
+    Down(i32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Step {
+    when: When,
+    change: Change,
+}
+
+struct Playback {
+    steps: Vec<Step>,
+    started: Instant,
+    next: usize,
+    // Time and beat position of the last applied step
+    last_time: Instant,
+    last_beats: f64,
+}
+
+static PLAYBACK: Mutex<Option<Playback>> = Mutex::new(None);
+// MIDI clock ticks since the sequence was started
+static TICKS: AtomicU64 = AtomicU64::new(0);
+// Bumped on start/stop and every applied step so MQTT can publish the progress
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+pub fn is_active() -> bool {
+    PLAYBACK.lock().map(|p| p.is_some()).unwrap_or(false)
+}
+
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+/// `30s`, `5m`, `90` or `1:30`
+fn parse_time(text: &str) -> Option<Duration> {
+    crate::general::debug::parse_duration(text).or_else(|| crate::general::automation::parse_time(text))
+}
+
+fn parse_bars(text: &str) -> Option<u32> {
+    text.parse().ok().filter(|&bars| bars > 0)
+}
+
+/// Parse one step: `at <time>`, `at bar <n>`, `after <duration>` or `after <n> bars`,
+/// followed by `to <transpose>`, `up <semitones>` or `down <semitones>`
+fn parse_step(text: &str) -> Option<Step> {
+    let lower = text.trim().to_ascii_lowercase();
+    let words: Vec<&str> = lower.split_whitespace().collect();
+    let (when, rest) = match words.as_slice() {
+        ["at", "bar", bar, rest @ ..] => (When::AtBar(parse_bars(bar)?), rest),
+        ["at", time, rest @ ..] => (When::AtTime(parse_time(time)?), rest),
+        ["after", bars, "bar" | "bars", rest @ ..] => (When::AfterBars(parse_bars(bars)?), rest),
+        ["after", time, rest @ ..] => (When::After(parse_time(time)?), rest),
+        _ => return None,
+    };
+    let change = match rest {
+        ["to", value] => Change::To(value.trim_start_matches('+').parse().ok()?),
+        ["up", value] => Change::Up(value.parse().ok()?),
+        ["down", value] => Change::Down(value.parse().ok()?),
+        _ => return None,
+    };
+    Some(Step { when, change })
+}
+
+fn parse_steps(steps: &[String]) -> Result<Vec<Step>, String> {
+    steps
+        .iter()
+        .enumerate()
+        .map(|(idx, text)| {
+            parse_step(text).ok_or_else(|| {
+                format!("sequence step {} '{}': expected 'at <time>|at bar <n>|after <duration>|after <n> bars' + 'to <n>|up <n>|down <n>'", idx + 1, text)
+            })
+        })
+        .collect()
+}
+
+/// Check the configured steps at startup, so typos show up before the song
+pub fn check(config: &SequenceConfig) -> Result<(), String> {
+    parse_steps(&config.steps).map(|_| ())
+}
+
+/// Start the configured sequence from the beginning (restarts a running one)
+pub fn start() -> Result<usize, String> {
+    let steps = parse_steps(&crate::get_config().sequence.steps)?;
+    if steps.is_empty() {
+        return Err("no sequence.steps configured".to_string());
+    }
+    let count = steps.len();
+    let now = Instant::now();
+    TICKS.store(0, Ordering::SeqCst);
+    if let Ok(mut guard) = PLAYBACK.lock() {
+        *guard = Some(Playback { steps, started: now, next: 0, last_time: now, last_beats: 0.0 });
+    }
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    log::info!(target: "app", "Sequence started ({} steps)", count);
+    Ok(count)
+}
+
+/// Stop the sequence (the current transpose is kept)
+pub fn stop() {
+    let was_active = PLAYBACK.lock().map(|mut p| p.take().is_some()).unwrap_or(false);
+    if was_active {
+        GENERATION.fetch_add(1, Ordering::SeqCst);
+        log::info!(target: "app", "Sequence stopped");
+    }
+}
+
+/// Count MIDI clock ticks for bar positions (with `sequence.bpm` at 0)
+pub fn on_midi(msg: &[u8]) {
+    if msg.first() == Some(&0xF8) {
+        TICKS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Progress for `status` and MQTT, e.g. `step 2/5` or `off`
+pub fn progress() -> String {
+    let Ok(guard) = PLAYBACK.lock() else { return "off".to_string() };
+    match guard.as_ref() {
+        Some(p) => format!("step {}/{}", p.next, p.steps.len()),
+        None => "off".to_string(),
+    }
+}
+
+/// Beats since the start, from the configured tempo or the MIDI clock
+fn beats(playback: &Playback) -> f64 {
+    let bpm = crate::get_config().sequence.bpm;
+    if bpm > 0.0 {
+        playback.started.elapsed().as_secs_f64() * bpm / 60.0
+    } else {
+        TICKS.load(Ordering::Relaxed) as f64 / TICKS_PER_BEAT
+    }
+}
+
+fn is_due(playback: &Playback, when: When, beats: f64) -> bool {
+    let beats_per_bar = crate::get_config().sequence.beats_per_bar.max(1) as f64;
+    match when {
+        When::AtTime(at) => playback.started.elapsed() >= at,
+        When::AtBar(bar) => beats >= (bar - 1) as f64 * beats_per_bar,
+        When::After(delay) => playback.last_time.elapsed() >= delay,
+        When::AfterBars(bars) => beats - playback.last_beats >= bars as f64 * beats_per_bar,
+    }
+}
+
+/// Apply all steps that are due; returns false once the sequence has finished
+fn apply_due(playback: &mut Playback) -> bool {
+    while let Some(step) = playback.steps.get(playback.next).copied() {
+        let beats = beats(playback);
+        if !is_due(playback, step.when, beats) {
+            break;
+        }
+        playback.next += 1;
+        playback.last_time = Instant::now();
+        playback.last_beats = beats;
+        let current = crate::general::glide::target();
+        let value = crate::set_transpose_semitones(match step.change {
+            Change::To(value) => value,
+            Change::Up(semitones) => current + semitones,
+            Change::Down(semitones) => current - semitones,
+        });
+        GENERATION.fetch_add(1, Ordering::SeqCst);
+        let text = format!("Sequence step {}/{}: transpose {:+}", playback.next, playback.steps.len(), value);
+        log::info!(target: "app", "{}", text);
+        if crate::get_config().sequence.chatbox {
+            crate::remote::chatbox::send_chatbox(&text);
+        }
+    }
+    playback.next < playback.steps.len()
+}
+
+/// Spawn the player thread that applies the steps of a running sequence on time
+pub fn spawn_sequence_player() -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        while !crate::state().exit.load(Ordering::SeqCst) {
+            let finished = match PLAYBACK.lock() {
+                Ok(mut guard) => {
+                    let done = guard.as_mut().is_some_and(|playback| !apply_due(playback));
+                    if done {
+                        *guard = None;
+                    }
+                    done
+                }
+                Err(_) => false,
+            };
+            if finished {
+                GENERATION.fetch_add(1, Ordering::SeqCst);
+                log::info!(target: "app", "Sequence finished");
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+    })
+}
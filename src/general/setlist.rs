@@ -0,0 +1,91 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use crate::general::state::ConfigRef;
+
+/// One entry of the `setlist` config section
+#[derive(Debug, serde::Deserialize, Clone)]
+pub struct Song {
+    pub name: String,
+    /// Transpose set when the song becomes active; unset keeps the current (or profile's) one
+    #[serde(default)]
+    pub transpose: Option<i32>,
+    /// Profile applied before the song's transpose
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// Text posted to the VRChat chatbox when the song becomes active
+    #[serde(default)]
+    pub chatbox: Option<String>,
+}
+
+// Index of the active song; the lock also serializes song changes so all settings apply together
+static POSITION: Mutex<Option<usize>> = Mutex::new(None);
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+fn songs() -> ConfigRef<[Song]> {
+    crate::state().config_ref(|config| config.setlist.as_slice())
+}
+
+/// Active song (index, entry)
+pub fn current() -> Option<(usize, Song)> {
+    let idx = (*POSITION.lock().ok()?)?;
+    songs().get(idx).map(|s| (idx, s.clone()))
+}
+
+/// Bumped on every song change so MQTT can publish the new song
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+/// Advance to the next song (the first one if none is active yet)
+pub fn next() -> Option<Song> {
+    step(1)
+}
+
+/// Go back to the previous song
+pub fn prev() -> Option<Song> {
+    step(-1)
+}
+
+/// Jump to a song by index
+pub fn goto(idx: usize) -> Option<Song> {
+    let mut pos = POSITION.lock().ok()?;
+    let song = songs().get(idx)?.clone();
+    apply(&song);
+    *pos = Some(idx);
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    Some(song)
+}
+
+fn step(delta: isize) -> Option<Song> {
+    let list = songs();
+    if list.is_empty() {
+        return None;
+    }
+    let mut pos = POSITION.lock().ok()?;
+    let idx = match *pos {
+        None => 0,
+        Some(i) => (i as isize + delta).clamp(0, list.len() as isize - 1) as usize,
+    };
+    let song = list[idx].clone();
+    apply(&song);
+    *pos = Some(idx);
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    Some(song)
+}
+
+/// Apply profile, transpose and chatbox text of a song (caller holds the position lock)
+fn apply(song: &Song) {
+    if let Some(profile) = &song.profile {
+        if !crate::general::profile::apply(profile) {
+            log::warn!(target: "app", "[SETLIST] Unknown profile '{}' in song '{}'", profile, song.name);
+        }
+    }
+    if let Some(transpose) = song.transpose {
+        crate::set_transpose_semitones(transpose);
+    }
+    if let Some(text) = &song.chatbox {
+        crate::remote::chatbox::send_chatbox(text);
+    }
+    crate::general::now_playing::set(&song.name, song.chatbox.is_none());
+    log::info!(target: "app", "[SETLIST] Now playing: {} (transpose {})", song.name, crate::state().transpose.load(Ordering::SeqCst));
+}
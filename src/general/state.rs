@@ -0,0 +1,110 @@
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64};
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, RwLock};
+use crate::Config;
+
+/// Shared runtime state: the active configuration plus the runtime switches that the
+/// forwarder, OSC, MQTT and console threads read and toggle.
+///
+/// Statics elsewhere are private to their module and only changed through its functions:
+/// either a feature's own state (practice, LFO, setlist, ...), kept next to the generation
+/// counter MQTT and the dashboard poll, or a cache (e.g. the shared OSC socket or the last OSC
+/// controller). A flag that other modules set or read directly belongs here.
+pub struct AppState {
+    config: RwLock<Option<Arc<Config>>>,
+    /// Current transpose amount in semitones (set through `crate::set_transpose_semitones`)
+    pub transpose: AtomicI32,
+    /// Octave shift, applied as 12 semitones each on top of `transpose` (set through `crate::set_octave_shift`)
+    pub octave: AtomicI32,
+    /// When true all threads wind down and the program shuts down
+    pub exit: AtomicBool,
+    /// Verbose debug logging (runtime-togglable), initialized from `config.debug`
+    pub debug: AtomicBool,
+    /// The config was loaded from a file (not the built-in defaults)
+    pub config_from_file: AtomicBool,
+    /// OSC sending of MIDI data
+    pub osc_sending: AtomicBool,
+    /// Per-stream flags: original input MIDI and transposed MIDI via OSC (both also need `osc_sending`)
+    pub osc_original: AtomicBool,
+    pub osc_transposed: AtomicBool,
+    /// No MIDI output is connected (`midi.observer` or no output port available)
+    pub observer: AtomicBool,
+    /// Convert channel pressure into a CC in the forwarder (see `midi.aftertouch_cc`)
+    pub aftertouch_to_cc: AtomicBool,
+    /// MQTT listener enabled (runtime)
+    pub mqtt_enabled: AtomicBool,
+    /// MQTT connection state (set by the MQTT listener)
+    pub mqtt_connected: AtomicBool,
+    /// Auto mode: only send OSC while VRChat is detected
+    pub osc_sending_auto: AtomicBool,
+    /// The OSC listener is bound and running (set by the OSC listener)
+    pub osc_listener_running: AtomicBool,
+    /// OSC target picked at runtime (e.g. via `discover`), overriding the configured one
+    /// (set through `crate::osc_sender::set_target`)
+    pub osc_target: Mutex<Option<String>>,
+    /// Bumped on every `osc_target` change, so sender threads notice it without locking per message
+    pub osc_target_generation: AtomicU64,
+}
+
+static STATE: AppState = AppState {
+    config: RwLock::new(None),
+    transpose: AtomicI32::new(0),
+    octave: AtomicI32::new(0),
+    exit: AtomicBool::new(false),
+    debug: AtomicBool::new(false),
+    config_from_file: AtomicBool::new(false),
+    osc_sending: AtomicBool::new(false),
+    osc_original: AtomicBool::new(true),
+    osc_transposed: AtomicBool::new(false),
+    observer: AtomicBool::new(false),
+    aftertouch_to_cc: AtomicBool::new(false),
+    mqtt_enabled: AtomicBool::new(true),
+    mqtt_connected: AtomicBool::new(false),
+    osc_sending_auto: AtomicBool::new(false),
+    osc_listener_running: AtomicBool::new(false),
+    osc_target: Mutex::new(None),
+    osc_target_generation: AtomicU64::new(0),
+};
+
+/// A part of the active configuration (e.g. `midi.zones`) that keeps that configuration
+/// alive while it is used, even if a new one is installed meanwhile
+pub struct ConfigRef<T: ?Sized> {
+    config: Arc<Config>,
+    part: fn(&Config) -> &T,
+}
+
+impl<T: ?Sized> Deref for ConfigRef<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        (self.part)(&self.config)
+    }
+}
+
+/// The process-wide state
+pub fn state() -> &'static AppState {
+    &STATE
+}
+
+impl AppState {
+    /// Active configuration: the built-in defaults until `set_config` installs the loaded one
+    /// (main does that before it starts any other thread)
+    pub fn config(&self) -> Arc<Config> {
+        if let Some(config) = self.config.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+            return config.clone();
+        }
+        let mut current = self.config.write().unwrap_or_else(|e| e.into_inner());
+        current.get_or_insert_with(|| Arc::new(Config::default())).clone()
+    }
+
+    /// `part` of the active configuration
+    pub fn config_ref<T: ?Sized>(&self, part: fn(&Config) -> &T) -> ConfigRef<T> {
+        ConfigRef { config: self.config(), part }
+    }
+
+    /// Install a configuration. Threads still holding the previous one keep it until they
+    /// drop their `Arc`; it is freed after that.
+    pub fn set_config(&self, config: Config) {
+        *self.config.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(config));
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Statistics of the current session (input notes, before transposition)
+struct SessionStats {
+    started: Instant,
+    started_unix: u64,
+    total_notes: u64,
+    note_counts: [u64; 128],
+    held: HashSet<u8>,
+    peak_polyphony: usize,
+    // Seconds spent per transpose value, plus the value active since `transpose_since`
+    transpose_secs: BTreeMap<i32, f64>,
+    transpose: i32,
+    transpose_since: Instant,
+}
+
+static STATS: Mutex<Option<SessionStats>> = Mutex::new(None);
+
+fn with_stats<R>(f: impl FnOnce(&mut SessionStats) -> R) -> Option<R> {
+    let mut guard = STATS.lock().ok()?;
+    let stats = guard.get_or_insert_with(|| {
+        let now = Instant::now();
+        SessionStats {
+            started: now,
+            started_unix: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            total_notes: 0,
+            note_counts: [0; 128],
+            held: HashSet::new(),
+            peak_polyphony: 0,
+            transpose_secs: BTreeMap::new(),
+            transpose: 0,
+            transpose_since: now,
+        }
+    });
+    Some(f(stats))
+}
+
+/// Start the session clock with the initial transpose
+pub fn start(transpose: i32) {
+    with_stats(|s| {
+        s.transpose = transpose;
+        s.transpose_since = Instant::now();
+    });
+}
+
+/// Count note-on/off of an incoming MIDI message
+pub fn record_midi(message: &[u8]) {
+    if message.len() < 3 || message[1] > 127 {
+        return;
+    }
+    let note = message[1];
+    let on = message[0] & 0xF0 == 0x90 && message[2] > 0;
+    let off = message[0] & 0xF0 == 0x80 || (message[0] & 0xF0 == 0x90 && message[2] == 0);
+    if !on && !off {
+        return;
+    }
+    with_stats(|s| {
+        if on {
+            s.total_notes += 1;
+            s.note_counts[note as usize] += 1;
+            s.held.insert(note);
+            s.peak_polyphony = s.peak_polyphony.max(s.held.len());
+        } else {
+            s.held.remove(&note);
+        }
+    });
+}
+
+/// Input notes currently held, lowest first
+pub fn held_notes() -> Vec<u8> {
+    let mut notes: Vec<u8> = with_stats(|s| s.held.iter().copied().collect()).unwrap_or_default();
+    notes.sort_unstable();
+    notes
+}
+
+/// Account the time spent on the previous transpose value
+pub fn record_transpose(value: i32) {
+    with_stats(|s| {
+        if value == s.transpose {
+            return;
+        }
+        let now = Instant::now();
+        *s.transpose_secs.entry(s.transpose).or_default() += now.duration_since(s.transpose_since).as_secs_f64();
+        s.transpose = value;
+        s.transpose_since = now;
+    });
+}
+
+/// Most played note of a session report
+#[derive(Debug, serde::Serialize)]
+pub struct NoteCount {
+    pub note: u8,
+    pub name: String,
+    pub count: u64,
+}
+
+/// Summary of a session, printed on exit and optionally written as JSON (`stats_report`)
+#[derive(Debug, serde::Serialize)]
+pub struct SessionReport {
+    pub started_unix: u64,
+    pub duration_secs: u64,
+    pub total_notes: u64,
+    pub most_played_note: Option<NoteCount>,
+    pub peak_polyphony: usize,
+    pub transpose_secs: BTreeMap<i32, u64>,
+}
+
+/// Build the report for the session so far
+pub fn report() -> Option<SessionReport> {
+    with_stats(|s| {
+        let mut transpose_secs = s.transpose_secs.clone();
+        *transpose_secs.entry(s.transpose).or_default() += s.transpose_since.elapsed().as_secs_f64();
+        let most_played = (0..128u8)
+            .max_by_key(|n| s.note_counts[*n as usize])
+            .filter(|n| s.note_counts[*n as usize] > 0)
+            .map(|n| NoteCount { note: n, name: crate::general::notes::display_name(n), count: s.note_counts[n as usize] });
+        SessionReport {
+            started_unix: s.started_unix,
+            duration_secs: s.started.elapsed().as_secs(),
+            total_notes: s.total_notes,
+            most_played_note: most_played,
+            peak_polyphony: s.peak_polyphony,
+            transpose_secs: transpose_secs.into_iter().map(|(t, secs)| (t, secs.round() as u64)).collect(),
+        }
+    })
+}
+
+fn format_duration(secs: u64) -> String {
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// Print the session summary and write the JSON report if configured
+pub fn finish_session() {
+    let report = match report() {
+        Some(r) => r,
+        None => return,
+    };
+    log::info!(target: "app", "Session summary:");
+    log::info!(target: "app", "  Duration:         {}", format_duration(report.duration_secs));
+    log::info!(target: "app", "  Notes played:     {}", report.total_notes);
+    match &report.most_played_note {
+        Some(n) => log::info!(target: "app", "  Most played note: {} ({}x)", n.name, n.count),
+        None => log::info!(target: "app", "  Most played note: -"),
+    }
+    log::info!(target: "app", "  Peak polyphony:   {}", report.peak_polyphony);
+    for (transpose, secs) in &report.transpose_secs {
+        log::info!(target: "app", "  Transpose {:+}:     {}", transpose, format_duration(*secs));
+    }
+
+    if let Some(path) = &crate::get_config().stats_report {
+        match serde_json::to_string_pretty(&report) {
+            Ok(text) => match std::fs::write(path, text) {
+                Ok(()) => log::info!(target: "app", "Session report written to {}", path),
+                Err(err) => log::error!(target: "app", "[STATS] Failed to write {}: {}", path, err),
+            },
+            Err(err) => log::error!(target: "app", "[STATS] Failed to serialize report: {}", err),
+        }
+    }
+}
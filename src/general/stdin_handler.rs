@@ -1,136 +1,115 @@
-use std::io::stdin;
 use std::thread;
 use std::sync::atomic::Ordering;
+use crate::general::commands::{self, Outcome, Source};
+use crate::io::console;
+
+/// Console help, one line per command
+const HELP: &[&str] = &[
+    "Commands:",
+    "  <number>         - Set transpose in semitones (also 'transpose <number>')",
+    "  up/down          - Transpose one semitone up or down",
+    "  octave up/down   - Shift one octave up or down (also 'octave <n>'), on top of the transpose",
+    "  osc on/enable    - Enable OSC sending",
+    "  osc off/disable  - Disable OSC sending",
+    "  osc auto on/off  - Only send OSC while VRChat is detected",
+    "  osc original     - Send original input MIDI via OSC",
+    "  osc transposed   - Send transposed MIDI via OSC",
+    "  osc original on/off   - Enable/Disable only the original stream",
+    "  osc transposed on/off - Enable/Disable only the transposed stream",
+    "  osc targets      - List the OSC send targets",
+    "  osc target <name|n> on/off - Enable/Disable one send target",
+    "  osc notetype int|bool|float - Argument type of the note parameters",
+    "  osc velocity on/off   - Also send <note>_v with the velocity (notes encoding)",
+    "  mqtt on/off      - Enable/Disable MQTT listener",
+    "  aftertouch cc on/off  - Convert channel pressure into midi.aftertouch_cc",
+    "  chatbox status on/off - Show the transpose in the VRChat chatbox",
+    "  glide on/off     - Apply larger transpose changes in 1-semitone steps",
+    "  zone <name|n> <semitones> - Set the extra transpose of a keyboard zone",
+    "  pitchbend off|center|bend - Realize the transpose via pitch bend (see midi.pitch_bend_mode)",
+    "  osc init         - Reset all note parameters and custom controls on the OSC target",
+    "  discover         - Search the network for OSC targets and pick one",
+    "  debug on/off     - Enable/Disable verbose debug prints",
+    "  debug on 5m      - Enable debug prints for a limited time (s/m/h)",
+    "  debug <category> on/off - Toggle midi, osc, mqtt or config debug prints",
+    "  log [<subsystem|all>] <level> - Set log levels (app, midi, osc, mqtt, config; off..trace)",
+    "  next/prev        - Step through the setlist",
+    "  practice on/off  - Alternate between the two practice transposes",
+    "  automation start [file] - Play back a transpose automation lane",
+    "  automation stop  - Stop the automation lane",
+    "  sequence start/stop - Run the configured transpose sequence",
+    "  lfo on/off       - Step through the lfo.pattern in time with the MIDI clock",
+    "  lfo depth <0-1>  - Scale the LFO pattern",
+    "  lfo rate <beats> - Beats per LFO step (4 = one bar)",
+    "  song <n>         - Jump to setlist song n (1-based)",
+    "  profile <name>   - Apply a configured profile",
+    "  custom <slug> <value...> - Set a custom control (one value per argument)",
+    "  export profile <file> - Save the current settings as a shareable profile",
+    "  import profile <file> - Load and apply a profile file",
+    "  export layout <file>  - Write a TouchOSC (.tosc) or Open Stage Control (.json) layout",
+    "  at 22:30 <command> - Run a command at the next 22:30 local time",
+    "  in 5m <command>  - Run a command after a delay (s/m/h), e.g. 'in 2h exit'",
+    "  panic            - Send All Notes Off on every MIDI channel",
+    "  stats [reset]    - Show (or reset) the MIDI thru latency and jitter",
+    "  monitor on/off   - Print every MIDI message in and out, decoded",
+    "  schedule         - List scheduled actions",
+    "  schedule cancel <id|all> - Cancel scheduled actions",
+    "  status           - Show current state and queue/drop counters",
+    "  history          - List the transpose changes of this session",
+    "  check            - Show which components (MIDI, OSC, MQTT) are up or broken",
+    "  check vrchat     - Check that VRChat receives OSC (ICMP + OSCQuery)",
+    "  help/h           - Show this help",
+    "  exit/quit/q      - Exit program",
+];
 
 /// Spawn a thread that reads lines from stdin. Empty line or 'exit' sets the
-/// global `EXIT_FLAG`. A valid integer updates `TRANSPOSE_SEMITONES`.
+/// exit flag of `AppState`. A valid integer updates the transpose.
 pub fn spawn_stdin_handler() -> thread::JoinHandle<()> {
     thread::spawn(move || {
-        let stdin = stdin();
-        let mut line = String::new();
-        // Optional handle for MQTT thread when toggled from console
-        let mut mqtt_handle: Option<std::thread::JoinHandle<()>> = None;
         loop {
-            line.clear();
-            if stdin.read_line(&mut line).is_err() {
-                break;
-            }
+            // A closed stdin reads as an empty line (exit), except headless: then only
+            // injected lines (exit from a signal, the tray or a scheduled action) arrive
+            let line = match console::read_line(None) {
+                Ok(line) => line,
+                Err(_) if crate::general::cli::options().headless => {
+                    if crate::state().exit.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    thread::sleep(std::time::Duration::from_millis(200));
+                    continue;
+                }
+                Err(_) => String::new(),
+            };
             let cmd = line.trim();
             if cmd.is_empty() {
-                crate::EXIT_FLAG.store(true, Ordering::SeqCst);
-                crate::MQTT_ENABLED.store(false, Ordering::SeqCst);
+                crate::state().exit.store(true, Ordering::SeqCst);
+                crate::state().mqtt_enabled.store(false, Ordering::SeqCst);
                 break;
             }
             if cmd.eq_ignore_ascii_case("exit") || cmd.eq_ignore_ascii_case("quit") || cmd.eq_ignore_ascii_case("q") {
-                crate::EXIT_FLAG.store(true, Ordering::SeqCst);
-                crate::MQTT_ENABLED.store(false, Ordering::SeqCst);
+                crate::state().exit.store(true, Ordering::SeqCst);
+                crate::state().mqtt_enabled.store(false, Ordering::SeqCst);
                 break;
             }
             
-            // Debug toggle commands
-            if cmd.eq_ignore_ascii_case("debug on") || cmd.eq_ignore_ascii_case("debug enable") {
-                crate::DEBUG_ENABLED.store(true, std::sync::atomic::Ordering::SeqCst);
-                println!("Debug enabled");
-                continue;
-            }
-            if cmd.eq_ignore_ascii_case("debug off") || cmd.eq_ignore_ascii_case("debug disable") {
-                crate::DEBUG_ENABLED.store(false, std::sync::atomic::Ordering::SeqCst);
-                println!("Debug disabled");
-                continue;
-            }
-
-            // OSC commands (accept text and numeric forms)
-            if cmd.eq_ignore_ascii_case("osc on") || cmd.eq_ignore_ascii_case("osc enable") || cmd == "1" {
-                crate::OSC_SENDING_ENABLED.store(true, Ordering::SeqCst);
-                println!("OSC sending enabled");
-                continue;
-            }
-            if cmd.eq_ignore_ascii_case("osc off") || cmd.eq_ignore_ascii_case("osc disable") || cmd == "0" {
-                crate::OSC_SENDING_ENABLED.store(false, Ordering::SeqCst);
-                println!("OSC sending disabled");
-                continue;
-            }
-
-            // osc_original flag: text or numeric via 'osc_original 1' / 'osc_original 0'
-            if cmd.eq_ignore_ascii_case("osc original") || cmd.eq_ignore_ascii_case("osc input") || cmd.eq_ignore_ascii_case("osc_original") {
-                crate::OSC_SEND_ORIGINAL.store(true, Ordering::SeqCst);
-                println!("OSC sending original input MIDI");
-                continue;
-            }
-            if cmd.eq_ignore_ascii_case("osc transposed") || cmd.eq_ignore_ascii_case("osc output") || cmd.eq_ignore_ascii_case("osc_transposed") {
-                crate::OSC_SEND_ORIGINAL.store(false, Ordering::SeqCst);
-                println!("OSC sending transposed MIDI");
-                continue;
-            }
-
-            // Numeric and explicit forms for osc_original: allow 'osc_original 1' / 'osc_original 0' or 'osc_original:1'
-            if cmd.starts_with("osc_original ") || cmd.starts_with("osc_original:") || cmd.eq_ignore_ascii_case("osc_original on") || cmd.eq_ignore_ascii_case("osc_original off") || cmd.eq_ignore_ascii_case("osc_original enable") || cmd.eq_ignore_ascii_case("osc_original disable") {
-                let parts: Vec<&str> = cmd.split(|c| c == ' ' || c == ':').collect();
-                if parts.len() >= 2 {
-                    match parts[1].trim() {
-                        "1" => {
-                            crate::OSC_SEND_ORIGINAL.store(true, Ordering::SeqCst);
-                            println!("OSC sending original input MIDI");
-                            continue;
-                        }
-                        "0" => {
-                            crate::OSC_SEND_ORIGINAL.store(false, Ordering::SeqCst);
-                            println!("OSC sending transposed MIDI");
-                            continue;
-                        }
-                        _ => {
-                            // If the command was 'osc_original on/enable' or 'osc_original off/disable', handle it here
-                            if cmd.eq_ignore_ascii_case("osc_original on") || cmd.eq_ignore_ascii_case("osc_original enable") {
-                                crate::OSC_SEND_ORIGINAL.store(true, Ordering::SeqCst);
-                                println!("OSC sending original input MIDI");
-                                continue;
-                            }
-                            if cmd.eq_ignore_ascii_case("osc_original off") || cmd.eq_ignore_ascii_case("osc_original disable") {
-                                crate::OSC_SEND_ORIGINAL.store(false, Ordering::SeqCst);
-                                println!("OSC sending transposed MIDI");
-                                continue;
-                            }
-                            // fallthrough to unrecognized
-                        }
-                    }
-                }
-            }
             if cmd.eq_ignore_ascii_case("help") || cmd.eq_ignore_ascii_case("h") {
-                println!("Commands:");
-                println!("  <number>         - Set transpose in semitones");
-                println!("  osc on/enable    - Enable OSC sending");
-                println!("  osc off/disable  - Disable OSC sending");
-                println!("  osc original     - Send original input MIDI via OSC");
-                println!("  osc transposed   - Send transposed MIDI via OSC");
-                println!("  mqtt on/off      - Enable/Disable MQTT listener");
-                println!("  debug on/off     - Enable/Disable verbose debug prints");
-                println!("  help/h           - Show this help");
-                println!("  exit/quit/q      - Exit program");
-                continue;
-            }
-            
-            // MQTT toggle commands
-            if cmd.eq_ignore_ascii_case("mqtt on") || cmd.eq_ignore_ascii_case("mqtt enable") {
-                crate::MQTT_ENABLED.store(true, std::sync::atomic::Ordering::SeqCst);
-                // Spawn MQTT listener if not running yet
-                if mqtt_handle.is_none() {
-                    mqtt_handle = Some(crate::mqtt_listener::spawn_mqtt_listener());
+                for line in HELP {
+                    console::print_line(line.to_string());
                 }
-                println!("MQTT enabled");
-                continue;
-            }
-            if cmd.eq_ignore_ascii_case("mqtt off") || cmd.eq_ignore_ascii_case("mqtt disable") {
-                crate::MQTT_ENABLED.store(false, std::sync::atomic::Ordering::SeqCst);
-                println!("MQTT disabled (listener will stop on next reconnect/exit)");
                 continue;
             }
-
-            if let Ok(v) = cmd.parse::<i32>() {
-                let clamped_value = crate::set_transpose_semitones(v);
-                println!("Transpose set to {}", clamped_value);
-            } else {
-                println!("Unrecognized command: '{}'. Type 'help' for available commands.", cmd);
-            }
+            
+            // Control commands shared with OSC, MQTT and the web interface
+            let reply = match commands::parse(cmd) {
+                Some(Ok(command)) => match commands::execute(command, Source::Console) {
+                    Ok(Outcome::Transpose { applied, .. }) => format!("Transpose set to {}", applied),
+                    Ok(Outcome::Done(message)) => message,
+                    Ok(Outcome::Silent) => continue,
+                    Err(e) => e,
+                },
+                Some(Err(usage)) => usage,
+                None => format!("Unrecognized command: '{}'. Type 'help' for available commands.", cmd),
+            };
+            console::print_line(reply);
         }
     })
 }
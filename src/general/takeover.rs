@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// Hardware values this close to the stored value count as picked up (knobs can skip steps)
+const PICKUP_TOLERANCE: u8 = 1;
+
+// CC values set on the synth outside of the keyboard (profiles), by (channel 0-15, controller).
+// The forwarder sends them and keeps the hardware CC back until it picks up the value.
+static STORED: Mutex<Option<HashMap<(u8, u8), u8>>> = Mutex::new(None);
+static PENDING: Mutex<Vec<[u8; 3]>> = Mutex::new(Vec::new());
+
+/// Set a CC on the synth (channel 1-16): sent by the forwarder; with `midi.soft_takeover` the
+/// hardware control is ignored until it crosses the value
+pub fn set_synth_cc(channel: u8, controller: u8, value: u8) {
+    let (channel, controller, value) = (channel.clamp(1, 16) - 1, controller.min(127), value.min(127));
+    if crate::get_config().midi.soft_takeover {
+        if let Ok(mut stored) = STORED.lock() {
+            stored.get_or_insert_with(HashMap::new).insert((channel, controller), value);
+        }
+    }
+    if let Ok(mut pending) = PENDING.lock() {
+        pending.push([0xB0 | channel, controller, value]);
+    }
+}
+
+/// Panic: queue All Sound Off and All Notes Off (CC 120 and 123) on all 16 channels
+pub fn all_notes_off() {
+    if let Ok(mut pending) = PENDING.lock() {
+        for channel in 0..16u8 {
+            pending.push([0xB0 | channel, 120, 0]);
+            pending.push([0xB0 | channel, 123, 0]);
+        }
+    }
+}
+
+/// CC messages queued by `set_synth_cc` and `all_notes_off` for the MIDI output
+pub fn take_pending() -> Vec<Vec<u8>> {
+    PENDING.lock().map(|mut p| p.drain(..).map(|m| m.to_vec()).collect()).unwrap_or_default()
+}
+
+/// Whether a hardware move from `previous` to `value` reached or crossed `stored`
+fn picked_up(previous: Option<u8>, value: u8, stored: u8) -> bool {
+    value.abs_diff(stored) <= PICKUP_TOLERANCE || previous.is_some_and(|p| (p < stored) != (value < stored))
+}
+
+fn to_cc(control: &crate::custom_controls::CustomControl, value: f32) -> u8 {
+    if control.max <= control.min {
+        return 0;
+    }
+    ((value - control.min) / (control.max - control.min) * 127.0).round().clamp(0.0, 127.0) as u8
+}
+
+/// Per-CC state of the forwarder: last hardware value and the custom control values it set
+#[derive(Default)]
+pub struct SoftTakeover {
+    hardware: HashMap<(u8, u8), u8>,
+    applied: HashMap<String, f32>,
+    // Control values held back by `osc.continuous_rate_hz`, by slug
+    throttle: crate::osc_sender::RateLimiter<f32>,
+}
+
+impl SoftTakeover {
+    /// Handle an incoming control change: drive the custom controls mapped to it (`cc`), and
+    /// return false when the synth hasn't picked up a stored value yet so the message is dropped
+    pub fn process(&mut self, msg: &[u8], config: &crate::Config) -> bool {
+        if msg.len() < 3 || msg[0] & 0xF0 != 0xB0 {
+            return true;
+        }
+        let key = (msg[0] & 0x0F, msg[1]);
+        let value = msg[2];
+        let previous = self.hardware.insert(key, value);
+        let soft = config.midi.soft_takeover;
+
+        for control in crate::custom_controls::controls().iter().filter(|c| c.cc == Some(msg[1]) && !c.is_multi_arg()) {
+            let slug = control.slug();
+            let current = crate::custom_controls::value(&slug);
+            // Not set by the knob yet, or changed by a profile, MQTT, OSC or the avatar since
+            let moved_elsewhere = self.applied.get(&slug) != Some(&current);
+            if soft && moved_elsewhere && !picked_up(previous, value, to_cc(control, current)) {
+                continue;
+            }
+            let raw = control.min + (control.max - control.min) * value as f32 / 127.0;
+            // Same value as last time (e.g. a coarse control range): nothing to send
+            if config.osc.suppress_duplicates && self.applied.get(&slug) == Some(&control.normalize(raw)) && !moved_elsewhere {
+                continue;
+            }
+            if let Some(raw) = self.throttle.offer(&slug, raw) {
+                self.apply(slug, raw);
+            }
+        }
+
+        let Ok(mut stored) = STORED.lock() else { return true };
+        let Some(stored) = stored.as_mut() else { return true };
+        match stored.get(&key) {
+            Some(target) if !picked_up(previous, value, *target) => false,
+            Some(_) => {
+                stored.remove(&key);
+                log::debug!(target: "midi", "[MIDI] CC{} picked up at {}", key.1, value);
+                true
+            }
+            None => true,
+        }
+    }
+
+    fn apply(&mut self, slug: String, raw: f32) {
+        if let Some(applied) = crate::custom_controls::set_value(&slug, raw) {
+            self.applied.insert(slug, applied);
+        }
+    }
+
+    /// Send the control values held back by the rate limit whose interval is over
+    pub fn flush_due(&mut self) {
+        for (slug, raw) in self.throttle.take_due() {
+            self.apply(slug, raw);
+        }
+    }
+
+    /// Time until the next held-back control value is due
+    pub fn next_due_in(&self) -> Option<Duration> {
+        self.throttle.next_due_in()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn control(min: f32, max: f32) -> crate::custom_controls::CustomControl {
+        serde_json::from_value(serde_json::json!({ "name": "Pad", "address": "/avatar/parameters/Pad", "min": min, "max": max })).unwrap()
+    }
+
+    #[test]
+    fn pickup_needs_reaching_or_crossing_the_stored_value() {
+        assert!(picked_up(None, 64, 64));
+        assert!(picked_up(None, 65, 64));
+        assert!(!picked_up(None, 70, 64));
+        assert!(!picked_up(Some(10), 20, 64));
+        assert!(picked_up(Some(60), 70, 64));
+        assert!(picked_up(Some(70), 60, 64));
+        assert!(!picked_up(Some(80), 70, 64));
+    }
+
+    #[test]
+    fn pickup_at_the_ends_of_the_range() {
+        assert!(picked_up(Some(5), 0, 0));
+        assert!(picked_up(Some(120), 127, 127));
+        assert!(!picked_up(Some(120), 125, 127));
+    }
+
+    #[test]
+    fn control_values_scale_to_cc() {
+        assert_eq!(to_cc(&control(0.0, 1.0), 0.0), 0);
+        assert_eq!(to_cc(&control(0.0, 1.0), 0.5), 64);
+        assert_eq!(to_cc(&control(0.0, 1.0), 1.0), 127);
+        assert_eq!(to_cc(&control(-1.0, 1.0), 0.0), 64);
+        assert_eq!(to_cc(&control(0.0, 1.0), 2.0), 127);
+        assert_eq!(to_cc(&control(1.0, 1.0), 1.0), 0);
+    }
+
+    #[test]
+    fn all_notes_off_covers_every_channel() {
+        all_notes_off();
+        let pending = take_pending();
+        assert_eq!(pending.len(), 32);
+        assert!(pending.contains(&vec![0xBF, 120, 0]) && pending.contains(&vec![0xBF, 123, 0]));
+        assert!(take_pending().is_empty());
+    }
+}
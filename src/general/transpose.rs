@@ -1,3 +1,6 @@
+use std::sync::atomic::{AtomicI32, AtomicU8, Ordering};
+use std::sync::OnceLock;
+
 /// Small helper functions for transpose handling
 pub fn clamp_transpose(value: i32, min: i8, max: i8) -> i32 {
     value.clamp(min as i32, max as i32)
@@ -9,15 +12,370 @@ pub fn apply_transpose(buf: &mut [u8], semitones: i32) {
     if buf.is_empty() { return; }
     let status = buf[0] & 0xF0;
     match status {
-        0x80 | 0x90 => {
-            if buf.len() > 1 {
-                let note = buf[1] as i32;
-                let new_note = (note + semitones).clamp(0, 127) as u8;
-                buf[1] = new_note;
-            }
+        0x80 | 0x90 if buf.len() > 1 => {
+            let note = buf[1] as i32;
+            let new_note = (note + semitones).clamp(0, 127) as u8;
+            buf[1] = new_note;
         }
         _ => {
             // other messages unchanged
         }
     }
 }
+
+/// Rewrite a channel pressure message (0xDn value) into a control change (0xBn cc value) on the same channel.
+/// Other messages are left unchanged.
+pub fn channel_pressure_to_cc(msg: &mut Vec<u8>, cc: u8) {
+    if msg.len() == 2 && msg[0] & 0xF0 == 0xD0 {
+        let value = msg[1];
+        *msg = vec![0xB0 | (msg[0] & 0x0F), cc & 0x7F, value];
+    }
+}
+
+// Temporary offset from the lean pedal, layered on top of the base transpose
+static LEAN_OFFSET: AtomicI32 = AtomicI32::new(0);
+
+/// Current lean offset in semitones (0 = pedal released)
+pub fn lean_offset() -> i32 {
+    LEAN_OFFSET.load(Ordering::Relaxed)
+}
+
+/// Lean mode: if `msg` is the configured pedal CC, update the offset (pedal position scaled to
+/// `midi.lean_semitones`, rounded) and return true
+pub fn update_lean(msg: &[u8], midi: &crate::MidiConfig) -> bool {
+    match midi.lean_cc {
+        Some(cc) if msg.len() == 3 && msg[0] & 0xF0 == 0xB0 && msg[1] == cc => {
+            let offset = (msg[2] as f32 / 127.0 * midi.lean_semitones as f32).round() as i32;
+            if LEAN_OFFSET.swap(offset, Ordering::Relaxed) != offset && crate::is_debug_enabled_for(crate::DebugCategory::Midi) {
+                log::debug!(target: "midi", "[LEAN] Offset {:+}", offset);
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+// Sources currently holding the momentary transpose (bit 0 = pedal CC, bit 1 = note)
+static HOLD_SOURCES: AtomicU8 = AtomicU8::new(0);
+const HOLD_BY_CC: u8 = 1;
+const HOLD_BY_NOTE: u8 = 2;
+
+/// Current hold offset in semitones (`midi.hold_semitones` while the pedal or note is held, else 0)
+pub fn hold_offset(midi: &crate::MidiConfig) -> i32 {
+    if HOLD_SOURCES.load(Ordering::Relaxed) != 0 {
+        midi.hold_semitones
+    } else {
+        0
+    }
+}
+
+/// Hold mode: if `msg` is the configured hold pedal CC (down at 64 and above) or the hold note
+/// (any channel), update the held state and return true so the trigger isn't forwarded
+pub fn update_hold(msg: &[u8], midi: &crate::MidiConfig) -> bool {
+    if msg.len() != 3 {
+        return false;
+    }
+    let (source, held) = match msg[0] & 0xF0 {
+        0xB0 if midi.hold_cc == Some(msg[1]) => (HOLD_BY_CC, msg[2] >= 64),
+        0x90 | 0x80 if midi.hold_note == Some(msg[1]) => (HOLD_BY_NOTE, msg[0] & 0xF0 == 0x90 && msg[2] > 0),
+        _ => return false,
+    };
+    let before = if held {
+        HOLD_SOURCES.fetch_or(source, Ordering::Relaxed)
+    } else {
+        HOLD_SOURCES.fetch_and(!source, Ordering::Relaxed)
+    };
+    let after = if held { before | source } else { before & !source };
+    if (before == 0) != (after == 0) && crate::is_debug_enabled_for(crate::DebugCategory::Midi) {
+        log::debug!(target: "midi", "[HOLD] Offset {:+}", hold_offset(midi));
+    }
+    true
+}
+
+/// How the transpose interacts with pitch bend (`midi.pitch_bend_mode`, per profile)
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PitchBendMode {
+    /// Notes are transposed, pitch bend passes through unchanged
+    #[default]
+    Off,
+    /// Notes are transposed and the pitch bend center is offset by the transpose as well
+    /// (for synths that interpret bend relative to a fixed reference)
+    Center,
+    /// Transposes within the bend range are played purely via pitch bend, larger ones via the notes
+    Bend,
+}
+
+impl PitchBendMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            PitchBendMode::Off => "off",
+            PitchBendMode::Center => "center",
+            PitchBendMode::Bend => "bend",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "off" => Some(PitchBendMode::Off),
+            "center" => Some(PitchBendMode::Center),
+            "bend" => Some(PitchBendMode::Bend),
+            _ => None,
+        }
+    }
+}
+
+/// What happens to transposed notes outside `midi.note_min`..`midi.note_max` (`midi.out_of_range`)
+#[derive(Debug, serde::Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NoteOutOfRange {
+    /// Don't send them
+    #[default]
+    Drop,
+    /// Move them by octaves back into the range
+    Fold,
+    /// Pin them to the lowest/highest note
+    Clamp,
+}
+
+static PITCH_BEND_MODE: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_pitch_bend_mode(mode: PitchBendMode) {
+    PITCH_BEND_MODE.store(mode as u8, Ordering::SeqCst);
+}
+
+pub fn pitch_bend_mode() -> PitchBendMode {
+    match PITCH_BEND_MODE.load(Ordering::SeqCst) {
+        1 => PitchBendMode::Center,
+        2 => PitchBendMode::Bend,
+        _ => PitchBendMode::Off,
+    }
+}
+
+const BEND_CENTER: i32 = 8192;
+
+// Resolved `midi.note_min` / `midi.note_max` (names need the loaded `note_names` config)
+static NOTE_LIMITS: OnceLock<(i32, i32)> = OnceLock::new();
+
+/// Notes the forwarder lets through as (lowest, highest); the full MIDI range when unset or invalid
+pub fn note_limits() -> (i32, i32) {
+    *NOTE_LIMITS.get_or_init(|| {
+        let config = &crate::get_config().midi;
+        let bound = |spec: &Option<crate::osc_sender::NoteSpec>, default: u8, key: &str| match spec {
+            Some(spec) => spec.resolve().unwrap_or_else(|| {
+                log::warn!(target: "config", "[CONFIG] midi.{}: invalid note {:?}; ignored", key, spec);
+                default
+            }),
+            None => default,
+        };
+        limits(bound(&config.note_min, 0, "note_min"), bound(&config.note_max, 127, "note_max"))
+    })
+}
+
+/// Limits from the resolved note_min/note_max; an empty range (min above max) lets every note through
+fn limits(low: u8, high: u8) -> (i32, i32) {
+    if low > high {
+        log::warn!(target: "config", "[CONFIG] midi.note_min is above midi.note_max; note filter ignored");
+        return (0, 127);
+    }
+    (low as i32, high as i32)
+}
+
+/// Fit a transposed note (may be outside 0-127) into `midi.note_min`..`midi.note_max`
+/// according to `midi.out_of_range`; `None` = drop it. Ranges narrower than an octave
+/// drop notes that can't be folded in.
+pub fn fit_note(note: i32, midi: &crate::MidiConfig) -> Option<u8> {
+    fit_into(note, note_limits(), midi.out_of_range)
+}
+
+fn fit_into(note: i32, (low, high): (i32, i32), out_of_range: NoteOutOfRange) -> Option<u8> {
+    if (low..=high).contains(&note) {
+        return Some(note as u8);
+    }
+    match out_of_range {
+        NoteOutOfRange::Drop => None,
+        NoteOutOfRange::Clamp => Some(note.clamp(low, high) as u8),
+        NoteOutOfRange::Fold => {
+            let mut folded = note;
+            while folded < low {
+                folded += 12;
+            }
+            while folded > high {
+                folded -= 12;
+            }
+            (folded >= low).then_some(folded as u8)
+        }
+    }
+}
+
+/// Part of a transpose that moves the notes of a channel (0-based); the rest is pitch bend
+pub fn note_shift(semitones: i32, channel: u8, midi: &crate::MidiConfig) -> i32 {
+    split_transpose(semitones, channel, midi).0
+}
+
+/// Split a transpose into (note shift, bend offset in semitones) for a channel (0-based)
+fn split_transpose(semitones: i32, channel: u8, midi: &crate::MidiConfig) -> (i32, i32) {
+    let range = midi.pitch_bend_range as i32;
+    let supported = midi.pitch_bend_channels.is_empty() || midi.pitch_bend_channels.contains(&(channel + 1));
+    split(semitones, pitch_bend_mode(), range, supported)
+}
+
+/// `split_transpose` for a bend range of `range` semitones on a channel that does (not) support it
+fn split(semitones: i32, mode: PitchBendMode, range: i32, supported: bool) -> (i32, i32) {
+    match mode {
+        _ if !supported || range == 0 => (semitones, 0),
+        PitchBendMode::Off => (semitones, 0),
+        PitchBendMode::Center => (semitones, semitones.clamp(-range, range)),
+        PitchBendMode::Bend if semitones.abs() <= range => (0, semitones),
+        PitchBendMode::Bend => (semitones, 0),
+    }
+}
+
+/// 14-bit bend value of the player's bend `input` moved by `offset` semitones of a `range`-semitone bend range
+fn bend_value(input: i32, offset: i32, range: i32) -> i32 {
+    (input + offset * BEND_CENTER / range.max(1)).clamp(0, 16383)
+}
+
+/// Per-channel pitch bend bookkeeping for the forwarder: the player's own bend position and
+/// the offset the output currently carries, so transpose changes can re-send the bend
+pub struct PitchBendState {
+    input: [i32; 16],
+    sent_offset: [Option<i32>; 16],
+}
+
+impl Default for PitchBendState {
+    fn default() -> Self {
+        PitchBendState { input: [BEND_CENTER; 16], sent_offset: [None; 16] }
+    }
+}
+
+impl PitchBendState {
+    fn bend_message(&self, channel: u8, offset: i32, midi: &crate::MidiConfig) -> Vec<u8> {
+        let value = bend_value(self.input[channel as usize], offset, midi.pitch_bend_range as i32);
+        vec![0xE0 | channel, (value & 0x7F) as u8, (value >> 7) as u8]
+    }
+
+    /// Apply the transpose to a message: notes get the note share, pitch bend gets the bend share.
+    /// Returns extra bend messages to send first when the channel's bend offset changed.
+    pub fn apply(&mut self, msg: &mut [u8], semitones: i32, midi: &crate::MidiConfig) -> Vec<Vec<u8>> {
+        if msg.is_empty() || msg[0] >= 0xF0 || msg[0] < 0x80 {
+            return Vec::new();
+        }
+        let channel = msg[0] & 0x0F;
+        let (notes, offset) = split_transpose(semitones, channel, midi);
+        let mut extra = Vec::new();
+        if msg[0] & 0xF0 == 0xE0 && msg.len() == 3 {
+            self.input[channel as usize] = msg[1] as i32 | (msg[2] as i32) << 7;
+            let bent = self.bend_message(channel, offset, midi);
+            msg.copy_from_slice(&bent);
+            self.sent_offset[channel as usize] = Some(offset);
+        } else {
+            let sent = self.sent_offset[channel as usize];
+            if sent != Some(offset) && (sent.is_some() || offset != 0) {
+                extra.push(self.bend_message(channel, offset, midi));
+            }
+            self.sent_offset[channel as usize] = Some(offset);
+            apply_transpose(msg, notes);
+        }
+        extra
+    }
+
+    /// Bend messages for channels whose offset changed since their last message
+    /// (transpose changed while nothing was played)
+    pub fn refresh(&mut self, semitones: i32, midi: &crate::MidiConfig) -> Vec<Vec<u8>> {
+        let mut extra = Vec::new();
+        for channel in 0..16u8 {
+            let Some(sent) = self.sent_offset[channel as usize] else { continue };
+            let (_, offset) = split_transpose(semitones, channel, midi);
+            if offset != sent {
+                extra.push(self.bend_message(channel, offset, midi));
+                self.sent_offset[channel as usize] = Some(offset);
+            }
+        }
+        extra
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FULL: (i32, i32) = (0, 127);
+
+    #[test]
+    fn notes_outside_the_midi_range_are_dropped() {
+        assert_eq!(fit_into(0, FULL, NoteOutOfRange::Drop), Some(0));
+        assert_eq!(fit_into(127, FULL, NoteOutOfRange::Drop), Some(127));
+        assert_eq!(fit_into(-1, FULL, NoteOutOfRange::Drop), None);
+        assert_eq!(fit_into(128, FULL, NoteOutOfRange::Drop), None);
+        assert_eq!(fit_into(127 + 12, FULL, NoteOutOfRange::Drop), None);
+    }
+
+    #[test]
+    fn note_limits_drop_notes_outside_them() {
+        assert_eq!(fit_into(35, (36, 96), NoteOutOfRange::Drop), None);
+        assert_eq!(fit_into(36, (36, 96), NoteOutOfRange::Drop), Some(36));
+        assert_eq!(fit_into(96, (36, 96), NoteOutOfRange::Drop), Some(96));
+        assert_eq!(fit_into(97, (36, 96), NoteOutOfRange::Drop), None);
+    }
+
+    #[test]
+    fn clamp_pins_notes_to_the_nearest_edge() {
+        assert_eq!(fit_into(-1, FULL, NoteOutOfRange::Clamp), Some(0));
+        assert_eq!(fit_into(128, FULL, NoteOutOfRange::Clamp), Some(127));
+        assert_eq!(fit_into(127 + 12, FULL, NoteOutOfRange::Clamp), Some(127));
+        assert_eq!(fit_into(-12, FULL, NoteOutOfRange::Clamp), Some(0));
+        assert_eq!(fit_into(20, (36, 96), NoteOutOfRange::Clamp), Some(36));
+    }
+
+    #[test]
+    fn fold_moves_notes_back_by_octaves() {
+        assert_eq!(fit_into(0, FULL, NoteOutOfRange::Fold), Some(0));
+        assert_eq!(fit_into(127, FULL, NoteOutOfRange::Fold), Some(127));
+        assert_eq!(fit_into(128, FULL, NoteOutOfRange::Fold), Some(116));
+        assert_eq!(fit_into(127 + 12, FULL, NoteOutOfRange::Fold), Some(127));
+        assert_eq!(fit_into(-1, FULL, NoteOutOfRange::Fold), Some(11));
+        assert_eq!(fit_into(-12, FULL, NoteOutOfRange::Fold), Some(0));
+        assert_eq!(fit_into(98, (36, 96), NoteOutOfRange::Fold), Some(86));
+    }
+
+    #[test]
+    fn fold_drops_notes_a_narrow_range_cannot_hold() {
+        // 60..64 has no F#, so 66 and 54 have nowhere to go
+        assert_eq!(fit_into(66, (60, 64), NoteOutOfRange::Fold), None);
+        assert_eq!(fit_into(54, (60, 64), NoteOutOfRange::Fold), None);
+        assert_eq!(fit_into(74, (60, 64), NoteOutOfRange::Fold), Some(62));
+    }
+
+    #[test]
+    fn pitch_bend_modes_split_the_transpose() {
+        assert_eq!(split(5, PitchBendMode::Off, 2, true), (5, 0));
+        assert_eq!(split(-2, PitchBendMode::Bend, 2, true), (0, -2));
+        assert_eq!(split(3, PitchBendMode::Bend, 2, true), (3, 0));
+        assert_eq!(split(1, PitchBendMode::Center, 2, true), (1, 1));
+        assert_eq!(split(-7, PitchBendMode::Center, 2, true), (-7, -2));
+    }
+
+    #[test]
+    fn pitch_bend_needs_a_supporting_channel_and_range() {
+        assert_eq!(split(2, PitchBendMode::Bend, 2, false), (2, 0));
+        assert_eq!(split(2, PitchBendMode::Bend, 0, true), (2, 0));
+        assert_eq!(split(2, PitchBendMode::Center, 0, true), (2, 0));
+    }
+
+    #[test]
+    fn bend_values_stay_in_14_bits() {
+        assert_eq!(bend_value(BEND_CENTER, 0, 2), BEND_CENTER);
+        assert_eq!(bend_value(BEND_CENTER, 1, 2), BEND_CENTER + 4096);
+        assert_eq!(bend_value(BEND_CENTER, -2, 2), 0);
+        assert_eq!(bend_value(16383, 2, 2), 16383);
+        assert_eq!(bend_value(BEND_CENTER, 1, 0), 16383);
+    }
+
+    #[test]
+    fn empty_range_lets_every_note_through() {
+        assert_eq!(limits(72, 48), FULL);
+        assert_eq!(limits(48, 48), (48, 48));
+        assert_eq!(fit_into(0, limits(72, 48), NoteOutOfRange::Drop), Some(0));
+    }
+}
@@ -0,0 +1,162 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use crate::general::state::ConfigRef;
+use crate::osc_sender::NoteSpec;
+
+/// One keyboard zone (`midi.zones`): input notes from `low` to `high` get an extra transpose
+/// and optionally go out on their own channel
+#[derive(Debug, serde::Deserialize, Clone)]
+pub struct ZoneConfig {
+    pub name: String,
+    pub low: NoteSpec,
+    pub high: NoteSpec,
+    /// Semitones added to the global transpose for this zone
+    #[serde(default)]
+    pub transpose: i32,
+    /// Output channel (1-16) of the zone's notes; unset keeps the input channel
+    #[serde(default)]
+    pub channel: Option<u8>,
+}
+
+impl ZoneConfig {
+    /// Topic and lookup name
+    pub fn slug(&self) -> String {
+        crate::custom_controls::slugify(&self.name)
+    }
+}
+
+// Resolved note ranges, indexed like `midi.zones` (`None` = invalid, zone ignored)
+static RANGES: OnceLock<Vec<Option<(u8, u8)>>> = OnceLock::new();
+// Current zone transposes, indexed like `midi.zones`
+static TRANSPOSE: Mutex<Vec<i32>> = Mutex::new(Vec::new());
+// Bumped on every zone transpose change so MQTT can publish it
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+pub fn zones() -> ConfigRef<[ZoneConfig]> {
+    crate::state().config_ref(|config| config.midi.zones.as_slice())
+}
+
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+/// Note range of a zone (note names with middle C in octave `middle_c_octave`), `None` when a
+/// bound is no note or `low` is above `high`
+fn resolve_range(zone: &ZoneConfig, middle_c_octave: i32) -> Option<(u8, u8)> {
+    match (zone.low.resolve_in(middle_c_octave), zone.high.resolve_in(middle_c_octave)) {
+        (Some(low), Some(high)) if low <= high => Some((low, high)),
+        _ => None,
+    }
+}
+
+fn ranges() -> &'static [Option<(u8, u8)>] {
+    RANGES.get_or_init(|| {
+        let middle_c_octave = crate::get_config().note_names.middle_c_octave;
+        zones()
+            .iter()
+            .map(|zone| {
+                let range = resolve_range(zone, middle_c_octave);
+                if range.is_none() {
+                    log::warn!(target: "config", "[CONFIG] midi.zones '{}': invalid note range {:?}..{:?}; zone ignored", zone.name, zone.low, zone.high);
+                }
+                range
+            })
+            .collect()
+    })
+}
+
+/// Index of the first zone whose range contains `note`
+fn zone_of(ranges: &[Option<(u8, u8)>], note: u8) -> Option<usize> {
+    ranges.iter().position(|range| range.is_some_and(|(low, high)| (low..=high).contains(&note)))
+}
+
+/// Take the initial zone transposes from the config
+pub fn init(zones: &[ZoneConfig]) {
+    if let Ok(mut transpose) = TRANSPOSE.lock() {
+        *transpose = zones.iter().map(|zone| clamp(zone.transpose)).collect();
+    }
+    ranges();
+}
+
+fn clamp(value: i32) -> i32 {
+    let config = &crate::get_config().transpose;
+    value.clamp(config.min as i32, config.max as i32)
+}
+
+/// Current transpose of zone `index`
+pub fn transpose(index: usize) -> i32 {
+    TRANSPOSE.lock().ok().and_then(|t| t.get(index).copied()).unwrap_or(0)
+}
+
+/// Set the transpose of zone `index` (clamped to the transpose range); returns the applied value
+pub fn set_transpose(index: usize, value: i32) -> Option<i32> {
+    let value = clamp(value);
+    let mut transpose = TRANSPOSE.lock().ok()?;
+    *transpose.get_mut(index)? = value;
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    Some(value)
+}
+
+/// Zone by name or slug (case-insensitive) or 1-based number
+pub fn find(name: &str) -> Option<usize> {
+    let name = name.trim();
+    if let Ok(n) = name.parse::<usize>() {
+        return n.checked_sub(1).filter(|&i| i < zones().len());
+    }
+    zones().iter().position(|zone| zone.name.eq_ignore_ascii_case(name) || zone.slug() == crate::custom_controls::slugify(name))
+}
+
+/// Extra transpose and output channel (0-15) for an input note: from the first of `zones`
+/// (`midi.zones` of the config in use) containing it
+pub fn lookup(zones: &[ZoneConfig], note: u8) -> Option<(i32, Option<u8>)> {
+    let index = zone_of(ranges(), note)?;
+    let channel = zones.get(index)?.channel.map(|c| c.clamp(1, 16) - 1);
+    Some((transpose(index), channel))
+}
+
+/// Zones for `status`, e.g. `Bass C-1..B2 -12 ch 2, Lead C3..G9 +0`
+pub fn describe() -> String {
+    zones()
+        .iter()
+        .enumerate()
+        .map(|(i, zone)| {
+            let range = match ranges().get(i).copied().flatten() {
+                Some((low, high)) => format!("{}..{}", crate::general::notes::display_name(low), crate::general::notes::display_name(high)),
+                None => "invalid range".to_string(),
+            };
+            let channel = zone.channel.map(|c| format!(" ch {}", c)).unwrap_or_default();
+            format!("{} {} {:+}{}", zone.name, range, transpose(i), channel)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zone(low: NoteSpec, high: NoteSpec) -> ZoneConfig {
+        ZoneConfig { name: "Zone".to_string(), low, high, transpose: 0, channel: None }
+    }
+
+    #[test]
+    fn ranges_resolve_numbers_and_names() {
+        assert_eq!(resolve_range(&zone(NoteSpec::Number(0), NoteSpec::Number(59)), 4), Some((0, 59)));
+        assert_eq!(resolve_range(&zone(NoteSpec::Name("C4".to_string()), NoteSpec::Name("B4".to_string())), 4), Some((60, 71)));
+        assert_eq!(resolve_range(&zone(NoteSpec::Name("C3".to_string()), NoteSpec::Name("B3".to_string())), 3), Some((60, 71)));
+        assert_eq!(resolve_range(&zone(NoteSpec::Number(72), NoteSpec::Number(60)), 4), None);
+        assert_eq!(resolve_range(&zone(NoteSpec::Number(0), NoteSpec::Number(128)), 4), None);
+        assert_eq!(resolve_range(&zone(NoteSpec::Name("H2".to_string()), NoteSpec::Number(60)), 4), None);
+    }
+
+    #[test]
+    fn first_matching_zone_wins() {
+        let ranges = [Some((0, 59)), None, Some((48, 127))];
+        assert_eq!(zone_of(&ranges, 0), Some(0));
+        assert_eq!(zone_of(&ranges, 59), Some(0));
+        assert_eq!(zone_of(&ranges, 55), Some(0));
+        assert_eq!(zone_of(&ranges, 60), Some(2));
+        assert_eq!(zone_of(&ranges, 127), Some(2));
+        assert_eq!(zone_of(&[Some((36, 47))], 48), None);
+    }
+}
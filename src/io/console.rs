@@ -0,0 +1,123 @@
+use std::io::{stdin, stderr, stdout, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+
+// Lines from stdin, read by a single background thread. Prompts with a timeout can give up
+// without leaving a blocked reader behind that would swallow the next console command.
+// The sender is kept so signals, the tray and scheduled actions can feed `exit` in as if it was typed.
+// While the TUI runs there is no stdin reader; the TUI command line feeds the lines instead.
+// A service (`--service`) has no stdin at all, only injected lines.
+static LINES: OnceLock<Mutex<Receiver<String>>> = OnceLock::new();
+static INJECT: OnceLock<Mutex<Sender<String>>> = OnceLock::new();
+// Set by the reader thread on EOF; the kept sender means the channel itself never disconnects
+static STDIN_CLOSED: AtomicBool = AtomicBool::new(false);
+const CLOSED_POLL_MS: u64 = 200;
+
+fn lines() -> &'static Mutex<Receiver<String>> {
+    LINES.get_or_init(|| {
+        let (tx, rx) = channel();
+        let reader_tx = tx.clone();
+        let _ = INJECT.set(Mutex::new(tx));
+        if crate::io::tui::is_active() || crate::general::cli::options().service {
+            return Mutex::new(rx);
+        }
+        thread::spawn(move || {
+            let stdin = stdin();
+            loop {
+                let mut line = String::new();
+                match stdin.read_line(&mut line) {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {
+                        if reader_tx.send(line).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+            STDIN_CLOSED.store(true, Ordering::SeqCst);
+        });
+        Mutex::new(rx)
+    })
+}
+
+/// Queue a command line as if it was typed on the console
+pub fn inject(line: &str) {
+    lines();
+    if let Some(Ok(tx)) = INJECT.get().map(|tx| tx.lock()) {
+        let _ = tx.send(format!("{}\n", line));
+    }
+}
+
+/// Read one line from stdin (including the newline). `None` waits forever.
+/// `Disconnected` means stdin was closed (EOF), e.g. when running as a service.
+pub fn read_line(timeout: Option<Duration>) -> Result<String, RecvTimeoutError> {
+    let rx = lines().lock().map_err(|_| RecvTimeoutError::Disconnected)?;
+    let deadline = timeout.map(|t| Instant::now() + t);
+    loop {
+        let mut wait = Duration::from_millis(CLOSED_POLL_MS);
+        if let Some(deadline) = deadline {
+            wait = wait.min(deadline.saturating_duration_since(Instant::now()));
+        }
+        match rx.recv_timeout(wait) {
+            Ok(line) => return Ok(line),
+            Err(RecvTimeoutError::Timeout) => {
+                if STDIN_CLOSED.load(Ordering::SeqCst) {
+                    return Err(RecvTimeoutError::Disconnected);
+                }
+                if deadline.is_some_and(|d| Instant::now() >= d) {
+                    return Err(RecvTimeoutError::Timeout);
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
+        }
+    }
+}
+
+/// Print one line of colored parts (`None` = default color) on stdout, or into the TUI log
+/// while it runs. Log messages end up here too.
+pub fn print_colored(parts: &[(Option<Color>, &str)]) {
+    if crate::io::tui::is_active() {
+        crate::io::tui::push(parts, true);
+        return;
+    }
+    let mut stdout = StandardStream::stdout(ColorChoice::Always);
+    for (color, text) in parts {
+        let _ = match color {
+            Some(color) => stdout.set_color(ColorSpec::new().set_fg(Some(*color)).set_intense(true)),
+            None => stdout.reset(),
+        };
+        let _ = write!(&mut stdout, "{}", text);
+    }
+    let _ = stdout.reset();
+    let _ = writeln!(&mut stdout);
+}
+
+pub fn print_line(line: String) {
+    if crate::io::tui::is_active() {
+        crate::io::tui::push(&[(None, &line)], true);
+    } else {
+        let _ = writeln!(stdout(), "{}", line);
+    }
+}
+
+/// Errors and warnings: stderr, or red in the TUI log
+pub fn print_error_line(line: String) {
+    if crate::io::tui::is_active() {
+        crate::io::tui::push(&[(Some(Color::Red), &line)], true);
+    } else {
+        let _ = writeln!(stderr(), "{}", line);
+    }
+}
+
+/// Text without a line break, e.g. a prompt
+pub fn print_text(text: String) {
+    if crate::io::tui::is_active() {
+        crate::io::tui::push(&[(None, &text)], false);
+    } else {
+        let _ = write!(stdout(), "{}", text);
+    }
+}
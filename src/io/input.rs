@@ -1,10 +1,45 @@
 use std::error::Error;
-use std::io::{stdin, stdout, Write};
+use std::io::{stdout, Write};
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use midir::Ignore;
+use super::console;
+
+// Names of the connected input ports, so `input_port_name_substrs` never opens a port twice
+static OPEN_INPUTS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Names of the connected input ports, in connection order
+pub fn open_inputs() -> Vec<String> {
+    OPEN_INPUTS.lock().map(|open| open.clone()).unwrap_or_default()
+}
+
+/// Build midir's input filter from the `ignore_*` flags in `MidiConfig`
+pub fn ignore_flags(config: &crate::MidiConfig) -> Ignore {
+    let mut flags = Ignore::None;
+    if config.ignore_sysex {
+        flags = flags | Ignore::Sysex;
+    }
+    if config.ignore_time {
+        flags = flags | Ignore::Time;
+    }
+    if config.ignore_active_sense {
+        flags = flags | Ignore::ActiveSense;
+    }
+    flags
+}
+
+/// Whether a port name contains any of the configured exclusion substrings
+pub fn is_excluded(name: &str, exclude: &[String]) -> bool {
+    exclude.iter().any(|pattern| !pattern.is_empty() && name.contains(pattern.as_str()))
+}
 
 /// Select a MIDI input port. First tries to find a port whose name contains
-/// `input_port_name_substr`. If no match is found and there are multiple ports,
-/// prompts the user to choose one interactively.
-pub fn choose_input_port(midi_in: &midir::MidiInput, input_port_name_substr: &str) -> Result<usize, Box<dyn Error>> {
+/// `input_port_name_substr` and none of the `exclude` substrings. If no match is found
+/// and there are multiple ports, prompts the user to choose one interactively.
+pub fn choose_input_port(midi_in: &midir::MidiInput, input_port_name_substr: &str, exclude: &[String]) -> Result<usize, Box<dyn Error>> {
     let ports = midi_in.ports();
     if ports.is_empty() {
         return Err("no input port found".into());
@@ -13,8 +48,8 @@ pub fn choose_input_port(midi_in: &midir::MidiInput, input_port_name_substr: &st
     // Try substring match first
     for (i, p) in ports.iter().enumerate() {
         if let Ok(name) = midi_in.port_name(p) {
-            if name.contains(input_port_name_substr) {
-                if crate::is_debug_enabled() { println!("Choosing input port matching '{}': {}", input_port_name_substr, name); }
+            if name.contains(input_port_name_substr) && !is_excluded(&name, exclude) {
+                log::debug!(target: "midi", "Choosing input port matching '{}': {}", input_port_name_substr, name);
                 return Ok(i);
             }
         }
@@ -22,22 +57,219 @@ pub fn choose_input_port(midi_in: &midir::MidiInput, input_port_name_substr: &st
 
     // Fallbacks: only one port -> choose it, otherwise list and ask
     if ports.len() == 1 {
-        if crate::is_debug_enabled() { println!("Choosing the only available input port: {}", midi_in.port_name(&ports[0])?); }
+        log::debug!(target: "midi", "Choosing the only available input port: {}", midi_in.port_name(&ports[0])?);
         return Ok(0);
     }
 
-    if crate::is_debug_enabled() { println!("\nAvailable input ports:"); }
+    log::debug!(target: "midi", "\nAvailable input ports:");
     for (i, p) in ports.iter().enumerate() {
-        if crate::is_debug_enabled() { println!("{}: {}", i, midi_in.port_name(p)?); }
+        log::debug!(target: "midi", "{}: {}", i, midi_in.port_name(p)?);
     }
 
-    print!("Please select input port: ");
+    let idx = match prompt_port_index("input", ports.len())? {
+        Some(idx) => idx,
+        None => {
+            // Best candidate: first port not excluded by the config
+            let idx = ports
+                .iter()
+                .position(|p| midi_in.port_name(p).is_ok_and(|name| !is_excluded(&name, exclude)))
+                .unwrap_or(0);
+            log::info!(target: "midi", "No selection, using input port {}: {}", idx, midi_in.port_name(&ports[idx])?);
+            return Ok(idx);
+        }
+    };
+    offer_to_remember("input_port_name_substr", &midi_in.port_name(&ports[idx])?)?;
+    Ok(idx)
+}
+
+/// Non-interactive pick for a port that appears later: the first port matching
+/// `input_port_name_substr`, else the first port that isn't excluded
+pub fn find_input_port(midi_in: &midir::MidiInput, input_port_name_substr: &str, exclude: &[String]) -> Option<usize> {
+    let names: Vec<String> = midi_in.ports().iter().map(|p| midi_in.port_name(p).unwrap_or_default()).collect();
+    names
+        .iter()
+        .position(|name| name.contains(input_port_name_substr) && !is_excluded(name, exclude))
+        .or_else(|| names.iter().position(|name| !is_excluded(name, exclude)))
+}
+
+/// Multi-input mode: the first port containing `substr` that is neither excluded nor in `taken`
+pub fn find_named_input_port(midi_in: &midir::MidiInput, substr: &str, exclude: &[String], taken: &[String]) -> Option<usize> {
+    midi_in.ports().iter().position(|p| {
+        midi_in
+            .port_name(p)
+            .is_ok_and(|name| name.contains(substr) && !is_excluded(&name, exclude) && !taken.contains(&name))
+    })
+}
+
+/// Open the input port named exactly `name` with its own `MidiInput` (midir consumes it on connect)
+pub fn connect<F>(name: &str, mut on_message: F) -> Result<midir::MidiInputConnection<()>, Box<dyn Error>>
+where
+    F: FnMut(&[u8]) + Send + 'static,
+{
+    let mut midi_in = midir::MidiInput::new("midir reading input")?;
+    midi_in.ignore(ignore_flags(&crate::get_config().midi));
+    let port = midi_in
+        .ports()
+        .into_iter()
+        .find(|p| midi_in.port_name(p).is_ok_and(|n| n == name))
+        .ok_or_else(|| format!("input port '{}' disappeared", name))?;
+    let conn = midi_in.connect(&port, "midir-read-input", move |_stamp, message, _| on_message(message), ())?;
+    if let Ok(mut open) = OPEN_INPUTS.lock() {
+        open.push(name.to_string());
+    }
+    Ok(conn)
+}
+
+/// Report the MIDI input component: up once every configured input is connected, degraded
+/// (with the patterns still missing) while waiting
+pub fn report_inputs() {
+    use crate::general::check::{report, ComponentState, MIDI_IN};
+    let config = &crate::get_config().midi;
+    let open = open_inputs();
+    let missing: Vec<&str> = config
+        .input_port_name_substrs
+        .iter()
+        .filter(|pattern| !open.iter().any(|name| name.contains(pattern.as_str())))
+        .map(|pattern| pattern.as_str())
+        .collect();
+    if open.is_empty() {
+        report(MIDI_IN, ComponentState::Degraded, "waiting for a device");
+    } else if missing.is_empty() {
+        report(MIDI_IN, ComponentState::Up, open.join(", "));
+    } else {
+        report(MIDI_IN, ComponentState::Degraded, format!("{}; waiting for '{}'", open.join(", "), missing.join("', '")));
+    }
+}
+
+/// Keep one input source connected. Without a `connection` from startup, poll every
+/// `device_poll_secs` until its port shows up; with `midi.reconnect`, also notice when the port
+/// disappears (USB unplug) and reopen it when it comes back. `pattern` is one of
+/// `input_port_name_substrs`; without it the port connected last (else the first port matching
+/// `input_port_name_substr`, else any port not excluded) is taken. The connection is held until exit.
+pub fn spawn_input_watcher<F>(pattern: Option<String>, connection: Option<(String, midir::MidiInputConnection<()>)>, on_message: F) -> thread::JoinHandle<()>
+where
+    F: FnMut(&[u8]) + Clone + Send + 'static,
+{
+    thread::spawn(move || {
+        let config = &crate::get_config().midi;
+        let poll = Duration::from_secs(config.device_poll_secs.max(1));
+        let mut last_name = connection.as_ref().map(|(name, _)| name.clone());
+        let mut connection = connection;
+        let mut next_poll = if connection.is_some() { Instant::now() + poll } else { Instant::now() };
+        while !crate::state().exit.load(Ordering::SeqCst) {
+            if (connection.is_none() || config.reconnect) && Instant::now() >= next_poll {
+                next_poll = Instant::now() + poll;
+                let midi_in = match midir::MidiInput::new("midir reading input") {
+                    Ok(midi_in) => midi_in,
+                    Err(_) => continue,
+                };
+                let names: Vec<String> = midi_in.ports().iter().map(|p| midi_in.port_name(p).unwrap_or_default()).collect();
+                if let Some((name, _)) = &connection {
+                    if !names.contains(name) {
+                        let name = name.clone();
+                        connection = None;
+                        if let Ok(mut open) = OPEN_INPUTS.lock() {
+                            open.retain(|open| *open != name);
+                        }
+                        crate::general::check::print_device_lost("input", &name);
+                        report_inputs();
+                    }
+                    continue;
+                }
+                let previous = last_name.clone().filter(|name| names.contains(name) && !open_inputs().contains(name));
+                let idx = match &pattern {
+                    Some(pattern) => find_named_input_port(&midi_in, pattern, &config.input_port_name_exclude, &open_inputs()),
+                    None => find_input_port(&midi_in, &config.input_port_name_substr, &config.input_port_name_exclude),
+                };
+                let Some(name) = previous.or_else(|| idx.map(|idx| names[idx].clone())) else {
+                    continue;
+                };
+                match connect(&name, on_message.clone()) {
+                    Ok(conn) => {
+                        if last_name.is_some() {
+                            crate::general::check::print_device_restored("input", &name);
+                        } else {
+                            log::info!(target: "midi", "MIDI input connected: {}", name);
+                        }
+                        last_name = Some(name.clone());
+                        connection = Some((name, conn));
+                        report_inputs();
+                    }
+                    Err(e) => log::error!(target: "midi", "Failed to open MIDI input {}: {}", name, e),
+                }
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        drop(connection);
+    })
+}
+
+// Selection timeout from the config; 0 waits forever
+fn selection_timeout() -> Option<Duration> {
+    let secs = crate::get_config().midi.port_selection_timeout_secs;
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+/// Ask for a port number. Returns `None` when nothing was entered within
+/// `midi.port_selection_timeout_secs` or stdin is closed, so the caller can pick its best
+/// candidate; with `port_selection_fail_on_timeout` that case is an error instead.
+/// `--headless` never asks and takes that path right away.
+pub fn prompt_port_index(kind: &str, port_count: usize) -> Result<Option<usize>, Box<dyn Error>> {
+    if crate::general::cli::options().headless {
+        if crate::get_config().midi.port_selection_fail_on_timeout {
+            return Err(format!("no {} port matches (headless, no selection possible)", kind).into());
+        }
+        return Ok(None);
+    }
+    let timeout = selection_timeout();
+    match timeout {
+        Some(t) => console::print_text(format!("Please select {} port (auto-select in {}s): ", kind, t.as_secs())),
+        None => console::print_text(format!("Please select {} port: ", kind)),
+    }
+    stdout().flush()?;
+    match console::read_line(timeout) {
+        Ok(choice) => {
+            let idx = choice.trim().parse::<usize>()?;
+            if idx >= port_count {
+                return Err(format!("invalid {} port selected", kind).into());
+            }
+            Ok(Some(idx))
+        }
+        Err(err) => {
+            console::print_line(String::new());
+            if crate::get_config().midi.port_selection_fail_on_timeout {
+                return Err(match err {
+                    RecvTimeoutError::Timeout => format!("no {} port selected within {}s", kind, timeout.map_or(0, |t| t.as_secs())),
+                    RecvTimeoutError::Disconnected => format!("no {} port selected (stdin closed)", kind),
+                }
+                .into());
+            }
+            Ok(None)
+        }
+    }
+}
+
+/// After an interactive selection, ask whether the exact port name should be written to the
+/// config file (`midi.<key>`) so the matcher finds it on the next launch
+pub fn offer_to_remember(key: &str, port_name: &str) -> Result<(), Box<dyn Error>> {
+    if crate::general::cli::options().headless {
+        return Ok(());
+    }
+    console::print_text(format!("Remember '{}' for future launches? [y/N]: ", port_name));
     stdout().flush()?;
-    let mut choice = String::new();
-    stdin().read_line(&mut choice)?;
-    let idx = choice.trim().parse::<usize>()?;
-    if idx >= ports.len() {
-        return Err("invalid input port selected".into());
+    let answer = match console::read_line(selection_timeout()) {
+        Ok(answer) => answer,
+        Err(_) => {
+            console::print_line(String::new());
+            return Ok(());
+        }
+    };
+    if !matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes") {
+        return Ok(());
     }
-    Ok(idx)
+    match crate::update_config_file("midi", key, serde_json::Value::String(port_name.to_string())) {
+        Ok(path) => log::info!(target: "config", "Saved midi.{} to {}", key, path.display()),
+        Err(err) => log::error!(target: "config", "[CONFIG] Could not save port selection: {}", err),
+    }
+    Ok(())
 }
@@ -1,2 +1,7 @@
 pub mod input;
 pub mod output;
+pub mod console;
+pub mod tui;
+pub mod tray;
+pub mod service;
+pub mod signals;
@@ -1,42 +1,202 @@
 use std::error::Error;
-use std::io::{stdin, stdout, Write};
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// One entry of `midi.output_ports`
+#[derive(Debug, serde::Deserialize, Clone)]
+pub struct OutputPortConfig {
+    /// Substring of the port name
+    pub name: String,
+    /// Channels (1-16) sent to this port; empty = all. System messages always pass
+    #[serde(default)]
+    pub channels: Vec<u8>,
+}
+
+/// An open MIDI output with its channel filter
+pub struct MidiTarget {
+    pub name: String,
+    conn: midir::MidiOutputConnection,
+    channels: Vec<u8>,
+}
+
+impl MidiTarget {
+    /// Send `msg` if it passes the channel filter
+    pub fn send(&mut self, msg: &[u8]) {
+        let filtered = matches!(msg.first(), Some(0x80..=0xEF)) && !self.channels.is_empty() && !self.channels.contains(&((msg[0] & 0x0F) + 1));
+        if filtered {
+            return;
+        }
+        if let Err(err) = self.conn.send(msg) {
+            log::error!(target: "midi", "Error sending MIDI message to output {}: {}", self.name, err);
+        }
+    }
+}
+
+/// A hot-plug change of the connected outputs, applied by the forwarder
+pub enum TargetChange {
+    /// The port disappeared; stop sending to it
+    Lost(String),
+    /// The port is back with a new connection
+    Restored(MidiTarget),
+}
+
+static TARGET_CHANGES: Mutex<Vec<TargetChange>> = Mutex::new(Vec::new());
+
+/// Output changes found by the watcher since the last call
+pub fn take_changes() -> Vec<TargetChange> {
+    TARGET_CHANGES.lock().map(|mut changes| changes.drain(..).collect()).unwrap_or_default()
+}
+
+/// `midi.reconnect`: poll the output ports every `device_poll_secs`; a port that disappears
+/// (USB unplug) is dropped from the forwarder and reopened with its channel filter when it
+/// comes back. `extra` names outputs that can't disappear (virtual port), for the report.
+pub fn spawn_output_watcher(ports: Vec<(String, Vec<u8>)>, extra: Vec<String>) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        use crate::general::check::{print_device_lost, print_device_restored, report, ComponentState, MIDI_OUT};
+        let poll = Duration::from_secs(crate::get_config().midi.device_poll_secs.max(1));
+        let mut present = vec![true; ports.len()];
+        let mut next_poll = Instant::now() + poll;
+        while !crate::state().exit.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(100));
+            if Instant::now() < next_poll {
+                continue;
+            }
+            next_poll = Instant::now() + poll;
+            let Ok(midi_out) = midir::MidiOutput::new("midir forwarding output") else { continue };
+            let names: Vec<String> = midi_out.ports().iter().map(|p| midi_out.port_name(p).unwrap_or_default()).collect();
+            let mut changed = false;
+            for ((name, channels), present) in ports.iter().zip(present.iter_mut()) {
+                let change = match (*present, names.contains(name)) {
+                    (true, false) => {
+                        print_device_lost("output", name);
+                        TargetChange::Lost(name.clone())
+                    }
+                    (false, true) => match connect(name, channels.clone()) {
+                        Ok(target) => {
+                            print_device_restored("output", name);
+                            TargetChange::Restored(target)
+                        }
+                        Err(e) => {
+                            log::error!(target: "midi", "Failed to reopen MIDI output {}: {}", name, e);
+                            continue;
+                        }
+                    },
+                    _ => continue,
+                };
+                *present = !*present;
+                changed = true;
+                if let Ok(mut changes) = TARGET_CHANGES.lock() {
+                    changes.push(change);
+                }
+            }
+            if changed {
+                let mut connected: Vec<&str> = ports.iter().zip(&present).filter(|(_, p)| **p).map(|((name, _), _)| name.as_str()).collect();
+                connected.extend(extra.iter().map(String::as_str));
+                let lost: Vec<&str> = ports.iter().zip(&present).filter(|(_, p)| !**p).map(|((name, _), _)| name.as_str()).collect();
+                if lost.is_empty() {
+                    report(MIDI_OUT, ComponentState::Up, connected.join(" + "));
+                } else {
+                    report(MIDI_OUT, ComponentState::Degraded, format!("{}; lost '{}'", connected.join(" + "), lost.join("', '")));
+                }
+            }
+        }
+    })
+}
+
+/// Open the output port named exactly `name` with its own `MidiOutput` (midir consumes it on connect)
+pub fn connect(name: &str, channels: Vec<u8>) -> Result<MidiTarget, Box<dyn Error>> {
+    let midi_out = midir::MidiOutput::new("midir forwarding output")?;
+    let port = midi_out
+        .ports()
+        .into_iter()
+        .find(|p| midi_out.port_name(p).is_ok_and(|n| n == name))
+        .ok_or_else(|| format!("output port '{}' disappeared", name))?;
+    let conn = midi_out.connect(&port, "midir-forward-output")?;
+    Ok(MidiTarget { name: name.to_string(), conn, channels })
+}
+
+/// Create a virtual output port named `name` that other software can connect to
+#[cfg(unix)]
+pub fn create_virtual(name: &str) -> Result<MidiTarget, Box<dyn Error>> {
+    use midir::os::unix::VirtualOutput;
+    let midi_out = midir::MidiOutput::new("midir virtual output")?;
+    let conn = midi_out.create_virtual(name)?;
+    Ok(MidiTarget { name: name.to_string(), conn, channels: Vec::new() })
+}
+
+/// Windows MIDI has no virtual ports; a loopback driver has to provide one
+#[cfg(not(unix))]
+pub fn create_virtual(_name: &str) -> Result<MidiTarget, Box<dyn Error>> {
+    Err("virtual ports are not supported on Windows; create a port with loopMIDI (https://www.tobias-erichsen.de/software/loopmidi.html) and add it to midi.output_ports".into())
+}
+
+/// Fan-out mode: the port name for every `midi.output_ports` entry that has a port, each the first
+/// one containing its substring that isn't excluded, an input port or taken by an earlier entry
+pub fn find_output_ports(midi_out: &midir::MidiOutput, entries: &[OutputPortConfig], exclude: &[String], in_port_names: &[&str]) -> Vec<(String, Vec<u8>)> {
+    let names: Vec<String> = midi_out.ports().iter().map(|p| midi_out.port_name(p).unwrap_or_default()).collect();
+    let mut chosen: Vec<(String, Vec<u8>)> = Vec::new();
+    for entry in entries {
+        let found = names.iter().find(|name| {
+            name.contains(entry.name.as_str())
+                && !crate::input::is_excluded(name, exclude)
+                && !in_port_names.contains(&name.as_str())
+                && !chosen.iter().any(|(taken, _)| taken == *name)
+        });
+        match found {
+            Some(name) => chosen.push((name.clone(), entry.channels.clone())),
+            None => log::warn!(target: "midi", "[MIDI] No output port matches '{}'; skipping it", entry.name),
+        }
+    }
+    chosen
+}
 
 /// Select a MIDI output port. Prefers a port whose name contains
-/// `output_port_name_substr` and is not identical to `in_port_name`.
-pub fn choose_output_port(midi_out: &midir::MidiOutput, output_port_name_substr: &str, in_port_name: &str) -> Result<usize, Box<dyn Error>> {
+/// `output_port_name_substr`, none of the `exclude` substrings, and is not identical to `in_port_name`.
+/// Returns `None` when there is no output port at all (observer mode).
+pub fn choose_output_port(midi_out: &midir::MidiOutput, output_port_name_substr: &str, exclude: &[String], in_port_name: &str) -> Result<Option<usize>, Box<dyn Error>> {
     let ports = midi_out.ports();
     if ports.is_empty() {
-        return Err("no output port found".into());
+        log::info!(target: "midi", "No MIDI output port found; running in observer mode (OSC/MQTT only)");
+        return Ok(None);
     }
 
     // Try to find a matching port (but avoid selecting the same name as the input)
     for (i, p) in ports.iter().enumerate() {
         if let Ok(name) = midi_out.port_name(p) {
-            if name.contains(output_port_name_substr) && name != in_port_name {
-                if crate::is_debug_enabled() { println!("Choosing output port matching '{}': {}", output_port_name_substr, name); }
-                return Ok(i);
+            if name.contains(output_port_name_substr) && !crate::input::is_excluded(&name, exclude) && name != in_port_name {
+                log::debug!(target: "midi", "Choosing output port matching '{}': {}", output_port_name_substr, name);
+                return Ok(Some(i));
             }
         }
     }
 
     // Fallbacks: single port or interactive selection
     if ports.len() == 1 {
-        if crate::is_debug_enabled() { println!("Choosing the only available output port: {}", midi_out.port_name(&ports[0])?); }
-        return Ok(0);
+        log::debug!(target: "midi", "Choosing the only available output port: {}", midi_out.port_name(&ports[0])?);
+        return Ok(Some(0));
     }
 
-    if crate::is_debug_enabled() { println!("\nAvailable output ports:"); }
+    log::debug!(target: "midi", "\nAvailable output ports:");
     for (i, p) in ports.iter().enumerate() {
-        if crate::is_debug_enabled() { println!("{}: {}", i, midi_out.port_name(p)?); }
+        log::debug!(target: "midi", "{}: {}", i, midi_out.port_name(p)?);
     }
 
-    print!("Please select output port: ");
-    stdout().flush()?;
-    let mut choice = String::new();
-    stdin().read_line(&mut choice)?;
-    let idx = choice.trim().parse::<usize>()?;
-    if idx >= ports.len() {
-        return Err("invalid output port selected".into());
-    }
-    Ok(idx)
+    let idx = match crate::input::prompt_port_index("output", ports.len())? {
+        Some(idx) => idx,
+        None => {
+            // Best candidate: first port that isn't excluded or the input port itself
+            let names: Vec<String> = ports.iter().map(|p| midi_out.port_name(p).unwrap_or_default()).collect();
+            let idx = names
+                .iter()
+                .position(|name| name != in_port_name && !crate::input::is_excluded(name, exclude))
+                .or_else(|| names.iter().position(|name| name != in_port_name))
+                .unwrap_or(0);
+            log::info!(target: "midi", "No selection, using output port {}: {}", idx, names[idx]);
+            return Ok(Some(idx));
+        }
+    };
+    crate::input::offer_to_remember("output_port_name_substr", &midi_out.port_name(&ports[idx])?)?;
+    Ok(Some(idx))
 }
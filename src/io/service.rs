@@ -0,0 +1,107 @@
+use std::error::Error;
+
+/// The program itself (`run` in main.rs)
+type Run = fn() -> Result<(), Box<dyn Error>>;
+
+/// Tell systemd about the service state (`READY=1`, `STOPPING=1`) when it started the process
+/// with `Type=notify`; does nothing otherwise
+#[cfg(unix)]
+pub fn notify(state: &str) {
+    use std::os::unix::net::UnixDatagram;
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else { return };
+    let Ok(socket) = UnixDatagram::unbound() else { return };
+    let path = path.to_string_lossy();
+    let sent = match path.strip_prefix('@') {
+        // Abstract socket address
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            std::os::unix::net::SocketAddr::from_abstract_name(name).and_then(|addr| socket.send_to_addr(state.as_bytes(), &addr))
+        }
+        _ => socket.send_to(state.as_bytes(), path.as_ref()),
+    };
+    if let Err(e) = sent {
+        log::warn!(target: "app", "[SERVICE] systemd notify '{}' failed: {}", state, e);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn notify(_state: &str) {}
+
+/// `--service`: on Windows, hand the process to the service control manager, which calls
+/// `run` and stops it through `exit`. Started from a console instead, `run` runs directly.
+#[cfg(target_os = "windows")]
+pub fn run_service(run: Run) -> Result<(), Box<dyn Error>> {
+    windows::dispatch(run)
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn run_service(run: Run) -> Result<(), Box<dyn Error>> {
+    run()
+}
+
+#[cfg(target_os = "windows")]
+mod windows {
+    use std::ffi::OsString;
+    use std::sync::OnceLock;
+    use std::time::Duration;
+    use windows_service::service::{ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus, ServiceType};
+    use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
+
+    // Ignored by the service manager for a service in its own process, but required by the API
+    const SERVICE_NAME: &str = "transposer2025";
+    static RUN: OnceLock<super::Run> = OnceLock::new();
+
+    windows_service::define_windows_service!(ffi_service_main, service_main);
+
+    pub fn dispatch(run: super::Run) -> Result<(), Box<dyn std::error::Error>> {
+        // Raised when the process wasn't started by the service manager
+        const ERROR_FAILED_SERVICE_CONTROLLER_CONNECT: i32 = 1063;
+        let _ = RUN.set(run);
+        match windows_service::service_dispatcher::start(SERVICE_NAME, ffi_service_main) {
+            Ok(()) => Ok(()),
+            Err(windows_service::Error::Winapi(e)) if e.raw_os_error() == Some(ERROR_FAILED_SERVICE_CONTROLLER_CONNECT) => run(),
+            Err(e) => Err(format!("service dispatcher failed: {}", e).into()),
+        }
+    }
+
+    fn status(state: ServiceState, exit_code: u32) -> ServiceStatus {
+        ServiceStatus {
+            service_type: ServiceType::OWN_PROCESS,
+            current_state: state,
+            controls_accepted: if state == ServiceState::Running { ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN } else { ServiceControlAccept::empty() },
+            exit_code: ServiceExitCode::ServiceSpecific(exit_code),
+            checkpoint: 0,
+            wait_hint: Duration::from_secs(10),
+            process_id: None,
+        }
+    }
+
+    fn service_main(_arguments: Vec<OsString>) {
+        let handler = service_control_handler::register(SERVICE_NAME, |control| match control {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                crate::io::signals::request_exit();
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        });
+        let handler = match handler {
+            Ok(handler) => handler,
+            Err(e) => {
+                log::error!(target: "app", "[SERVICE] Cannot register the control handler: {}", e);
+                return;
+            }
+        };
+        // Services start in System32; config, state and log files live next to the program
+        if let Some(dir) = std::env::current_exe().ok().and_then(|exe| exe.parent().map(|dir| dir.to_path_buf())) {
+            let _ = std::env::set_current_dir(dir);
+        }
+        let _ = handler.set_service_status(status(ServiceState::Running, 0));
+        let result = RUN.get().map_or(Ok(()), |run| run());
+        if let Err(e) = &result {
+            log::error!(target: "app", "[SERVICE] Stopped with an error: {}", e);
+        }
+        let _ = handler.set_service_status(status(ServiceState::Stopped, result.is_err() as u32));
+    }
+}
@@ -0,0 +1,99 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Set once `run` has returned, so a closing console window waits for the shutdown
+static FINISHED: AtomicBool = AtomicBool::new(false);
+
+/// Shut down like the `exit` console command: the MQTT thread publishes `offline`, the
+/// forwarder releases sounding notes and main joins the threads
+pub fn request_exit() {
+    crate::state().exit.store(true, Ordering::SeqCst);
+    crate::state().mqtt_enabled.store(false, Ordering::SeqCst);
+    // Ends the console thread, which waits for its next line
+    crate::io::console::inject("exit");
+}
+
+/// Shutdown is complete; lets a pending console close event go ahead
+pub fn mark_finished() {
+    FINISHED.store(true, Ordering::SeqCst);
+}
+
+#[cfg(unix)]
+static SIGNALLED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn on_signal(_signal: libc::c_int) {
+    // Only an atomic store is safe in a signal handler; the thread below does the rest
+    SIGNALLED.store(true, Ordering::SeqCst);
+}
+
+/// Shut down cleanly on Ctrl+C and SIGTERM (`kill`, systemd stop). A second Ctrl+C while
+/// shutting down ends the process right away.
+#[cfg(unix)]
+pub fn install() {
+    // SAFETY: the handler only stores to an atomic
+    unsafe {
+        libc::signal(libc::SIGTERM, on_signal as *const () as libc::sighandler_t);
+        libc::signal(libc::SIGINT, on_signal as *const () as libc::sighandler_t);
+    }
+    std::thread::spawn(|| {
+        while !crate::state().exit.load(Ordering::SeqCst) {
+            if SIGNALLED.load(Ordering::SeqCst) {
+                log::info!(target: "app", "[SIGNAL] Stop signal received, shutting down");
+                request_exit();
+                // SAFETY: restores the default action, no handler involved
+                unsafe {
+                    libc::signal(libc::SIGTERM, libc::SIG_DFL);
+                    libc::signal(libc::SIGINT, libc::SIG_DFL);
+                }
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(200));
+        }
+    });
+}
+
+/// Shut down cleanly on Ctrl+C, Ctrl+Break and when the console window is closed, the user
+/// logs off or Windows shuts down. Stopping a service is handled by its control handler.
+#[cfg(windows)]
+pub fn install() {
+    if crate::general::cli::options().service {
+        return;
+    }
+    // SAFETY: registers a handler function that lives for the whole program
+    if unsafe { windows_sys::Win32::System::Console::SetConsoleCtrlHandler(Some(windows::on_console_event), 1) } == 0 {
+        log::warn!(target: "app", "[SIGNAL] Cannot install the Ctrl+C handler: {}", std::io::Error::last_os_error());
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn install() {}
+
+#[cfg(windows)]
+mod windows {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::{Duration, Instant};
+    use windows_sys::Win32::Foundation::BOOL;
+    use windows_sys::Win32::System::Console::{CTRL_BREAK_EVENT, CTRL_C_EVENT};
+
+    // Windows ends the process 5 seconds after a close, logoff or shutdown event
+    const CLOSE_WAIT: Duration = Duration::from_millis(4500);
+    static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+    /// Runs on its own thread, started by Windows for every console event
+    pub unsafe extern "system" fn on_console_event(event: u32) -> BOOL {
+        // A second Ctrl+C while shutting down falls through to the default handler (exit now)
+        if REQUESTED.swap(true, Ordering::SeqCst) && matches!(event, CTRL_C_EVENT | CTRL_BREAK_EVENT) {
+            return 0;
+        }
+        log::info!(target: "app", "[SIGNAL] Console event {} received, shutting down", event);
+        super::request_exit();
+        if !matches!(event, CTRL_C_EVENT | CTRL_BREAK_EVENT) {
+            // The process is ended as soon as this returns: give the shutdown time to finish
+            let deadline = Instant::now() + CLOSE_WAIT;
+            while !super::FINISHED.load(Ordering::SeqCst) && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(50));
+            }
+        }
+        1
+    }
+}
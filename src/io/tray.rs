@@ -0,0 +1,199 @@
+use std::thread;
+
+/// Detach from the console window and show an icon in the Windows system tray until exit
+/// (`--tray`). Its menu steps the transpose, toggles OSC sending, opens the config file and
+/// quits; the tooltip shows the current transpose. Console output is gone from then on, so
+/// `logging.file` is the place to look for messages.
+#[cfg(target_os = "windows")]
+pub fn spawn_tray() -> Option<thread::JoinHandle<()>> {
+    // SAFETY: detaching from the console has no preconditions; writes to stdout fail quietly afterwards
+    unsafe { windows_sys::Win32::System::Console::FreeConsole() };
+    Some(thread::spawn(windows::run))
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn spawn_tray() -> Option<thread::JoinHandle<()>> {
+    log::warn!(target: "app", "[TRAY] The tray icon is only available on Windows; keeping the console");
+    None
+}
+
+#[cfg(target_os = "windows")]
+mod windows {
+    use std::ptr::{null, null_mut};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use windows_sys::Win32::Foundation::{HWND, LPARAM, LRESULT, POINT, WPARAM};
+    use windows_sys::Win32::System::LibraryLoader::GetModuleHandleW;
+    use windows_sys::Win32::UI::Shell::{Shell_NotifyIconW, NIF_ICON, NIF_MESSAGE, NIF_TIP, NIM_ADD, NIM_DELETE, NIM_MODIFY, NOTIFYICONDATAW};
+    use windows_sys::Win32::UI::WindowsAndMessaging::*;
+    use crate::general::commands::{self, Command, Outcome, Source};
+
+    // Notification message of the tray icon
+    const WM_TRAY: u32 = WM_APP + 1;
+    // How often the tooltip is refreshed and the exit flag checked
+    const TIMER_MS: u32 = 500;
+    // Icon embedded by winres (build.rs)
+    const ICON_RESOURCE: usize = 1;
+    const ID_UP: usize = 1;
+    const ID_DOWN: usize = 2;
+    const ID_OSC: usize = 3;
+    const ID_CONFIG: usize = 4;
+    const ID_QUIT: usize = 5;
+
+    // Sent by Explorer after a restart; the icon has to be added again
+    static TASKBAR_CREATED: AtomicU32 = AtomicU32::new(0);
+
+    /// Run a menu entry like the matching console command
+    fn run_command(command: Command) {
+        match commands::execute(command, Source::Console) {
+            Ok(Outcome::Transpose { applied, .. }) => log::info!(target: "app", "[TRAY] Transpose set to {}", applied),
+            Ok(Outcome::Done(message)) => log::info!(target: "app", "[TRAY] {}", message),
+            Ok(Outcome::Silent) => {}
+            Err(e) => log::warn!(target: "app", "[TRAY] {}", e),
+        }
+    }
+
+    fn open_config() {
+        match crate::config_path().filter(|path| path.exists()) {
+            Some(path) => {
+                if let Err(e) = std::process::Command::new("explorer").arg(path).spawn() {
+                    log::error!(target: "app", "[TRAY] Cannot open {}: {}", path.display(), e);
+                }
+            }
+            None => log::warn!(target: "app", "[TRAY] No config file to open (running with the defaults)"),
+        }
+    }
+
+    /// Tooltip and menu title, e.g. `MIDI Transposer +3 | OSC on`
+    fn summary() -> String {
+        let state = crate::state();
+        format!(
+            "MIDI Transposer {:+} | OSC {}",
+            state.transpose.load(Ordering::SeqCst),
+            if state.osc_sending.load(Ordering::SeqCst) { "on" } else { "off" }
+        )
+    }
+
+    fn wide(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    fn icon_data(hwnd: HWND) -> NOTIFYICONDATAW {
+        // SAFETY: NOTIFYICONDATAW is plain data; all-zero is its documented empty state
+        let mut data: NOTIFYICONDATAW = unsafe { std::mem::zeroed() };
+        data.cbSize = std::mem::size_of::<NOTIFYICONDATAW>() as u32;
+        data.hWnd = hwnd;
+        data.uID = 1;
+        let tip: Vec<u16> = summary().encode_utf16().take(data.szTip.len() - 1).collect();
+        data.szTip[..tip.len()].copy_from_slice(&tip);
+        data.uFlags = NIF_TIP;
+        data
+    }
+
+    unsafe fn add_icon(hwnd: HWND) {
+        let mut data = icon_data(hwnd);
+        data.uFlags |= NIF_ICON | NIF_MESSAGE;
+        data.uCallbackMessage = WM_TRAY;
+        data.hIcon = LoadIconW(GetModuleHandleW(null()), ICON_RESOURCE as *const u16);
+        if data.hIcon.is_null() {
+            data.hIcon = LoadIconW(null_mut(), IDI_APPLICATION);
+        }
+        if Shell_NotifyIconW(NIM_ADD, &data) == 0 {
+            log::error!(target: "app", "[TRAY] Could not add the tray icon");
+        }
+    }
+
+    unsafe fn show_menu(hwnd: HWND) {
+        let osc = crate::state().osc_sending.load(Ordering::SeqCst);
+        let title = wide(&summary());
+        let items = [
+            (MF_STRING, ID_UP, wide("Transpose up")),
+            (MF_STRING, ID_DOWN, wide("Transpose down")),
+            (MF_STRING | if osc { MF_CHECKED } else { MF_UNCHECKED }, ID_OSC, wide("OSC sending")),
+            (MF_STRING, ID_CONFIG, wide("Open config")),
+        ];
+        let quit = wide("Quit");
+        let menu = CreatePopupMenu();
+        AppendMenuW(menu, MF_STRING | MF_GRAYED, 0, title.as_ptr());
+        AppendMenuW(menu, MF_SEPARATOR, 0, null());
+        for (flags, id, label) in &items {
+            AppendMenuW(menu, *flags, *id, label.as_ptr());
+        }
+        AppendMenuW(menu, MF_SEPARATOR, 0, null());
+        AppendMenuW(menu, MF_STRING, ID_QUIT, quit.as_ptr());
+        let mut cursor = POINT { x: 0, y: 0 };
+        GetCursorPos(&mut cursor);
+        // Without this the menu doesn't close when clicking elsewhere
+        SetForegroundWindow(hwnd);
+        let chosen = TrackPopupMenu(menu, TPM_RETURNCMD | TPM_RIGHTBUTTON, cursor.x, cursor.y, 0, hwnd, null());
+        DestroyMenu(menu);
+        match chosen as usize {
+            ID_UP => run_command(Command::TransposeStep(1)),
+            ID_DOWN => run_command(Command::TransposeStep(-1)),
+            ID_OSC => run_command(Command::OscSending(!osc)),
+            ID_CONFIG => open_config(),
+            // Same path as typing `exit`, so the console thread ends too
+            ID_QUIT => crate::io::console::inject("exit"),
+            _ => {}
+        }
+        Shell_NotifyIconW(NIM_MODIFY, &icon_data(hwnd));
+    }
+
+    unsafe extern "system" fn window_proc(hwnd: HWND, message: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+        match message {
+            WM_TRAY if matches!(lparam as u32, WM_LBUTTONUP | WM_RBUTTONUP) => {
+                show_menu(hwnd);
+                0
+            }
+            WM_TIMER => {
+                if crate::state().exit.load(Ordering::SeqCst) {
+                    PostQuitMessage(0);
+                } else {
+                    Shell_NotifyIconW(NIM_MODIFY, &icon_data(hwnd));
+                }
+                0
+            }
+            _ if message != 0 && message == TASKBAR_CREATED.load(Ordering::SeqCst) => {
+                add_icon(hwnd);
+                0
+            }
+            _ => DefWindowProcW(hwnd, message, wparam, lparam),
+        }
+    }
+
+    /// Hidden window for the icon's messages; runs its message loop until exit
+    pub fn run() {
+        // SAFETY: plain Win32 calls on this thread's own window; all pointers outlive the calls
+        unsafe {
+            let instance = GetModuleHandleW(null());
+            let class_name = wide("VrcMidiTransposerTray");
+            let class = WNDCLASSW {
+                style: 0,
+                lpfnWndProc: Some(window_proc),
+                cbClsExtra: 0,
+                cbWndExtra: 0,
+                hInstance: instance,
+                hIcon: null_mut(),
+                hCursor: null_mut(),
+                hbrBackground: null_mut(),
+                lpszMenuName: null(),
+                lpszClassName: class_name.as_ptr(),
+            };
+            RegisterClassW(&class);
+            let hwnd = CreateWindowExW(0, class_name.as_ptr(), class_name.as_ptr(), 0, 0, 0, 0, 0, null_mut(), null_mut(), instance, null());
+            if hwnd.is_null() {
+                log::error!(target: "app", "[TRAY] Could not create the tray window");
+                return;
+            }
+            TASKBAR_CREATED.store(RegisterWindowMessageW(wide("TaskbarCreated").as_ptr()), Ordering::SeqCst);
+            add_icon(hwnd);
+            SetTimer(hwnd, 1, TIMER_MS, None);
+            let mut message: MSG = std::mem::zeroed();
+            while GetMessageW(&mut message, null_mut(), 0, 0) > 0 {
+                TranslateMessage(&message);
+                DispatchMessageW(&message);
+            }
+            Shell_NotifyIconW(NIM_DELETE, &icon_data(hwnd));
+            DestroyWindow(hwnd);
+        }
+    }
+}
@@ -0,0 +1,306 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Bar, BarChart, BarGroup, Block, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+// Console lines kept for the log pane
+const LOG_LINES: usize = 1000;
+// Redraw interval; also how long a key press may wait
+const FRAME: Duration = Duration::from_millis(50);
+// Share of a channel meter left after one frame
+const METER_DECAY: f32 = 0.8;
+// Meter level of channel messages other than note-on (CC, pitch bend, ...)
+const METER_OTHER: u8 = 24;
+
+/// One console line in colored parts (`None` = default color)
+type LogLine = Vec<(Option<termcolor::Color>, String)>;
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static STOP: AtomicBool = AtomicBool::new(false);
+static HANDLE: Mutex<Option<thread::JoinHandle<()>>> = Mutex::new(None);
+// Console output while the TUI runs; the last line stays open after text without a line break
+static LOG: Mutex<VecDeque<LogLine>> = Mutex::new(VecDeque::new());
+static LINE_OPEN: AtomicBool = AtomicBool::new(false);
+// Per-channel activity: velocity of the last note-on, decaying every frame
+static ACTIVITY: [AtomicU8; 16] = [const { AtomicU8::new(0) }; 16];
+
+/// Whether the TUI owns the terminal (console output goes to its log pane)
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::SeqCst)
+}
+
+/// Append console output to the log pane; `line_break` = false leaves the line open (prompts)
+pub fn push(parts: &[(Option<termcolor::Color>, &str)], line_break: bool) {
+    let Ok(mut log) = LOG.lock() else { return };
+    let mut open = LINE_OPEN.load(Ordering::SeqCst) && !log.is_empty();
+    for (color, text) in parts {
+        for (index, piece) in text.split('\n').enumerate() {
+            if index > 0 || !open {
+                log.push_back(Vec::new());
+                open = true;
+            }
+            if let Some(line) = log.back_mut() {
+                line.push((*color, piece.to_string()));
+            }
+        }
+    }
+    while log.len() > LOG_LINES {
+        log.pop_front();
+    }
+    LINE_OPEN.store(!line_break, Ordering::SeqCst);
+}
+
+/// Feed the channel activity meters from the MIDI input
+pub fn record_midi(message: &[u8]) {
+    if !is_active() || message.is_empty() || !(0x80..0xF0).contains(&message[0]) {
+        return;
+    }
+    let level = match (message[0] & 0xF0, message.get(2)) {
+        (0x90, Some(&velocity)) if velocity > 0 => velocity,
+        (0x80 | 0x90, _) => return,
+        _ => METER_OTHER,
+    };
+    ACTIVITY[(message[0] & 0x0F) as usize].fetch_max(level, Ordering::Relaxed);
+}
+
+/// Take over the terminal (`--tui`): live state, channel meters and connections on top, the
+/// console output below and a command line at the bottom. Returns false if the terminal
+/// can't be used, so the plain console stays.
+pub fn start() -> bool {
+    let terminal = match ratatui::try_init() {
+        Ok(terminal) => terminal,
+        Err(e) => {
+            log::error!(target: "app", "[TUI] Terminal not usable, staying with the plain console: {}", e);
+            return false;
+        }
+    };
+    ACTIVE.store(true, Ordering::SeqCst);
+    let handle = thread::spawn(move || run(terminal));
+    if let Ok(mut current) = HANDLE.lock() {
+        *current = Some(handle);
+    }
+    true
+}
+
+/// Give the terminal back (no-op without a TUI); console output is printed normally again
+pub fn stop() {
+    STOP.store(true, Ordering::SeqCst);
+    let handle = HANDLE.lock().ok().and_then(|mut handle| handle.take());
+    if let Some(handle) = handle {
+        let _ = handle.join();
+    }
+}
+
+/// Command line being typed, with the earlier entries for Up/Down
+#[derive(Default)]
+struct Input {
+    text: String,
+    history: Vec<String>,
+    // Index into `history` while browsing it
+    recalled: Option<usize>,
+    // Log lines scrolled up from the bottom
+    scroll: usize,
+}
+
+impl Input {
+    /// Handle one key; false once the TUI should quit (Ctrl+C, Ctrl+D)
+    fn key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> bool {
+        match code {
+            KeyCode::Char('c' | 'd') if modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Char(c) => self.text.push(c),
+            KeyCode::Backspace => {
+                self.text.pop();
+            }
+            KeyCode::Esc => self.text.clear(),
+            KeyCode::Enter => self.submit(),
+            KeyCode::Up if !self.history.is_empty() => {
+                let index = self.recalled.map_or(self.history.len() - 1, |i| i.saturating_sub(1));
+                self.recalled = Some(index);
+                self.text = self.history[index].clone();
+            }
+            KeyCode::Down => match self.recalled {
+                Some(index) if index + 1 < self.history.len() => {
+                    self.recalled = Some(index + 1);
+                    self.text = self.history[index + 1].clone();
+                }
+                _ => {
+                    self.recalled = None;
+                    self.text.clear();
+                }
+            },
+            KeyCode::PageUp => self.scroll = (self.scroll + 10).min(LOG.lock().map_or(0, |log| log.len())),
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_sub(10),
+            _ => {}
+        }
+        true
+    }
+
+    /// Run the line like a console command; an empty line only answers an open prompt
+    /// (it would quit on the plain console)
+    fn submit(&mut self) {
+        let line = std::mem::take(&mut self.text);
+        let prompt = LINE_OPEN.load(Ordering::SeqCst);
+        if line.trim().is_empty() && !prompt {
+            return;
+        }
+        if prompt {
+            push(&[(None, &line)], true);
+        } else {
+            push(&[(Some(termcolor::Color::Cyan), &format!("> {}", line))], true);
+        }
+        if !line.trim().is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+        self.recalled = None;
+        self.scroll = 0;
+        crate::io::console::inject(&line);
+    }
+}
+
+fn run(mut terminal: DefaultTerminal) {
+    let mut input = Input::default();
+    while !STOP.load(Ordering::SeqCst) && !crate::state().exit.load(Ordering::SeqCst) {
+        for meter in &ACTIVITY {
+            let _ = meter.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |level| Some((level as f32 * METER_DECAY) as u8));
+        }
+        if let Err(e) = terminal.draw(|frame| draw(frame, &input)) {
+            log::error!(target: "app", "[TUI] Drawing failed: {}", e);
+            break;
+        }
+        match event::poll(FRAME).and_then(|ready| if ready { event::read().map(Some) } else { Ok(None) }) {
+            Ok(Some(Event::Key(key))) if key.kind == KeyEventKind::Press => {
+                if !input.key(key.code, key.modifiers) {
+                    break;
+                }
+            }
+            Ok(_) => {}
+            Err(e) => {
+                log::error!(target: "app", "[TUI] Reading the terminal failed: {}", e);
+                break;
+            }
+        }
+    }
+    ACTIVE.store(false, Ordering::SeqCst);
+    ratatui::restore();
+    // The stdin handler waits for a line that no longer comes from the TUI
+    crate::io::console::inject("exit");
+}
+
+fn color(color: Option<termcolor::Color>) -> Color {
+    match color {
+        Some(termcolor::Color::Black) => Color::Black,
+        Some(termcolor::Color::Blue) => Color::LightBlue,
+        Some(termcolor::Color::Green) => Color::LightGreen,
+        Some(termcolor::Color::Red) => Color::LightRed,
+        Some(termcolor::Color::Cyan) => Color::LightCyan,
+        Some(termcolor::Color::Magenta) => Color::LightMagenta,
+        Some(termcolor::Color::Yellow) => Color::LightYellow,
+        Some(termcolor::Color::White) => Color::White,
+        Some(termcolor::Color::Ansi256(index)) => Color::Indexed(index),
+        Some(termcolor::Color::Rgb(r, g, b)) => Color::Rgb(r, g, b),
+        _ => Color::Reset,
+    }
+}
+
+fn on_off(on: bool) -> Span<'static> {
+    if on {
+        Span::styled("on", Style::new().fg(Color::LightGreen))
+    } else {
+        Span::styled("off", Style::new().fg(Color::DarkGray))
+    }
+}
+
+fn draw(frame: &mut Frame, input: &Input) {
+    let [header, middle, log, command] =
+        Layout::vertical([Constraint::Length(5), Constraint::Length(8), Constraint::Min(3), Constraint::Length(3)]).areas(frame.area());
+    let [connections, meters] = Layout::horizontal([Constraint::Length(50), Constraint::Min(20)]).areas(middle);
+    draw_header(frame, header);
+    draw_connections(frame, connections);
+    draw_meters(frame, meters);
+    draw_log(frame, log, input.scroll);
+    let prompt = if LINE_OPEN.load(Ordering::SeqCst) { "? " } else { "> " };
+    frame.render_widget(Paragraph::new(format!("{}{}", prompt, input.text)).block(Block::bordered().title(" Command ")), command);
+    frame.set_cursor_position((command.x + 1 + (prompt.len() + input.text.chars().count()) as u16, command.y + 1));
+}
+
+fn draw_header(frame: &mut Frame, area: Rect) {
+    let state = crate::state();
+    let config = crate::get_config();
+    let transpose = state.transpose.load(Ordering::SeqCst);
+    let latency = crate::general::latency::snapshot();
+    let held: Vec<String> = crate::general::stats::held_notes().into_iter().map(crate::general::notes::display_name).collect();
+    let bold = Style::new().add_modifier(Modifier::BOLD);
+    let lines = vec![
+        Line::from(vec![
+            Span::raw("Transpose "),
+            Span::styled(format!("{:+}", transpose), bold.fg(Color::LightCyan)),
+            Span::raw(format!("   Octave {:+}   Range {:+}..{:+}", state.octave.load(Ordering::SeqCst), config.transpose.min, config.transpose.max)),
+        ]),
+        Line::from(vec![
+            Span::raw("OSC "),
+            on_off(state.osc_sending.load(Ordering::SeqCst)),
+            Span::raw(format!(" -> {}   MQTT ", crate::osc_sender::current_target())),
+            on_off(state.mqtt_enabled.load(Ordering::SeqCst)),
+            Span::raw(format!("   {} messages, avg latency {:.2} ms", latency.count, latency.avg_us() / 1000.0)),
+        ]),
+        Line::from(format!("Held: {}", if held.is_empty() { "-".to_string() } else { held.join(" ") })),
+    ];
+    frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(" MIDI Transposer ")), area);
+}
+
+fn draw_connections(frame: &mut Frame, area: Rect) {
+    use crate::general::check::ComponentState;
+    let lines: Vec<Line> = crate::general::check::component_report()
+        .into_iter()
+        .map(|(name, entry)| {
+            let (state, detail) = match entry {
+                Some((state, detail)) => (Some(state), detail),
+                None => (None, String::new()),
+            };
+            Line::from(vec![
+                Span::styled("● ", Style::new().fg(color(state.and_then(ComponentState::color)))),
+                Span::raw(format!("{:<24} ", name)),
+                Span::styled(detail, Style::new().fg(Color::DarkGray)),
+            ])
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(" Connections ")), area);
+}
+
+fn draw_meters(frame: &mut Frame, area: Rect) {
+    let bars: Vec<Bar> = ACTIVITY
+        .iter()
+        .enumerate()
+        .map(|(channel, level)| Bar::default().value(level.load(Ordering::Relaxed) as u64).label(Line::from((channel + 1).to_string())).text_value(String::new()))
+        .collect();
+    let chart = BarChart::default()
+        .block(Block::bordered().title(" Channel activity "))
+        .data(BarGroup::default().bars(&bars))
+        .bar_width(2)
+        .bar_gap(1)
+        .max(127)
+        .bar_style(Style::new().fg(Color::LightGreen));
+    frame.render_widget(chart, area);
+}
+
+fn draw_log(frame: &mut Frame, area: Rect, scroll: usize) {
+    let height = area.height.saturating_sub(2) as usize;
+    let lines: Vec<Line> = match LOG.lock() {
+        Ok(log) => {
+            let end = log.len().saturating_sub(scroll.min(log.len().saturating_sub(height)));
+            log.range(end.saturating_sub(height)..end)
+                .map(|parts| Line::from(parts.iter().map(|(c, text)| Span::styled(text.clone(), Style::new().fg(color(*c)))).collect::<Vec<_>>()))
+                .collect()
+        }
+        Err(_) => Vec::new(),
+    };
+    let title = if scroll > 0 { " Log (PgDn to follow) " } else { " Log " };
+    frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(title)), area);
+}
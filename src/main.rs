@@ -1,14 +1,13 @@
 use std::error::Error;
 use std::io::Write;
 // no direct stdin/stdout usage here; stdin is handled by `stdin_handler.rs`
-use std::sync::mpsc::channel;
-use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::atomic::Ordering;
 use std::thread;
 use std::time::Duration;
 use std::sync::OnceLock;
 use std::env;
 
-use midir::{Ignore, MidiInput, MidiOutput};
+use midir::{MidiInput, MidiOutput};
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 
 mod io;
@@ -23,7 +22,11 @@ pub use general::transpose;
 pub use remote::osc_listener;
 pub use remote::osc_sender;
 pub use remote::mqtt_listener;
+pub use remote::discovery;
+pub use remote::custom_controls;
+pub use remote::vrchat;
 pub use general::forwarder;
+pub use general::debug::DebugCategory;
 
 // ---------------------------------------------------------------------------
 // Splash: print ASCII art logo in blue on supported terminals (incl. Windows CMD)
@@ -43,7 +46,7 @@ fn print_ascii_logo() {
 // ---------------------------------------------------------------------------
 // Configuration structure loaded from config.json
 // ---------------------------------------------------------------------------
-#[derive(Debug, serde::Deserialize, Clone)]
+#[derive(Debug, serde::Deserialize, Clone, Default)]
 pub struct Config {
     pub midi: MidiConfig,
     pub osc: OscConfig,
@@ -52,12 +55,238 @@ pub struct Config {
     /// Enable verbose logging (e.g., per-note OSC send logs)
     #[serde(default)]
     pub debug: bool,
+    /// Optional instance name for running several transposers side by side.
+    /// Suffixes MQTT client ID, topics and HA device; selects a per-instance state file.
+    #[serde(default)]
+    pub instance_name: Option<String>,
+    /// Capacities of the internal message queues
+    #[serde(default)]
+    pub queues: QueueConfig,
+    /// Named runtime override sets, applied via `profile <name>` or setlist songs
+    #[serde(default)]
+    pub profiles: std::collections::HashMap<String, general::profile::Profile>,
+    /// Ordered songs stepped through with next/prev
+    #[serde(default)]
+    pub setlist: Vec<general::setlist::Song>,
+    /// Write the session statistics as JSON to this file on exit
+    #[serde(default)]
+    pub stats_report: Option<String>,
+    /// Practice mode: alternate between two transposes
+    #[serde(default)]
+    pub practice: general::practice::PracticeConfig,
+    /// Transpose automation lane played back alongside a backing track
+    #[serde(default)]
+    pub automation: general::automation::AutomationConfig,
+    /// Planned key changes started via `osc.sequence_start_path`, MQTT or `sequence start`
+    #[serde(default)]
+    pub sequence: general::sequence::SequenceConfig,
+    /// Note naming convention (letters/solfège, octave of middle C)
+    #[serde(default)]
+    pub note_names: general::notes::NoteNamesConfig,
+    /// Clock-synced transpose pattern (e.g. alternate octaves every bar)
+    #[serde(default)]
+    pub lfo: general::lfo::LfoConfig,
+    /// Announce song titles (setlist, automation lanes) in the chatbox and over MQTT
+    #[serde(default)]
+    pub now_playing: general::now_playing::NowPlayingConfig,
+    /// Show the transpose state in the chatbox
+    #[serde(default)]
+    pub chatbox_status: remote::chatbox::ChatboxStatusConfig,
+    /// Log levels per subsystem and the optional log file
+    #[serde(default)]
+    pub logging: general::logging::LoggingConfig,
+    /// Serve the capability document over HTTP on this `host:port`
+    #[serde(default)]
+    pub capabilities_http: Option<String>,
+    /// Serve MIDI thru latency metrics for Prometheus on this `host:port`
+    #[serde(default)]
+    pub metrics_http: Option<String>,
+    /// Serve the live control dashboard, event API and REST API on this `host:port`
+    #[serde(default)]
+    pub web_ui: Option<String>,
+    /// Require this token (`Authorization: Bearer <token>` or `?token=`) on the web API
+    #[serde(default)]
+    pub web_token: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, Clone)]
+#[serde(default)]
+pub struct QueueConfig {
+    // MIDI input -> forwarder and MIDI -> OSC sender queues (oldest non-note message dropped when full)
+    pub capacity: usize,
+    // rumqttc outgoing request queue
+    pub mqtt_capacity: usize,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        QueueConfig {
+            capacity: general::queue::DEFAULT_CAPACITY,
+            mqtt_capacity: 64,
+        }
+    }
 }
 
 #[derive(Debug, serde::Deserialize, Clone)]
 pub struct MidiConfig {
     pub input_port_name_substr: String,
+    /// Open one input per substring and merge them into one stream (replaces `input_port_name_substr`)
+    #[serde(default)]
+    pub input_port_name_substrs: Vec<String>,
     pub output_port_name_substr: String,
+    /// Ports whose names contain any of these substrings are skipped by the automatic matcher
+    #[serde(default)]
+    pub input_port_name_exclude: Vec<String>,
+    #[serde(default)]
+    pub output_port_name_exclude: Vec<String>,
+    /// Send to several outputs, each with an optional channel filter (replaces `output_port_name_substr`)
+    #[serde(default)]
+    pub output_ports: Vec<output::OutputPortConfig>,
+    /// Also publish the transposed stream on a virtual output port (Linux/macOS)
+    #[serde(default)]
+    pub create_virtual_output: bool,
+    #[serde(default = "default_virtual_output_name")]
+    pub virtual_output_name: String,
+    /// Interactive port selection gives up after this many seconds (0 = wait forever)
+    #[serde(default)]
+    pub port_selection_timeout_secs: u64,
+    /// On timeout: fail with an error instead of auto-selecting the best candidate
+    #[serde(default)]
+    pub port_selection_fail_on_timeout: bool,
+    /// Look for missing (not yet plugged in or unplugged) devices every this many seconds
+    #[serde(default = "default_device_poll_secs")]
+    pub device_poll_secs: u64,
+    /// Reopen inputs and outputs that disappear and come back (USB unplug/replug)
+    #[serde(default = "default_reconnect")]
+    pub reconnect: bool,
+    /// Input filters passed to midir (all off = receive everything)
+    #[serde(default)]
+    pub ignore_sysex: bool,
+    #[serde(default)]
+    pub ignore_time: bool,
+    #[serde(default)]
+    pub ignore_active_sense: bool,
+    /// Drop Active Sensing (0xFE) in the forwarder instead of passing it to the output
+    #[serde(default)]
+    pub strip_active_sensing: bool,
+    /// Pace the output to this many bytes per second (DIN MIDI: 3125); 0 = send immediately
+    #[serde(default)]
+    pub output_bytes_per_sec: u32,
+    /// Messages held back by pacing before the least important ones are dropped
+    #[serde(default = "default_output_buffer_capacity")]
+    pub output_buffer_capacity: usize,
+    /// Observer mode: never open a MIDI output, only drive OSC/MQTT (also used automatically
+    /// when no output port exists)
+    #[serde(default)]
+    pub observer: bool,
+    /// Convert channel pressure (aftertouch) into `aftertouch_cc` before forwarding (initial state)
+    #[serde(default)]
+    pub aftertouch_to_cc: bool,
+    /// Controller number channel pressure is converted to (11 = expression)
+    #[serde(default = "default_aftertouch_cc")]
+    pub aftertouch_cc: u8,
+    /// Lean mode: this CC (e.g. an expression pedal) adds a temporary offset to the transpose
+    #[serde(default)]
+    pub lean_cc: Option<u8>,
+    /// Offset at full pedal (negative leans down); the pedal position scales it
+    #[serde(default = "default_lean_semitones")]
+    pub lean_semitones: i32,
+    /// Forward the lean pedal CC to the output as well
+    #[serde(default)]
+    pub lean_pass_through: bool,
+    /// Hold mode: while this CC (e.g. a sustain-style pedal) is down, `hold_semitones` is added
+    #[serde(default)]
+    pub hold_cc: Option<u8>,
+    /// Hold mode: while this note is held, `hold_semitones` is added (the note itself is not played)
+    #[serde(default)]
+    pub hold_note: Option<u8>,
+    /// Offset while held (negative holds down)
+    #[serde(default = "default_hold_semitones")]
+    pub hold_semitones: i32,
+    /// Realize the transpose (partly) via pitch bend (initial mode, profiles can switch it)
+    #[serde(default)]
+    pub pitch_bend_mode: transpose::PitchBendMode,
+    /// Bend range of the receiving synth in semitones
+    #[serde(default = "default_pitch_bend_range")]
+    pub pitch_bend_range: u8,
+    /// Channels (1-16) the pitch bend mode applies to; empty = all
+    #[serde(default)]
+    pub pitch_bend_channels: Vec<u8>,
+    /// Ignore a hardware CC after its value was set elsewhere until the control crosses that value
+    #[serde(default)]
+    pub soft_takeover: bool,
+    /// Channel (1-16) of the profile `midi_cc` values
+    #[serde(default = "default_cc_channel")]
+    pub cc_channel: u8,
+    /// Input channel (1-16) -> output channel, or `null` to drop the channel; unlisted channels pass unchanged
+    #[serde(default)]
+    pub channel_map: std::collections::HashMap<u8, Option<u8>>,
+    // Keyboard split: note ranges with their own extra transpose and output channel
+    #[serde(default)]
+    pub zones: Vec<general::zones::ZoneConfig>,
+    /// Lowest/highest note sent to the MIDI output; transposed notes outside are dropped instead of clamped
+    #[serde(default)]
+    pub note_min: Option<osc_sender::NoteSpec>,
+    #[serde(default)]
+    pub note_max: Option<osc_sender::NoteSpec>,
+    /// Transposed notes outside note_min..note_max: "drop", "fold" (by octaves) or "clamp"
+    #[serde(default)]
+    pub out_of_range: transpose::NoteOutOfRange,
+}
+
+fn default_output_buffer_capacity() -> usize { 256 }
+fn default_device_poll_secs() -> u64 { 2 }
+fn default_reconnect() -> bool { true }
+fn default_aftertouch_cc() -> u8 { 11 }
+fn default_lean_semitones() -> i32 { 12 }
+fn default_hold_semitones() -> i32 { 12 }
+fn default_pitch_bend_range() -> u8 { 2 }
+fn default_cc_channel() -> u8 { 1 }
+fn default_virtual_output_name() -> String { "VRC MIDI Transposer".to_string() }
+
+// Built-in defaults, used when no config file exists (the port names are required in a file)
+impl Default for MidiConfig {
+    fn default() -> Self {
+        MidiConfig {
+            input_port_name_substr: "MRCC".to_string(),
+            input_port_name_substrs: Vec::new(),
+            output_port_name_substr: "MIDIOUT7 (MRCC)".to_string(),
+            input_port_name_exclude: Vec::new(),
+            output_port_name_exclude: Vec::new(),
+            output_ports: Vec::new(),
+            create_virtual_output: false,
+            virtual_output_name: default_virtual_output_name(),
+            port_selection_timeout_secs: 0,
+            port_selection_fail_on_timeout: false,
+            device_poll_secs: default_device_poll_secs(),
+            reconnect: default_reconnect(),
+            ignore_sysex: false,
+            ignore_time: false,
+            ignore_active_sense: false,
+            strip_active_sensing: false,
+            output_bytes_per_sec: 0,
+            output_buffer_capacity: default_output_buffer_capacity(),
+            observer: false,
+            aftertouch_to_cc: false,
+            aftertouch_cc: default_aftertouch_cc(),
+            lean_cc: None,
+            lean_semitones: default_lean_semitones(),
+            lean_pass_through: false,
+            hold_cc: None,
+            hold_note: None,
+            hold_semitones: default_hold_semitones(),
+            pitch_bend_mode: Default::default(),
+            pitch_bend_range: default_pitch_bend_range(),
+            pitch_bend_channels: Vec::new(),
+            soft_takeover: false,
+            cc_channel: default_cc_channel(),
+            channel_map: std::collections::HashMap::new(),
+            zones: Vec::new(),
+            note_min: None,
+            note_max: None,
+            out_of_range: Default::default(),
+        }
+    }
 }
 
 #[derive(Debug, serde::Deserialize, Clone)]
@@ -65,15 +294,111 @@ pub struct MidiConfig {
 pub struct OscConfig {
     pub listening_host: String,
     pub listening_port: u16,
+    // Ports tried in order when `listening_port` is already in use
+    pub listening_port_fallbacks: Vec<u16>,
     pub transpose_path: String,
     pub transpose_up_path: String,
     pub transpose_down_path: String,
+    // Octave shift steps (argument 1 triggers), added on top of the semitone transpose
+    pub octave_up_path: String,
+    pub octave_down_path: String,
+    // Absolute transpose as a float in -1..1 spread over transpose.min..max (e.g. a radial puppet)
+    pub transpose_norm_path: String,
     pub sending_addr: String,
     pub sending_port: u16,
+    // Several send targets (e.g. VRChat and a visualizer); replaces sending_addr/sending_port when set
+    pub sending_targets: Vec<osc_sender::OscTargetConfig>,
     // Whether OSC sending of MIDI is enabled at startup
     pub sending_enabled: bool,
     // Whether to send original (true) or transposed (false) MIDI via OSC at startup
     pub send_original: bool,
+    // Per-stream enable flags; when unset they follow `send_original`
+    pub original_enabled: Option<bool>,
+    pub transposed_enabled: Option<bool>,
+    // Browse mDNS for OSC services at startup and offer them as send targets
+    pub discover_on_startup: bool,
+    // How long an mDNS browse (startup or `discover` command) waits for answers
+    pub discovery_timeout_ms: u64,
+    // Advertise the OSC listener via OSCQuery (HTTP + mDNS `_oscjson._tcp`); port 0 = any free port
+    pub oscquery_enabled: bool,
+    pub oscquery_port: u16,
+    // User-defined avatar parameters controllable via MQTT and OSC
+    pub sending_addresses: Vec<custom_controls::CustomControl>,
+    // Incoming OSC prefix for setting custom controls (`<path>/<slug>`)
+    pub custom_control_path: String,
+    // Auto mode: only send while VRChat is detected (process or recent /avatar message)
+    pub sending_auto: bool,
+    // How long an incoming /avatar message counts as "VRChat is running"
+    pub vrchat_activity_timeout_secs: u64,
+    // Background VRChat connectivity check interval while sending (0 = off)
+    pub vrchat_check_interval_secs: u64,
+    // Incoming OSC path taking any console control command as a string argument
+    pub command_path: String,
+    // Setlist navigation paths (argument 1 triggers)
+    pub setlist_next_path: String,
+    pub setlist_prev_path: String,
+    // Start/stop the configured transpose sequence (argument 1 triggers)
+    pub sequence_start_path: String,
+    pub sequence_stop_path: String,
+    // Reset all note parameters and custom controls on the target at startup
+    pub init_parameters_on_startup: bool,
+    // Controller layout (TouchOSC `.tosc` or Open Stage Control `.json`) rewritten at every startup
+    pub layout_file: Option<String>,
+    // Send the effective transpose (int) to the OSC target on every change, including glide steps
+    pub transpose_state_path: Option<String>,
+    // Note encoding: one parameter per key ("notes") or one float per octave ("octave")
+    pub encoding: osc_sender::OscEncoding,
+    // Notes encoding: argument type of the note parameters ("int", "bool" or "float")
+    pub note_arg_type: osc_sender::NoteArgType,
+    // Per-target override of `note_arg_type`, keyed by "host:port"
+    pub note_arg_types: std::collections::HashMap<String, osc_sender::NoteArgType>,
+    // Notes encoding: also send `<note>_v` with the velocity as float
+    pub note_velocity: bool,
+    // Note parameters as on/off or as velocity floats, globally and per note parameter path
+    pub note_value_mode: osc_sender::NoteValueMode,
+    pub note_value_modes: std::collections::HashMap<String, osc_sender::NoteValueMode>,
+    // Address of the note parameters; `{note}`, `{name}`, `{octave}` and `{number}` are replaced
+    pub note_path_template: String,
+    // OSC note names with sharps (CSHARP4) or flats (DFLAT4), and an offset on their octave numbers
+    pub note_accidentals: osc_sender::NoteAccidentals,
+    pub note_octave_offset: i32,
+    // Notes the avatar supports; others are folded by octaves or dropped
+    pub note_range: osc_sender::NoteRangeConfig,
+    // Control changes sent as avatar parameters, keyed by CC number
+    pub cc_map: std::collections::HashMap<u8, osc_sender::CcMapping>,
+    // Aftertouch as floats: channel pressure to `pressure_parameter`, poly pressure to `<pressure_parameter>_<note>`
+    pub channel_pressure: bool,
+    pub poly_pressure: bool,
+    pub pressure_parameter: String,
+    // Program changes as the Int `program_parameter` (0-127)
+    pub program_change: bool,
+    pub program_parameter: String,
+    // Skip messages whose value for the address didn't change since the last send
+    pub suppress_duplicates: bool,
+    // Max sends per second per address for pitch bend and CC driven controls (0 = unlimited)
+    pub continuous_rate_hz: f32,
+    // Lossy networks: send note state messages twice, `redundant_gap_ms` apart, with a sequence counter
+    pub redundant_send: bool,
+    pub redundant_gap_ms: u64,
+    pub sequence_parameter: String,
+    // Bitmap encoding: send one OSC blob instead of 16 Int parameters
+    pub bitmap_blob: bool,
+    // Bitmap encoding: resend interval for late joiners (0 = only on change)
+    pub bitmap_resend_ms: u64,
+    // Proxy mode: relay unrecognized incoming messages to `proxy_target` (host:port)
+    pub proxy_enabled: bool,
+    pub proxy_target: String,
+    // Proxy mode: log every incoming message
+    pub proxy_log: bool,
+    // Proxy mode: path prefixes that are dropped instead of relayed
+    pub proxy_drop_paths: Vec<String>,
+    // Incoming avatar parameters (name without `/avatar/parameters/`) that trigger actions
+    pub avatar_parameter_actions: std::collections::HashMap<String, osc_listener::AvatarParameterAction>,
+    // Raw mirror: every incoming MIDI message as a blob to `raw_mirror_path`
+    pub raw_mirror: bool,
+    pub raw_mirror_path: String,
+    // Raw mirror target (host:port); the OSC send target when unset
+    pub raw_mirror_target: Option<String>,
 }
 
 impl Default for OscConfig {
@@ -81,13 +406,68 @@ impl Default for OscConfig {
         OscConfig {
             listening_host: "127.0.0.1".to_string(),
             listening_port: 9069,
+            listening_port_fallbacks: Vec::new(),
             transpose_path: "/transpose".to_string(),
             transpose_up_path: "/transposeUp".to_string(),
             transpose_down_path: "/transposeDown".to_string(),
+            octave_up_path: "/octaveUp".to_string(),
+            octave_down_path: "/octaveDown".to_string(),
+            transpose_norm_path: "/transposeNorm".to_string(),
             sending_addr: "127.0.0.1".to_string(),
             sending_port: 9000,
+            sending_targets: Vec::new(),
             sending_enabled: false,
             send_original: true,
+            original_enabled: None,
+            transposed_enabled: None,
+            discover_on_startup: false,
+            discovery_timeout_ms: 2000,
+            oscquery_enabled: false,
+            oscquery_port: 0,
+            sending_addresses: Vec::new(),
+            custom_control_path: "/custom".to_string(),
+            sending_auto: false,
+            vrchat_activity_timeout_secs: 300,
+            vrchat_check_interval_secs: 60,
+            command_path: "/command".to_string(),
+            setlist_next_path: "/setlist/next".to_string(),
+            setlist_prev_path: "/setlist/prev".to_string(),
+            sequence_start_path: "/sequence/start".to_string(),
+            sequence_stop_path: "/sequence/stop".to_string(),
+            init_parameters_on_startup: false,
+            layout_file: None,
+            transpose_state_path: None,
+            encoding: osc_sender::OscEncoding::Notes,
+            note_arg_type: osc_sender::NoteArgType::Int,
+            note_arg_types: std::collections::HashMap::new(),
+            note_velocity: false,
+            note_value_mode: osc_sender::NoteValueMode::Bool,
+            note_value_modes: std::collections::HashMap::new(),
+            note_path_template: "/avatar/parameters/{note}".to_string(),
+            note_accidentals: osc_sender::NoteAccidentals::Sharp,
+            note_octave_offset: 0,
+            note_range: osc_sender::NoteRangeConfig::default(),
+            cc_map: std::collections::HashMap::new(),
+            channel_pressure: false,
+            poly_pressure: false,
+            pressure_parameter: "/avatar/parameters/Pressure".to_string(),
+            program_change: false,
+            program_parameter: "/avatar/parameters/Program".to_string(),
+            suppress_duplicates: true,
+            continuous_rate_hz: 0.0,
+            redundant_send: false,
+            redundant_gap_ms: 15,
+            sequence_parameter: "/avatar/parameters/OscSeq".to_string(),
+            bitmap_blob: false,
+            bitmap_resend_ms: 1000,
+            proxy_enabled: false,
+            proxy_target: "127.0.0.1:9000".to_string(),
+            proxy_log: false,
+            proxy_drop_paths: Vec::new(),
+            avatar_parameter_actions: std::collections::HashMap::new(),
+            raw_mirror: false,
+            raw_mirror_path: "/midi/raw".to_string(),
+            raw_mirror_target: None,
         }
     }
 }
@@ -101,14 +481,90 @@ pub struct MqttConfig {
     pub password: String,
     #[serde(default = "default_mqtt_enabled")]
     pub enabled: bool,
+    /// MIDI note numbers exposed as HA binary sensors (on while the note is held)
+    #[serde(default)]
+    pub trigger_notes: Vec<u8>,
+    /// Stream note on/off events as JSON to `<base>/events/midi` (for live visualizers)
+    #[serde(default)]
+    pub note_events: bool,
+    /// Maximum note-on events per second on the event stream (0 = unlimited)
+    #[serde(default = "default_note_events_max_per_sec")]
+    pub note_events_max_per_sec: u32,
+    /// Per-profile topics under `<base>/profiles/<name>/`: state of the active profile and commands gated on it
+    #[serde(default)]
+    pub profile_topics: bool,
 }
 
 fn default_mqtt_enabled() -> bool { true }
+fn default_note_events_max_per_sec() -> u32 { 50 }
+
+// Built-in defaults, used when no config file exists
+impl Default for MqttConfig {
+    fn default() -> Self {
+        MqttConfig {
+            broker_host: "192.168.50.200".to_string(),
+            broker_port: 1883,
+            base_topic: "midi_transposer".to_string(),
+            username: String::new(),
+            password: String::new(),
+            enabled: default_mqtt_enabled(),
+            trigger_notes: Vec::new(),
+            note_events: false,
+            note_events_max_per_sec: default_note_events_max_per_sec(),
+            profile_topics: false,
+        }
+    }
+}
 
 #[derive(Debug, serde::Deserialize, Clone)]
 pub struct TransposeConfig {
     pub min: i8,
     pub max: i8,
+    // Keep stepping while a transpose up/down button is held (value 1 until 0)
+    #[serde(default)]
+    pub auto_repeat: bool,
+    #[serde(default = "default_repeat_delay_ms")]
+    pub repeat_delay_ms: u64,
+    #[serde(default = "default_repeat_interval_ms")]
+    pub repeat_interval_ms: u64,
+    // Transpose changes kept in memory for `history` and MQTT (0 = off)
+    #[serde(default = "default_history_size")]
+    pub history_size: usize,
+    // Range of the octave shift, in octaves
+    #[serde(default = "default_octave_min")]
+    pub octave_min: i8,
+    #[serde(default = "default_octave_max")]
+    pub octave_max: i8,
+    // Apply transpose changes of more than one semitone in 1-semitone steps
+    #[serde(default)]
+    pub glide: bool,
+    #[serde(default = "default_glide_step_ms")]
+    pub glide_step_ms: u64,
+}
+
+fn default_repeat_delay_ms() -> u64 { 400 }
+fn default_repeat_interval_ms() -> u64 { 150 }
+fn default_history_size() -> usize { 200 }
+fn default_octave_min() -> i8 { -3 }
+fn default_octave_max() -> i8 { 3 }
+fn default_glide_step_ms() -> u64 { 60 }
+
+// Built-in defaults, used when no config file exists
+impl Default for TransposeConfig {
+    fn default() -> Self {
+        TransposeConfig {
+            min: -24,
+            max: 24,
+            auto_repeat: false,
+            repeat_delay_ms: default_repeat_delay_ms(),
+            repeat_interval_ms: default_repeat_interval_ms(),
+            history_size: default_history_size(),
+            octave_min: default_octave_min(),
+            octave_max: default_octave_max(),
+            glide: false,
+            glide_step_ms: default_glide_step_ms(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -117,99 +573,122 @@ pub struct MqttCredentials {
     pub password: String,
 }
 
+/// Config file of this run: `--config`, else `config.<instance>.json` of a named instance
+/// (via TRANSPOSER_INSTANCE) if it exists, else `config.json`
+fn config_file_path() -> std::path::PathBuf {
+    let env_instance = general::instance::instance_from_env();
+    let instance_path = general::instance::config_path_for(env_instance.as_deref());
+    match &general::cli::options().config {
+        Some(path) => path.clone(),
+        None if instance_path.exists() => instance_path,
+        None => general::instance::config_path_for(None),
+    }
+}
+
 fn load_config() -> Config {
-    let path = std::path::Path::new("config.json");
-    
+    let env_instance = general::instance::instance_from_env();
+    let path_buf = config_file_path();
+    let path = path_buf.as_path();
+    let mut config = read_config_file(path);
+    let _ = CONFIG_PATH.set(path_buf.clone());
+    general::cli::apply(general::cli::options(), &mut config);
+    for problem in custom_controls::resolve_slugs(&mut config.osc.sending_addresses) {
+        log::warn!(target: "config", "[CONFIG] {}", problem);
+    }
+    if let Err(problem) = general::sequence::check(&config.sequence) {
+        log::warn!(target: "config", "[CONFIG] {}", problem);
+    }
+    if env_instance.is_some() {
+        config.instance_name = env_instance;
+    } else {
+        config.instance_name = config.instance_name.as_deref().and_then(general::instance::sanitize_instance_name);
+    }
+    config
+}
+
+fn read_config_file(path: &std::path::Path) -> Config {
     // Default configuration if file doesn't exist
-    let default_config = Config {
-        midi: MidiConfig {
-            input_port_name_substr: "MRCC".to_string(),
-            output_port_name_substr: "MIDIOUT7 (MRCC)".to_string(),
-        },
-        osc: OscConfig {
-            listening_host: "127.0.0.1".to_string(),
-            listening_port: 9069,
-            transpose_path: "/transpose".to_string(),
-            transpose_up_path: "/transposeUp".to_string(),
-            transpose_down_path: "/transposeDown".to_string(),
-            sending_addr: "127.0.0.1".to_string(),
-            sending_port: 9000,
-            sending_enabled: false,
-            send_original: true,
-        },
-        mqtt: MqttConfig {
-            broker_host: "192.168.50.200".to_string(),
-            broker_port: 1883,
-            base_topic: "midi_transposer".to_string(),
-            username: "".to_string(),
-            password: "".to_string(),
-            enabled: true,
-        },
-        transpose: TransposeConfig {
-            min: -24,
-            max: 24,
-        },
-        debug: false,
-    };
+    let default_config = Config::default();
 
     if !path.exists() {
-        eprintln!("[CONFIG] config.json not found; using defaults");
+        log::warn!(target: "config", "[CONFIG] {} not found; using defaults", path.display());
         return default_config;
     }
     
     match std::fs::read_to_string(path) {
-        Ok(text) => match serde_json::from_str::<Config>(&text) {
-            Ok(config) => {
-                CONFIG_LOADED_FROM_FILE.store(true, Ordering::SeqCst);
-                config
-            },
-            Err(err) => {
-                eprintln!("[CONFIG] Failed to parse config.json: {} (using defaults)", err);
-                default_config
+        Ok(text) => {
+            // Every problem is listed; fields of the wrong type fall back to their default
+            let (config, problems) = general::config_check::parse(&text, general::config_format::ConfigFormat::of(path));
+            general::config_check::report(path, &problems);
+            match config {
+                Some(config) => {
+                    state().config_from_file.store(true, Ordering::SeqCst);
+                    config
+                }
+                None => {
+                    log::error!(target: "config", "[CONFIG] Failed to parse {} (using defaults)", path.display());
+                    default_config
+                }
             }
-        },
+        }
         Err(err) => {
-            eprintln!("[CONFIG] Failed to read config.json: {} (using defaults)", err);
+            log::error!(target: "config", "[CONFIG] Failed to read {}: {} (using defaults)", path.display(), err);
             default_config
         }
     }
 }
 
+/// Write a single value into the config file that was loaded at startup (`section.key`),
+/// keeping all other settings. Takes effect on the next launch.
+pub fn update_config_file(section: &str, key: &str, value: serde_json::Value) -> Result<std::path::PathBuf, Box<dyn Error>> {
+    let path = CONFIG_PATH.get().ok_or("config path unknown")?.clone();
+    if !state().config_from_file.load(Ordering::SeqCst) {
+        return Err(format!("{} was not loaded (missing or invalid)", path.display()).into());
+    }
+    let text = std::fs::read_to_string(&path)?;
+    let text = general::config_format::set_value(&text, general::config_format::ConfigFormat::of(&path), section, key, value)?;
+    std::fs::write(&path, text)?;
+    Ok(path)
+}
+
 // ---------------------------------------------------------------------------
-// Global runtime state (shared via atomics)
+// Global runtime state (see `general::state::AppState`)
 // ---------------------------------------------------------------------------
-/// Current transpose amount in semitones. Updated by stdin handler thread.
-static TRANSPOSE_SEMITONES: AtomicI32 = AtomicI32::new(0);
-
-/// When true the main loop will terminate and the program will shut down.
-static EXIT_FLAG: AtomicBool = AtomicBool::new(false);
-
-/// Global configuration loaded at startup (thread-safe, write-once)
-static GLOBAL_CONFIG: OnceLock<Config> = OnceLock::new();
+pub use general::state::state;
 
-/// Global debug flag (runtime-togglable). Initialized from config.debug.
-pub(crate) static DEBUG_ENABLED: AtomicBool = AtomicBool::new(false);
+/// Path of the config file chosen at startup (per-instance or `config.json`)
+static CONFIG_PATH: OnceLock<std::path::PathBuf> = OnceLock::new();
 
-/// Whether config was successfully loaded from config.json (not defaults)
-pub(crate) static CONFIG_LOADED_FROM_FILE: AtomicBool = AtomicBool::new(false);
+pub fn config_path() -> Option<&'static std::path::Path> {
+    CONFIG_PATH.get().map(|p| p.as_path())
+}
 
-/// Get the global configuration (must be loaded first)
-pub fn get_config() -> &'static Config {
-    GLOBAL_CONFIG.get().expect("Config not loaded")
+/// Get the active configuration (the built-in defaults until it is loaded)
+pub fn get_config() -> std::sync::Arc<Config> {
+    state().config()
 }
 
 /// Check whether verbose debug logging is enabled
 pub fn is_debug_enabled() -> bool {
-    DEBUG_ENABLED.load(Ordering::SeqCst)
+    state().debug.load(Ordering::SeqCst)
+}
+
+/// Check whether debug logging of one module category (midi, osc, mqtt, config) is enabled
+pub fn is_debug_enabled_for(category: DebugCategory) -> bool {
+    general::debug::category_enabled(category)
 }
 
-/// Sets the transpose value with range clamping
+/// Sets the transpose value with range clamping (with `transpose.glide`, larger changes
+/// are applied step by step by the glide thread)
 pub fn set_transpose_semitones(value: i32) -> i32 {
     let config = get_config();
     let clamped = value.clamp(config.transpose.min as i32, config.transpose.max as i32);
-    TRANSPOSE_SEMITONES.store(clamped, Ordering::SeqCst);
+    if !general::glide::begin(clamped) {
+        apply_transpose(clamped);
+    }
     if value != clamped {
-        eprintln!(
+        log::warn!(
+            target: "app",
             "[TRANSPOSE] Clamped {} to range [{}, {}] -> {}",
             value, config.transpose.min, config.transpose.max, clamped
         );
@@ -217,59 +696,97 @@ pub fn set_transpose_semitones(value: i32) -> i32 {
     clamped
 }
 
-/// Enable OSC sending of MIDI data (true = enabled, false = disabled)
-static OSC_SENDING_ENABLED: AtomicBool = AtomicBool::new(false);
-
-/// Send original input MIDI (true) or transposed MIDI (false) via OSC
-pub static OSC_SEND_ORIGINAL: AtomicBool = AtomicBool::new(true);
+/// Store the effective transpose (already clamped) and record it for stats and history
+pub fn apply_transpose(value: i32) {
+    let previous = state().transpose.swap(value, Ordering::SeqCst);
+    general::stats::record_transpose(value);
+    general::history::record(previous, value);
+}
 
-/// MQTT enabled flag (runtime)
-pub(crate) static MQTT_ENABLED: AtomicBool = AtomicBool::new(true);
+/// Sets the octave shift with range clamping
+pub fn set_octave_shift(value: i32) -> i32 {
+    let config = get_config();
+    let clamped = value.clamp(config.transpose.octave_min as i32, config.transpose.octave_max as i32);
+    state().octave.store(clamped, Ordering::SeqCst);
+    clamped
+}
 
-/// MQTT connection state (set by mqtt_listener)
-pub(crate) static MQTT_CONNECTED: AtomicBool = AtomicBool::new(false);
+/// Send only the original (true) or only the transposed (false) stream via OSC
+pub fn set_osc_send_original(original: bool) {
+    state().osc_original.store(original, Ordering::SeqCst);
+    state().osc_transposed.store(!original, Ordering::SeqCst);
+}
 
 fn main() {
-    match run() {
+    // A Windows service is started by the service manager, which then calls `run`
+    let result = if env::args().any(|arg| arg == "--service") { io::service::run_service(run) } else { run() };
+    // Give the terminal back before the error (if any) is printed
+    io::tui::stop();
+    io::signals::mark_finished();
+    match result {
         Ok(_) => (),
-        Err(err) => println!("Error: {}", err),
+        Err(err) => {
+            log::error!(target: "app", "Error: {}", err);
+            std::process::exit(1);
+        }
     }
 }
 
 fn run() -> Result<(), Box<dyn Error>> {
+    // OSC receiver debug tool: `--listen-debug <port>` prints received OSC instead of transposing
+    let args: Vec<String> = env::args().collect();
+    general::logging::install();
+    let cli = general::cli::parse(&args[1..])?;
+    if cli.help {
+        io::console::print_line(general::cli::USAGE.to_string());
+        return Ok(());
+    }
+    general::cli::set(cli.clone());
+    if cli.check_config {
+        return general::config_check::run_check_config(&config_file_path());
+    }
+    if cli.list_ports {
+        state().set_config(load_config());
+        io::console::print_line(serde_json::to_string_pretty(&general::cli::port_report())?);
+        return Ok(());
+    }
+    if let Some(port) = remote::osc_debug::listen_debug_port(&args) {
+        state().set_config(load_config());
+        return remote::osc_debug::run_listen_debug(port?);
+    }
+    // Control surface self-test: `--send-test [host:port]` drives a running instance and checks its responses
+    if let Some(target) = remote::self_test::send_test_target(&args) {
+        state().set_config(load_config());
+        return remote::self_test::run_send_test(target);
+    }
+
     // Show a nice splash logo at startup
-    print_ascii_logo();
+    if !cli.headless && !cli.tui {
+        print_ascii_logo();
+    }
 
     // CI test mode: skip MIDI/OSC/MQTT and only verify clean exit via stdin
     if env::var("CI_TEST_EXIT").as_deref() == Ok("1") {
         // Minimal default config sufficient for getters; MQTT disabled to avoid background threads
         let config = Config {
-            midi: MidiConfig { input_port_name_substr: "".into(), output_port_name_substr: "".into() },
-            osc: OscConfig { ..Default::default() },
-            mqtt: MqttConfig {
-                broker_host: "127.0.0.1".into(),
-                broker_port: 1883,
-                base_topic: "midi_transposer".into(),
-                username: "".into(),
-                password: "".into(),
-                enabled: false,
-            },
-            transpose: TransposeConfig { min: -24, max: 24 },
-            debug: false,
+            midi: MidiConfig { input_port_name_substr: String::new(), output_port_name_substr: String::new(), ..Default::default() },
+            mqtt: MqttConfig { broker_host: "127.0.0.1".to_string(), enabled: false, ..Default::default() },
+            ..Default::default()
         };
-        let _ = GLOBAL_CONFIG.set(config.clone());
-        DEBUG_ENABLED.store(config.debug, Ordering::SeqCst);
-        MQTT_ENABLED.store(false, Ordering::SeqCst);
-        TRANSPOSE_SEMITONES.store(0, Ordering::SeqCst);
-        EXIT_FLAG.store(false, Ordering::SeqCst);
+        state().set_config(config.clone());
+        general::logging::configure(&config.logging);
+        general::debug::set_enabled(config.debug);
+        state().mqtt_enabled.store(false, Ordering::SeqCst);
+        state().transpose.store(0, Ordering::SeqCst);
+        state().exit.store(false, Ordering::SeqCst);
 
         // Only stdin handler; no other threads
         let stdin_handle = stdin_handler::spawn_stdin_handler();
-        if is_debug_enabled() { println!("[CI] Waiting for exit via stdin..."); }
-        while !EXIT_FLAG.load(Ordering::SeqCst) {
+        log::debug!(target: "app", "[CI] Waiting for exit via stdin...");
+        while !state().exit.load(Ordering::SeqCst) {
             thread::sleep(Duration::from_millis(50));
         }
-        println!("Closing connections and exiting...");
+        log::info!(target: "app", "Closing connections and exiting...");
         let _ = stdin_handle.join();
         return Ok(());
     }
@@ -278,163 +795,426 @@ fn run() -> Result<(), Box<dyn Error>> {
     let config = load_config();
     
     // Store config in global static for other modules to access
-    let _ = GLOBAL_CONFIG.set(config.clone());
+    state().set_config(config.clone());
+    general::logging::configure(&config.logging);
+    // Terminal UI (before the port prompts, which it answers from its command line)
+    if general::cli::options().tui {
+        io::tui::start();
+    }
+    // Tray icon instead of the console window (Windows)
+    let tray_handle = general::cli::options().tray.then(io::tray::spawn_tray).flatten();
+    // Ctrl+C, SIGTERM and closing the console window shut down like `exit`
+    io::signals::install();
     // Initialize runtime debug flag from config
-    DEBUG_ENABLED.store(config.debug, Ordering::SeqCst);
+    general::debug::set_enabled(config.debug);
     // Inform about config source when debug is enabled
-    if is_debug_enabled() && CONFIG_LOADED_FROM_FILE.load(Ordering::SeqCst) {
-        println!("[CONFIG] Loaded configuration from config.json");
+    if is_debug_enabled_for(DebugCategory::Config) && state().config_from_file.load(Ordering::SeqCst) {
+        log::debug!(target: "config", "[CONFIG] Loaded configuration from file");
+    }
+    if let Some(name) = &config.instance_name {
+        log::info!(target: "app", "Running as instance '{}'", name);
     }
 
-    let mut midi_in = MidiInput::new("midir reading input")?;
-    midi_in.ignore(Ignore::None);
+    let midi_in = MidiInput::new("midir reading input")?;
 
     let midi_out = MidiOutput::new("midir forwarding output")?;
 
-    // Choose input port by substring match (first match). Falls back to explicit selection if none/multiple found.
-    // Choose input port (substring or interactive selection)
-    let input_index = input::choose_input_port(&midi_in, &config.midi.input_port_name_substr)?;
+    // Input port names, one per source (`None` = not present yet, opened later by a waiter).
+    // Several `input_port_name_substrs` each take the first matching port; a single
+    // `input_port_name_substr` falls back to explicit selection if none/multiple found.
+    // Without any input port (keyboard still off) the rest starts anyway.
     let in_ports = midi_in.ports();
-    let in_port = &in_ports[input_index];
+    let mut in_port_names: Vec<Option<String>> = Vec::new();
+    if !config.midi.input_port_name_substrs.is_empty() {
+        for pattern in &config.midi.input_port_name_substrs {
+            let taken: Vec<String> = in_port_names.iter().flatten().cloned().collect();
+            let index = input::find_named_input_port(&midi_in, pattern, &config.midi.input_port_name_exclude, &taken);
+            let name = index.map(|i| midi_in.port_name(&in_ports[i])).transpose()?;
+            if name.is_none() {
+                log::info!(target: "midi", "No MIDI input port matches '{}'; waiting for it", pattern);
+            }
+            in_port_names.push(name);
+        }
+    } else if in_ports.is_empty() {
+        log::info!(target: "midi", "No MIDI input port found; starting without MIDI input and waiting for a device");
+        in_port_names.push(None);
+    } else {
+        let input_index = input::choose_input_port(&midi_in, &config.midi.input_port_name_substr, &config.midi.input_port_name_exclude)?;
+        in_port_names.push(Some(midi_in.port_name(&in_ports[input_index])?));
+    }
 
-    if is_debug_enabled() { println!("\nOpening input connection"); }
-    let in_port_name = midi_in.port_name(in_port)?;
+    log::debug!(target: "midi", "\nOpening input connection");
+    let open_names: Vec<&str> = in_port_names.iter().flatten().map(String::as_str).collect();
+    let in_port_name = if open_names.is_empty() { "(waiting for a device)".to_string() } else { open_names.join(" + ") };
 
-    // Channel: midi input callback -> forwarder thread
-    let (tx, rx) = channel::<Vec<u8>>();
+    // Queue: midi input callback -> forwarder thread
+    // (tagged with the input source: index into `in_port_names`, and the arrival time)
+    let (tx, rx) = general::queue::bounded::<(u8, Vec<u8>, std::time::Instant)>("midi_forward", config.queues.capacity);
     
-    // Channel: original MIDI -> OSC sender (for original input MIDI)
-    let (osc_original_tx, osc_original_rx) = osc_sender::create_osc_sender_channel();
+    // Queues: original MIDI -> OSC senders (for original input MIDI), one per send target
+    let (osc_original_tx, osc_original_rx) = osc_sender::create_osc_target_channels("osc_original");
     
-    // Channel: transposed MIDI -> OSC sender (for transposed MIDI)
-    let (osc_transposed_tx, osc_transposed_rx) = osc_sender::create_osc_sender_channel();
-
-    // Open the MIDI output port (choose by name substring). Prefer an output whose name
-    // matches the requested substring but is not the exact same name as the selected input port.
-    // Choose output port (substring or interactive selection)
-    let output_index = output::choose_output_port(&midi_out, &config.midi.output_port_name_substr, &in_port_name)?;
-    let out_ports = midi_out.ports();
-    let out_port = &out_ports[output_index];
-
-    // Resolve output port name before connecting (connect takes ownership of midi_out)
-    let out_port_name = midi_out.port_name(out_port)?;
+    // Queues: transposed MIDI -> OSC senders (for transposed MIDI), one per send target
+    let (osc_transposed_tx, osc_transposed_rx) = osc_sender::create_osc_target_channels("osc_transposed");
+
+    // Queue: raw input MIDI -> raw OSC mirror (only when enabled)
+    let (osc_raw_tx, osc_raw_rx) = if config.osc.raw_mirror {
+        let (tx, rx) = osc_sender::create_osc_sender_channel("osc_raw");
+        (Some(tx), Some(rx))
+    } else {
+        (None, None)
+    };
+
+    // Choose the MIDI output ports with their channel filters: every `output_ports` entry, or one
+    // port by name substring (falling back to interactive selection). Prefer an output whose name
+    // matches the requested substring but is not the exact same name as a selected input port.
+    // Observer mode (configured, or no output port at all): OSC/MQTT only
+    let out_port_names: Vec<(String, Vec<u8>)> = if config.midi.observer {
+        log::info!(target: "midi", "Observer mode: no MIDI output is opened (OSC/MQTT only)");
+        Vec::new()
+    } else if !config.midi.output_ports.is_empty() {
+        output::find_output_ports(&midi_out, &config.midi.output_ports, &config.midi.output_port_name_exclude, &open_names)
+    } else {
+        match output::choose_output_port(&midi_out, &config.midi.output_port_name_substr, &config.midi.output_port_name_exclude, &in_port_name)? {
+            Some(index) => vec![(midi_out.port_name(&midi_out.ports()[index])?, Vec::new())],
+            None => Vec::new(),
+        }
+    };
+    // Virtual output other software on this machine can read, in addition to the ports above
+    let virtual_out = if config.midi.create_virtual_output {
+        let name = general::instance::with_suffix(&config.midi.virtual_output_name);
+        match output::create_virtual(&name) {
+            Ok(target) => {
+                log::info!(target: "midi", "Virtual MIDI output '{}' created", name);
+                Some(target)
+            }
+            Err(e) => {
+                log::warn!(target: "midi", "[MIDI] Virtual output not created: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let observer = out_port_names.is_empty() && virtual_out.is_none();
+    state().observer.store(observer, Ordering::SeqCst);
+    if observer {
+        general::check::report(general::check::MIDI_OUT, general::check::ComponentState::Off, "observer mode");
+    }
+    let out_port_name = if observer {
+        "(observer mode, no output)".to_string()
+    } else {
+        out_port_names.iter().map(|(name, _)| name.as_str()).chain(virtual_out.as_ref().map(|t| t.name.as_str())).collect::<Vec<_>>().join(" + ")
+    };
     // Use default initial transpose 0 so forwarding starts immediately.
     // The spawned stdin handler thread still accepts numbers to change transpose later.
     let initial_transpose: i32 = 0;
     // Initialize OSC-related atomics from configuration
-    OSC_SENDING_ENABLED.store(config.osc.sending_enabled, Ordering::SeqCst);
-    OSC_SEND_ORIGINAL.store(config.osc.send_original, Ordering::SeqCst);
+    state().osc_sending.store(config.osc.sending_enabled, Ordering::SeqCst);
+    set_osc_send_original(config.osc.send_original);
+    if let Some(enabled) = config.osc.original_enabled {
+        state().osc_original.store(enabled, Ordering::SeqCst);
+    }
+    if let Some(enabled) = config.osc.transposed_enabled {
+        state().osc_transposed.store(enabled, Ordering::SeqCst);
+    }
+    state().osc_sending_auto.store(config.osc.sending_auto, Ordering::SeqCst);
+    state().aftertouch_to_cc.store(config.midi.aftertouch_to_cc, Ordering::SeqCst);
+    osc_sender::set_encoding(config.osc.encoding);
+    osc_sender::set_note_arg_type(config.osc.note_arg_type);
+    osc_sender::set_note_velocity(config.osc.note_velocity);
+    remote::chatbox::set_status_enabled(config.chatbox_status.enabled);
+    general::monitor::set_enabled(general::cli::options().monitor);
+    transpose::set_pitch_bend_mode(config.midi.pitch_bend_mode);
+    general::lfo::init(&config.lfo);
+    general::glide::init(&config.transpose);
+    general::zones::init(&config.midi.zones);
 
     if is_debug_enabled() {
-        println!("Using initial transpose: {} semitones", initial_transpose);
-        println!("OSC sending: {} (to {}:{})", 
-            if OSC_SENDING_ENABLED.load(Ordering::SeqCst) { "enabled" } else { "disabled" },
-            config.osc.sending_addr, config.osc.sending_port);
-        println!("OSC sending mode: {}", if OSC_SEND_ORIGINAL.load(Ordering::SeqCst) { "original" } else { "transposed" });
+        log::debug!(target: "app", "Using initial transpose: {} semitones", initial_transpose);
+        log::debug!(target: "app", "OSC sending: {} (to {})", 
+            if state().osc_sending.load(Ordering::SeqCst) { "enabled" } else { "disabled" },
+            osc_sender::describe_targets().join(", "));
+        log::debug!(
+            target: "app",
+            "OSC streams: original {}, transposed {}",
+            if state().osc_original.load(Ordering::SeqCst) { "on" } else { "off" },
+            if state().osc_transposed.load(Ordering::SeqCst) { "on" } else { "off" }
+        );
     }
 
     // Initialize global atomics used by helper threads
-    TRANSPOSE_SEMITONES.store(initial_transpose, Ordering::SeqCst);
-    EXIT_FLAG.store(false, Ordering::SeqCst);
+    state().transpose.store(initial_transpose, Ordering::SeqCst);
+    general::stats::start(initial_transpose);
+    general::history::start();
+    state().exit.store(false, Ordering::SeqCst);
+    // Restore the last transpose of this instance (state file is per instance)
+    if let Some(state) = general::instance::load_state() {
+        set_transpose_semitones(state.transpose);
+    }
+    if let Some(transpose) = general::cli::options().transpose {
+        set_transpose_semitones(transpose);
+    }
 
-    // Connect the output; we'll move this connection into the forwarding thread
-    let conn_out = midi_out.connect(out_port, "midir-forward-output")?;
+    // Connect the outputs; we'll move these connections into the forwarding thread
+    let mut conns_out = out_port_names
+        .iter()
+        .map(|(name, channels)| output::connect(name, channels.clone()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let virtual_names: Vec<String> = virtual_out.iter().map(|target| target.name.clone()).collect();
+    conns_out.extend(virtual_out);
+    // Hot-plug: reopen outputs that come back after an unplug
+    let output_watch_handle = (config.midi.reconnect && !out_port_names.is_empty()).then(|| output::spawn_output_watcher(out_port_names, virtual_names));
+    if !conns_out.is_empty() {
+        general::check::report(general::check::MIDI_OUT, general::check::ComponentState::Up, out_port_name.clone());
+    }
 
-    // Connect the input: print incoming messages (so you can see them) and send raw messages to the channel
-    let _conn_in = midi_in.connect(
-        in_port,
-        "midir-read-input",
-        move |_stamp, message, _| {
+    // Input handler: send raw messages to the forwarder, OSC and MQTT
+    let on_midi = move |source: u8, message: &[u8]| {
+            general::monitor::log_in(source, message);
             // Forward raw bytes so sustain/pitchwheel/etc. are preserved
-            let _ = tx.send(message.to_vec());
+            tx.send((source, message.to_vec(), std::time::Instant::now()));
+
+            // Trigger notes -> MQTT binary sensors
+            mqtt_listener::publish_trigger_note(message);
+            mqtt_listener::publish_note_event(message);
+            remote::event_api::publish_midi(message);
+            io::tui::record_midi(message);
+            general::stats::record_midi(message);
+            general::program::record(message);
             
-            // Send original MIDI to OSC if enabled and configured for original
-            if OSC_SENDING_ENABLED.load(Ordering::SeqCst) && OSC_SEND_ORIGINAL.load(Ordering::SeqCst) {
-                let _ = osc_original_tx.send(message.to_vec());
+            // Full-fidelity copy for downstream tools, independent of the avatar encoding
+            if let Some(raw_tx) = &osc_raw_tx {
+                raw_tx.send(message.to_vec());
             }
-        },
-        (),
-    )?;
 
-    if is_debug_enabled() {
-        println!(
-            "Connection open, forwarding from '{}' -> '{}' (type number+Enter to change transpose, empty line or 'exit' to quit)...",
-            in_port_name,
-            out_port_name
-        );
+            // Send original MIDI to OSC if sending and the original stream are enabled
+            if state().osc_sending.load(Ordering::SeqCst) && state().osc_original.load(Ordering::SeqCst) {
+                osc_sender::send_to_targets(&osc_original_tx, message);
+            }
+    };
+    // Connect the inputs now; one watcher per input holds the connection, keeps polling for a
+    // missing device and reconnects after an unplug (`midi.reconnect`)
+    let mut midi_watch_handles = Vec::new();
+    for (source, name) in in_port_names.iter().enumerate() {
+        let on_message = {
+            let on_midi = on_midi.clone();
+            let source = source as u8;
+            move |message: &[u8]| on_midi(source, message)
+        };
+        let connection = match name {
+            Some(name) => Some((name.clone(), input::connect(name, on_message.clone())?)),
+            None => None,
+        };
+        let pattern = config.midi.input_port_name_substrs.get(source).cloned();
+        midi_watch_handles.push(input::spawn_input_watcher(pattern, connection, on_message));
+    }
+    // Only the watchers may hold the forwarder sender, so the forwarder ends once they stop
+    drop(on_midi);
+    input::report_inputs();
+
+    log::debug!(
+        target: "midi",
+        "Connection open, forwarding from '{}' -> '{}' (type number+Enter to change transpose, empty line or 'exit' to quit)...",
+        in_port_name,
+        out_port_name
+    );
+
+    // Resolve the OSC send target before stdin is handed to the console thread,
+    // since mDNS discovery may prompt for a selection
+    if config.osc.discover_on_startup {
+        if let Some(target) = discovery::choose_target_interactive(Duration::from_millis(config.osc.discovery_timeout_ms)) {
+            log::info!(target: "osc", "OSC send target: {} ({})", target.name, target.target_addr());
+            osc_sender::set_target(target.target_addr());
+        }
     }
 
-    // Spawn forwarder thread (owns the output connection and applies transpose)
-    let forward_handle = forwarder::spawn_forwarder(conn_out, rx, Some(osc_transposed_tx));
+    // Spawn forwarder thread (owns the output connections and applies transpose)
+    let forward_handle = forwarder::spawn_forwarder(conns_out, rx, osc_transposed_tx);
 
-    // Spawn stdin handler (updates TRANSPOSE_SEMITONES and EXIT_FLAG)
+    // Spawn stdin handler (updates the transpose and the exit flag)
     let stdin_handle = stdin_handler::spawn_stdin_handler();
 
-    // Spawn OSC listener on UDP port 9069 (updates TRANSPOSE_SEMITONES on /transpose)
+    // Spawn OSC listener on UDP port 9069 (updates the transpose on /transpose)
     let osc_handle = osc_listener::spawn_osc_listener();
+    let oscquery_handle = if config.osc.oscquery_enabled {
+        match remote::oscquery::spawn_server() {
+            Ok(handle) => Some(handle),
+            Err(e) => {
+                log::warn!(target: "osc", "[OSCQUERY] Server not started: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
 
     // Initialize MQTT enabled flag from config
-    MQTT_ENABLED.store(config.mqtt.enabled, Ordering::SeqCst);
+    state().mqtt_enabled.store(config.mqtt.enabled, Ordering::SeqCst);
+    if !config.mqtt.enabled {
+        general::check::report(general::check::MQTT, general::check::ComponentState::Off, "disabled in config");
+    }
 
     // Spawn MQTT listener only if enabled
-    let mqtt_handle = if MQTT_ENABLED.load(Ordering::SeqCst) {
+    let mqtt_handle = if state().mqtt_enabled.load(Ordering::SeqCst) {
         Some(mqtt_listener::spawn_mqtt_listener())
     } else {
         None
     };
 
-    // Spawn OSC sender threads for both original and transposed MIDI
-    let osc_target_addr = format!("{}:{}", config.osc.sending_addr, config.osc.sending_port);
-    let osc_original_handle = osc_sender::spawn_osc_sender(
-        osc_target_addr.clone(),
-        osc_original_rx,
-        &OSC_SENDING_ENABLED,
-    );
-    let osc_transposed_handle = osc_sender::spawn_osc_sender(
-        osc_target_addr,
-        osc_transposed_rx,
-        &OSC_SENDING_ENABLED,
-    );
+    // Capability document for companion apps over HTTP (optional)
+    let capabilities_handle = config.capabilities_http.as_deref().and_then(|addr| match remote::capabilities::spawn_http_server(addr) {
+        Ok(handle) => {
+            log::info!(target: "app", "Capability document at http://{}/capabilities", addr);
+            Some(handle)
+        }
+        Err(e) => {
+            log::error!(target: "app", "[HTTP] Failed to bind {}: {}", addr, e);
+            None
+        }
+    });
+
+    // Spawn OSC sender threads for both original and transposed MIDI, per send target
+    let mut osc_sender_handles = Vec::new();
+    for (target, rx) in osc_original_rx.into_iter().enumerate() {
+        osc_sender_handles.push(osc_sender::spawn_osc_sender(general::check::OSC_SENDER_ORIGINAL, target, rx, &state().osc_original));
+    }
+    for (target, rx) in osc_transposed_rx.into_iter().enumerate() {
+        osc_sender_handles.push(osc_sender::spawn_osc_sender(general::check::OSC_SENDER_TRANSPOSED, target, rx, &state().osc_transposed));
+    }
+
+    let osc_raw_handle = osc_raw_rx.map(osc_sender::spawn_raw_mirror);
+
+    // Start the avatar from a clean state instead of values left over from the last session
+    if config.osc.init_parameters_on_startup && state().osc_sending.load(Ordering::SeqCst) {
+        osc_sender::initialize_parameters();
+    }
+
+    // Keep the tablet layout in sync with the config
+    if let Some(file) = &config.osc.layout_file {
+        match remote::layout::export(std::path::Path::new(file)) {
+            Ok(count) => log::info!(target: "osc", "Controller layout with {} controls written to {}", count, file),
+            Err(e) => log::warn!(target: "app", "Controller layout not written: {}", e),
+        }
+    }
+
+    // Watch for the VRChat process (used by OSC auto mode)
+    let vrchat_handle = vrchat::spawn_process_watcher();
+    let vrchat_check_handle = vrchat::spawn_connectivity_checker();
+
+    // Practice mode timer (idle until `practice on`)
+    let practice_handle = general::practice::spawn_practice_timer();
+    // Automation lane player (idle until `automation start`)
+    let automation_handle = general::automation::spawn_automation_player();
+    let sequence_handle = general::sequence::spawn_sequence_player();
+    let glide_handle = general::glide::spawn_glide_thread();
+    // Scheduled actions (`at 22:30 ...`, `in 5m ...`)
+    let scheduler_handle = general::scheduler::spawn_scheduler();
+    // Auto-repeat of held transpose buttons
+    let repeat_handle = general::repeat::spawn_repeater();
+    // Transpose status in the chatbox (idle until enabled)
+    let chatbox_status_handle = remote::chatbox::spawn_status_poster();
 
     // After all services are up, print final status once (ensures other debug logs appear before)
     crate::general::check::print_final_status_after_startup();
+    io::service::notify("READY=1");
 
     // Wait for exit signal coming from stdin handler
-    while !EXIT_FLAG.load(Ordering::SeqCst) {
+    while !state().exit.load(Ordering::SeqCst) {
         thread::sleep(Duration::from_millis(100));
     }
 
     // Proactively disable OSC sending and MQTT to let background threads idle quickly
-    OSC_SENDING_ENABLED.store(false, Ordering::SeqCst);
-    MQTT_ENABLED.store(false, Ordering::SeqCst);
-    println!("Closing connections and exiting...");
-    // Dropping _conn_in will stop the input callback which will eventually close the sender and end the forward thread
-    drop(_conn_in);
+    state().osc_sending.store(false, Ordering::SeqCst);
+    state().mqtt_enabled.store(false, Ordering::SeqCst);
+    // Back to the plain console for the shutdown messages and session summary
+    io::tui::stop();
+    io::service::notify("STOPPING=1");
+    log::info!(target: "app", "Closing connections and exiting...");
+    general::instance::save_state();
+    general::stats::finish_session();
+    if let Some(h) = output_watch_handle {
+        let _ = h.join();
+    }
+    // The input watchers drop their connections on exit, which closes the sender and ends the forward thread
+    for h in midi_watch_handles {
+        let _ = h.join();
+    }
     // Join helper threads
-    if is_debug_enabled() { println!("[SHUTDOWN] Joining stdin handler..."); }
+    log::debug!(target: "app", "[SHUTDOWN] Joining stdin handler...");
     let _ = stdin_handle.join();
-    if is_debug_enabled() { println!("[SHUTDOWN] stdin handler joined"); }
+    log::debug!(target: "app", "[SHUTDOWN] stdin handler joined");
 
-    if is_debug_enabled() { println!("[SHUTDOWN] Joining forwarder..."); }
+    log::debug!(target: "app", "[SHUTDOWN] Joining forwarder...");
     let _ = forward_handle.join();
-    if is_debug_enabled() { println!("[SHUTDOWN] forwarder joined"); }
+    log::debug!(target: "app", "[SHUTDOWN] forwarder joined");
 
-    if is_debug_enabled() { println!("[SHUTDOWN] Joining OSC listener..."); }
+    log::debug!(target: "app", "[SHUTDOWN] Joining OSC listener...");
     let _ = osc_handle.join();
-    if is_debug_enabled() { println!("[SHUTDOWN] OSC listener joined"); }
+    log::debug!(target: "app", "[SHUTDOWN] OSC listener joined");
 
-    if is_debug_enabled() { println!("[SHUTDOWN] Joining OSC sender (original)..."); }
-    let _ = osc_original_handle.join();
-    if is_debug_enabled() { println!("[SHUTDOWN] OSC sender (original) joined"); }
+    log::debug!(target: "app", "[SHUTDOWN] Joining OSC senders...");
+    for h in osc_sender_handles {
+        let _ = h.join();
+    }
+    log::debug!(target: "app", "[SHUTDOWN] OSC senders joined");
+    if let Some(h) = osc_raw_handle {
+        let _ = h.join();
+    }
+
+    let _ = vrchat_handle.join();
+    let _ = vrchat_check_handle.join();
+    let _ = practice_handle.join();
+    // Latency metrics for Prometheus over HTTP (optional)
+    let metrics_handle = config.metrics_http.as_deref().and_then(|addr| match remote::metrics::spawn_http_server(addr) {
+        Ok(handle) => {
+            log::info!(target: "app", "Metrics at http://{}/metrics", addr);
+            Some(handle)
+        }
+        Err(e) => {
+            log::error!(target: "app", "[HTTP] Failed to bind {}: {}", addr, e);
+            None
+        }
+    });
 
-    if is_debug_enabled() { println!("[SHUTDOWN] Joining OSC sender (transposed)..."); }
-    let _ = osc_transposed_handle.join();
-    if is_debug_enabled() { println!("[SHUTDOWN] OSC sender (transposed) joined"); }
+    // Live control dashboard in the browser (optional)
+    let web_handle = config.web_ui.as_deref().and_then(|addr| match remote::web::spawn_web_server(addr) {
+        Ok(handle) => {
+            log::info!(target: "app", "Web dashboard at http://{}/", addr);
+            Some(handle)
+        }
+        Err(e) => {
+            log::error!(target: "app", "[WEB] Failed to bind {}: {}", addr, e);
+            None
+        }
+    });
+
+    let _ = automation_handle.join();
+    let _ = sequence_handle.join();
+    let _ = glide_handle.join();
+    let _ = scheduler_handle.join();
+    let _ = repeat_handle.join();
+    let _ = chatbox_status_handle.join();
+    if let Some(h) = capabilities_handle {
+        let _ = h.join();
+    }
+    if let Some(h) = metrics_handle {
+        let _ = h.join();
+    }
+    if let Some(h) = web_handle {
+        let _ = h.join();
+    }
+    if let Some(h) = tray_handle {
+        let _ = h.join();
+    }
+    if let Some(h) = oscquery_handle {
+        let _ = h.join();
+    }
 
     if let Some(h) = mqtt_handle {
-        if is_debug_enabled() { println!("[SHUTDOWN] Joining MQTT listener..."); }
+        log::debug!(target: "app", "[SHUTDOWN] Joining MQTT listener...");
         let _ = h.join();
-        if is_debug_enabled() { println!("[SHUTDOWN] MQTT listener joined"); }
+        log::debug!(target: "app", "[SHUTDOWN] MQTT listener joined");
     }
 
     Ok(())
@@ -0,0 +1,114 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+
+/// Version of the config file layout; bumped on incompatible changes (renamed or removed fields)
+pub const CONFIG_SCHEMA_VERSION: u32 = 1;
+/// Version of the capability document itself
+const DOCUMENT_VERSION: u32 = 1;
+
+/// Machine-readable description of this instance for companion apps: commands, OSC paths,
+/// MQTT topics and custom controls, with the program and config schema versions (JSON)
+pub fn document() -> String {
+    let config = crate::get_config();
+    let osc = &config.osc;
+    let controls: Vec<serde_json::Value> = crate::custom_controls::controls()
+        .iter()
+        .map(|c| {
+            serde_json::json!({
+                "name": c.name,
+                "slug": c.slug(),
+                "address": c.address,
+                "kind": format!("{:?}", c.kind).to_lowercase(),
+                "min": c.min,
+                "max": c.max,
+                "args": c.args,
+            })
+        })
+        .collect();
+    let document = serde_json::json!({
+        "document_version": DOCUMENT_VERSION,
+        "name": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+        "config_schema_version": CONFIG_SCHEMA_VERSION,
+        "instance": config.instance_name,
+        "transpose": { "min": config.transpose.min, "max": config.transpose.max },
+        "commands": crate::general::commands::SYNTAX,
+        "osc": {
+            "listening_port": osc.listening_port,
+            "paths": {
+                "transpose": osc.transpose_path,
+                "transpose_clamped_reply": format!("{}/clamped", osc.transpose_path),
+                "transpose_up": osc.transpose_up_path,
+                "transpose_down": osc.transpose_down_path,
+                "transpose_normalized": osc.transpose_norm_path,
+                "octave_up": osc.octave_up_path,
+                "octave_down": osc.octave_down_path,
+                "command": osc.command_path,
+                "setlist_next": osc.setlist_next_path,
+                "setlist_prev": osc.setlist_prev_path,
+                "sequence_start": osc.sequence_start_path,
+                "sequence_stop": osc.sequence_stop_path,
+                "custom_control_prefix": osc.custom_control_path,
+            },
+            "encoding": crate::osc_sender::encoding().name(),
+            "targets": crate::osc_sender::targets()
+                .iter()
+                .map(|t| serde_json::json!({ "name": t.name, "slug": t.slug, "address": t.addr }))
+                .collect::<Vec<_>>(),
+        },
+        "mqtt": {
+            "enabled": config.mqtt.enabled,
+            "base_topic": crate::general::instance::with_suffix(&config.mqtt.base_topic),
+            "topics": crate::mqtt_listener::topic_map(),
+        },
+        "custom_controls": controls,
+        "profiles": config.profiles.keys().collect::<std::collections::BTreeSet<_>>(),
+        "setlist": config.setlist.iter().map(|s| &s.name).collect::<Vec<_>>(),
+    });
+    serde_json::to_string_pretty(&document).unwrap_or_default()
+}
+
+fn respond(mut stream: TcpStream) {
+    stream.set_read_timeout(Some(Duration::from_millis(500))).ok();
+    let mut request = [0u8; 1024];
+    let size = stream.read(&mut request).unwrap_or(0);
+    let request = String::from_utf8_lossy(&request[..size]);
+    let path = request.split_whitespace().nth(1).unwrap_or("");
+    let (status, body) = match (request.starts_with("GET "), path) {
+        (true, "/" | "/capabilities" | "/capabilities.json") => ("200 OK", document()),
+        (true, _) => ("404 Not Found", "{\"error\":\"not found\"}".to_string()),
+        (false, _) => ("405 Method Not Allowed", "{\"error\":\"GET only\"}".to_string()),
+    };
+    let response = format!(
+        "HTTP/1.0 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes());
+}
+
+/// Serve the capability document at `GET /capabilities` (and `/`) on `addr` until exit
+pub fn spawn_http_server(addr: &str) -> std::io::Result<thread::JoinHandle<()>> {
+    let listener = TcpListener::bind(addr)?;
+    // Non-blocking accept so the thread notices the exit flag
+    listener.set_nonblocking(true)?;
+    Ok(thread::spawn(move || {
+        while !crate::state().exit.load(Ordering::SeqCst) {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let _ = stream.set_nonblocking(false);
+                    respond(stream);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(100)),
+                Err(e) => {
+                    log::error!(target: "app", "[HTTP] Accept failed: {}", e);
+                    thread::sleep(Duration::from_millis(100));
+                }
+            }
+        }
+    }))
+}
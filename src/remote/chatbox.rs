@@ -0,0 +1,96 @@
+use rosc::OscType;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// VRChat chatbox input address: args (text, send immediately, play notification sound)
+const CHATBOX_INPUT_PATH: &str = "/chatbox/input";
+// VRChat truncates chatbox messages beyond this many characters
+const CHATBOX_MAX_CHARS: usize = 144;
+// VRChat drops chatbox messages sent faster than this; a burst of changes posts its final state
+const STATUS_MIN_GAP_MS: u64 = 1500;
+// How often the status thread checks for changes
+const STATUS_POLL_MS: u64 = 100;
+
+/// `chatbox_status` config section: show the transpose state in the VRChat chatbox
+#[derive(Debug, serde::Deserialize, Clone)]
+#[serde(default)]
+pub struct ChatboxStatusConfig {
+    /// Post the status at startup (switch at runtime with `chatbox status on|off`)
+    pub enabled: bool,
+    /// Repost the unchanged status this often, as chatbox messages time out (`0` = only on change)
+    pub interval_secs: u64,
+    /// Chatbox text; `{transpose}`, `{octave}`, `{total}` and `{key}` are replaced
+    pub format: String,
+}
+
+impl Default for ChatboxStatusConfig {
+    fn default() -> Self {
+        ChatboxStatusConfig { enabled: false, interval_secs: 0, format: "Transpose {transpose} | Octave {octave} | Key {key}".to_string() }
+    }
+}
+
+// Status posting, switchable at runtime from the console and MQTT
+static STATUS_ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn set_status_enabled(enabled: bool) {
+    STATUS_ENABLED.store(enabled, Ordering::SeqCst);
+}
+
+pub fn status_enabled() -> bool {
+    STATUS_ENABLED.load(Ordering::SeqCst)
+}
+
+fn signed(value: i32) -> String {
+    if value > 0 { format!("+{}", value) } else { value.to_string() }
+}
+
+/// Chatbox text of the current transpose state from `chatbox_status.format`
+pub fn status_text() -> String {
+    let transpose = crate::state().transpose.load(Ordering::SeqCst);
+    let octave = crate::state().octave.load(Ordering::SeqCst);
+    let total = transpose + 12 * octave;
+    crate::get_config()
+        .chatbox_status
+        .format
+        .replace("{transpose}", &signed(transpose))
+        .replace("{octave}", &signed(octave))
+        .replace("{total}", &signed(total))
+        .replace("{key}", &crate::general::notes::key_name(total))
+}
+
+/// Post a message to the VRChat chatbox via the configured OSC target
+pub fn send_chatbox(text: &str) {
+    let text: String = text.chars().take(CHATBOX_MAX_CHARS).collect();
+    let target = crate::osc_sender::current_target();
+    let args = vec![OscType::String(text), OscType::Bool(true), OscType::Bool(false)];
+    if let Err(e) = crate::osc_sender::send_single_osc_message(&target, CHATBOX_INPUT_PATH, args) {
+        log::error!(target: "osc", "[OSC] Failed to send chatbox message: {}", e);
+    }
+}
+
+/// Spawn the thread posting the status when it changes (at most every `STATUS_MIN_GAP_MS`) and
+/// every `chatbox_status.interval_secs`, while status posting is enabled
+pub fn spawn_status_poster() -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut last: Option<(String, Instant)> = None;
+        while !crate::state().exit.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(STATUS_POLL_MS));
+            if !status_enabled() {
+                last = None;
+                continue;
+            }
+            let text = status_text();
+            let interval = crate::get_config().chatbox_status.interval_secs;
+            let due = match &last {
+                None => true,
+                Some((_, sent)) if sent.elapsed() < Duration::from_millis(STATUS_MIN_GAP_MS) => false,
+                Some((previous, sent)) => *previous != text || (interval > 0 && sent.elapsed() >= Duration::from_secs(interval)),
+            };
+            if due {
+                send_chatbox(&text);
+                last = Some((text, Instant::now()));
+            }
+        }
+    })
+}
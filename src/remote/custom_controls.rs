@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use crate::general::state::ConfigRef;
+use rosc::OscType;
+
+/// Value type of a custom control, deciding the OSC argument type and the HA entity
+#[derive(Debug, serde::Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ControlKind {
+    #[default]
+    Bool,
+    Int,
+    Float,
+}
+
+/// Home Assistant representation of a multi-argument control
+#[derive(Debug, serde::Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MultiArgEntity {
+    /// One linked number entity per argument
+    #[default]
+    Numbers,
+    /// One text entity holding all arguments as a JSON array
+    Text,
+}
+
+/// A user-defined avatar parameter controllable from MQTT and OSC (`osc.sending_addresses`)
+#[derive(Debug, serde::Deserialize, Clone)]
+pub struct CustomControl {
+    /// Display name (HA entity name); the slug used in topics/paths is derived from it unless `id` is set
+    pub name: String,
+    /// Explicit identifier for topics, OSC paths and the HA unique ID; keeps them stable across renames
+    #[serde(default)]
+    pub id: Option<String>,
+    /// OSC address the value is sent to, e.g. `/avatar/parameters/PadFX`
+    pub address: String,
+    #[serde(default)]
+    pub kind: ControlKind,
+    #[serde(default)]
+    pub min: f32,
+    #[serde(default = "default_control_max")]
+    pub max: f32,
+    /// Value sent by the bulk parameter initialization (startup / `osc init`)
+    #[serde(default)]
+    pub initial: f32,
+    /// MIDI controller (any channel) driving the value, 0..127 scaled to `min..max`; single-argument controls only
+    #[serde(default)]
+    pub cc: Option<u8>,
+    /// Argument names for a multi-argument message (e.g. `["r", "g", "b"]`); all of `kind`
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// How a multi-argument control appears in Home Assistant
+    #[serde(default)]
+    pub ha_entity: MultiArgEntity,
+    // Unique slug assigned by `resolve_slugs` at config load
+    #[serde(skip)]
+    resolved_slug: String,
+}
+
+fn default_control_max() -> f32 { 1.0 }
+
+impl CustomControl {
+    /// Topic/path-safe identifier ("Pad FX" -> "pad_fx"), unique among all controls
+    pub fn slug(&self) -> String {
+        if self.resolved_slug.is_empty() {
+            slugify(self.id.as_deref().unwrap_or(&self.name))
+        } else {
+            self.resolved_slug.clone()
+        }
+    }
+
+    pub fn is_multi_arg(&self) -> bool {
+        !self.args.is_empty()
+    }
+
+    /// Keys of the stored values: the slug, or `<slug>/<arg>` per argument of a multi-argument control
+    pub fn value_keys(&self) -> Vec<String> {
+        let slug = self.slug();
+        if self.args.is_empty() {
+            vec![slug]
+        } else {
+            self.args.iter().map(|arg| format!("{}/{}", slug, arg)).collect()
+        }
+    }
+
+    /// Current values of all arguments, as JSON array text (multi-argument state payload)
+    pub fn format_values(&self) -> String {
+        let values: Vec<serde_json::Value> = self
+            .value_keys()
+            .iter()
+            .map(|key| match self.kind {
+                ControlKind::Float => serde_json::Value::from(value(key)),
+                _ => serde_json::Value::from(value(key) as i64),
+            })
+            .collect();
+        serde_json::Value::from(values).to_string()
+    }
+
+    /// Clamp and round a raw value according to the control kind
+    pub fn normalize(&self, value: f32) -> f32 {
+        match self.kind {
+            ControlKind::Bool => if value >= 0.5 { 1.0 } else { 0.0 },
+            ControlKind::Int => value.round().clamp(self.min, self.max),
+            ControlKind::Float => value.clamp(self.min, self.max),
+        }
+    }
+
+    /// OSC argument for a (normalized) value
+    pub fn osc_arg(&self, value: f32) -> OscType {
+        match self.kind {
+            ControlKind::Bool => OscType::Bool(value >= 0.5),
+            ControlKind::Int => OscType::Int(value as i32),
+            ControlKind::Float => OscType::Float(value),
+        }
+    }
+
+    /// Text payload for MQTT state topics
+    pub fn format_value(&self, value: f32) -> String {
+        match self.kind {
+            ControlKind::Bool => if value >= 0.5 { "1".to_string() } else { "0".to_string() },
+            ControlKind::Int => (value as i32).to_string(),
+            ControlKind::Float => value.to_string(),
+        }
+    }
+}
+
+/// Lowercase ASCII slug with `_` separators
+pub fn slugify(name: &str) -> String {
+    let slug: String = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    slug.trim_matches('_').to_string()
+}
+
+/// Assign every control a unique slug. Explicit `id`s are kept as given; a duplicate `id` is an
+/// error and that control is removed. Colliding names get `_2`, `_3`, ... appended.
+/// Returns the problems found, for logging.
+pub fn resolve_slugs(controls: &mut Vec<CustomControl>) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut taken = std::collections::HashSet::new();
+    // Explicit ids first so a derived slug can never take one of them
+    controls.retain(|control| {
+        let Some(id) = &control.id else { return true };
+        let slug = slugify(id);
+        if slug.is_empty() {
+            problems.push(format!("Custom control '{}': id '{}' has no usable characters; using the name", control.name, id));
+            true
+        } else if !taken.insert(slug.clone()) {
+            problems.push(format!("Custom control '{}': id '{}' is already used by another control; control ignored", control.name, slug));
+            false
+        } else {
+            true
+        }
+    });
+    for control in controls.iter_mut() {
+        // Argument names become topic/path segments
+        let args: Vec<String> = control.args.iter().map(|arg| slugify(arg)).collect();
+        if args.iter().any(String::is_empty) || args.iter().enumerate().any(|(i, a)| args[..i].contains(a)) {
+            problems.push(format!("Custom control '{}': argument names must be distinct and non-empty; using arg1, arg2, ...", control.name));
+            control.args = (1..=args.len()).map(|i| format!("arg{}", i)).collect();
+        } else {
+            control.args = args;
+        }
+        let explicit = control.id.as_deref().map(slugify).filter(|s| !s.is_empty());
+        control.resolved_slug = match explicit {
+            Some(slug) => slug,
+            None => {
+                let base = Some(slugify(&control.name)).filter(|s| !s.is_empty()).unwrap_or_else(|| "control".to_string());
+                let mut slug = base.clone();
+                let mut n = 2;
+                while !taken.insert(slug.clone()) {
+                    slug = format!("{}_{}", base, n);
+                    n += 1;
+                }
+                if slug != base {
+                    problems.push(format!(
+                        "Custom control '{}': slug '{}' collides with another control; using '{}' (set an explicit `id` to keep it stable)",
+                        control.name, base, slug
+                    ));
+                }
+                slug
+            }
+        };
+    }
+    problems
+}
+
+// Current values by slug; the generation counter lets MQTT/OSC echo loops detect changes
+static VALUES: Mutex<Option<HashMap<String, f32>>> = Mutex::new(None);
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// All configured custom controls
+pub fn controls() -> ConfigRef<[CustomControl]> {
+    crate::state().config_ref(|config| config.osc.sending_addresses.as_slice())
+}
+
+/// Find a configured control by slug
+pub fn find(slug: &str) -> Option<CustomControl> {
+    controls().iter().find(|c| c.slug() == slug).cloned()
+}
+
+/// Find the control of a value key (`<slug>` or `<slug>/<arg>`) and the argument index
+fn find_key(key: &str) -> Option<(CustomControl, Option<usize>)> {
+    let (slug, arg) = match key.split_once('/') {
+        Some((slug, arg)) => (slug, Some(arg)),
+        None => (key, None),
+    };
+    let control = find(slug)?;
+    match arg {
+        Some(arg) => {
+            let index = control.args.iter().position(|a| a == arg)?;
+            Some((control, Some(index)))
+        }
+        None => Some((control, None)),
+    }
+}
+
+/// Current value of a control (0 until set)
+pub fn value(slug: &str) -> f32 {
+    VALUES
+        .lock()
+        .ok()
+        .and_then(|guard| guard.as_ref().and_then(|m| m.get(slug).copied()))
+        .unwrap_or(0.0)
+}
+
+/// Change counter, bumped whenever a control value changes
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+/// Remember a value and bump the generation if it changed; returns false if it was already current
+fn store(slug: &str, value: f32) -> bool {
+    let changed = match VALUES.lock() {
+        Ok(mut guard) => guard.get_or_insert_with(HashMap::new).insert(slug.to_string(), value) != Some(value),
+        Err(_) => false,
+    };
+    if changed {
+        GENERATION.fetch_add(1, Ordering::SeqCst);
+    }
+    changed
+}
+
+/// Take over values reported by the OSC target (VRChat sends `/avatar/parameters/...` when the
+/// avatar menu or another app changes a parameter). Nothing is sent back to the target; MQTT and
+/// the OSC controller get the new state through the generation counter.
+pub fn update_from_target(address: &str, raw: &[f32]) -> Option<CustomControl> {
+    let control = controls().iter().find(|c| c.address == address)?.clone();
+    let mut changed = false;
+    for (key, raw) in control.value_keys().iter().zip(raw) {
+        changed |= store(key, control.normalize(*raw));
+    }
+    if changed && crate::is_debug_enabled_for(crate::DebugCategory::Osc) {
+        log::debug!(target: "osc", "[CUSTOM] {} <- {} (from target)", control.name, describe(&control));
+    }
+    Some(control)
+}
+
+/// Current value text for logs: the single value or the JSON array of all arguments
+fn describe(control: &CustomControl) -> String {
+    if control.is_multi_arg() {
+        control.format_values()
+    } else {
+        control.format_value(value(&control.slug()))
+    }
+}
+
+/// Send the control's current value(s) as one message to the OSC target
+fn send(control: &CustomControl) {
+    let args = control.value_keys().iter().map(|key| control.osc_arg(value(key))).collect();
+    let target = crate::osc_sender::current_target();
+    if let Err(e) = crate::osc_sender::send_single_osc_message(&target, &control.address, args) {
+        log::error!(target: "osc", "[OSC] Failed to send custom control '{}': {}", control.name, e);
+    }
+    log::debug!(target: "osc", "[CUSTOM] {} -> {}", control.name, describe(control));
+}
+
+/// Set a control value by key (`<slug>`, or `<slug>/<arg>` for one argument of a multi-argument
+/// control; a bare multi-argument slug sets every argument): normalizes it, sends the control to
+/// the OSC target and marks it changed so MQTT and the OSC controller get the new state echoed.
+/// Returns the applied value.
+pub fn set_value(key: &str, raw: f32) -> Option<f32> {
+    let (control, arg) = find_key(key)?;
+    let value = control.normalize(raw);
+    match arg {
+        Some(index) => {
+            store(&control.value_keys()[index], value);
+        }
+        None => {
+            for key in control.value_keys() {
+                store(&key, value);
+            }
+        }
+    }
+    send(&control);
+    Some(value)
+}
+
+/// Set all arguments of a multi-argument control at once (missing trailing values keep their
+/// current value) and send it. Returns false for an unknown slug.
+pub fn set_values(slug: &str, raw: &[f32]) -> bool {
+    let Some(control) = find(slug) else { return false };
+    for (key, raw) in control.value_keys().iter().zip(raw) {
+        store(key, control.normalize(*raw));
+    }
+    send(&control);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slugs() {
+        assert_eq!(slugify("Ear Wiggle"), "ear_wiggle");
+        assert_eq!(slugify("  FX-Pad #2! "), "fx_pad__2");
+        assert_eq!(slugify("Äpfel"), "pfel");
+        assert_eq!(slugify("***"), "");
+    }
+}
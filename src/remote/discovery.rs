@@ -0,0 +1,93 @@
+use std::error::Error;
+use std::io::{stdout, Write};
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use mdns_sd::{ServiceDaemon, ServiceEvent};
+
+// OSC apps (VRChat, VRCOSC, TouchOSC) advertise their UDP input under this service type
+const OSC_SERVICE_TYPE: &str = "_osc._udp.local.";
+
+/// An OSC service found via mDNS/Bonjour
+#[derive(Clone, Debug)]
+pub struct DiscoveredTarget {
+    pub name: String,
+    pub addr: IpAddr,
+    pub port: u16,
+}
+
+impl DiscoveredTarget {
+    /// Target string usable by `OscSender` (IPv6 addresses are bracketed)
+    pub fn target_addr(&self) -> String {
+        crate::osc_sender::join_host_port(&self.addr.to_string(), self.port)
+    }
+}
+
+/// Browse the local network for OSC services for `timeout` and return all resolved instances.
+/// When a service reports several addresses, IPv4 is preferred.
+pub fn discover_osc_targets(timeout: Duration) -> Result<Vec<DiscoveredTarget>, Box<dyn Error>> {
+    let daemon = ServiceDaemon::new()?;
+    let receiver = daemon.browse(OSC_SERVICE_TYPE)?;
+    let deadline = Instant::now() + timeout;
+    let mut targets: Vec<DiscoveredTarget> = Vec::new();
+
+    while let Ok(event) = receiver.recv_deadline(deadline) {
+        if let ServiceEvent::ServiceResolved(info) = event {
+            let addrs = info.get_addresses();
+            let addr = match addrs.iter().find(|a| a.is_ipv4()).or_else(|| addrs.iter().next()) {
+                Some(a) => *a,
+                None => continue,
+            };
+            // Strip the service type suffix for display ("VRChat-Client-XYZ._osc._udp.local." -> "VRChat-Client-XYZ")
+            let name = info
+                .get_fullname()
+                .trim_end_matches(OSC_SERVICE_TYPE)
+                .trim_end_matches('.')
+                .to_string();
+            if !targets.iter().any(|t| t.name == name && t.port == info.get_port()) {
+                log::debug!(target: "osc", "[DISCOVERY] Found {} at {}:{}", name, addr, info.get_port());
+                targets.push(DiscoveredTarget { name, addr, port: info.get_port() });
+            }
+        }
+    }
+
+    let _ = daemon.stop_browse(OSC_SERVICE_TYPE);
+    let _ = daemon.shutdown();
+    Ok(targets)
+}
+
+/// Discover OSC services and let the user pick one on the console.
+/// Returns `None` when nothing was found or the user kept the current target (empty line).
+pub fn choose_target_interactive(timeout: Duration) -> Option<DiscoveredTarget> {
+    log::info!(target: "osc", "Searching for OSC services ({} ms)...", timeout.as_millis());
+    let targets = match discover_osc_targets(timeout) {
+        Ok(t) => t,
+        Err(err) => {
+            log::error!(target: "osc", "[DISCOVERY] mDNS browse failed: {}", err);
+            return None;
+        }
+    };
+    if targets.is_empty() {
+        log::info!(target: "osc", "No OSC services found");
+        return None;
+    }
+
+    log::info!(target: "osc", "Discovered OSC services:");
+    for (i, t) in targets.iter().enumerate() {
+        log::info!(target: "osc", "{}: {} ({})", i, t.name, t.target_addr());
+    }
+    crate::io::console::print_text("Select OSC send target (Enter to keep current): ".to_string());
+    let _ = stdout().flush();
+
+    let choice = crate::io::console::read_line(None).ok()?;
+    let choice = choice.trim();
+    if choice.is_empty() {
+        return None;
+    }
+    match choice.parse::<usize>() {
+        Ok(idx) if idx < targets.len() => Some(targets[idx].clone()),
+        _ => {
+            log::warn!(target: "osc", "Invalid selection '{}', keeping current target", choice);
+            None
+        }
+    }
+}
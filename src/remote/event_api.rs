@@ -0,0 +1,193 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::Mutex;
+use std::time::Duration;
+use crate::general::commands::{self, Command, Outcome, Source};
+use crate::remote::websocket::{Received, WebSocket};
+
+/// How long an API connection waits for client frames between event checks
+pub const TICK: Duration = Duration::from_millis(10);
+// Events held for a slow client before new ones are dropped
+const EVENT_QUEUE: usize = 256;
+
+static SUBSCRIBERS: Mutex<Vec<SyncSender<String>>> = Mutex::new(Vec::new());
+static SUBSCRIBER_COUNT: AtomicUsize = AtomicUsize::new(0);
+// Transposed note of every note-on event per channel, so its note-off reports the same pitch
+static TRANSPOSED: Mutex<[[Option<u8>; 128]; 16]> = Mutex::new([[None; 128]; 16]);
+
+/// Flag name, current value and the command that sets it
+type Flag = (&'static str, fn() -> bool, fn(bool) -> Command);
+
+/// Flags clients can set (`{"glide": true}`) or flip (`{"toggle": "glide"}`)
+const FLAGS: &[Flag] = &[
+    ("osc_sending", || crate::state().osc_sending.load(Ordering::SeqCst), Command::OscSending),
+    ("osc_auto", || crate::state().osc_sending_auto.load(Ordering::SeqCst), Command::OscAuto),
+    ("aftertouch_cc", || crate::state().aftertouch_to_cc.load(Ordering::SeqCst), Command::AftertouchToCc),
+    ("chatbox_status", crate::remote::chatbox::status_enabled, Command::ChatboxStatus),
+    ("glide", crate::general::glide::is_enabled, Command::Glide),
+    ("practice", crate::general::practice::is_active, Command::Practice),
+    ("lfo", crate::general::lfo::is_enabled, Command::Lfo),
+    ("monitor", crate::general::monitor::enabled, Command::Monitor),
+];
+
+/// Transpose, octave and every flag
+fn control_state() -> serde_json::Value {
+    let config = crate::get_config();
+    let mut state = serde_json::json!({
+        "type": "state",
+        "transpose": crate::state().transpose.load(Ordering::SeqCst),
+        "min": config.transpose.min,
+        "max": config.transpose.max,
+        "octave": crate::state().octave.load(Ordering::SeqCst),
+        "osc_target": crate::osc_sender::current_target(),
+        "mqtt_enabled": crate::state().mqtt_enabled.load(Ordering::SeqCst),
+    });
+    for (name, value, _) in FLAGS {
+        state[*name] = value().into();
+    }
+    state
+}
+
+fn broadcast(event: serde_json::Value) {
+    let text = event.to_string();
+    if let Ok(mut subscribers) = SUBSCRIBERS.lock() {
+        subscribers.retain(|tx| !matches!(tx.try_send(text.clone()), Err(TrySendError::Disconnected(_))));
+        SUBSCRIBER_COUNT.store(subscribers.len(), Ordering::Relaxed);
+    }
+}
+
+/// Stream note on/off of an incoming MIDI message to the API clients.
+/// Called from the MIDI input callback; never blocks (events for a slow client are dropped).
+pub fn publish_midi(message: &[u8]) {
+    if SUBSCRIBER_COUNT.load(Ordering::Relaxed) == 0 || message.len() < 3 {
+        return;
+    }
+    let on = match message[0] & 0xF0 {
+        0x90 => message[2] > 0,
+        0x80 => false,
+        _ => return,
+    };
+    let (channel, note) = ((message[0] & 0x0F) as usize, message[1] & 0x7F);
+    let Ok(mut transposed) = TRANSPOSED.lock() else { return };
+    if on {
+        // Same pitch as the forwarder sends: octave shift, pedals, zones and note range included
+        transposed[channel][note as usize] = crate::forwarder::transposed_note(note, channel as u8);
+    }
+    let output = transposed[channel][note as usize];
+    drop(transposed);
+    broadcast(serde_json::json!({
+        "type": if on { "note_on" } else { "note_off" },
+        "channel": channel + 1,
+        "note": note,
+        "name": crate::general::notes::display_name(note),
+        "velocity": message[2],
+        "transposed": output,
+    }));
+}
+
+/// The command of a client message: `{"command": "<console command>"}`, `{"transpose": n}`,
+/// `{"transpose_step": n}`, `{"octave": n}`, `{"<flag>": true|false}` or `{"toggle": "<flag>"}`
+fn parse_request(request: &serde_json::Value) -> Result<Command, String> {
+    let int = |key: &str| {
+        request.get(key).map(|v| {
+            let n = v.as_i64().ok_or_else(|| format!("'{}' must be an integer", key))?;
+            i32::try_from(n).map_err(|_| format!("'{}' is out of range", key))
+        })
+    };
+    if let Some(text) = request.get("command") {
+        let text = text.as_str().ok_or("'command' must be a string")?;
+        return commands::parse(text).unwrap_or_else(|| Err(format!("Unknown command '{}'", text)));
+    }
+    if let Some(value) = int("transpose") {
+        return value.map(Command::Transpose);
+    }
+    if let Some(delta) = int("transpose_step") {
+        return delta.map(Command::TransposeStep);
+    }
+    if let Some(octave) = int("octave") {
+        return octave.map(Command::Octave);
+    }
+    if let Some(name) = request.get("toggle") {
+        let name = name.as_str().unwrap_or("");
+        let (_, value, command) = FLAGS.iter().find(|(flag, _, _)| *flag == name).ok_or_else(|| format!("Unknown flag '{}'", name))?;
+        return Ok(command(!value()));
+    }
+    for (name, _, command) in FLAGS {
+        if let Some(value) = request.get(*name) {
+            return value.as_bool().map(command).ok_or_else(|| format!("'{}' must be true or false", name));
+        }
+    }
+    Err("Expected command, transpose, transpose_step, octave, toggle or a flag".to_string())
+}
+
+/// `ok` plus the message or error of a command result, with the requested and applied value of a transpose
+pub fn outcome_json(result: Result<Outcome, String>) -> serde_json::Value {
+    match result {
+        Ok(Outcome::Transpose { requested, applied }) => serde_json::json!({
+            "ok": true,
+            "message": format!("Transpose {:+}", applied),
+            "requested": requested,
+            "applied": applied,
+        }),
+        Ok(Outcome::Done(message)) => serde_json::json!({ "ok": true, "message": message }),
+        Ok(Outcome::Silent) => serde_json::json!({ "ok": true }),
+        Err(e) => serde_json::json!({ "ok": false, "error": e }),
+    }
+}
+
+/// Run one client message; the reply echoes its `id`
+fn handle_request(text: &str) -> serde_json::Value {
+    let request: serde_json::Value = serde_json::from_str(text).unwrap_or_else(|_| serde_json::json!({ "command": text }));
+    let result = parse_request(&request).and_then(|command| commands::execute(command, Source::Web));
+    if let Err(e) = &result {
+        log::warn!(target: "app", "[API] {}", e);
+    }
+    let mut reply = serde_json::json!({ "type": "reply" });
+    if let Some(id) = request.get("id") {
+        reply["id"] = id.clone();
+    }
+    if let (Some(reply), serde_json::Value::Object(outcome)) = (reply.as_object_mut(), outcome_json(result)) {
+        reply.extend(outcome);
+    }
+    reply
+}
+
+/// Serve one event API client until it disconnects: `state` and `status` on connect and on
+/// every change, `transpose` on transpose changes, note events from the MIDI input and
+/// `reply` for each request
+pub fn serve(mut socket: WebSocket) {
+    let (tx, rx) = mpsc::sync_channel(EVENT_QUEUE);
+    if let Ok(mut subscribers) = SUBSCRIBERS.lock() {
+        subscribers.push(tx);
+        SUBSCRIBER_COUNT.store(subscribers.len(), Ordering::Relaxed);
+    }
+    let mut last_state = serde_json::Value::Null;
+    let mut last_status = serde_json::Value::Null;
+    while !crate::state().exit.load(Ordering::SeqCst) {
+        let mut events = Vec::new();
+        let state = control_state();
+        if state != last_state {
+            if !last_state.is_null() && (state["transpose"] != last_state["transpose"] || state["octave"] != last_state["octave"]) {
+                events.push(serde_json::json!({ "type": "transpose", "value": state["transpose"], "octave": state["octave"] }).to_string());
+            }
+            events.push(state.to_string());
+            last_state = state;
+        }
+        let status = crate::general::check::components_json();
+        if status != last_status {
+            events.push(serde_json::json!({ "type": "status", "components": status }).to_string());
+            last_status = status;
+        }
+        events.extend(rx.try_iter());
+        for received in socket.poll() {
+            match received {
+                Received::Text(text) => events.push(handle_request(&text).to_string()),
+                Received::Closed => return,
+            }
+        }
+        if events.iter().any(|event| socket.send_text(event).is_err()) {
+            return;
+        }
+    }
+    socket.close();
+}
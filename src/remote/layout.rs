@@ -0,0 +1,261 @@
+use std::path::Path;
+
+// Grid of the generated layouts in pixels: full-width faders, buttons and toggles four per row
+const LAYOUT_WIDTH: u32 = 800;
+const ROW_HEIGHT: u32 = 90;
+const COLUMNS: u32 = 4;
+const MARGIN: u32 = 10;
+const OPEN_STAGE_CONTROL_VERSION: &str = "1.26.2";
+
+/// One control of a generated layout; all of them talk to the OSC listener
+enum Widget {
+    /// Sends the value scaled to `min..max` to `address`
+    Fader { label: String, address: String, min: f32, max: f32 },
+    /// Sends 1 on press and 0 on release (transpose up/down, setlist)
+    Button { label: String, address: String },
+    /// Sends 1 / 0 to `address` (bool custom controls)
+    Toggle { label: String, address: String },
+    /// Sends the console command `on` / `off` as a string to `osc.command_path`
+    CommandToggle { label: String, on: String, off: String },
+}
+
+impl Widget {
+    fn label(&self) -> &str {
+        match self {
+            Widget::Fader { label, .. } | Widget::Button { label, .. } | Widget::Toggle { label, .. } | Widget::CommandToggle { label, .. } => label,
+        }
+    }
+}
+
+/// Controls for the current config: transpose fader and buttons, setlist buttons, the runtime
+/// switches (via the command path) and one fader or toggle per custom control value
+fn widgets() -> Vec<Widget> {
+    let config = crate::get_config();
+    let osc = &config.osc;
+    let mut widgets = vec![
+        Widget::Fader { label: "Transpose".to_string(), address: osc.transpose_path.clone(), min: config.transpose.min as f32, max: config.transpose.max as f32 },
+        Widget::Button { label: "Down".to_string(), address: osc.transpose_down_path.clone() },
+        Widget::Button { label: "Up".to_string(), address: osc.transpose_up_path.clone() },
+        Widget::Button { label: "Octave Down".to_string(), address: osc.octave_down_path.clone() },
+        Widget::Button { label: "Octave Up".to_string(), address: osc.octave_up_path.clone() },
+    ];
+    if !config.setlist.is_empty() {
+        widgets.push(Widget::Button { label: "Prev Song".to_string(), address: osc.setlist_prev_path.clone() });
+        widgets.push(Widget::Button { label: "Next Song".to_string(), address: osc.setlist_next_path.clone() });
+    }
+    let switches = [
+        ("OSC Sending", "osc on", "osc off"),
+        ("Send Original", "osc original", "osc transposed"),
+        ("Original Stream", "osc original on", "osc original off"),
+        ("Transposed Stream", "osc transposed on", "osc transposed off"),
+        ("Note Velocity", "osc velocity on", "osc velocity off"),
+        ("Aftertouch CC", "aftertouch cc on", "aftertouch cc off"),
+        ("Practice", "practice on", "practice off"),
+        ("LFO", "lfo on", "lfo off"),
+    ];
+    widgets.extend(switches.iter().map(|(label, on, off)| Widget::CommandToggle { label: label.to_string(), on: on.to_string(), off: off.to_string() }));
+    for control in &osc.sending_addresses {
+        for key in control.value_keys() {
+            let label = match key.split_once('/') {
+                Some((_, arg)) => format!("{} {}", control.name, arg),
+                None => control.name.clone(),
+            };
+            let address = format!("{}/{}", osc.custom_control_path, key);
+            widgets.push(match control.kind {
+                crate::custom_controls::ControlKind::Bool => Widget::Toggle { label, address },
+                _ => Widget::Fader { label, address, min: control.min, max: control.max },
+            });
+        }
+    }
+    widgets
+}
+
+/// Pixel frames (left, top, width, height): faders take a full row, other widgets fill rows of `COLUMNS`
+fn frames(widgets: &[Widget]) -> Vec<(u32, u32, u32, u32)> {
+    let cell = LAYOUT_WIDTH / COLUMNS;
+    let mut frames = Vec::new();
+    let (mut row, mut column) = (0, 0);
+    for widget in widgets {
+        if matches!(widget, Widget::Fader { .. }) {
+            if column > 0 {
+                row += 1;
+                column = 0;
+            }
+            frames.push((MARGIN, row * ROW_HEIGHT + MARGIN, LAYOUT_WIDTH - 2 * MARGIN, ROW_HEIGHT - 2 * MARGIN));
+            row += 1;
+        } else {
+            frames.push((column * cell + MARGIN, row * ROW_HEIGHT + MARGIN, cell - 2 * MARGIN, ROW_HEIGHT - 2 * MARGIN));
+            column += 1;
+            if column == COLUMNS {
+                row += 1;
+                column = 0;
+            }
+        }
+    }
+    frames
+}
+
+fn layout_height(frames: &[(u32, u32, u32, u32)]) -> u32 {
+    frames.iter().map(|(_, top, _, height)| top + height + MARGIN).max().unwrap_or(ROW_HEIGHT)
+}
+
+/// Open Stage Control session (JSON). Widgets send to the server's default targets (`--send`).
+fn open_stage_control(widgets: &[Widget]) -> String {
+    let command_path = &crate::get_config().osc.command_path;
+    let content: Vec<serde_json::Value> = widgets
+        .iter()
+        .zip(frames(widgets))
+        .enumerate()
+        .map(|(i, (widget, (left, top, width, height)))| {
+            let mut json = serde_json::json!({
+                "id": format!("widget_{}", i + 1),
+                "label": widget.label(),
+                "left": left,
+                "top": top,
+                "width": width,
+                "height": height,
+            });
+            let fields = match widget {
+                Widget::Fader { address, min, max, .. } => {
+                    serde_json::json!({ "type": "fader", "horizontal": true, "address": address, "range": { "min": min, "max": max } })
+                }
+                Widget::Button { address, .. } => serde_json::json!({ "type": "button", "mode": "push", "address": address, "on": 1, "off": 0 }),
+                Widget::Toggle { address, .. } => serde_json::json!({ "type": "button", "mode": "toggle", "address": address, "on": 1, "off": 0 }),
+                Widget::CommandToggle { on, off, .. } => {
+                    serde_json::json!({ "type": "button", "mode": "toggle", "address": command_path, "on": on, "off": off })
+                }
+            };
+            if let (Some(json), serde_json::Value::Object(fields)) = (json.as_object_mut(), fields) {
+                json.extend(fields);
+            }
+            json
+        })
+        .collect();
+    let session = serde_json::json!({
+        "createdWith": "Open Stage Control",
+        "version": OPEN_STAGE_CONTROL_VERSION,
+        "type": "session",
+        "content": { "type": "root", "id": "root", "widgets": content, "tabs": [] },
+    });
+    serde_json::to_string_pretty(&session).unwrap_or_default()
+}
+
+fn cdata(text: &str) -> String {
+    format!("<![CDATA[{}]]>", text.replace("]]>", "]]]]><![CDATA[>"))
+}
+
+fn property(kind: char, key: &str, value: &str) -> String {
+    format!("<property type='{}'><key>{}</key><value>{}</value></property>", kind, cdata(key), value)
+}
+
+fn frame_property((left, top, width, height): (u32, u32, u32, u32)) -> String {
+    property('r', "frame", &format!("<x>{}</x><y>{}</y><w>{}</w><h>{}</h>", left, top, width, height))
+}
+
+/// An OSC message of a TouchOSC control: sent on `condition` (ANY, RISE, FALL) of value `x`
+fn touchosc_message(condition: &str, path: &str, argument: &str) -> String {
+    format!(
+        "<osc><enabled>1</enabled><send>1</send><receive>0</receive><feedback>0</feedback><connections>00001</connections>\
+         <triggers><trigger><var>x</var><condition>{}</condition></trigger></triggers>\
+         <path><partial><type>CONSTANT</type><conversion>STRING</conversion><value>{}</value><scaleMin>0</scaleMin><scaleMax>1</scaleMax></partial></path>\
+         <arguments>{}</arguments></osc>",
+        condition,
+        cdata(path),
+        argument
+    )
+}
+
+fn value_argument(conversion: &str, min: f32, max: f32) -> String {
+    format!("<partial><type>VALUE</type><conversion>{}</conversion><value>x</value><scaleMin>{}</scaleMin><scaleMax>{}</scaleMax></partial>", conversion, min, max)
+}
+
+fn string_argument(text: &str) -> String {
+    format!("<partial><type>CONSTANT</type><conversion>STRING</conversion><value>{}</value><scaleMin>0</scaleMin><scaleMax>1</scaleMax></partial>", cdata(text))
+}
+
+fn touchosc_node(id: usize, node_type: &str, properties: &[String], messages: &str, children: &str) -> String {
+    format!(
+        "<node ID='{:08x}-7c3a-4d1e-9b2f-{:012x}' type='{}'><properties>{}</properties><values></values><messages>{}</messages><children>{}</children></node>",
+        id,
+        id,
+        node_type,
+        properties.concat(),
+        messages,
+        children
+    )
+}
+
+/// TouchOSC layout (`.tosc`: zlib-wrapped XML). Connection 1 has to point at the OSC listener.
+fn touchosc(widgets: &[Widget]) -> Vec<u8> {
+    let command_path = &crate::get_config().osc.command_path;
+    let frames = frames(widgets);
+    let mut children = String::new();
+    for (i, (widget, frame)) in widgets.iter().zip(frames.iter().copied()).enumerate() {
+        let name = property('s', "name", &cdata(widget.label()));
+        let (node_type, button_type, messages) = match widget {
+            Widget::Fader { address, min, max, .. } => ("FADER", None, touchosc_message("ANY", address, &value_argument("FLOAT", *min, *max))),
+            Widget::Button { address, .. } => ("BUTTON", Some(0), touchosc_message("ANY", address, &value_argument("INTEGER", 0.0, 1.0))),
+            Widget::Toggle { address, .. } => ("BUTTON", Some(1), touchosc_message("ANY", address, &value_argument("INTEGER", 0.0, 1.0))),
+            Widget::CommandToggle { on, off, .. } => (
+                "BUTTON",
+                Some(1),
+                touchosc_message("RISE", command_path, &string_argument(on)) + &touchosc_message("FALL", command_path, &string_argument(off)),
+            ),
+        };
+        let mut properties = vec![name, frame_property(frame)];
+        if let Some(button_type) = button_type {
+            properties.push(property('i', "buttonType", &button_type.to_string()));
+        }
+        if node_type == "FADER" {
+            properties.push(property('i', "orientation", "1"));
+        }
+        children += &touchosc_node(2 * i + 1, node_type, &properties, &messages, "");
+        // Caption on top of the control (doesn't take touches)
+        let caption = [
+            property('s', "name", &cdata(&format!("{} label", widget.label()))),
+            frame_property(frame),
+            property('s', "text", &cdata(widget.label())),
+            property('b', "interactive", "0"),
+        ];
+        children += &touchosc_node(2 * i + 2, "LABEL", &caption, "", "");
+    }
+    let root = [property('s', "name", &cdata("VRC MIDI Transposer")), frame_property((0, 0, LAYOUT_WIDTH, layout_height(&frames)))];
+    let xml = format!(
+        "<?xml version='1.0' encoding='UTF-8'?><lexml version='3'>{}</lexml>",
+        touchosc_node(0, "GROUP", &root, "", &children)
+    );
+    zlib_stored(xml.as_bytes())
+}
+
+/// Wrap data in a zlib stream of uncompressed (stored) deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut chunks = data.chunks(u16::MAX as usize).peekable();
+    if chunks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(chunk) = chunks.next() {
+        let len = chunk.len() as u16;
+        out.push(if chunks.peek().is_none() { 1 } else { 0 });
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(chunk);
+    }
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    out.extend_from_slice(&((b << 16) | a).to_be_bytes());
+    out
+}
+
+/// Write a controller layout for the current config: TouchOSC for `.tosc` files, otherwise an
+/// Open Stage Control session (JSON). Returns the number of controls.
+pub fn export(path: &Path) -> Result<usize, String> {
+    let widgets = widgets();
+    let is_tosc = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("tosc"));
+    let data = if is_tosc { touchosc(&widgets) } else { open_stage_control(&widgets).into_bytes() };
+    std::fs::write(path, data).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(widgets.len())
+}
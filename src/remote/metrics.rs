@@ -0,0 +1,47 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+
+fn respond(mut stream: TcpStream) {
+    stream.set_read_timeout(Some(Duration::from_millis(500))).ok();
+    let mut request = [0u8; 1024];
+    let size = stream.read(&mut request).unwrap_or(0);
+    let request = String::from_utf8_lossy(&request[..size]);
+    let path = request.split_whitespace().nth(1).unwrap_or("");
+    let (status, body) = match (request.starts_with("GET "), path) {
+        (true, "/metrics") => ("200 OK", crate::general::latency::prometheus()),
+        (true, _) => ("404 Not Found", "not found\n".to_string()),
+        (false, _) => ("405 Method Not Allowed", "GET only\n".to_string()),
+    };
+    let response = format!(
+        "HTTP/1.0 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes());
+}
+
+/// Serve latency and drop metrics for Prometheus at `GET /metrics` on `addr` until exit
+pub fn spawn_http_server(addr: &str) -> std::io::Result<thread::JoinHandle<()>> {
+    let listener = TcpListener::bind(addr)?;
+    // Non-blocking accept so the thread notices the exit flag
+    listener.set_nonblocking(true)?;
+    Ok(thread::spawn(move || {
+        while !crate::state().exit.load(Ordering::SeqCst) {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let _ = stream.set_nonblocking(false);
+                    respond(stream);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(100)),
+                Err(e) => {
+                    log::error!(target: "app", "[HTTP] Metrics accept failed: {}", e);
+                    thread::sleep(Duration::from_millis(100));
+                }
+            }
+        }
+    }))
+}
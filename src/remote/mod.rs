@@ -1,3 +1,17 @@
 pub mod osc_listener;
 pub mod osc_sender;
 pub mod mqtt_listener;
+pub mod discovery;
+pub mod custom_controls;
+pub mod vrchat;
+pub mod chatbox;
+pub mod osc_debug;
+pub mod self_test;
+pub mod layout;
+pub mod capabilities;
+pub mod metrics;
+pub mod web;
+pub mod rest;
+pub mod websocket;
+pub mod event_api;
+pub mod oscquery;
@@ -1,39 +1,248 @@
-use rumqttc::{Client, Event, Incoming, LastWill, MqttOptions, QoS};
+use rumqttc::{Client, Event, Incoming, LastWill, MqttOptions, Outgoing, QoS, RecvTimeoutError};
 use std::thread;
-use std::time::Duration;
-use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
+use crate::general::commands::{self, Command, Outcome, Source};
 
 // MQTT Configuration Constants
 const CLIENT_ID: &str = "transposer2025";
-// Keep-alive kept low so the blocking event loop wakes up promptly and checks EXIT_FLAG on shutdown
+// Keep-alive kept low so the broker notices a lost connection (and sends the last will) quickly
 const KEEP_ALIVE_SECS: u64 = 2;
 const RECONNECT_DELAY_SECS: u64 = 1;
-const LOOP_DELAY_MS: u64 = 50;
-// Queue for outgoing MQTT requests (subscribe/publish). Needs to be large enough
-// to hold initial discovery publishes + subscriptions until the event loop drains.
-const QUEUE_SIZE: usize = 64;
+// Longest the event loop waits for the broker before checking the exit flag, and the longest
+// a state change waits for the publisher to notice it
+const LOOP_TICK_MS: u64 = 50;
+// Interval of the latency sensor updates while MIDI is flowing
+const LATENCY_PUBLISH_SECS: u64 = 5;
+// Upper bound for flushing the final states and `offline` on shutdown
+const SHUTDOWN_FLUSH_MS: u64 = 1500;
 
 // Home Assistant Discovery Constants
 const DEVICE_ID: &str = "midi_transposer_transposer2025";
 const DEVICE_NAME: &str = "MIDI Transposer 2025";
 const DEVICE_MANUFACTURER: &str = "MidiTransposer";
 const DEVICE_MODEL: &str = "MidiTransposer";
+// Node ID segment of the discovery topics (homeassistant/<component>/<node>/<object>/config)
+const DISCOVERY_NODE_ID: &str = "midi_transposer";
+
+/// MQTT client ID, suffixed with the instance name so instances don't kick each other off the broker
+fn client_id() -> String {
+    crate::general::instance::with_suffix(CLIENT_ID)
+}
+
+/// HA device name; named instances show up as separate devices
+fn device_name() -> String {
+    match crate::general::instance::instance_name() {
+        Some(name) => format!("{} ({})", DEVICE_NAME, name),
+        None => DEVICE_NAME.to_string(),
+    }
+}
+
+/// Discovery config topic for one entity of this instance
+fn discovery_topic(component: &str, object_id: &str) -> String {
+    format!(
+        "homeassistant/{}/{}/{}/config",
+        component,
+        crate::general::instance::with_suffix(DISCOVERY_NODE_ID),
+        object_id
+    )
+}
+
+/// Home Assistant presentation of a discovery entity
+struct EntityMetadata {
+    /// `config` or `diagnostic`; `None` keeps the entity among the main controls
+    entity_category: Option<&'static str>,
+    icon: Option<&'static str>,
+    device_class: Option<&'static str>,
+}
+
+const fn meta(entity_category: Option<&'static str>, icon: &'static str) -> EntityMetadata {
+    EntityMetadata { entity_category, icon: Some(icon), device_class: None }
+}
+
+// Metadata by discovery object id; a trailing `*` matches every object id with that prefix
+const ENTITY_METADATA: &[(&str, EntityMetadata)] = &[
+    ("transpose", meta(None, "mdi:piano")),
+    ("transpose_up", meta(None, "mdi:arrow-up-bold")),
+    ("transpose_down", meta(None, "mdi:arrow-down-bold")),
+    ("octave_shift", meta(None, "mdi:piano")),
+    ("octave_up", meta(None, "mdi:chevron-double-up")),
+    ("octave_down", meta(None, "mdi:chevron-double-down")),
+    ("osc_sending_enabled", meta(Some("config"), "mdi:broadcast")),
+    ("osc_send_original", meta(Some("config"), "mdi:swap-horizontal")),
+    ("osc_original_enabled", meta(Some("config"), "mdi:broadcast")),
+    ("osc_transposed_enabled", meta(Some("config"), "mdi:broadcast")),
+    ("osc_target_*", meta(Some("config"), "mdi:access-point-network")),
+    ("aftertouch_to_cc", meta(Some("config"), "mdi:tune-vertical")),
+    ("osc_note_velocity", meta(Some("config"), "mdi:speedometer")),
+    ("chatbox_status", meta(Some("config"), "mdi:message-text")),
+    ("transpose_glide", meta(Some("config"), "mdi:stairs-up")),
+    ("zone_*", meta(None, "mdi:piano-keyboard")),
+    ("debug_*", meta(Some("diagnostic"), "mdi:bug")),
+    ("log_level_*", meta(Some("config"), "mdi:text-box-search")),
+    ("setlist_next", meta(None, "mdi:skip-next")),
+    ("setlist_prev", meta(None, "mdi:skip-previous")),
+    ("current_song", meta(None, "mdi:playlist-music")),
+    ("active_profile", meta(None, "mdi:tune")),
+    ("practice_enabled", meta(None, "mdi:school")),
+    ("practice_phase", meta(None, "mdi:music-clef-treble")),
+    ("automation_enabled", meta(None, "mdi:timeline-clock")),
+    ("automation_progress", meta(None, "mdi:timeline-text")),
+    ("sequence_enabled", meta(None, "mdi:stairs")),
+    ("sequence_progress", meta(None, "mdi:stairs-box")),
+    ("now_playing", meta(None, "mdi:music-note")),
+    ("transpose_history", meta(None, "mdi:chart-timeline-variant")),
+    ("lfo_*", meta(None, "mdi:sine-wave")),
+    ("dropped_messages", meta(Some("diagnostic"), "mdi:message-alert")),
+    ("midi_latency", meta(Some("diagnostic"), "mdi:timer-outline")),
+    ("osc_listener", meta(Some("diagnostic"), "mdi:lan-connect")),
+    ("midi_devices", meta(Some("diagnostic"), "mdi:usb")),
+    ("midi_program", meta(None, "mdi:piano")),
+    ("note_*", meta(None, "mdi:piano")),
+];
+
+fn entity_metadata(object_id: &str) -> Option<&'static EntityMetadata> {
+    ENTITY_METADATA.iter().find_map(|(pattern, metadata)| {
+        let matches = match pattern.strip_suffix('*') {
+            Some(prefix) => object_id.starts_with(prefix),
+            None => *pattern == object_id,
+        };
+        matches.then_some(metadata)
+    })
+}
+
+/// Client handle + trigger-note state topic prefix + note event topic used to publish directly
+/// from the MIDI input callback (the event loop may be blocked waiting for the broker)
+static TRIGGER_PUBLISHER: Mutex<Option<(Client, String, String)>> = Mutex::new(None);
+
+/// Rate limit of the note event stream: note-ons per one-second window, and which notes were
+/// published (per channel) so their note-offs pass and dropped notes don't leave hanging offs
+struct NoteEventLimiter {
+    window_start: Option<Instant>,
+    count: u32,
+    published: [u128; 16],
+    // Transposed note announced with each published note-on, repeated by its note-off
+    transposed: [[u8; 128]; 16],
+}
+
+static NOTE_EVENT_LIMITER: Mutex<NoteEventLimiter> =
+    Mutex::new(NoteEventLimiter { window_start: None, count: 0, published: [0; 16], transposed: [[0; 128]; 16] });
+
+/// Set when the listener thread is spawned; it only ends on exit, so `mqtt on` must not start a second one
+static LISTENER_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Drop counter for publishes rejected because the rumqttc request queue was full
+static MQTT_QUEUE_STATS: OnceLock<Arc<crate::general::queue::QueueStats>> = OnceLock::new();
 
 /// Struktur für MQTT Topics
+#[derive(serde::Serialize)]
 struct MqttTopics {
     transpose_set: String,
     transpose_up: String,
     transpose_down: String,
     transpose_state: String,
+    transpose_attributes: String,
+    // Octave shift on top of the transpose
+    octave_set: String,
+    octave_up: String,
+    octave_down: String,
+    octave_state: String,
     availability: String,
     // OSC related
     osc_sending_enabled_set: String,
     osc_sending_enabled_state: String,
     osc_send_original_set: String,
     osc_send_original_state: String,
+    osc_original_enabled_set: String,
+    osc_original_enabled_state: String,
+    osc_transposed_enabled_set: String,
+    osc_transposed_enabled_state: String,
+    // Per send target (<base>/osc/target/<slug>, <base>/state/osc/target/<slug>)
+    osc_target_set_prefix: String,
+    osc_target_state_prefix: String,
+    // MIDI processing switches
+    aftertouch_to_cc_set: String,
+    aftertouch_to_cc_state: String,
+    note_velocity_set: String,
+    note_velocity_state: String,
+    // Transpose status in the VRChat chatbox
+    chatbox_status_set: String,
+    chatbox_status_state: String,
+    // Transpose glide (1-semitone steps)
+    glide_set: String,
+    glide_state: String,
     // Debug related
     debug_enabled_set: String,
     debug_enabled_state: String,
+    // Debug categories (<base>/debug/<category>, <base>/state/debug/<category>)
+    debug_category_set_prefix: String,
+    debug_category_state_prefix: String,
+    // Log levels per subsystem (<base>/log/<subsystem>, <base>/state/log/<subsystem>)
+    log_level_set_prefix: String,
+    log_level_state_prefix: String,
+    // Trigger note binary sensors (<base>/state/notes/<note>)
+    notes_state_prefix: String,
+    // Keyboard zone transposes (<base>/zone/<slug>/transpose, <base>/state/zone/<slug>/transpose)
+    zone_set_prefix: String,
+    zone_state_prefix: String,
+    // Custom controls (<base>/custom/<slug>, <base>/state/custom/<slug>)
+    custom_set_prefix: String,
+    custom_state_prefix: String,
+    // Setlist / profile
+    setlist_next: String,
+    setlist_prev: String,
+    song_state: String,
+    profile_state: String,
+    profile_export: String,
+    profile_import: String,
+    // Full runtime state snapshot (retained) and its restore command
+    full_state: String,
+    // Capability document for companion apps (retained)
+    capabilities: String,
+    // Per-profile namespace `<prefix>/<name>/state` and `<prefix>/<name>/command` (`mqtt.profile_topics`)
+    profile_scope_prefix: String,
+    state_restore: String,
+    // Practice mode
+    practice_set: String,
+    practice_state: String,
+    practice_phase_state: String,
+    // Automation lane
+    automation_set: String,
+    automation_state: String,
+    automation_progress_state: String,
+    // Transpose sequence
+    sequence_set: String,
+    sequence_state: String,
+    sequence_progress_state: String,
+    now_playing_state: String,
+    // Transpose history: one JSON event per change, plus the buffered changes (retained)
+    transpose_history: String,
+    transpose_history_buffer: String,
+    // Transpose LFO
+    lfo_set: String,
+    lfo_state: String,
+    lfo_depth_set: String,
+    lfo_depth_state: String,
+    lfo_rate_set: String,
+    lfo_rate_state: String,
+    // Scheduled actions: command (e.g. "in 5m osc off") and pending list
+    schedule_set: String,
+    command: String,
+    schedule_state: String,
+    // Note event stream for visualizers
+    midi_events: String,
+    // Diagnostics
+    dropped_state: String,
+    queues_attributes: String,
+    // MIDI thru latency (average in ms; min/max/last/jitter as attributes)
+    latency_state: String,
+    latency_attributes: String,
+    osc_listener_state: String,
+    midi_devices_state: String,
+    // Last MIDI program change (number, channel as attributes)
+    program_state: String,
+    program_attributes: String,
 }
 
 impl MqttTopics {
@@ -43,15 +252,83 @@ impl MqttTopics {
             transpose_up: format!("{}/transposeUp", base_topic),
             transpose_down: format!("{}/transposeDown", base_topic),
             transpose_state: format!("{}/state/transpose", base_topic),
+            transpose_attributes: format!("{}/state/transpose/attributes", base_topic),
+            octave_set: format!("{}/octave", base_topic),
+            octave_up: format!("{}/octaveUp", base_topic),
+            octave_down: format!("{}/octaveDown", base_topic),
+            octave_state: format!("{}/state/octave", base_topic),
             availability: format!("{}/availability", base_topic),
             // OSC switches
             osc_sending_enabled_set: format!("{}/osc/sendingEnabled", base_topic),
             osc_sending_enabled_state: format!("{}/state/osc/sendingEnabled", base_topic),
             osc_send_original_set: format!("{}/osc/sendOriginal", base_topic),
             osc_send_original_state: format!("{}/state/osc/sendOriginal", base_topic),
+            osc_original_enabled_set: format!("{}/osc/originalEnabled", base_topic),
+            osc_original_enabled_state: format!("{}/state/osc/originalEnabled", base_topic),
+            osc_transposed_enabled_set: format!("{}/osc/transposedEnabled", base_topic),
+            osc_transposed_enabled_state: format!("{}/state/osc/transposedEnabled", base_topic),
+            osc_target_set_prefix: format!("{}/osc/target", base_topic),
+            osc_target_state_prefix: format!("{}/state/osc/target", base_topic),
+            aftertouch_to_cc_set: format!("{}/midi/aftertouchToCc", base_topic),
+            aftertouch_to_cc_state: format!("{}/state/midi/aftertouchToCc", base_topic),
+            note_velocity_set: format!("{}/osc/noteVelocity", base_topic),
+            note_velocity_state: format!("{}/state/osc/noteVelocity", base_topic),
+            chatbox_status_set: format!("{}/chatbox/status", base_topic),
+            chatbox_status_state: format!("{}/state/chatbox/status", base_topic),
+            glide_set: format!("{}/glide/enabled", base_topic),
+            glide_state: format!("{}/state/glide/enabled", base_topic),
             // Debug switch
             debug_enabled_set: format!("{}/debug/enabled", base_topic),
             debug_enabled_state: format!("{}/state/debug/enabled", base_topic),
+            debug_category_set_prefix: format!("{}/debug", base_topic),
+            debug_category_state_prefix: format!("{}/state/debug", base_topic),
+            log_level_set_prefix: format!("{}/log", base_topic),
+            log_level_state_prefix: format!("{}/state/log", base_topic),
+            notes_state_prefix: format!("{}/state/notes", base_topic),
+            zone_set_prefix: format!("{}/zone", base_topic),
+            zone_state_prefix: format!("{}/state/zone", base_topic),
+            custom_set_prefix: format!("{}/custom", base_topic),
+            custom_state_prefix: format!("{}/state/custom", base_topic),
+            setlist_next: format!("{}/setlist/next", base_topic),
+            setlist_prev: format!("{}/setlist/prev", base_topic),
+            song_state: format!("{}/state/setlist/song", base_topic),
+            profile_state: format!("{}/state/profile", base_topic),
+            profile_export: format!("{}/profile/export", base_topic),
+            profile_import: format!("{}/profile/import", base_topic),
+            full_state: format!("{}/state/full", base_topic),
+            capabilities: format!("{}/capabilities", base_topic),
+            profile_scope_prefix: format!("{}/profiles", base_topic),
+            state_restore: format!("{}/state/restore", base_topic),
+            practice_set: format!("{}/practice/enabled", base_topic),
+            practice_state: format!("{}/state/practice/enabled", base_topic),
+            practice_phase_state: format!("{}/state/practice/phase", base_topic),
+            automation_set: format!("{}/automation/enabled", base_topic),
+            automation_state: format!("{}/state/automation/enabled", base_topic),
+            automation_progress_state: format!("{}/state/automation/progress", base_topic),
+            sequence_set: format!("{}/sequence/enabled", base_topic),
+            sequence_state: format!("{}/state/sequence/enabled", base_topic),
+            sequence_progress_state: format!("{}/state/sequence/progress", base_topic),
+            now_playing_state: format!("{}/state/nowPlaying", base_topic),
+            transpose_history: format!("{}/history/transpose", base_topic),
+            transpose_history_buffer: format!("{}/state/history/transpose", base_topic),
+            lfo_set: format!("{}/lfo/enabled", base_topic),
+            lfo_state: format!("{}/state/lfo/enabled", base_topic),
+            lfo_depth_set: format!("{}/lfo/depth", base_topic),
+            lfo_depth_state: format!("{}/state/lfo/depth", base_topic),
+            lfo_rate_set: format!("{}/lfo/rate", base_topic),
+            lfo_rate_state: format!("{}/state/lfo/rate", base_topic),
+            schedule_set: format!("{}/schedule", base_topic),
+            command: format!("{}/command", base_topic),
+            schedule_state: format!("{}/state/schedule", base_topic),
+            midi_events: format!("{}/events/midi", base_topic),
+            dropped_state: format!("{}/state/diagnostics/dropped", base_topic),
+            queues_attributes: format!("{}/state/diagnostics/queues", base_topic),
+            latency_state: format!("{}/state/diagnostics/latency", base_topic),
+            latency_attributes: format!("{}/state/diagnostics/latency/attributes", base_topic),
+            osc_listener_state: format!("{}/state/osc/listener", base_topic),
+            midi_devices_state: format!("{}/state/midi/devices", base_topic),
+            program_state: format!("{}/state/midi/program", base_topic),
+            program_attributes: format!("{}/state/midi/program/attributes", base_topic),
         }
     }
 }
@@ -81,180 +358,596 @@ fn parse_boolean_payload(payload: &[u8]) -> bool {
         .trim()
         .to_ascii_lowercase();
     
-    s == "1" || s == "true" || s == "on"
+    s == "1" || s == "true" || s == "on" || s == "press"
 }
 
-/// Erstellt Device JSON für Home Assistant Discovery
-fn create_device_json() -> String {
-    format!(
-        r#"{{
-  "identifiers": ["{}"],
-  "name": "{}",
-  "manufacturer": "{}",
-  "model": "{}"
-}}"#,
-        DEVICE_ID, DEVICE_NAME, DEVICE_MANUFACTURER, DEVICE_MODEL
-    )
+/// Custom control value: a number, or on/off words as 1/0 (HA switches send `ON`/`OFF`).
+/// None for anything else, so a typo never switches a control off.
+fn parse_control_payload(text: &str) -> Option<f32> {
+    if let Ok(value) = text.parse::<f32>() {
+        return Some(value);
+    }
+    match text.to_ascii_lowercase().as_str() {
+        "true" | "on" | "press" => Some(1.0),
+        "false" | "off" | "release" => Some(0.0),
+        _ => None,
+    }
+}
+
+/// Payload of a momentary button press (no release follows, so it never auto-repeats)
+fn is_press_payload(payload: &[u8]) -> bool {
+    std::str::from_utf8(payload).is_ok_and(|s| s.trim().eq_ignore_ascii_case("press"))
+}
+
+/// Device block shared by all discovery configs
+#[derive(Debug, Clone, Default, serde::Serialize)]
+struct DiscoveryDevice {
+    identifiers: Vec<String>,
+    name: String,
+    manufacturer: &'static str,
+    model: &'static str,
+}
+
+/// Erstellt das Device für Home Assistant Discovery
+fn create_device() -> DiscoveryDevice {
+    DiscoveryDevice {
+        identifiers: vec![crate::general::instance::with_suffix(DEVICE_ID)],
+        name: device_name(),
+        manufacturer: DEVICE_MANUFACTURER,
+        model: DEVICE_MODEL,
+    }
+}
+
+/// One Home Assistant MQTT discovery config; unset fields are left out of the JSON
+#[derive(Debug, Default, serde::Serialize)]
+struct DiscoveryEntity {
+    name: String,
+    unique_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    command_topic: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state_topic: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payload_on: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payload_off: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state_on: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state_off: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payload_press: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    step: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<Vec<&'static str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unit_of_measurement: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value_template: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    json_attributes_topic: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state_class: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entity_category: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    icon: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    device_class: Option<&'static str>,
+    availability_topic: String,
+    device: DiscoveryDevice,
+}
+
+impl DiscoveryEntity {
+    /// Entity with name, `<client_id>_<object_id>` unique ID, availability and device
+    fn new(name: impl Into<String>, client_id: &str, object_id: &str, availability: &str, device: &DiscoveryDevice) -> Self {
+        DiscoveryEntity {
+            name: name.into(),
+            unique_id: format!("{}_{}", client_id, object_id),
+            availability_topic: availability.to_string(),
+            device: device.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// On/off switch with "1"/"0" payloads
+    fn switch(mut self, command_topic: &str, state_topic: &str) -> Self {
+        self.command_topic = Some(command_topic.to_string());
+        self.state_topic = Some(state_topic.to_string());
+        self.payload_on = Some("1");
+        self.payload_off = Some("0");
+        self.state_on = Some("1");
+        self.state_off = Some("0");
+        self
+    }
+
+    /// Button sending "PRESS"
+    fn button(mut self, command_topic: &str) -> Self {
+        self.command_topic = Some(command_topic.to_string());
+        self.payload_press = Some("PRESS");
+        self
+    }
+
+    /// Read-only sensor
+    fn sensor(mut self, state_topic: &str) -> Self {
+        self.state_topic = Some(state_topic.to_string());
+        self
+    }
+
+    /// Number with range and step
+    fn number(mut self, command_topic: &str, state_topic: &str, min: f64, max: f64, step: f64) -> Self {
+        self.command_topic = Some(command_topic.to_string());
+        self.state_topic = Some(state_topic.to_string());
+        self.min = Some(min);
+        self.max = Some(max);
+        self.step = Some(step);
+        self
+    }
+
+    /// Select from a fixed list of options (command and state)
+    fn select(mut self, command_topic: &str, state_topic: &str, options: Vec<&'static str>) -> Self {
+        self.command_topic = Some(command_topic.to_string());
+        self.state_topic = Some(state_topic.to_string());
+        self.options = Some(options);
+        self
+    }
+
+    /// Free text (command and state)
+    fn text(mut self, command_topic: &str, state_topic: &str) -> Self {
+        self.command_topic = Some(command_topic.to_string());
+        self.state_topic = Some(state_topic.to_string());
+        self
+    }
+
+    /// Apply `entity_category`, `icon` and `device_class` from `ENTITY_METADATA`
+    fn with_metadata(mut self, object_id: &str) -> Self {
+        if let Some(metadata) = entity_metadata(object_id) {
+            self.entity_category = metadata.entity_category;
+            self.icon = metadata.icon;
+            self.device_class = metadata.device_class;
+        }
+        self
+    }
+}
+
+/// Publish a discovery config (retained), with metadata from `ENTITY_METADATA`
+fn publish_discovery(client: &Client, component: &str, object_id: &str, entity: DiscoveryEntity) {
+    match serde_json::to_string_pretty(&entity.with_metadata(object_id)) {
+        Ok(payload) => {
+            let _ = client.publish(discovery_topic(component, object_id), QoS::AtLeastOnce, true, payload);
+        }
+        Err(e) => log::error!(target: "mqtt", "[MQTT] Failed to serialize discovery config {}: {}", object_id, e),
+    }
 }
 
 /// Publiziert Home Assistant MQTT Discovery-Konfigurationen
 fn publish_homeassistant_discovery(client: &Client, topics: &MqttTopics) {
-    let device_json = create_device_json();
+    let device = create_device();
+    let client_id = client_id();
+    let entity = |name: &str, object_id: &str| DiscoveryEntity::new(name, &client_id, object_id, &topics.availability, &device);
 
     // Number Entity für absoluten Transpose-Wert
-    let number_config = format!(
-        r#"{{
-  "name": "MIDI Transpose",
-  "unique_id": "{}_transpose",
-  "command_topic": "{}",
-  "state_topic": "{}",
-  "min": {},
-  "max": {},
-  "step": 1,
-  "unit_of_measurement": "semitones",
-  "availability_topic": "{}",
-  "device": {}
-}}"#,
-        CLIENT_ID,
-        topics.transpose_set,
-        topics.transpose_state,
-        crate::get_config().transpose.min,
-        crate::get_config().transpose.max,
-        topics.availability,
-        device_json
-    );
-    let _ = client.publish(
-        "homeassistant/number/midi_transposer/transpose/config",
-        QoS::AtLeastOnce,
-        true,
-        number_config,
+    let transpose_range = &crate::get_config().transpose;
+    let mut transpose = entity("MIDI Transpose", "transpose").number(
+        &topics.transpose_set,
+        &topics.transpose_state,
+        transpose_range.min as f64,
+        transpose_range.max as f64,
+        1.0,
     );
+    transpose.unit_of_measurement = Some("semitones");
+    transpose.json_attributes_topic = Some(topics.transpose_attributes.clone());
+    publish_discovery(client, "number", "transpose", transpose);
 
-    // Button für Transpose Up
-    let button_up_config = format!(
-        r#"{{
-  "name": "Transpose Up",
-  "unique_id": "{}_transpose_up",
-  "command_topic": "{}",
-  "payload_press": "1",
-  "availability_topic": "{}",
-  "device": {}
-}}"#,
-        CLIENT_ID, topics.transpose_up, topics.availability, device_json
-    );
-    let _ = client.publish(
-        "homeassistant/button/midi_transposer/transpose_up/config",
-        QoS::AtLeastOnce,
-        true,
-        button_up_config,
-    );
+    // Buttons für Transpose Up/Down
+    publish_discovery(client, "button", "transpose_up", entity("Transpose Up", "transpose_up").button(&topics.transpose_up));
+    publish_discovery(client, "button", "transpose_down", entity("Transpose Down", "transpose_down").button(&topics.transpose_down));
 
-    // Button für Transpose Down
-    let button_down_config = format!(
-        r#"{{
-  "name": "Transpose Down",
-  "unique_id": "{}_transpose_down",
-  "command_topic": "{}",
-  "payload_press": "1",
-  "availability_topic": "{}",
-  "device": {}
-}}"#,
-        CLIENT_ID, topics.transpose_down, topics.availability, device_json
-    );
-    let _ = client.publish(
-        "homeassistant/button/midi_transposer/transpose_down/config",
-        QoS::AtLeastOnce,
-        true,
-        button_down_config,
+    // Octave shift: number plus up/down buttons
+    let octave_range = &crate::get_config().transpose;
+    let mut octave = entity("Octave Shift", "octave_shift").number(
+        &topics.octave_set,
+        &topics.octave_state,
+        octave_range.octave_min as f64,
+        octave_range.octave_max as f64,
+        1.0,
     );
+    octave.unit_of_measurement = Some("octaves");
+    publish_discovery(client, "number", "octave_shift", octave);
+    publish_discovery(client, "button", "octave_up", entity("Octave Up", "octave_up").button(&topics.octave_up));
+    publish_discovery(client, "button", "octave_down", entity("Octave Down", "octave_down").button(&topics.octave_down));
 
-    // Switch: OSC Sending Enabled
-    let switch_osc_send_cfg = format!(
-        r#"{{
-  "name": "OSC Sending Enabled",
-  "unique_id": "{}_osc_sending_enabled",
-  "command_topic": "{}",
-  "state_topic": "{}",
-  "payload_on": "1",
-  "payload_off": "0",
-  "state_on": "1",
-  "state_off": "0",
-  "availability_topic": "{}",
-  "device": {}
-}}"#,
-        CLIENT_ID,
-        topics.osc_sending_enabled_set,
-        topics.osc_sending_enabled_state,
-        topics.availability,
-        device_json
-    );
-    let _ = client.publish(
-        "homeassistant/switch/midi_transposer/osc_sending_enabled/config",
-        QoS::AtLeastOnce,
-        true,
-        switch_osc_send_cfg,
-    );
+    // Switches: OSC sending, send original (if off -> send transposed), per-stream enable, MIDI processing
+    for (object_id, name, command_topic, state_topic) in [
+        ("osc_sending_enabled", "OSC Sending Enabled", &topics.osc_sending_enabled_set, &topics.osc_sending_enabled_state),
+        ("osc_send_original", "OSC Send Original", &topics.osc_send_original_set, &topics.osc_send_original_state),
+        ("osc_original_enabled", "OSC Original Stream", &topics.osc_original_enabled_set, &topics.osc_original_enabled_state),
+        ("osc_transposed_enabled", "OSC Transposed Stream", &topics.osc_transposed_enabled_set, &topics.osc_transposed_enabled_state),
+        ("aftertouch_to_cc", "Aftertouch to CC", &topics.aftertouch_to_cc_set, &topics.aftertouch_to_cc_state),
+        ("osc_note_velocity", "OSC Note Velocity", &topics.note_velocity_set, &topics.note_velocity_state),
+        ("chatbox_status", "Chatbox Status", &topics.chatbox_status_set, &topics.chatbox_status_state),
+        ("transpose_glide", "Transpose Glide", &topics.glide_set, &topics.glide_state),
+        ("debug_enabled", "Debug Enabled", &topics.debug_enabled_set, &topics.debug_enabled_state),
+        ("practice_enabled", "Practice Mode", &topics.practice_set, &topics.practice_state),
+        ("automation_enabled", "Transpose Automation", &topics.automation_set, &topics.automation_state),
+        ("sequence_enabled", "Transpose Sequence", &topics.sequence_set, &topics.sequence_state),
+        ("lfo_enabled", "Transpose LFO", &topics.lfo_set, &topics.lfo_state),
+    ] {
+        publish_discovery(client, "switch", object_id, entity(name, object_id).switch(command_topic, state_topic));
+    }
 
-    // Switch: OSC Send Original (if off -> send transposed)
-    let switch_send_original_cfg = format!(
-        r#"{{
-  "name": "OSC Send Original",
-  "unique_id": "{}_osc_send_original",
-  "command_topic": "{}",
-  "state_topic": "{}",
-  "payload_on": "1",
-  "payload_off": "0",
-  "state_on": "1",
-  "state_off": "0",
-  "availability_topic": "{}",
-  "device": {}
-}}"#,
-        CLIENT_ID,
-        topics.osc_send_original_set,
-        topics.osc_send_original_state,
-        topics.availability,
-        device_json
-    );
-    let _ = client.publish(
-        "homeassistant/switch/midi_transposer/osc_send_original/config",
-        QoS::AtLeastOnce,
-        true,
-        switch_send_original_cfg,
-    );
+    // Switches: one per OSC send target (only with several targets)
+    if crate::osc_sender::targets().len() > 1 {
+        for target in crate::osc_sender::targets() {
+            let object_id = format!("osc_target_{}", target.slug);
+            let switch = entity(&format!("OSC Target {}", target.name), &object_id).switch(
+                &format!("{}/{}", topics.osc_target_set_prefix, target.slug),
+                &format!("{}/{}", topics.osc_target_state_prefix, target.slug),
+            );
+            publish_discovery(client, "switch", &object_id, switch);
+        }
+    }
 
-    // Switch: Debug Enabled
-    let switch_debug_cfg = format!(
-        r#"{{
-  "name": "Debug Enabled",
-  "unique_id": "{}_debug_enabled",
-  "command_topic": "{}",
-  "state_topic": "{}",
-  "payload_on": "1",
-  "payload_off": "0",
-  "state_on": "1",
-  "state_off": "0",
-  "availability_topic": "{}",
-  "device": {}
-}}"#,
-        CLIENT_ID,
-        topics.debug_enabled_set,
-        topics.debug_enabled_state,
-        topics.availability,
-        device_json
-    );
-    let _ = client.publish(
-        "homeassistant/switch/midi_transposer/debug_enabled/config",
-        QoS::AtLeastOnce,
-        true,
-        switch_debug_cfg,
+    // Switches: per-category debug logging
+    for category in crate::DebugCategory::ALL {
+        let object_id = format!("debug_{}", category.name());
+        let switch = entity(&format!("Debug {}", category.name().to_uppercase()), &object_id).switch(
+            &format!("{}/{}", topics.debug_category_set_prefix, category.name()),
+            &format!("{}/{}", topics.debug_category_state_prefix, category.name()),
+        );
+        publish_discovery(client, "switch", &object_id, switch);
+    }
+
+    // Selects: log level per subsystem
+    for subsystem in crate::general::logging::SUBSYSTEMS {
+        let object_id = format!("log_level_{}", subsystem);
+        let select = entity(&format!("Log Level {}", subsystem.to_uppercase()), &object_id).select(
+            &format!("{}/{}", topics.log_level_set_prefix, subsystem),
+            &format!("{}/{}", topics.log_level_state_prefix, subsystem),
+            crate::general::logging::LogLevel::ALL.iter().map(|l| l.name()).collect(),
+        );
+        publish_discovery(client, "select", &object_id, select);
+    }
+
+    // Numbers: transpose of every keyboard zone
+    for zone in crate::general::zones::zones().iter() {
+        let slug = zone.slug();
+        let object_id = format!("zone_{}_transpose", slug);
+        let mut number = entity(&format!("Zone {} Transpose", zone.name), &object_id).number(
+            &format!("{}/{}/transpose", topics.zone_set_prefix, slug),
+            &format!("{}/{}/transpose", topics.zone_state_prefix, slug),
+            transpose_range.min as f64,
+            transpose_range.max as f64,
+            1.0,
+        );
+        number.unit_of_measurement = Some("semitones");
+        publish_discovery(client, "number", &object_id, number);
+    }
+
+    // Setlist buttons and sensors (only with a configured setlist / profiles)
+    if !crate::get_config().setlist.is_empty() {
+        publish_discovery(client, "button", "setlist_next", entity("Next Song", "setlist_next").button(&topics.setlist_next));
+        publish_discovery(client, "button", "setlist_prev", entity("Previous Song", "setlist_prev").button(&topics.setlist_prev));
+        publish_discovery(client, "sensor", "current_song", entity("Current Song", "current_song").sensor(&topics.song_state));
+    }
+    if !crate::get_config().profiles.is_empty() {
+        publish_discovery(client, "sensor", "active_profile", entity("Active Profile", "active_profile").sensor(&topics.profile_state));
+    }
+
+    // LFO depth (pattern multiplier) and rate (beats per step)
+    publish_discovery(client, "number", "lfo_depth", entity("LFO Depth", "lfo_depth").number(&topics.lfo_depth_set, &topics.lfo_depth_state, 0.0, 1.0, 0.05));
+    let mut rate = entity("LFO Rate", "lfo_rate").number(
+        &topics.lfo_rate_set,
+        &topics.lfo_rate_state,
+        crate::general::lfo::MIN_BEATS_PER_STEP as f64,
+        crate::general::lfo::MAX_BEATS_PER_STEP as f64,
+        0.25,
     );
+    rate.unit_of_measurement = Some("beats");
+    publish_discovery(client, "number", "lfo_rate", rate);
+
+    // Practice mode and automation lane sensors
+    publish_discovery(client, "sensor", "practice_phase", entity("Practice Key", "practice_phase").sensor(&topics.practice_phase_state));
+    publish_discovery(client, "sensor", "automation_progress", entity("Automation Lane", "automation_progress").sensor(&topics.automation_progress_state));
+    publish_discovery(client, "sensor", "sequence_progress", entity("Sequence Step", "sequence_progress").sensor(&topics.sequence_progress_state));
+    publish_discovery(client, "sensor", "now_playing", entity("Now Playing", "now_playing").sensor(&topics.now_playing_state));
+
+    // Sensor: last transpose change (measurement, so HA keeps a trend graph), the event as attributes
+    if crate::get_config().transpose.history_size > 0 {
+        let mut history = entity("Transpose History", "transpose_history").sensor(&topics.transpose_history);
+        history.value_template = Some("{{ value_json.transpose }}");
+        history.json_attributes_topic = Some(topics.transpose_history.clone());
+        history.state_class = Some("measurement");
+        publish_discovery(client, "sensor", "transpose_history", history);
+    }
+
+    // Sensor: dropped messages over all queues (per-queue details as attributes)
+    let mut dropped = entity("Dropped Messages", "dropped_messages").sensor(&topics.dropped_state);
+    dropped.json_attributes_topic = Some(topics.queues_attributes.clone());
+    dropped.state_class = Some("total_increasing");
+    publish_discovery(client, "sensor", "dropped_messages", dropped);
+
+    // Sensor: average MIDI thru latency (min/max/last/jitter as attributes)
+    let mut latency = entity("MIDI Latency", "midi_latency").sensor(&topics.latency_state);
+    latency.json_attributes_topic = Some(topics.latency_attributes.clone());
+    latency.unit_of_measurement = Some("ms");
+    latency.state_class = Some("measurement");
+    publish_discovery(client, "sensor", "midi_latency", latency);
+
+    // Sensor: OSC listener bind result (shows a port conflict and the fallback in use)
+    publish_discovery(client, "sensor", "osc_listener", entity("OSC Listener", "osc_listener").sensor(&topics.osc_listener_state));
+
+    // Sensor: MIDI in/out state (shows unplugged devices and their reconnect)
+    publish_discovery(client, "sensor", "midi_devices", entity("MIDI Devices", "midi_devices").sensor(&topics.midi_devices_state));
+
+    // Sensor: last program change (patch number; channel as attribute)
+    let mut program = entity("MIDI Program", "midi_program").sensor(&topics.program_state);
+    program.json_attributes_topic = Some(topics.program_attributes.clone());
+    publish_discovery(client, "sensor", "midi_program", program);
+
+    // Binary Sensors: configured trigger notes
+    for &note in &crate::get_config().mqtt.trigger_notes {
+        let object_id = format!("note_{}", note);
+        let mut sensor = entity(&format!("Note {} ({})", crate::general::notes::display_name(note), note), &object_id)
+            .sensor(&format!("{}/{}", topics.notes_state_prefix, note));
+        sensor.payload_on = Some("1");
+        sensor.payload_off = Some("0");
+        publish_discovery(client, "binary_sensor", &object_id, sensor);
+    }
+
+    // Custom controls: switch for bool, number for int/float; multi-argument controls get one
+    // entity per argument or a single JSON text entity
+    for control in crate::custom_controls::controls().iter() {
+        let slug = control.slug();
+        if control.is_multi_arg() && control.ha_entity == crate::custom_controls::MultiArgEntity::Text {
+            let object_id = format!("custom_{}", slug);
+            let command_topic = format!("{}/{}", topics.custom_set_prefix, slug);
+            let state_topic = format!("{}/{}", topics.custom_state_prefix, slug);
+            publish_discovery(client, "text", &object_id, entity(&control.name, &object_id).text(&command_topic, &state_topic));
+            continue;
+        }
+        let parts: Vec<(String, String)> = if control.is_multi_arg() {
+            control.args.iter().map(|arg| (format!("{}/{}", slug, arg), format!("{} {}", control.name, arg))).collect()
+        } else {
+            vec![(slug.clone(), control.name.clone())]
+        };
+        for (key, name) in parts {
+            let object_id = format!("custom_{}", key.replace('/', "_"));
+            let command_topic = format!("{}/{}", topics.custom_set_prefix, key);
+            let state_topic = format!("{}/{}", topics.custom_state_prefix, key);
+            let base = entity(&name, &object_id);
+            let (component, config) = match control.kind {
+                crate::custom_controls::ControlKind::Bool => ("switch", base.switch(&command_topic, &state_topic)),
+                kind => {
+                    let step = if kind == crate::custom_controls::ControlKind::Int { 1.0 } else { 0.01 };
+                    ("number", base.number(&command_topic, &state_topic, control.min as f64, control.max as f64, step))
+                }
+            };
+            publish_discovery(client, component, &object_id, config);
+        }
+    }
+
+    log::debug!(target: "mqtt", "[MQTT] Home Assistant Discovery configured");
+}
+
+/// Publish the current value of every custom control (retained)
+fn publish_custom_control_states(client: &Client, topics: &MqttTopics) {
+    for control in crate::custom_controls::controls().iter() {
+        for key in control.value_keys() {
+            let value = crate::custom_controls::value(&key);
+            let _ = client.publish(format!("{}/{}", topics.custom_state_prefix, key), QoS::AtLeastOnce, true, control.format_value(value));
+        }
+        // Multi-argument controls: all values as one JSON array
+        if control.is_multi_arg() {
+            let _ = client.publish(format!("{}/{}", topics.custom_state_prefix, control.slug()), QoS::AtLeastOnce, true, control.format_values());
+        }
+    }
+}
+
+/// Publish the on/off state of every OSC send target (retained)
+fn publish_osc_target_states(client: &Client, topics: &MqttTopics) {
+    for target in crate::osc_sender::targets() {
+        let state = if target.enabled.load(Ordering::SeqCst) { "1" } else { "0" };
+        let _ = client.publish(format!("{}/{}", topics.osc_target_state_prefix, target.slug), QoS::AtLeastOnce, true, state);
+    }
+}
+
+fn publish_program_state(client: &Client, topics: &MqttTopics) {
+    let _ = client.publish(&topics.program_attributes, QoS::AtLeastOnce, true, crate::general::program::attributes_json());
+    let _ = client.publish(&topics.program_state, QoS::AtLeastOnce, true, crate::general::program::state());
+}
+
+fn publish_practice_state(client: &Client, topics: &MqttTopics) {
+    let active = if crate::general::practice::is_active() { "1" } else { "0" };
+    let _ = client.publish(&topics.practice_state, QoS::AtLeastOnce, true, active);
+    let _ = client.publish(&topics.practice_phase_state, QoS::AtLeastOnce, true, crate::general::practice::phase());
+}
 
-    if crate::is_debug_enabled() { println!("[MQTT] Home Assistant Discovery configured"); }
+fn publish_automation_state(client: &Client, topics: &MqttTopics) {
+    let active = crate::general::automation::is_active();
+    let _ = client.publish(&topics.automation_state, QoS::AtLeastOnce, true, if active { "1" } else { "0" });
+    let lane = if active { crate::general::automation::progress() } else { "off".to_string() };
+    let _ = client.publish(&topics.automation_progress_state, QoS::AtLeastOnce, true, lane);
+}
+
+fn publish_sequence_state(client: &Client, topics: &MqttTopics) {
+    let active = crate::general::sequence::is_active();
+    let _ = client.publish(&topics.sequence_state, QoS::AtLeastOnce, true, if active { "1" } else { "0" });
+    let _ = client.publish(&topics.sequence_progress_state, QoS::AtLeastOnce, true, crate::general::sequence::progress());
+}
+
+fn publish_lfo_state(client: &Client, topics: &MqttTopics) {
+    let enabled = if crate::general::lfo::is_enabled() { "1" } else { "0" };
+    let _ = client.publish(&topics.lfo_state, QoS::AtLeastOnce, true, enabled);
+    let _ = client.publish(&topics.lfo_depth_state, QoS::AtLeastOnce, true, crate::general::lfo::depth().to_string());
+    let _ = client.publish(&topics.lfo_rate_state, QoS::AtLeastOnce, true, crate::general::lfo::beats_per_step().to_string());
+}
+
+/// Publish the pending scheduled actions as a JSON list of strings
+fn publish_schedule_state(client: &Client, topics: &MqttTopics) {
+    let pending = serde_json::Value::from(crate::general::scheduler::pending());
+    let _ = client.publish(&topics.schedule_state, QoS::AtLeastOnce, true, pending.to_string());
+}
+
+/// Publish the current transpose of every keyboard zone
+fn publish_zone_states(client: &Client, topics: &MqttTopics) {
+    for (index, zone) in crate::general::zones::zones().iter().enumerate() {
+        let value = crate::general::zones::transpose(index).to_string();
+        let _ = client.publish(format!("{}/{}/transpose", topics.zone_state_prefix, zone.slug()), QoS::AtLeastOnce, true, value);
+    }
+}
+
+/// Publish the current log level of every subsystem
+fn publish_log_levels(client: &Client, topics: &MqttTopics) {
+    for subsystem in crate::general::logging::SUBSYSTEMS {
+        let level = crate::general::logging::level(subsystem).name();
+        let _ = client.publish(format!("{}/{}", topics.log_level_state_prefix, subsystem), QoS::AtLeastOnce, true, level);
+    }
+}
+
+/// Publish the active song and profile names (empty when none)
+fn publish_setlist_state(client: &Client, topics: &MqttTopics) {
+    let song = crate::general::setlist::current().map(|(_, s)| s.name.clone()).unwrap_or_default();
+    let profile = crate::general::profile::active().unwrap_or_default();
+    let _ = client.publish(&topics.song_state, QoS::AtLeastOnce, true, song);
+    let _ = client.publish(&topics.profile_state, QoS::AtLeastOnce, true, profile);
+}
+
+/// With `mqtt.profile_topics`, publish the state snapshot under the active profile's namespace too,
+/// so automations can subscribe to `<base>/profiles/<name>/state` and only see that rig's changes
+fn publish_profile_scoped_state(client: &Client, topics: &MqttTopics, snapshot: &str) {
+    if !crate::get_config().mqtt.profile_topics {
+        return;
+    }
+    if let Some(profile) = crate::general::profile::active() {
+        let topic = format!("{}/{}/state", topics.profile_scope_prefix, profile);
+        let _ = client.publish(topic, QoS::AtLeastOnce, true, snapshot.to_string());
+    }
+}
+
+/// Publish total drops and per-queue counters
+fn publish_queue_diagnostics(client: &Client, topics: &MqttTopics) {
+    let queues: serde_json::Map<String, serde_json::Value> = crate::general::queue::all_stats()
+        .iter()
+        .map(|q| {
+            (
+                q.name.clone(),
+                serde_json::json!({ "queued": q.queued(), "capacity": q.capacity, "dropped": q.dropped() }),
+            )
+        })
+        .collect();
+    let _ = client.publish(&topics.dropped_state, QoS::AtLeastOnce, true, crate::general::queue::total_dropped().to_string());
+    let _ = client.publish(&topics.queues_attributes, QoS::AtLeastOnce, true, serde_json::Value::Object(queues).to_string());
+}
+
+/// Publish the MIDI thru latency statistics (milliseconds)
+fn publish_latency(client: &Client, topics: &MqttTopics) {
+    let stats = crate::general::latency::snapshot();
+    let ms = |micros: f64| (micros / 10.0).round() / 100.0;
+    let attributes = serde_json::json!({
+        "messages": stats.count,
+        "min_ms": ms(stats.min_us as f64),
+        "max_ms": ms(stats.max_us as f64),
+        "last_ms": ms(stats.last_us as f64),
+        "jitter_ms": ms(stats.jitter_us),
+    });
+    let _ = client.publish(&topics.latency_state, QoS::AtLeastOnce, true, ms(stats.avg_us()).to_string());
+    let _ = client.publish(&topics.latency_attributes, QoS::AtLeastOnce, true, attributes.to_string());
+}
+
+/// Publish note on/off for configured trigger notes. Called from the MIDI input callback
+/// with the original (untransposed) message; never blocks (QoS 0, dropped if the queue is full).
+pub fn publish_trigger_note(message: &[u8]) {
+    if message.len() < 3 || !crate::state().mqtt_enabled.load(Ordering::SeqCst) {
+        return;
+    }
+    let on = match message[0] & 0xF0 {
+        0x90 => message[2] > 0,
+        0x80 => false,
+        _ => return,
+    };
+    let note = message[1];
+    if !crate::get_config().mqtt.trigger_notes.contains(&note) {
+        return;
+    }
+    if let Ok(guard) = TRIGGER_PUBLISHER.lock() {
+        if let Some((client, prefix, _)) = guard.as_ref() {
+            if client.try_publish(format!("{}/{}", prefix, note), QoS::AtMostOnce, false, if on { "1" } else { "0" }).is_err() {
+                if let Some(stats) = MQTT_QUEUE_STATS.get() {
+                    stats.record_drop();
+                }
+            }
+            log::debug!(target: "mqtt", "[MQTT] Trigger note {} ({}) -> {}", note, crate::general::notes::display_name(note), if on { "on" } else { "off" });
+        }
+    }
+}
+
+/// Publish a note on/off as JSON to `<base>/events/midi` (opt-in via `mqtt.note_events`).
+/// Called from the MIDI input callback with the original message; QoS 0, not retained.
+/// Note-ons beyond `note_events_max_per_sec` are dropped together with their note-offs; a
+/// note-off reports the transposed note of its note-on, even if the transpose changed since.
+pub fn publish_note_event(message: &[u8]) {
+    let config = &crate::get_config().mqtt;
+    if !config.note_events || message.len() < 3 || !crate::state().mqtt_enabled.load(Ordering::SeqCst) {
+        return;
+    }
+    let on = match message[0] & 0xF0 {
+        0x90 => message[2] > 0,
+        0x80 => false,
+        _ => return,
+    };
+    let (channel, note) = ((message[0] & 0x0F) as usize, message[1] & 0x7F);
+    let bit = 1u128 << note;
+    let transpose = crate::state().transpose.load(Ordering::Relaxed);
+    let Ok(mut limiter) = NOTE_EVENT_LIMITER.lock() else { return };
+    let transposed = if on { (note as i32 + transpose).clamp(0, 127) as u8 } else { limiter.transposed[channel][note as usize] };
+    if on {
+        let now = Instant::now();
+        if limiter.window_start.is_none_or(|start| now.duration_since(start) >= Duration::from_secs(1)) {
+            limiter.window_start = Some(now);
+            limiter.count = 0;
+        }
+        if config.note_events_max_per_sec > 0 && limiter.count >= config.note_events_max_per_sec {
+            return;
+        }
+        limiter.count += 1;
+        limiter.published[channel] |= bit;
+        limiter.transposed[channel][note as usize] = transposed;
+    } else if limiter.published[channel] & bit == 0 {
+        return;
+    } else {
+        limiter.published[channel] &= !bit;
+    }
+    drop(limiter);
+
+    let payload = serde_json::json!({
+        "type": if on { "note_on" } else { "note_off" },
+        "channel": channel + 1,
+        "note": note,
+        "name": crate::general::notes::display_name(note),
+        "velocity": message[2],
+        "transposed": transposed,
+        "transpose": transpose,
+    });
+    if let Ok(guard) = TRIGGER_PUBLISHER.lock() {
+        if let Some((client, _, topic)) = guard.as_ref() {
+            if client.try_publish(topic.as_str(), QoS::AtMostOnce, false, payload.to_string()).is_err() {
+                if let Some(stats) = MQTT_QUEUE_STATS.get() {
+                    stats.record_drop();
+                }
+            }
+        }
+    }
 }
 
 /// Erstellt MQTT-Optionen mit Konfiguration und Last Will Testament
 fn create_mqtt_options(host: &str, port: u16, creds: &crate::MqttCredentials, availability_topic: &str) -> MqttOptions {
-    let mut options = MqttOptions::new(CLIENT_ID, host, port);
+    let mut options = MqttOptions::new(client_id(), host, port);
     options.set_keep_alive(Duration::from_secs(KEEP_ALIVE_SECS));
     options.set_credentials(&creds.username, &creds.password);
     
@@ -274,20 +967,62 @@ fn subscribe_to_topics(client: &Client, topics: &MqttTopics) -> Result<(), Box<d
     client.subscribe(&topics.transpose_set, QoS::AtLeastOnce)?;
     client.subscribe(&topics.transpose_up, QoS::AtLeastOnce)?;
     client.subscribe(&topics.transpose_down, QoS::AtLeastOnce)?;
+    client.subscribe(&topics.octave_set, QoS::AtLeastOnce)?;
+    client.subscribe(&topics.octave_up, QoS::AtLeastOnce)?;
+    client.subscribe(&topics.octave_down, QoS::AtLeastOnce)?;
     // OSC related switches
     client.subscribe(&topics.osc_sending_enabled_set, QoS::AtLeastOnce)?;
     client.subscribe(&topics.osc_send_original_set, QoS::AtLeastOnce)?;
+    client.subscribe(&topics.osc_original_enabled_set, QoS::AtLeastOnce)?;
+    client.subscribe(&topics.osc_transposed_enabled_set, QoS::AtLeastOnce)?;
+    client.subscribe(format!("{}/+", topics.osc_target_set_prefix), QoS::AtLeastOnce)?;
+    client.subscribe(&topics.aftertouch_to_cc_set, QoS::AtLeastOnce)?;
+    client.subscribe(&topics.note_velocity_set, QoS::AtLeastOnce)?;
+    client.subscribe(&topics.chatbox_status_set, QoS::AtLeastOnce)?;
+    client.subscribe(&topics.glide_set, QoS::AtLeastOnce)?;
     // Debug switch
     client.subscribe(&topics.debug_enabled_set, QoS::AtLeastOnce)?;
-    
-    if crate::is_debug_enabled() {
-        println!(
-            "[MQTT] Subscribed to topics: {}, {}, {}, {}, {}, {}", 
-            topics.transpose_set, topics.transpose_up, topics.transpose_down,
-            topics.osc_sending_enabled_set, topics.osc_send_original_set,
-            topics.debug_enabled_set
-        );
+    client.subscribe(&topics.practice_set, QoS::AtLeastOnce)?;
+    client.subscribe(&topics.automation_set, QoS::AtLeastOnce)?;
+    client.subscribe(&topics.sequence_set, QoS::AtLeastOnce)?;
+    client.subscribe(&topics.lfo_set, QoS::AtLeastOnce)?;
+    client.subscribe(&topics.schedule_set, QoS::AtLeastOnce)?;
+    client.subscribe(&topics.command, QoS::AtLeastOnce)?;
+    client.subscribe(&topics.lfo_depth_set, QoS::AtLeastOnce)?;
+    client.subscribe(&topics.lfo_rate_set, QoS::AtLeastOnce)?;
+    client.subscribe(&topics.profile_export, QoS::AtLeastOnce)?;
+    client.subscribe(&topics.profile_import, QoS::AtLeastOnce)?;
+    client.subscribe(&topics.state_restore, QoS::AtLeastOnce)?;
+    for category in crate::DebugCategory::ALL {
+        client.subscribe(format!("{}/{}", topics.debug_category_set_prefix, category.name()), QoS::AtLeastOnce)?;
+    }
+    for subsystem in crate::general::logging::SUBSYSTEMS {
+        client.subscribe(format!("{}/{}", topics.log_level_set_prefix, subsystem), QoS::AtLeastOnce)?;
+    }
+    // Setlist buttons
+    if !crate::get_config().setlist.is_empty() {
+        client.subscribe(&topics.setlist_next, QoS::AtLeastOnce)?;
+        client.subscribe(&topics.setlist_prev, QoS::AtLeastOnce)?;
     }
+    for zone in crate::general::zones::zones().iter() {
+        client.subscribe(format!("{}/{}/transpose", topics.zone_set_prefix, zone.slug()), QoS::AtLeastOnce)?;
+    }
+    // Custom controls
+    if !crate::custom_controls::controls().is_empty() {
+        client.subscribe(format!("{}/#", topics.custom_set_prefix), QoS::AtLeastOnce)?;
+    }
+    // Profile-scoped commands
+    if crate::get_config().mqtt.profile_topics {
+        client.subscribe(format!("{}/+/command", topics.profile_scope_prefix), QoS::AtLeastOnce)?;
+    }
+    
+    log::debug!(
+        target: "mqtt",
+        "[MQTT] Subscribed to topics: {}, {}, {}, {}, {}, {}", 
+        topics.transpose_set, topics.transpose_up, topics.transpose_down,
+        topics.osc_sending_enabled_set, topics.osc_send_original_set,
+        topics.debug_enabled_set
+    );
     
     Ok(())
 }
@@ -298,30 +1033,73 @@ fn subscribe_to_topics(client: &Client, topics: &MqttTopics) -> Result<(), Box<d
 /// - `<base>/transpose` - Setzt absoluten Transpose-Wert (Integer)
 /// - `<base>/transposeUp` - Erhöht Transpose um 1 (1/true/on)
 /// - `<base>/transposeDown` - Verringert Transpose um 1 (1/true/on)
+/// - `<base>/octave`, `<base>/octaveUp`, `<base>/octaveDown` - Oktavverschiebung zusätzlich zum Transpose
 /// 
 /// Publizierte Topics:
 /// - `<base>/state/transpose` - Aktueller Transpose-Wert
 /// - `<base>/availability` - Online/Offline Status
 pub fn spawn_mqtt_listener() -> thread::JoinHandle<()> {
+    LISTENER_STARTED.store(true, Ordering::SeqCst);
     let config = crate::get_config();
-    let host = &config.mqtt.broker_host;
+    let host = config.mqtt.broker_host.clone();
     let port = config.mqtt.broker_port;
-    let base_topic = &config.mqtt.base_topic;
+    let base_topic = crate::general::instance::with_suffix(&config.mqtt.base_topic);
     let creds = crate::MqttCredentials {
         username: config.mqtt.username.clone(),
         password: config.mqtt.password.clone(),
     };
 
     thread::spawn(move || {
-        let topics = MqttTopics::new(base_topic);
-        let mqtt_options = create_mqtt_options(host, port, &creds, &topics.availability);
-        let (client, connection) = Client::new(mqtt_options, QUEUE_SIZE);
+        let topics = MqttTopics::new(&base_topic);
+        let mqtt_options = create_mqtt_options(&host, port, &creds, &topics.availability);
+        // The publisher waits for room, so the connect burst (discovery, states) needn't fit;
+        // only the non-blocking publishes from the MIDI callback are dropped while it is full
+        let queue_size = crate::get_config().queues.mqtt_capacity.max(1);
+        let (client, connection) = Client::new(mqtt_options, queue_size);
+        MQTT_QUEUE_STATS.get_or_init(|| crate::general::queue::register("mqtt_out", queue_size));
+        if let Ok(mut guard) = TRIGGER_PUBLISHER.lock() {
+            *guard = Some((client.clone(), topics.notes_state_prefix.clone(), topics.midi_events.clone()));
+        }
 
-        // Hauptschleife für MQTT-Nachrichten (publishes erfolgen nach ConnAck)
-        run_mqtt_message_loop(connection, &client, &topics);
+        // The event loop only talks to the broker; everything that publishes runs on the
+        // publisher, so a full request queue can't block the loop that drains it
+        let (events_tx, events_rx) = mpsc::channel();
+        thread::scope(|scope| {
+            scope.spawn(|| run_mqtt_publisher(events_rx, &client, &topics));
+            run_mqtt_event_loop(connection, &client, &topics, events_tx);
+        });
     })
 }
 
+/// Start the listener for `mqtt on`, unless it already runs
+pub fn ensure_started() {
+    if !LISTENER_STARTED.load(Ordering::SeqCst) {
+        drop(spawn_mqtt_listener());
+    }
+}
+
+/// All topics of this instance by name (`transpose_set`, `full_state`, ...), for the capability document
+pub fn topic_map() -> serde_json::Value {
+    let base_topic = crate::general::instance::with_suffix(&crate::get_config().mqtt.base_topic);
+    serde_json::to_value(MqttTopics::new(&base_topic)).unwrap_or_default()
+}
+
+/// Publish the applied transpose plus an attributes JSON telling the controlling UI
+/// whether (and why) the requested value was clamped
+fn publish_transpose_result(client: &Client, topics: &MqttTopics, requested: i32, applied: i32) {
+    let config = crate::get_config();
+    let attributes = serde_json::json!({
+        "requested": requested,
+        "applied": applied,
+        "clamped": requested != applied,
+        "min": config.transpose.min,
+        "max": config.transpose.max,
+        "profile": crate::general::profile::active(),
+    });
+    let _ = client.publish(&topics.transpose_state, QoS::AtLeastOnce, true, applied.to_string());
+    let _ = client.publish(&topics.transpose_attributes, QoS::AtLeastOnce, true, attributes.to_string());
+}
+
 /// Behandelt eingehende MQTT-Nachrichten und aktualisiert Transpose-Werte
 fn handle_mqtt_message(
     client: &Client,
@@ -332,131 +1110,448 @@ fn handle_mqtt_message(
     if topic == topics.transpose_set {
         // Absoluter Transpose-Wert
         if let Some(value) = parse_transpose_payload(payload) {
-            let clamped_value = crate::set_transpose_semitones(value);
-            if crate::is_debug_enabled() { println!("[MQTT] Transpose set to {}", clamped_value); }
-            let _ = client.publish(&topics.transpose_state, QoS::AtLeastOnce, true, clamped_value.to_string());
-            return Some(clamped_value);
+            return run_command(client, topics, Command::Transpose(value));
         } else {
-            eprintln!("[MQTT] Invalid /transpose payload: {:?}", payload);
+            log::warn!(target: "mqtt", "[MQTT] Invalid /transpose payload: {:?}", payload);
         }
-    } else if topic == topics.transpose_up {
-        // Transpose erhöhen
+    } else if topic == topics.transpose_up || topic == topics.transpose_down {
+        // Transpose erhöhen/verringern
+        let delta = if topic == topics.transpose_up { 1 } else { -1 };
         if parse_boolean_payload(payload) {
-            let current = crate::TRANSPOSE_SEMITONES.load(Ordering::SeqCst);
-            let new_value = crate::set_transpose_semitones(current + 1);
-            if crate::is_debug_enabled() { println!("[MQTT] Transpose UP: {} -> {}", current, new_value); }
-            let _ = client.publish(&topics.transpose_state, QoS::AtLeastOnce, true, new_value.to_string());
-            return Some(new_value);
-        }
-    } else if topic == topics.transpose_down {
-        // Transpose verringern
+            let new_value = run_command(client, topics, Command::TransposeStep(delta));
+            // "PRESS" (Home Assistant button) is a single step; "1" holds until "0"
+            if !is_press_payload(payload) {
+                crate::general::repeat::press(delta);
+            }
+            return new_value;
+        }
+        crate::general::repeat::release(delta);
+    } else if topic == topics.octave_set {
+        // Absolute Oktavverschiebung (State wird von der Nachrichtenschleife publiziert)
+        match std::str::from_utf8(payload).ok().and_then(|text| text.trim().parse::<f32>().ok()) {
+            Some(octaves) => {
+                run_command(client, topics, Command::Octave(octaves.round() as i32));
+            }
+            None => log::warn!(target: "mqtt", "[MQTT] Invalid /octave payload: {:?}", payload),
+        }
+    } else if topic == topics.octave_up || topic == topics.octave_down {
+        // Oktave hoch/runter, eine Stufe pro Tastendruck
         if parse_boolean_payload(payload) {
-            let current = crate::TRANSPOSE_SEMITONES.load(Ordering::SeqCst);
-            let new_value = crate::set_transpose_semitones(current - 1);
-            if crate::is_debug_enabled() { println!("[MQTT] Transpose DOWN: {} -> {}", current, new_value); }
-            let _ = client.publish(&topics.transpose_state, QoS::AtLeastOnce, true, new_value.to_string());
-            return Some(new_value);
+            run_command(client, topics, Command::OctaveStep(if topic == topics.octave_up { 1 } else { -1 }));
+        }
+    } else if topic == topics.command {
+        // Beliebiger Konsolen-Steuerbefehl, z.B. "lfo on" oder "profile Live"
+        let text = std::str::from_utf8(payload).unwrap_or("");
+        match commands::parse(text) {
+            Some(Ok(command)) => return run_command(client, topics, command),
+            Some(Err(usage)) => log::warn!(target: "mqtt", "[MQTT] {}", usage),
+            None => log::warn!(target: "mqtt", "[MQTT] Unknown command '{}'", text.trim()),
         }
     } else if topic == topics.osc_sending_enabled_set {
         // Toggle OSC sending enabled
         let enable = parse_boolean_payload(payload);
-        crate::OSC_SENDING_ENABLED.store(enable, Ordering::SeqCst);
-    if crate::is_debug_enabled() { println!("[MQTT] OSC Sending Enabled -> {}", enable); }
+        run_command(client, topics, Command::OscSending(enable));
         let _ = client.publish(&topics.osc_sending_enabled_state, QoS::AtLeastOnce, true, if enable { "1" } else { "0" });
     } else if topic == topics.osc_send_original_set {
         // Toggle whether to send original (true) or transposed (false)
         let send_orig = parse_boolean_payload(payload);
-        crate::OSC_SEND_ORIGINAL.store(send_orig, Ordering::SeqCst);
-    if crate::is_debug_enabled() { println!("[MQTT] OSC Send Original -> {}", send_orig); }
+        run_command(client, topics, Command::OscSendOriginal(send_orig));
         let _ = client.publish(&topics.osc_send_original_state, QoS::AtLeastOnce, true, if send_orig { "1" } else { "0" });
-    } else if topic == topics.debug_enabled_set {
-        // Toggle Debug enabled (verbose logging)
+        // Stream switch states follow via the message loop
+    } else if topic == topics.osc_original_enabled_set || topic == topics.osc_transposed_enabled_set {
+        // Enable/disable a single OSC stream
+        let enable = parse_boolean_payload(payload);
+        let original = topic == topics.osc_original_enabled_set;
+        let state_topic = if original { &topics.osc_original_enabled_state } else { &topics.osc_transposed_enabled_state };
+        run_command(client, topics, Command::OscStream { original, enabled: enable });
+        let _ = client.publish(state_topic, QoS::AtLeastOnce, true, if enable { "1" } else { "0" });
+    } else if topic == topics.aftertouch_to_cc_set {
+        // Channel pressure -> CC conversion in the forwarder
         let enable = parse_boolean_payload(payload);
-        crate::DEBUG_ENABLED.store(enable, Ordering::SeqCst);
-        // Note: This message is intentionally not gated by debug to ensure visibility if enabled
-        if crate::is_debug_enabled() { println!("[MQTT] Debug Enabled -> {}", enable); }
+        run_command(client, topics, Command::AftertouchToCc(enable));
+        let _ = client.publish(&topics.aftertouch_to_cc_state, QoS::AtLeastOnce, true, if enable { "1" } else { "0" });
+    } else if topic == topics.note_velocity_set {
+        // Per-note velocity parameters of the notes encoding
+        let enable = parse_boolean_payload(payload);
+        run_command(client, topics, Command::NoteVelocity(enable));
+        let _ = client.publish(&topics.note_velocity_state, QoS::AtLeastOnce, true, if enable { "1" } else { "0" });
+    } else if topic == topics.chatbox_status_set {
+        // Transpose-Status in der VRChat-Chatbox
+        let enable = parse_boolean_payload(payload);
+        run_command(client, topics, Command::ChatboxStatus(enable));
+        let _ = client.publish(&topics.chatbox_status_state, QoS::AtLeastOnce, true, if enable { "1" } else { "0" });
+    } else if topic == topics.glide_set {
+        // Transpose-Änderungen in Halbtonschritten
+        let enable = parse_boolean_payload(payload);
+        run_command(client, topics, Command::Glide(enable));
+        let _ = client.publish(&topics.glide_state, QoS::AtLeastOnce, true, if enable { "1" } else { "0" });
+    } else if topic == topics.debug_enabled_set {
+        // Toggle Debug enabled (verbose logging); also accepts timed payloads like "5m" or "on 5m"
+        let text = std::str::from_utf8(payload).unwrap_or("");
+        if commands::execute(Command::Debug(text.to_string()), Source::Mqtt).is_err() {
+            let state = if parse_boolean_payload(payload) { "on" } else { "off" };
+            run_command(client, topics, Command::Debug(state.to_string()));
+        }
+        let enable = crate::is_debug_enabled();
         let _ = client.publish(&topics.debug_enabled_state, QoS::AtLeastOnce, true, if enable { "1" } else { "0" });
+    } else if let Some(category) = topic
+        .strip_prefix(topics.debug_category_set_prefix.as_str())
+        .and_then(|rest| rest.strip_prefix('/'))
+        .and_then(crate::DebugCategory::from_name)
+    {
+        // Toggle a single debug category (state is published by the message loop)
+        run_command(client, topics, Command::DebugCategory(category, parse_boolean_payload(payload)));
+    } else if let Some(subsystem) = topic.strip_prefix(topics.log_level_set_prefix.as_str()).and_then(|rest| rest.strip_prefix('/')) {
+        // Log-Level eines Subsystems, z.B. "debug" (State wird von der Nachrichtenschleife gesendet)
+        let level = std::str::from_utf8(payload).unwrap_or("").trim();
+        run_command(client, topics, Command::Log(format!("{} {}", subsystem, level)));
+    } else if topic == topics.practice_set {
+        // Practice mode on/off (phase is published by the message loop)
+        run_command(client, topics, Command::Practice(parse_boolean_payload(payload)));
+    } else if topic == topics.automation_set {
+        // Start the configured lane or stop playback (state is published by the message loop)
+        if parse_boolean_payload(payload) {
+            if let Err(e) = commands::execute(Command::AutomationStart(None), Source::Mqtt) {
+                log::warn!(target: "mqtt", "[MQTT] {}", e);
+                let _ = client.publish(&topics.automation_state, QoS::AtLeastOnce, true, "0");
+            }
+        } else {
+            run_command(client, topics, Command::AutomationStop);
+        }
+    } else if topic == topics.sequence_set {
+        // Start or stop the configured sequence (state and each step are published by the message loop)
+        if parse_boolean_payload(payload) {
+            if let Err(e) = commands::execute(Command::SequenceStart, Source::Mqtt) {
+                log::warn!(target: "mqtt", "[MQTT] {}", e);
+                let _ = client.publish(&topics.sequence_state, QoS::AtLeastOnce, true, "0");
+            }
+        } else {
+            run_command(client, topics, Command::SequenceStop);
+        }
+    } else if topic == topics.schedule_set {
+        // Schedule a console command (pending list is published by the message loop)
+        let spec = std::str::from_utf8(payload).unwrap_or("").to_string();
+        match commands::execute(Command::Schedule(spec), Source::Mqtt) {
+            Ok(Outcome::Done(message)) => log::info!(target: "mqtt", "[MQTT] {}", message),
+            Ok(_) => {}
+            Err(e) => log::warn!(target: "mqtt", "[MQTT] {}", e),
+        }
+    } else if topic == topics.lfo_set {
+        // LFO on/off (state is published by the message loop)
+        run_command(client, topics, Command::Lfo(parse_boolean_payload(payload)));
+    } else if topic == topics.lfo_depth_set || topic == topics.lfo_rate_set {
+        let text = std::str::from_utf8(payload).unwrap_or("").trim();
+        match text.parse::<f32>() {
+            Ok(v) if topic == topics.lfo_depth_set => {
+                run_command(client, topics, Command::LfoDepth(v));
+            }
+            Ok(v) => {
+                run_command(client, topics, Command::LfoRate(v));
+            }
+            Err(_) => log::warn!(target: "mqtt", "[MQTT] {} requires a number (got '{}')", topic, text),
+        }
+    } else if topic == topics.profile_export || topic == topics.profile_import {
+        // Profile files: payload is a bare file name in the working directory
+        let name = std::str::from_utf8(payload).unwrap_or("").to_string();
+        run_command(client, topics, if topic == topics.profile_export { Command::ExportProfile(name) } else { Command::ImportProfile(name) });
+    } else if topic == topics.state_restore {
+        // Snapshot from <base>/state/full (e.g. stored by a Home Assistant scene)
+        match crate::general::profile::restore(std::str::from_utf8(payload).unwrap_or("")) {
+            Ok(()) => {
+                log::debug!(target: "mqtt", "[MQTT] Runtime state restored from snapshot");
+            }
+            Err(e) => log::warn!(target: "mqtt", "[MQTT] Invalid state snapshot: {}", e),
+        }
+    } else if topic == topics.setlist_next || topic == topics.setlist_prev {
+        // Setlist navigation (song/profile/transpose state is published by the message loop)
+        if parse_boolean_payload(payload) {
+            run_command(client, topics, if topic == topics.setlist_next { Command::SetlistNext } else { Command::SetlistPrev });
+        }
+    } else if let Some(profile) = topic
+        .strip_prefix(topics.profile_scope_prefix.as_str())
+        .and_then(|rest| rest.strip_prefix('/'))
+        .and_then(|rest| rest.strip_suffix("/command"))
+    {
+        // Command for one profile: only runs while that profile is active
+        let text = std::str::from_utf8(payload).unwrap_or("");
+        if crate::general::profile::active().as_deref() != Some(profile) {
+            log::debug!(target: "mqtt", "[MQTT] Ignoring '{}' for inactive profile '{}'", text.trim(), profile);
+            return None;
+        }
+        match commands::parse(text) {
+            Some(Ok(command)) => return run_command(client, topics, command),
+            Some(Err(usage)) => log::warn!(target: "mqtt", "[MQTT] {}", usage),
+            None => log::warn!(target: "mqtt", "[MQTT] Unknown command '{}'", text.trim()),
+        }
+    } else if let Some(slug) = topic.strip_prefix(topics.osc_target_set_prefix.as_str()).and_then(|rest| rest.strip_prefix('/')) {
+        // Ein einzelnes Sendeziel ein-/ausschalten (State kommt über die Message-Loop)
+        run_command(client, topics, Command::OscTarget(slug.to_string(), parse_boolean_payload(payload)));
+    } else if let Some(slug) = topic
+        .strip_prefix(topics.zone_set_prefix.as_str())
+        .and_then(|rest| rest.strip_prefix('/'))
+        .and_then(|rest| rest.strip_suffix("/transpose"))
+    {
+        // Transpose einer Keyboard-Zone (State kommt über die Message-Loop)
+        match parse_transpose_payload(payload) {
+            Some(value) => {
+                run_command(client, topics, Command::Zone(slug.to_string(), value));
+            }
+            None => log::warn!(target: "mqtt", "[MQTT] Invalid zone transpose payload: {:?}", payload),
+        }
+    } else if let Some(key) = topic.strip_prefix(topics.custom_set_prefix.as_str()).and_then(|rest| rest.strip_prefix('/')) {
+        // Custom control value (state is echoed by the message loop). A JSON array sets all
+        // arguments of a multi-argument control, e.g. `[1.0, 0.5, 0.0]`
+        let text = std::str::from_utf8(payload).unwrap_or("").trim();
+        let values = if text.starts_with('[') {
+            match serde_json::from_str::<Vec<f32>>(text) {
+                Ok(values) => values,
+                Err(e) => {
+                    log::warn!(target: "mqtt", "[MQTT] Invalid value list for custom control '{}': {}", key, e);
+                    return None;
+                }
+            }
+        } else {
+            match parse_control_payload(text) {
+                Some(value) => vec![value],
+                None => {
+                    log::warn!(target: "mqtt", "[MQTT] Invalid value for custom control '{}': {:?}", key, text);
+                    return None;
+                }
+            }
+        };
+        run_command(client, topics, Command::Custom(key.to_string(), values));
     }
     
     None
 }
 
-/// Hauptschleife für MQTT-Nachrichten-Verarbeitung
-fn run_mqtt_message_loop(mut connection: rumqttc::Connection, client: &Client, topics: &MqttTopics) {
-    let mut iter = connection.iter();
-    let mut last_state_sent = crate::TRANSPOSE_SEMITONES.load(Ordering::SeqCst);
-    let mut last_osc_enabled = crate::OSC_SENDING_ENABLED.load(Ordering::SeqCst);
-    let mut last_send_original = crate::OSC_SEND_ORIGINAL.load(Ordering::SeqCst);
-    let mut last_debug_enabled = crate::DEBUG_ENABLED.load(Ordering::SeqCst);
+/// Execute a command received over MQTT. Transpose results are published right away
+/// (with clamp attributes); returns the applied transpose for those.
+fn run_command(client: &Client, topics: &MqttTopics, command: Command) -> Option<i32> {
+    match commands::execute(command, Source::Mqtt) {
+        Ok(Outcome::Transpose { requested, applied }) => {
+            log::debug!(target: "mqtt", "[MQTT] Transpose {} -> {}", requested, applied);
+            publish_transpose_result(client, topics, requested, applied);
+            return Some(applied);
+        }
+        Ok(Outcome::Done(message)) => {
+            log::debug!(target: "mqtt", "[MQTT] {}", message);
+        }
+        Ok(Outcome::Silent) => {}
+        Err(e) => log::warn!(target: "mqtt", "[MQTT] {}", e),
+    }
+    None
+}
 
+/// Publish the final states and `offline`, then disconnect once the broker acknowledged them.
+/// A clean DISCONNECT suppresses the last will, so `offline` has to reach the broker before it;
+/// waits for the PubAcks of all publishes still in flight, bounded by `SHUTDOWN_FLUSH_MS`.
+fn flush_and_disconnect(connection: &mut rumqttc::Connection, client: &Client, topics: &MqttTopics) {
+    if !crate::state().mqtt_connected.load(Ordering::SeqCst) {
+        let _ = client.try_disconnect();
+        return;
+    }
+    let final_value = crate::state().transpose.load(Ordering::SeqCst).to_string();
+    let final_publishes = [
+        client.try_publish(&topics.transpose_state, QoS::AtLeastOnce, true, final_value),
+        client.try_publish(&topics.availability, QoS::AtLeastOnce, true, "offline"),
+    ];
+    let expected = final_publishes.iter().filter(|r| r.is_ok()).count();
+
+    let deadline = Instant::now() + Duration::from_millis(SHUTDOWN_FLUSH_MS);
+    let mut sent = 0;
+    let mut in_flight = std::collections::HashSet::new();
+    let mut disconnecting = false;
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        if !disconnecting && sent >= expected && in_flight.is_empty() {
+            let _ = client.try_disconnect();
+            disconnecting = true;
+        }
+        match connection.recv_timeout(remaining) {
+            Ok(Ok(Event::Outgoing(Outgoing::Publish(pkid)))) => {
+                sent += 1;
+                if pkid != 0 {
+                    in_flight.insert(pkid);
+                }
+            }
+            Ok(Ok(Event::Incoming(Incoming::PubAck(ack)))) => {
+                in_flight.remove(&ack.pkid);
+            }
+            Ok(Ok(Event::Outgoing(Outgoing::Disconnect))) => {
+                log::debug!(target: "mqtt", "[MQTT] Final states flushed, disconnected");
+                return;
+            }
+            Ok(Ok(_)) => {}
+            Ok(Err(_)) | Err(_) => break,
+        }
+    }
+    log::warn!(target: "mqtt", "[MQTT] Shutdown flush incomplete; broker will fall back to the last will");
+    if !disconnecting {
+        let _ = client.try_disconnect();
+    }
+}
+
+/// What the event loop hands to the publisher
+enum MqttEvent {
+    /// Connected (again): subscribe and publish discovery and all states
+    Connected,
+    /// Incoming message (topic, payload)
+    Message(String, Vec<u8>),
+}
+
+/// Drive the connection: reconnect, hand connects and incoming messages to the publisher and
+/// flush the final states on exit. Never publishes with a blocking call itself.
+fn run_mqtt_event_loop(mut connection: rumqttc::Connection, client: &Client, topics: &MqttTopics, events: mpsc::Sender<MqttEvent>) {
     loop {
-        // Prüfe Exit-Flag
-        if crate::EXIT_FLAG.load(Ordering::SeqCst) {
-            if crate::is_debug_enabled() { println!("[MQTT] Shutdown requested, stopping listener"); }
-            // Versuche sauberes Disconnect, ignorieren bei Fehlern
-            let _ = client.disconnect();
+        if crate::state().exit.load(Ordering::SeqCst) {
+            log::debug!(target: "mqtt", "[MQTT] Shutdown requested, stopping listener");
+            flush_and_disconnect(&mut connection, client, topics);
+            break;
+        }
+        let event = match connection.recv_timeout(Duration::from_millis(LOOP_TICK_MS)) {
+            Ok(Ok(Event::Incoming(Incoming::Publish(publish)))) => MqttEvent::Message(publish.topic, publish.payload.to_vec()),
+            Ok(Ok(Event::Incoming(Incoming::ConnAck(ack)))) => {
+                log::debug!(target: "mqtt", "[MQTT] ConnAck: session_present={}, code={:?}", ack.session_present, ack.code);
+                crate::state().mqtt_connected.store(true, Ordering::SeqCst);
+                MqttEvent::Connected
+            }
+            Ok(Ok(_)) | Err(RecvTimeoutError::Timeout) => continue,
+            Ok(Err(e)) => {
+                log::error!(target: "mqtt", "[MQTT] Connection error: {} (reconnecting in {}s)", e, RECONNECT_DELAY_SECS);
+                // On connection error, mark disconnected and show red banner (only if MQTT enabled)
+                crate::state().mqtt_connected.store(false, Ordering::SeqCst);
+                crate::general::check::report(crate::general::check::MQTT, crate::general::check::ComponentState::Broken, e.to_string());
+                if crate::state().mqtt_enabled.load(Ordering::SeqCst) {
+                    crate::general::check::print_connections_broken();
+                }
+                thread::sleep(Duration::from_secs(RECONNECT_DELAY_SECS));
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                log::warn!(target: "mqtt", "[MQTT] Connection iterator ended");
+                break;
+            }
+        };
+        if events.send(event).is_err() {
             break;
         }
+    }
+    log::debug!(target: "mqtt", "[MQTT] Listener loop terminated");
+}
+
+/// Handle connects and incoming messages from the event loop, and publish every state change
+/// (from MQTT or any other source) within one loop tick. Ends with the event loop.
+fn run_mqtt_publisher(events: mpsc::Receiver<MqttEvent>, client: &Client, topics: &MqttTopics) {
+    let mut last_state_sent = crate::state().transpose.load(Ordering::SeqCst);
+    let mut last_octave = crate::state().octave.load(Ordering::SeqCst);
+    let mut last_osc_enabled = crate::state().osc_sending.load(Ordering::SeqCst);
+    let mut last_original_enabled = crate::state().osc_original.load(Ordering::SeqCst);
+    let mut last_transposed_enabled = crate::state().osc_transposed.load(Ordering::SeqCst);
+    let mut last_aftertouch_to_cc = crate::state().aftertouch_to_cc.load(Ordering::SeqCst);
+    let mut last_note_velocity = crate::osc_sender::note_velocity();
+    let mut last_chatbox_status = crate::remote::chatbox::status_enabled();
+    let mut last_glide = crate::general::glide::is_enabled();
+    let mut last_debug_enabled = crate::state().debug.load(Ordering::SeqCst);
+    let mut last_debug_categories = crate::DebugCategory::ALL.map(crate::is_debug_enabled_for);
+    let mut last_log_generation = crate::general::logging::generation();
+    let mut last_custom_generation = crate::custom_controls::generation();
+    let mut last_zone_generation = crate::general::zones::generation();
+    let mut last_osc_targets_generation = crate::osc_sender::targets_generation();
+    let mut last_total_dropped = crate::general::queue::total_dropped();
+    let mut last_latency_publish = Instant::now();
+    let mut last_latency_count = crate::general::latency::snapshot().count;
+    let mut last_setlist_generation = (crate::general::setlist::generation(), crate::general::profile::generation());
+    let mut last_practice_generation = crate::general::practice::generation();
+    let mut last_automation_generation = crate::general::automation::generation();
+    let mut last_sequence_generation = crate::general::sequence::generation();
+    let mut last_now_playing_generation = crate::general::now_playing::generation();
+    let mut last_history_seq = crate::general::history::generation();
+    let mut last_lfo_generation = crate::general::lfo::generation();
+    let mut last_schedule_generation = crate::general::scheduler::generation();
+    let mut last_osc_listener_generation = crate::general::check::osc_listener_generation();
+    let mut last_midi_generation = crate::general::check::midi_generation();
+    let mut last_program_generation = crate::general::program::generation();
+    let mut last_snapshot = String::new();
 
-        // Verarbeite nächste MQTT-Nachricht
-        if let Some(result) = iter.next() {
-            match result {
-                Ok(Event::Incoming(Incoming::Publish(publish))) => {
-                    let topic = publish.topic.as_str();
-                    let payload = publish.payload.as_ref();
-                    
-                    if let Some(new_value) = handle_mqtt_message(client, topics, topic, payload) {
-                        last_state_sent = new_value;
-                    }
+    loop {
+        match events.recv_timeout(Duration::from_millis(LOOP_TICK_MS)) {
+            Ok(MqttEvent::Message(topic, payload)) => {
+                if let Some(new_value) = handle_mqtt_message(client, topics, &topic, &payload) {
+                    last_state_sent = new_value;
                 }
-                Ok(Event::Incoming(Incoming::ConnAck(ack))) => {
-                    if crate::is_debug_enabled() { println!("[MQTT] ConnAck: session_present={}, code={:?}", ack.session_present, ack.code); }
-                    // Mark connected; print green banner after we finished setup below
-                    crate::MQTT_CONNECTED.store(true, Ordering::SeqCst);
-
-                    // Beim (Re-)Connect: subscriben und initiale States/Discovery publizieren
-                    if let Err(e) = subscribe_to_topics(client, topics) {
-                        eprintln!("[MQTT] Subscription failed: {}", e);
-                    }
-
-                    // Discovery und Anfangszustände publizieren (einmal je Start; bei Reconnect erneut okay)
-                    publish_homeassistant_discovery(client, topics);
-                    let _ = client.publish(&topics.availability, QoS::AtLeastOnce, true, "online");
-                    let initial_value = crate::TRANSPOSE_SEMITONES.load(Ordering::SeqCst).to_string();
-                    let _ = client.publish(&topics.transpose_state, QoS::AtLeastOnce, true, initial_value);
-                    let osc_enabled = if crate::OSC_SENDING_ENABLED.load(Ordering::SeqCst) { "1" } else { "0" };
-                    let _ = client.publish(&topics.osc_sending_enabled_state, QoS::AtLeastOnce, true, osc_enabled);
-                    let send_orig = if crate::OSC_SEND_ORIGINAL.load(Ordering::SeqCst) { "1" } else { "0" };
-                    let _ = client.publish(&topics.osc_send_original_state, QoS::AtLeastOnce, true, send_orig);
-                    let debug_enabled = if crate::DEBUG_ENABLED.load(Ordering::SeqCst) { "1" } else { "0" };
-                    let _ = client.publish(&topics.debug_enabled_state, QoS::AtLeastOnce, true, debug_enabled);
-                    // initial state published after ConnAck
-                    // Now that subscriptions and discovery/state publishes are done, show green banner
-                    if crate::MQTT_ENABLED.load(Ordering::SeqCst) {
-                        crate::general::check::print_connections_active();
-                    }
+            }
+            Ok(MqttEvent::Connected) => {
+                // Beim (Re-)Connect: subscriben und initiale States/Discovery publizieren
+                if let Err(e) = subscribe_to_topics(client, topics) {
+                    log::error!(target: "mqtt", "[MQTT] Subscription failed: {}", e);
                 }
-                Ok(_) => {
-                    // Ignore other events
+
+                // Discovery und Anfangszustände publizieren (einmal je Start; bei Reconnect erneut okay)
+                publish_homeassistant_discovery(client, topics);
+                let _ = client.publish(&topics.availability, QoS::AtLeastOnce, true, "online");
+                let initial_value = crate::state().transpose.load(Ordering::SeqCst).to_string();
+                let _ = client.publish(&topics.transpose_state, QoS::AtLeastOnce, true, initial_value);
+                let octave = crate::state().octave.load(Ordering::SeqCst).to_string();
+                let _ = client.publish(&topics.octave_state, QoS::AtLeastOnce, true, octave);
+                let osc_enabled = if crate::state().osc_sending.load(Ordering::SeqCst) { "1" } else { "0" };
+                let _ = client.publish(&topics.osc_sending_enabled_state, QoS::AtLeastOnce, true, osc_enabled);
+                let send_orig = if crate::state().osc_original.load(Ordering::SeqCst) { "1" } else { "0" };
+                let _ = client.publish(&topics.osc_send_original_state, QoS::AtLeastOnce, true, send_orig);
+                let _ = client.publish(&topics.osc_original_enabled_state, QoS::AtLeastOnce, true, send_orig);
+                let transposed = if crate::state().osc_transposed.load(Ordering::SeqCst) { "1" } else { "0" };
+                let _ = client.publish(&topics.osc_transposed_enabled_state, QoS::AtLeastOnce, true, transposed);
+                let aftertouch = if crate::state().aftertouch_to_cc.load(Ordering::SeqCst) { "1" } else { "0" };
+                let _ = client.publish(&topics.aftertouch_to_cc_state, QoS::AtLeastOnce, true, aftertouch);
+                let note_velocity = if crate::osc_sender::note_velocity() { "1" } else { "0" };
+                let _ = client.publish(&topics.note_velocity_state, QoS::AtLeastOnce, true, note_velocity);
+                let chatbox_status = if crate::remote::chatbox::status_enabled() { "1" } else { "0" };
+                let _ = client.publish(&topics.chatbox_status_state, QoS::AtLeastOnce, true, chatbox_status);
+                let glide = if crate::general::glide::is_enabled() { "1" } else { "0" };
+                let _ = client.publish(&topics.glide_state, QoS::AtLeastOnce, true, glide);
+                let debug_enabled = if crate::state().debug.load(Ordering::SeqCst) { "1" } else { "0" };
+                let _ = client.publish(&topics.debug_enabled_state, QoS::AtLeastOnce, true, debug_enabled);
+                for category in crate::DebugCategory::ALL {
+                    let state = if crate::is_debug_enabled_for(category) { "1" } else { "0" };
+                    let _ = client.publish(format!("{}/{}", topics.debug_category_state_prefix, category.name()), QoS::AtLeastOnce, true, state);
                 }
-                Err(e) => {
-                    eprintln!("[MQTT] Connection error: {} (reconnecting in {}s)", e, RECONNECT_DELAY_SECS);
-                    // On connection error, mark disconnected and show red banner (only if MQTT enabled)
-                    crate::MQTT_CONNECTED.store(false, Ordering::SeqCst);
-                    if crate::MQTT_ENABLED.load(Ordering::SeqCst) {
-                        crate::general::check::print_connections_broken();
-                    }
-                    thread::sleep(Duration::from_secs(RECONNECT_DELAY_SECS));
+                publish_log_levels(client, topics);
+                publish_zone_states(client, topics);
+                publish_custom_control_states(client, topics);
+                publish_osc_target_states(client, topics);
+                publish_queue_diagnostics(client, topics);
+                publish_latency(client, topics);
+                publish_setlist_state(client, topics);
+                publish_practice_state(client, topics);
+                publish_automation_state(client, topics);
+                publish_sequence_state(client, topics);
+                let _ = client.publish(&topics.now_playing_state, QoS::AtLeastOnce, true, crate::general::now_playing::title());
+                let _ = client.publish(&topics.transpose_history_buffer, QoS::AtLeastOnce, true, crate::general::history::json());
+                publish_lfo_state(client, topics);
+                publish_schedule_state(client, topics);
+                let _ = client.publish(&topics.osc_listener_state, QoS::AtLeastOnce, true, crate::general::check::osc_listener_status());
+                let _ = client.publish(&topics.midi_devices_state, QoS::AtLeastOnce, true, crate::general::check::midi_status());
+                publish_program_state(client, topics);
+                last_snapshot = crate::general::profile::snapshot_json();
+                let _ = client.publish(&topics.full_state, QoS::AtLeastOnce, true, last_snapshot.clone());
+                let _ = client.publish(&topics.capabilities, QoS::AtLeastOnce, true, crate::remote::capabilities::document());
+                publish_profile_scoped_state(client, topics, &last_snapshot);
+                for note in &crate::get_config().mqtt.trigger_notes {
+                    let _ = client.publish(format!("{}/{}", topics.notes_state_prefix, note), QoS::AtMostOnce, false, "0");
+                }
+                // initial state published after ConnAck
+                let broker = crate::osc_sender::join_host_port(&crate::get_config().mqtt.broker_host, crate::get_config().mqtt.broker_port);
+                crate::general::check::report(crate::general::check::MQTT, crate::general::check::ComponentState::Up, format!("connected to {}", broker));
+                // Now that subscriptions and discovery/state publishes are done, show green banner
+                if crate::state().mqtt_enabled.load(Ordering::SeqCst) {
+                    crate::general::check::print_connections_active();
                 }
             }
-        } else {
-            eprintln!("[MQTT] Connection iterator ended");
-            if crate::is_debug_enabled() { println!("[MQTT] Connection iterator ended"); }
-            break;
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
         }
 
         // Publiziere Zustandsänderung von anderen Quellen (stdin/OSC)
-        let current_value = crate::TRANSPOSE_SEMITONES.load(Ordering::SeqCst);
+        let current_value = crate::state().transpose.load(Ordering::SeqCst);
         if current_value != last_state_sent {
             let _ = client.publish(
                 &topics.transpose_state,
@@ -467,8 +1562,14 @@ fn run_mqtt_message_loop(mut connection: rumqttc::Connection, client: &Client, t
             last_state_sent = current_value;
         }
 
+        let octave_now = crate::state().octave.load(Ordering::SeqCst);
+        if octave_now != last_octave {
+            let _ = client.publish(&topics.octave_state, QoS::AtLeastOnce, true, octave_now.to_string());
+            last_octave = octave_now;
+        }
+
         // Publish OSC switch state changes (if altered externally)
-        let osc_enabled_now = crate::OSC_SENDING_ENABLED.load(Ordering::SeqCst);
+        let osc_enabled_now = crate::state().osc_sending.load(Ordering::SeqCst);
         if osc_enabled_now != last_osc_enabled {
             let _ = client.publish(
                 &topics.osc_sending_enabled_state,
@@ -479,19 +1580,52 @@ fn run_mqtt_message_loop(mut connection: rumqttc::Connection, client: &Client, t
             last_osc_enabled = osc_enabled_now;
         }
 
-        let send_original_now = crate::OSC_SEND_ORIGINAL.load(Ordering::SeqCst);
-        if send_original_now != last_send_original {
+        // "Send Original" mirrors the original stream switch
+        let original_enabled_now = crate::state().osc_original.load(Ordering::SeqCst);
+        if original_enabled_now != last_original_enabled {
+            let payload = if original_enabled_now { "1" } else { "0" };
+            let _ = client.publish(&topics.osc_send_original_state, QoS::AtLeastOnce, true, payload);
+            let _ = client.publish(&topics.osc_original_enabled_state, QoS::AtLeastOnce, true, payload);
+            last_original_enabled = original_enabled_now;
+        }
+
+        let transposed_enabled_now = crate::state().osc_transposed.load(Ordering::SeqCst);
+        if transposed_enabled_now != last_transposed_enabled {
             let _ = client.publish(
-                &topics.osc_send_original_state,
+                &topics.osc_transposed_enabled_state,
                 QoS::AtLeastOnce,
                 true,
-                if send_original_now { "1" } else { "0" },
+                if transposed_enabled_now { "1" } else { "0" },
             );
-            last_send_original = send_original_now;
+            last_transposed_enabled = transposed_enabled_now;
+        }
+
+        let aftertouch_to_cc_now = crate::state().aftertouch_to_cc.load(Ordering::SeqCst);
+        if aftertouch_to_cc_now != last_aftertouch_to_cc {
+            let _ = client.publish(&topics.aftertouch_to_cc_state, QoS::AtLeastOnce, true, if aftertouch_to_cc_now { "1" } else { "0" });
+            last_aftertouch_to_cc = aftertouch_to_cc_now;
+        }
+
+        let note_velocity_now = crate::osc_sender::note_velocity();
+        if note_velocity_now != last_note_velocity {
+            let _ = client.publish(&topics.note_velocity_state, QoS::AtLeastOnce, true, if note_velocity_now { "1" } else { "0" });
+            last_note_velocity = note_velocity_now;
+        }
+
+        let chatbox_status_now = crate::remote::chatbox::status_enabled();
+        if chatbox_status_now != last_chatbox_status {
+            let _ = client.publish(&topics.chatbox_status_state, QoS::AtLeastOnce, true, if chatbox_status_now { "1" } else { "0" });
+            last_chatbox_status = chatbox_status_now;
+        }
+
+        let glide_now = crate::general::glide::is_enabled();
+        if glide_now != last_glide {
+            let _ = client.publish(&topics.glide_state, QoS::AtLeastOnce, true, if glide_now { "1" } else { "0" });
+            last_glide = glide_now;
         }
 
         // Publish Debug switch state changes
-        let debug_enabled_now = crate::DEBUG_ENABLED.load(Ordering::SeqCst);
+        let debug_enabled_now = crate::state().debug.load(Ordering::SeqCst);
         if debug_enabled_now != last_debug_enabled {
             let _ = client.publish(
                 &topics.debug_enabled_state,
@@ -502,8 +1636,225 @@ fn run_mqtt_message_loop(mut connection: rumqttc::Connection, client: &Client, t
             last_debug_enabled = debug_enabled_now;
         }
 
-        // Vermeide Busy-Loop
-        thread::sleep(Duration::from_millis(LOOP_DELAY_MS));
+        let debug_categories_now = crate::DebugCategory::ALL.map(crate::is_debug_enabled_for);
+        for (i, category) in crate::DebugCategory::ALL.into_iter().enumerate() {
+            if debug_categories_now[i] != last_debug_categories[i] {
+                let _ = client.publish(
+                    format!("{}/{}", topics.debug_category_state_prefix, category.name()),
+                    QoS::AtLeastOnce,
+                    true,
+                    if debug_categories_now[i] { "1" } else { "0" },
+                );
+            }
+        }
+        last_debug_categories = debug_categories_now;
+
+        let log_generation_now = crate::general::logging::generation();
+        if log_generation_now != last_log_generation {
+            publish_log_levels(client, topics);
+            last_log_generation = log_generation_now;
+        }
+
+        let osc_targets_generation_now = crate::osc_sender::targets_generation();
+        if osc_targets_generation_now != last_osc_targets_generation {
+            publish_osc_target_states(client, topics);
+            last_osc_targets_generation = osc_targets_generation_now;
+        }
+
+        let zone_generation_now = crate::general::zones::generation();
+        if zone_generation_now != last_zone_generation {
+            publish_zone_states(client, topics);
+            last_zone_generation = zone_generation_now;
+        }
+
+        // Publish custom control changes (from MQTT or OSC)
+        let custom_generation_now = crate::custom_controls::generation();
+        if custom_generation_now != last_custom_generation {
+            publish_custom_control_states(client, topics);
+            last_custom_generation = custom_generation_now;
+        }
+
+        // Publish song/profile changes
+        let setlist_generation_now = (crate::general::setlist::generation(), crate::general::profile::generation());
+        if setlist_generation_now != last_setlist_generation {
+            publish_setlist_state(client, topics);
+            last_setlist_generation = setlist_generation_now;
+        }
+
+        // Publish practice mode start/stop and key switches
+        let practice_generation_now = crate::general::practice::generation();
+        if practice_generation_now != last_practice_generation {
+            publish_practice_state(client, topics);
+            last_practice_generation = practice_generation_now;
+        }
+
+        // Publish automation start/stop/finish
+        let automation_generation_now = crate::general::automation::generation();
+        if automation_generation_now != last_automation_generation {
+            publish_automation_state(client, topics);
+            last_automation_generation = automation_generation_now;
+        }
+
+        let sequence_generation_now = crate::general::sequence::generation();
+        if sequence_generation_now != last_sequence_generation {
+            publish_sequence_state(client, topics);
+            last_sequence_generation = sequence_generation_now;
+        }
+
+        // Publish the announced song title
+        let now_playing_generation_now = crate::general::now_playing::generation();
+        if now_playing_generation_now != last_now_playing_generation {
+            let _ = client.publish(&topics.now_playing_state, QoS::AtLeastOnce, true, crate::general::now_playing::title());
+            last_now_playing_generation = now_playing_generation_now;
+        }
+
+        // Publish each transpose change with its timestamp, then the updated buffer
+        let history_seq_now = crate::general::history::generation();
+        if history_seq_now != last_history_seq {
+            for change in crate::general::history::changes_since(last_history_seq) {
+                if let Ok(event) = serde_json::to_string(&change) {
+                    // An event, not a state: a retained copy would replay the last change to every new subscriber
+                    let _ = client.publish(&topics.transpose_history, QoS::AtLeastOnce, false, event);
+                }
+            }
+            let _ = client.publish(&topics.transpose_history_buffer, QoS::AtLeastOnce, true, crate::general::history::json());
+            last_history_seq = history_seq_now;
+        }
+
+        // Publish LFO switch/depth/rate changes
+        let lfo_generation_now = crate::general::lfo::generation();
+        if lfo_generation_now != last_lfo_generation {
+            publish_lfo_state(client, topics);
+            last_lfo_generation = lfo_generation_now;
+        }
+
+        // Publish the pending scheduled actions when one was added, run or cancelled
+        let schedule_generation_now = crate::general::scheduler::generation();
+        if schedule_generation_now != last_schedule_generation {
+            publish_schedule_state(client, topics);
+            last_schedule_generation = schedule_generation_now;
+        }
+
+        // Publish the OSC listener status once it bound (or failed to bind)
+        let osc_listener_generation_now = crate::general::check::osc_listener_generation();
+        if osc_listener_generation_now != last_osc_listener_generation {
+            let _ = client.publish(&topics.osc_listener_state, QoS::AtLeastOnce, true, crate::general::check::osc_listener_status());
+            last_osc_listener_generation = osc_listener_generation_now;
+        }
+
+        // MIDI devices unplugged or reconnected
+        let midi_generation_now = crate::general::check::midi_generation();
+        if midi_generation_now != last_midi_generation {
+            let _ = client.publish(&topics.midi_devices_state, QoS::AtLeastOnce, true, crate::general::check::midi_status());
+            last_midi_generation = midi_generation_now;
+        }
+
+        let program_generation_now = crate::general::program::generation();
+        if program_generation_now != last_program_generation {
+            publish_program_state(client, topics);
+            last_program_generation = program_generation_now;
+        }
+
+        // Publish the full state snapshot whenever any part of it changed
+        let snapshot_now = crate::general::profile::snapshot_json();
+        if snapshot_now != last_snapshot {
+            let _ = client.publish(&topics.full_state, QoS::AtLeastOnce, true, snapshot_now.clone());
+            publish_profile_scoped_state(client, topics, &snapshot_now);
+            last_snapshot = snapshot_now;
+        }
+
+        // Publish drop counters when anything was dropped since the last publish
+        let total_dropped_now = crate::general::queue::total_dropped();
+        if total_dropped_now != last_total_dropped {
+            publish_queue_diagnostics(client, topics);
+            last_total_dropped = total_dropped_now;
+        }
+
+        // Publish the latency statistics every few seconds while MIDI is flowing (or after a reset)
+        if last_latency_publish.elapsed() >= Duration::from_secs(LATENCY_PUBLISH_SECS) {
+            let count_now = crate::general::latency::snapshot().count;
+            if count_now != last_latency_count {
+                publish_latency(client, topics);
+                last_latency_count = count_now;
+            }
+            last_latency_publish = Instant::now();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_device() -> DiscoveryDevice {
+        DiscoveryDevice {
+            identifiers: vec!["device_id".to_string()],
+            name: "Device".to_string(),
+            manufacturer: DEVICE_MANUFACTURER,
+            model: DEVICE_MODEL,
+        }
+    }
+
+    fn to_json(entity: DiscoveryEntity) -> serde_json::Value {
+        let text = serde_json::to_string_pretty(&entity).expect("serializable");
+        serde_json::from_str(&text).expect("valid JSON")
+    }
+
+    #[test]
+    fn quotes_in_names_produce_valid_json() {
+        let name = r#"Pad "FX" \ 2"#;
+        let entity = DiscoveryEntity::new(name, "client", "custom_pad_fx_2", "base/availability", &test_device())
+            .switch("base/custom/pad_fx_2", "base/state/custom/pad_fx_2");
+        let json = to_json(entity);
+        assert_eq!(json["name"], name);
+        assert_eq!(json["unique_id"], "client_custom_pad_fx_2");
+    }
+
+    #[test]
+    fn unset_fields_are_omitted() {
+        let entity = DiscoveryEntity::new("Current Song", "client", "current_song", "base/availability", &test_device())
+            .sensor("base/state/setlist/song");
+        let json = to_json(entity);
+        let keys: Vec<&str> = json.as_object().unwrap().keys().map(String::as_str).collect();
+        assert_eq!(keys, ["name", "unique_id", "state_topic", "availability_topic", "device"]);
+        assert_eq!(json["device"]["identifiers"][0], "device_id");
+    }
+
+    #[test]
+    fn switch_and_number_fields() {
+        let switch = to_json(DiscoveryEntity::new("S", "c", "s", "a", &test_device()).switch("cmd", "state"));
+        assert_eq!(switch["command_topic"], "cmd");
+        assert_eq!(switch["payload_on"], "1");
+        assert_eq!(switch["state_off"], "0");
+
+        let number = to_json(DiscoveryEntity::new("N", "c", "n", "a", &test_device()).number("cmd", "state", -24.0, 24.0, 1.0));
+        assert_eq!(number["min"], -24.0);
+        assert_eq!(number["max"], 24.0);
+        assert_eq!(number["step"], 1.0);
+        assert!(number.get("payload_on").is_none());
+    }
+
+    #[test]
+    fn metadata_by_exact_id_and_prefix() {
+        let debug = to_json(DiscoveryEntity::new("Debug MIDI", "c", "debug_midi", "a", &test_device()).with_metadata("debug_midi"));
+        assert_eq!(debug["entity_category"], "diagnostic");
+        assert_eq!(debug["icon"], "mdi:bug");
+
+        let transpose = to_json(DiscoveryEntity::new("MIDI Transpose", "c", "transpose", "a", &test_device()).with_metadata("transpose"));
+        assert!(transpose.get("entity_category").is_none());
+        assert_eq!(transpose["icon"], "mdi:piano");
+
+        // "transpose" must not match "transpose_up" as a prefix
+        assert_eq!(entity_metadata("transpose_up").and_then(|m| m.icon), Some("mdi:arrow-up-bold"));
+        assert!(entity_metadata("custom_pad_fx").is_none());
+    }
+
+    #[test]
+    fn control_payloads() {
+        assert_eq!(parse_control_payload("0.5"), Some(0.5));
+        assert_eq!(parse_control_payload("ON"), Some(1.0));
+        assert_eq!(parse_control_payload("off"), Some(0.0));
+        assert_eq!(parse_control_payload("abc"), None);
+        assert_eq!(parse_control_payload(""), None);
     }
-    if crate::is_debug_enabled() { println!("[MQTT] Listener loop terminated"); }
 }
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::net::UdpSocket;
+use rosc::{decoder, OscMessage, OscPacket, OscType};
+
+/// Command line flag selecting the OSC receiver debug tool
+pub const LISTEN_DEBUG_FLAG: &str = "--listen-debug";
+
+/// Port given with `--listen-debug <port>`, if the flag is present.
+/// A missing or invalid port is an error.
+pub fn listen_debug_port(args: &[String]) -> Option<Result<u16, String>> {
+    let idx = args.iter().position(|a| a == LISTEN_DEBUG_FLAG)?;
+    Some(match args.get(idx + 1) {
+        Some(port) => port.parse().map_err(|_| format!("{}: invalid port '{}'", LISTEN_DEBUG_FLAG, port)),
+        None => Err(format!("{} needs a port, e.g. {} 9000", LISTEN_DEBUG_FLAG, LISTEN_DEBUG_FLAG)),
+    })
+}
+
+/// Note numbers by avatar parameter name, for every name the sender can produce
+struct NoteNames {
+    notes: HashMap<String, u8>,
+    // Octave number and its lowest note (C) by octave parameter name
+    octaves: HashMap<String, (i32, u8)>,
+}
+
+impl NoteNames {
+    fn new() -> Self {
+        let mut notes = HashMap::new();
+        let mut octaves = HashMap::new();
+        for note in 0..=127u8 {
+            notes.insert(crate::osc_sender::note_parameter(note), note);
+            let octave = crate::general::notes::osc_octave(note);
+            octaves.entry(crate::osc_sender::octave_parameter(octave)).or_insert((octave, note));
+        }
+        NoteNames { notes, octaves }
+    }
+
+    /// Human-readable meaning of a message sent by the transposer, if it is one of ours
+    fn describe(&self, msg: &OscMessage) -> Option<String> {
+        if let Some(&note) = self.notes.get(&msg.addr) {
+            let state = match msg.args.first() {
+                Some(OscType::Int(0)) => "off",
+                _ => "on",
+            };
+            return Some(format!("note {} (MIDI {}) {}", crate::general::notes::display_name(note), note, state));
+        }
+        if let Some(&(octave, lowest)) = self.octaves.get(&msg.addr) {
+            return Some(match msg.args.first() {
+                Some(OscType::Float(v)) if *v > 0.0 => {
+                    // Value k/12 is the k-th note of the octave (C = 1/12)
+                    let note = lowest.saturating_add(((v * 12.0).round() as u8).saturating_sub(1));
+                    format!("octave {}: {} (MIDI {})", octave, crate::general::notes::display_name(note), note)
+                }
+                _ => format!("octave {}: silent", octave),
+            });
+        }
+        if let Some(&note) = msg.addr.strip_suffix("_v").and_then(|addr| self.notes.get(addr)) {
+            if let Some(OscType::Float(v)) = msg.args.first() {
+                return Some(format!("note {} (MIDI {}) velocity {}", crate::general::notes::display_name(note), note, (v * 127.0).round()));
+            }
+        }
+        let param = msg.addr.strip_prefix("/avatar/parameters/")?;
+        if let Some(index) = param.strip_prefix("KeyBits").and_then(|i| i.parse::<u8>().ok()).filter(|i| *i < 16) {
+            if let Some(OscType::Int(bits)) = msg.args.first() {
+                let held: Vec<String> = (0..8u8)
+                    .filter(|bit| bits & (1 << bit) != 0)
+                    .map(|bit| crate::general::notes::display_name(index * 8 + bit))
+                    .collect();
+                return Some(format!("keys {}..{} held: {}", index * 8, index * 8 + 7, if held.is_empty() { "-".to_string() } else { held.join(" ") }));
+            }
+        }
+        if param == "KeyBitmap" {
+            if let Some(OscType::Blob(bytes)) = msg.args.first() {
+                let held: Vec<String> = bytes
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(i, byte)| (0..8u8).filter(move |bit| byte & (1 << bit) != 0).map(move |bit| i as u8 * 8 + bit))
+                    .filter(|n| *n <= 127)
+                    .map(crate::general::notes::display_name)
+                    .collect();
+                return Some(format!("held: {}", if held.is_empty() { "-".to_string() } else { held.join(" ") }));
+            }
+        }
+        match param {
+            "PitchUp" | "PitchDown" | "Velocity" => Some(param.to_lowercase()),
+            "OutOfRange" => Some("out-of-range warning".to_string()),
+            "OscSeq" => Some("sequence counter".to_string()),
+            _ => None,
+        }
+    }
+}
+
+fn format_arg(arg: &OscType) -> String {
+    match arg {
+        OscType::Int(v) => format!("int {}", v),
+        OscType::Long(v) => format!("long {}", v),
+        OscType::Float(v) => format!("float {}", v),
+        OscType::Double(v) => format!("double {}", v),
+        OscType::Bool(v) => format!("bool {}", v),
+        OscType::String(v) => format!("string {:?}", v),
+        OscType::Blob(v) => format!("blob[{}] {:02x?}", v.len(), v),
+        other => format!("{:?}", other),
+    }
+}
+
+fn print_packet(names: &NoteNames, packet: &OscPacket, indent: usize) {
+    let pad = " ".repeat(indent);
+    match packet {
+        OscPacket::Message(msg) => {
+            let args: Vec<String> = msg.args.iter().map(format_arg).collect();
+            match names.describe(msg) {
+                Some(meaning) => crate::io::console::print_line(format!("{}{} [{}]  -> {}", pad, msg.addr, args.join(", "), meaning)),
+                None => crate::io::console::print_line(format!("{}{} [{}]", pad, msg.addr, args.join(", "))),
+            }
+        }
+        OscPacket::Bundle(bundle) => {
+            crate::io::console::print_line(format!("{}bundle ({} elements)", pad, bundle.content.len()));
+            for inner in &bundle.content {
+                print_packet(names, inner, indent + 2);
+            }
+        }
+    }
+}
+
+/// Receive OSC on all interfaces at `port` and pretty-print every message (until the process is killed).
+/// Point the transposer's `sending_port` here to verify what VRChat would receive.
+pub fn run_listen_debug(port: u16) -> Result<(), Box<dyn std::error::Error>> {
+    let socket = UdpSocket::bind(("0.0.0.0", port))?;
+    crate::io::console::print_line(format!("OSC debug receiver listening on 0.0.0.0:{} (Ctrl+C to stop)", port));
+    let names = NoteNames::new();
+    let mut buf = [0u8; decoder::MTU];
+    loop {
+        match socket.recv_from(&mut buf) {
+            Ok((size, peer)) => match decoder::decode_udp(&buf[..size]) {
+                Ok((_, packet)) => {
+                    crate::io::console::print_text(format!("{} ", peer));
+                    print_packet(&names, &packet, 0);
+                }
+                Err(e) => crate::io::console::print_error_line(format!("{} sent {} undecodable bytes: {}", peer, size, e)),
+            },
+            Err(e) => crate::io::console::print_error_line(format!("[OSC] Receive failed: {}", e)),
+        }
+    }
+}
@@ -1,151 +1,460 @@
 use std::thread;
-use std::sync::atomic::Ordering;
-use std::net::UdpSocket;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::collections::HashMap;
+use std::sync::Mutex;
 use std::time::Duration;
-use rosc::{OscPacket, OscType, decoder};
+use rosc::{OscMessage, OscPacket, OscType, decoder, encoder};
+use crate::general::check::ComponentState;
+use crate::general::commands::{self, Command, Outcome, Source};
+
+/// Last peer that set a custom control; it receives custom control state echoes. Only the
+/// listener thread reads and writes it, so it stays here rather than in `AppState`.
+static LAST_CONTROLLER: Mutex<Option<SocketAddr>> = Mutex::new(None);
+// Last value of every avatar parameter mapped in `osc.avatar_parameter_actions`, for edge detection
+static AVATAR_PARAMETERS: Mutex<Option<HashMap<String, f32>>> = Mutex::new(None);
+
+/// Action driven by an incoming avatar parameter (`osc.avatar_parameter_actions`), e.g. bound
+/// to a hand gesture or an expression menu control
+#[derive(Debug, serde::Deserialize, Clone, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum AvatarParameterAction {
+    /// Int parameter value as transpose in semitones
+    Transpose,
+    /// Float parameter in -1..1 across the transpose range (radial puppet)
+    TransposeNormalized,
+    /// OSC sending follows a Bool parameter
+    OscSending,
+    /// All notes off when the parameter turns on
+    Panic,
+    /// Any console command when the parameter turns on, e.g. `"up"` or `"profile Live"`
+    Command { command: String },
+}
+
+// Port the listener is bound to, 0 while it isn't running
+static LISTENING_PORT: AtomicU16 = AtomicU16::new(0);
+
+/// Port the OSC listener is bound to (the configured one or a fallback), while it runs
+pub fn listening_port() -> Option<u16> {
+    Some(LISTENING_PORT.load(Ordering::SeqCst)).filter(|port| *port != 0)
+}
+
+/// Bind the listening socket on `listening_port`, falling back to `listening_port_fallbacks` in order
+/// while ports are in use (e.g. VRCOSC took 9001). Records the result for `status`, the banner and MQTT.
+fn bind_listener(osc: &crate::OscConfig) -> Option<(UdpSocket, String)> {
+    let mut ports = vec![osc.listening_port];
+    for port in &osc.listening_port_fallbacks {
+        if !ports.contains(port) {
+            ports.push(*port);
+        }
+    }
+    let mut in_use = Vec::new();
+    for port in ports {
+        let bind_addr = crate::osc_sender::join_host_port(&osc.listening_host, port);
+        match UdpSocket::bind(&bind_addr) {
+            Ok(socket) => {
+                if in_use.is_empty() {
+                    crate::general::check::set_osc_listener_status(format!("listening on {}", bind_addr), ComponentState::Up);
+                } else {
+                    log::warn!(target: "osc", "[OSC] Port {} in use{}; listening on fallback {} instead", join_ports(&in_use), instance_label(), bind_addr);
+                    report_clashes(&in_use);
+                    crate::general::check::set_osc_listener_status(format!("listening on {} ({} in use)", bind_addr, join_ports(&in_use)), ComponentState::Degraded);
+                }
+                return Some((socket, bind_addr));
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::AddrInUse => in_use.push(port),
+            Err(err) => {
+                log::error!(target: "osc", "OSC bind failed on {}: {}", bind_addr, err);
+                crate::general::check::set_osc_listener_status(format!("bind failed on {}: {}", bind_addr, err), ComponentState::Broken);
+                return None;
+            }
+        }
+    }
+    log::error!(target: "osc", "OSC bind failed{}: port {} already in use", instance_label(), join_ports(&in_use));
+    report_clashes(&in_use);
+    log::warn!(target: "osc", "[OSC] Another program (e.g. VRCOSC, or a second transposer instance) holds the port; set a free osc.listening_port or add osc.listening_port_fallbacks");
+    crate::general::check::set_osc_listener_status(format!("not listening, {} in use", join_ports(&in_use)), ComponentState::Broken);
+    None
+}
+
+/// ` for instance '<name>'` when running as a named instance
+fn instance_label() -> String {
+    crate::general::instance::instance_name().map(|name| format!(" for instance '{}'", name)).unwrap_or_default()
+}
+
+/// Name the config files of other instances set to the same listening ports
+fn report_clashes(ports: &[u16]) {
+    for port in ports {
+        let others = crate::general::instance::configs_listening_on(*port);
+        if !others.is_empty() {
+            log::warn!(target: "osc", "[OSC] Port {} is also the osc.listening_port of {}; give every instance its own port", port, others.join(", "));
+        }
+    }
+}
+
+fn join_ports(ports: &[u16]) -> String {
+    ports.iter().map(u16::to_string).collect::<Vec<_>>().join(", ")
+}
 
 /// Spawns a background thread that listens for OSC on configured address.
 /// Recognizes the message paths "/transpose", "/transposeUp", "/transposeDown"
-/// and updates `crate::TRANSPOSE_SEMITONES` accordingly.
-/// The thread checks `crate::EXIT_FLAG` periodically to shut down gracefully.
+/// and updates `crate::state().transpose` accordingly.
+/// The thread checks `crate::state().exit` periodically to shut down gracefully.
 pub fn spawn_osc_listener() -> thread::JoinHandle<()> {
     thread::spawn(move || {
         // Get configuration
         let config = crate::get_config();
         
-    crate::general::check::OSC_LISTENER_RUNNING.store(true, std::sync::atomic::Ordering::SeqCst);
+    crate::state().osc_listener_running.store(true, std::sync::atomic::Ordering::SeqCst);
 
-        // Bind UDP socket on configured host:port from config.json
-        let bind_addr = format!("{}:{}", config.osc.listening_host, config.osc.listening_port);
-        let socket = match UdpSocket::bind(&bind_addr) {
-            Ok(s) => s,
-            Err(err) => {
-                eprintln!("OSC bind failed on {}: {}", bind_addr, err);
+        // Bind UDP socket on configured host:port from config.json (or the first free fallback port)
+        let (socket, bind_addr) = match bind_listener(&config.osc) {
+            Some(bound) => bound,
+            None => {
+                crate::state().osc_listener_running.store(false, Ordering::SeqCst);
                 return;
             }
         };
         
-        // Set socket timeout so we can check EXIT_FLAG periodically
+        LISTENING_PORT.store(socket.local_addr().map(|a| a.port()).unwrap_or(0), Ordering::SeqCst);
+
+        // Set socket timeout so we can check the exit flag periodically
         socket.set_read_timeout(Some(Duration::from_millis(200))).ok();
         
-        if crate::is_debug_enabled() {
-            println!("OSC listener bound on {} (paths: {}, {}, {})", 
-                bind_addr, 
-                config.osc.transpose_path,
-                config.osc.transpose_up_path,
-                config.osc.transpose_down_path);
-        }
+        log::debug!(target: "osc", "OSC listener bound on {} (paths: {}, {}, {})", 
+            bind_addr, 
+            config.osc.transpose_path,
+            config.osc.transpose_up_path,
+            config.osc.transpose_down_path);
+
+        // Proxy mode: unrecognized messages are relayed to the real OSC app (e.g. VRChat)
+        let proxy = if config.osc.proxy_enabled {
+            match config.osc.proxy_target.to_socket_addrs().ok().and_then(|mut a| a.next()) {
+                Some(addr) => {
+                    log::info!(target: "osc", "OSC proxy: relaying unrecognized messages from {} to {}", bind_addr, addr);
+                    Some(addr)
+                }
+                None => {
+                    log::error!(target: "osc", "[PROXY] Cannot resolve proxy_target '{}'; proxy disabled", config.osc.proxy_target);
+                    None
+                }
+            }
+        } else {
+            None
+        };
 
         let mut buf = [0u8; rosc::decoder::MTU];
+        let mut last_custom_generation = crate::custom_controls::generation();
 
         // Listen for incoming packets
         loop {
             // Check if we should exit
-            if crate::EXIT_FLAG.load(Ordering::SeqCst) {
+            if crate::state().exit.load(Ordering::SeqCst) {
                 break;
             }
 
+            // Echo custom control changes (from MQTT or OSC) back to the OSC controller
+            let custom_generation = crate::custom_controls::generation();
+            if custom_generation != last_custom_generation {
+                last_custom_generation = custom_generation;
+                echo_custom_controls(&socket);
+            }
+
             match socket.recv_from(&mut buf) {
                 Ok((size, peer_addr)) => {
                     match decoder::decode_udp(&buf[..size]) {
                         Ok((_, packet)) => {
-                            handle_packet(packet);
+                            handle_packet(&socket, packet, peer_addr, proxy);
                         }
                         Err(err) => {
-                            eprintln!("OSC decode error from {}: {}", peer_addr, err);
+                            log::error!(target: "osc", "OSC decode error from {}: {}", peer_addr, err);
                         }
                     }
                 }
                 Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock || e.kind() == std::io::ErrorKind::TimedOut => {
-                    // Timeout, continue loop to check EXIT_FLAG
+                    // Timeout, continue loop to check the exit flag
                     continue;
                 }
                 Err(err) => {
-                    eprintln!("OSC recv error: {}", err);
+                    log::error!(target: "osc", "OSC recv error: {}", err);
                 }
             }
         }
 
-    if crate::is_debug_enabled() { println!("OSC listener exiting"); }
-            crate::general::check::OSC_LISTENER_RUNNING.store(false, std::sync::atomic::Ordering::SeqCst);
+    log::debug!(target: "osc", "OSC listener exiting");
+            LISTENING_PORT.store(0, Ordering::SeqCst);
+            crate::state().osc_listener_running.store(false, std::sync::atomic::Ordering::SeqCst);
     })
 }
 
-fn handle_packet(packet: OscPacket) {
+/// Send the current value of every custom control to the last OSC controller
+fn echo_custom_controls(socket: &UdpSocket) {
+    let peer = match LAST_CONTROLLER.lock() {
+        Ok(guard) => match *guard {
+            Some(p) => p,
+            None => return,
+        },
+        Err(_) => return,
+    };
+    let prefix = &crate::get_config().osc.custom_control_path;
+    for control in crate::custom_controls::controls().iter() {
+        let packet = OscPacket::Message(OscMessage {
+            addr: format!("{}/{}", prefix, control.slug()),
+            args: control.value_keys().iter().map(|key| control.osc_arg(crate::custom_controls::value(key))).collect(),
+        });
+        if let Ok(buf) = encoder::encode(&packet) {
+            let _ = socket.send_to(&buf, peer);
+        }
+    }
+}
+
+fn handle_packet(socket: &UdpSocket, packet: OscPacket, peer_addr: SocketAddr, proxy: Option<SocketAddr>) {
     match packet {
-        OscPacket::Message(msg) => handle_message(msg),
+        OscPacket::Message(msg) => {
+            let config = crate::get_config();
+            if proxy.is_some() && config.osc.proxy_log {
+                log::info!(target: "osc", "[PROXY] {} -> {} {:?}", peer_addr, msg.addr, msg.args);
+            }
+            let handled = handle_message(socket, &msg, peer_addr);
+            if let Some(target) = proxy {
+                if !handled {
+                    relay_message(socket, msg, target);
+                }
+            }
+        }
         OscPacket::Bundle(bundle) => {
             // Process all messages in the bundle
             for pkt in bundle.content {
-                handle_packet(pkt);
+                handle_packet(socket, pkt, peer_addr, proxy);
             }
         }
     }
 }
 
-fn handle_message(msg: rosc::OscMessage) {
+/// Proxy mode: forward a message we don't handle ourselves, unless a drop prefix matches
+fn relay_message(socket: &UdpSocket, msg: OscMessage, target: SocketAddr) {
+    let config = crate::get_config();
+    if config.osc.proxy_drop_paths.iter().any(|prefix| msg.addr.starts_with(prefix.as_str())) {
+        if config.osc.proxy_log {
+            log::info!(target: "osc", "[PROXY] Dropped {}", msg.addr);
+        }
+        return;
+    }
+    match encoder::encode(&OscPacket::Message(msg)) {
+        Ok(buf) => {
+            if let Err(err) = socket.send_to(&buf, target) {
+                log::error!(target: "osc", "[PROXY] Relay to {} failed: {}", target, err);
+            }
+        }
+        Err(err) => log::error!(target: "osc", "[PROXY] Encode failed: {}", err),
+    }
+}
+
+/// Tell the sender of a transpose command that its request was clamped:
+/// `<transpose_path>/clamped` with (requested, applied) int arguments
+fn reply_if_clamped(socket: &UdpSocket, peer_addr: SocketAddr, requested: i32, applied: i32) {
+    if requested == applied {
+        return;
+    }
+    let packet = OscPacket::Message(OscMessage {
+        addr: format!("{}/clamped", crate::get_config().osc.transpose_path),
+        args: vec![OscType::Int(requested), OscType::Int(applied)],
+    });
+    if let Ok(buf) = encoder::encode(&packet) {
+        let _ = socket.send_to(&buf, peer_addr);
+    }
+}
+
+/// An argument as a number (bools as 0/1)
+fn numeric_value(arg: &OscType) -> Option<f32> {
+    match arg {
+        OscType::Int(v) => Some(*v as f32),
+        OscType::Long(v) => Some(*v as f32),
+        OscType::Float(v) => Some(*v),
+        OscType::Double(v) => Some(*v as f32),
+        OscType::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+        _ => None,
+    }
+}
+
+/// All arguments as numbers, if every one of them is numeric or bool
+fn numeric_args(args: &[OscType]) -> Option<Vec<f32>> {
+    args.iter().map(numeric_value).collect()
+}
+
+/// Button press: 1 or true (0/false is the release)
+fn is_trigger(arg: &OscType) -> bool {
+    match *arg {
+        OscType::Int(v) => v == 1,
+        OscType::Long(v) => v == 1,
+        OscType::Float(v) => (v - 1.0).abs() < f32::EPSILON,
+        OscType::Double(v) => (v - 1.0).abs() < f64::EPSILON,
+        OscType::Bool(b) => b,
+        _ => false,
+    }
+}
+
+/// Execute a command received over OSC; out-of-range transposes get a clamp reply
+fn run_command(socket: &UdpSocket, peer_addr: SocketAddr, command: Command) {
+    match commands::execute(command, Source::Osc) {
+        Ok(Outcome::Transpose { requested, applied }) => {
+            log::debug!(target: "osc", "[OSC] Transpose {} -> {}", requested, applied);
+            reply_if_clamped(socket, peer_addr, requested, applied);
+        }
+        Ok(Outcome::Done(message)) => {
+            log::debug!(target: "osc", "[OSC] {}", message);
+        }
+        Ok(Outcome::Silent) => {}
+        Err(e) => log::warn!(target: "osc", "[OSC] {}", e),
+    }
+}
+
+/// Run the action of a mapped avatar parameter. VRChat repeats parameter values (e.g. after an
+/// avatar change), so values only act when they changed and triggers fire when turning on.
+fn handle_avatar_parameter(socket: &UdpSocket, peer_addr: SocketAddr, name: &str, action: &AvatarParameterAction, value: f32) {
+    let previous = match AVATAR_PARAMETERS.lock() {
+        Ok(mut values) => values.get_or_insert_with(HashMap::new).insert(name.to_string(), value),
+        Err(_) => None,
+    };
+    if previous == Some(value) {
+        return;
+    }
+    let on = value >= 0.5;
+    let turned_on = on && previous.is_none_or(|p| p < 0.5);
+    log::debug!(target: "osc", "[OSC] Avatar parameter {} = {}", name, value);
+    let command = match action {
+        AvatarParameterAction::Transpose => Command::Transpose(value.round() as i32),
+        AvatarParameterAction::TransposeNormalized => Command::TransposeNormalized(value),
+        AvatarParameterAction::OscSending => Command::OscSending(on),
+        AvatarParameterAction::Panic if turned_on => Command::Panic,
+        AvatarParameterAction::Command { command } if turned_on => match commands::parse(command) {
+            Some(Ok(command)) => command,
+            Some(Err(usage)) => {
+                log::warn!(target: "osc", "[OSC] Avatar parameter {}: {}", name, usage);
+                return;
+            }
+            None => {
+                log::warn!(target: "osc", "[OSC] Avatar parameter {}: unknown command '{}'", name, command);
+                return;
+            }
+        },
+        _ => return,
+    };
+    run_command(socket, peer_addr, command);
+}
+
+/// Apply a recognized message. Returns false for paths the transposer doesn't handle
+/// (relayed in proxy mode).
+fn handle_message(socket: &UdpSocket, msg: &rosc::OscMessage, peer_addr: SocketAddr) -> bool {
     let addr = &msg.addr;
     let args = &msg.args;
     let config = crate::get_config();
 
+    // Any /avatar/... message (e.g. /avatar/change) means VRChat is running
+    if addr.starts_with("/avatar/") {
+        crate::vrchat::mark_avatar_message();
+    }
+
+    // Avatar parameters mapped to actions. Only observed, so proxy mode still relays them.
+    if let Some(name) = addr.strip_prefix("/avatar/parameters/") {
+        if let (Some(action), Some(value)) = (config.osc.avatar_parameter_actions.get(name), args.first().and_then(numeric_value)) {
+            handle_avatar_parameter(socket, peer_addr, name, action, value);
+        }
+    }
+
+    // Custom control read-back: the target reports a control's own address (e.g. changed in the
+    // avatar menu). Only observed, so proxy mode still relays it.
+    if let Some(values) = numeric_args(args).filter(|v| !v.is_empty()) {
+        crate::custom_controls::update_from_target(addr, &values);
+    }
+
+    // Custom controls: <custom_control_path>/<slug> with a numeric or bool argument (one per
+    // argument of a multi-argument control), or <custom_control_path>/<slug>/<arg> with one
+    if let Some(key) = addr.strip_prefix(config.osc.custom_control_path.as_str()).and_then(|rest| rest.strip_prefix('/')) {
+        match numeric_args(args).filter(|v| !v.is_empty()) {
+            Some(values) => {
+                if let Ok(mut guard) = LAST_CONTROLLER.lock() {
+                    *guard = Some(peer_addr);
+                }
+                run_command(socket, peer_addr, Command::Custom(key.to_string(), values));
+            }
+            None => log::warn!(target: "osc", "[OSC] {} requires numeric argument (got {:?})", addr, args.first()),
+        }
+        return true;
+    }
+
+    // Any console control command as a string argument, e.g. "lfo on" or "profile Live"
+    if addr == &config.osc.command_path {
+        match args.first() {
+            Some(OscType::String(text)) => match commands::parse(text) {
+                Some(Ok(command)) => run_command(socket, peer_addr, command),
+                Some(Err(usage)) => log::warn!(target: "osc", "[OSC] {}", usage),
+                None => log::warn!(target: "osc", "[OSC] Unknown command '{}'", text),
+            },
+            other => log::warn!(target: "osc", "[OSC] {} requires a string argument (got {:?})", addr, other),
+        }
+        return true;
+    }
+
+    // Setlist navigation: trigger on 1/true like /transposeUp
+    if addr == &config.osc.setlist_next_path || addr == &config.osc.setlist_prev_path {
+        if args.first().is_some_and(is_trigger) {
+            run_command(socket, peer_addr, if addr == &config.osc.setlist_next_path { Command::SetlistNext } else { Command::SetlistPrev });
+        }
+        return true;
+    }
+
+    // Transpose sequence: trigger on 1/true
+    if addr == &config.osc.sequence_start_path || addr == &config.osc.sequence_stop_path {
+        if args.first().is_some_and(is_trigger) {
+            run_command(socket, peer_addr, if addr == &config.osc.sequence_start_path { Command::SequenceStart } else { Command::SequenceStop });
+        }
+        return true;
+    }
+
     if addr == &config.osc.transpose_path {
         // Handle /transpose - set absolute transpose value
         if let Some(arg) = args.first() {
-            let val_opt: Option<i32> = match arg {
-                &OscType::Int(v) => Some(v),
-                &OscType::Long(v) => i32::try_from(v).ok(),
-                &OscType::Float(v) => Some(v.round() as i32),
-                &OscType::Double(v) => Some(v.round() as i32),
+            let val_opt: Option<i32> = match *arg {
+                OscType::Int(v) => Some(v),
+                OscType::Long(v) => i32::try_from(v).ok(),
+                OscType::Float(v) => Some(v.round() as i32),
+                OscType::Double(v) => Some(v.round() as i32),
                 _ => None,
             };
             if let Some(v) = val_opt {
-                let clamped_value = crate::set_transpose_semitones(v);
-                if crate::is_debug_enabled() { println!("[OSC] Transpose set to {}", clamped_value); }
+                run_command(socket, peer_addr, Command::Transpose(v));
             } else {
-                eprintln!("[OSC] /transpose requires numeric argument (got {:?})", arg);
+                log::warn!(target: "osc", "[OSC] /transpose requires numeric argument (got {:?})", arg);
             }
         } else {
-            eprintln!("[OSC] /transpose without argument ignored");
+            log::warn!(target: "osc", "[OSC] /transpose without argument ignored");
         }
-    } else if addr == &config.osc.transpose_up_path {
-        // Handle /transposeUp - increment transpose by 1 if argument equals 1
-        if let Some(arg) = args.first() {
-            let should_increment = match arg {
-                &OscType::Int(v) => v == 1,
-                &OscType::Long(v) => v == 1,
-                &OscType::Float(v) => (v - 1.0).abs() < f32::EPSILON,
-                &OscType::Double(v) => (v - 1.0).abs() < f64::EPSILON,
-                &OscType::Bool(b) => b, // true is equivalent to 1
-                _ => false,
-            };
-            
-            if should_increment {
-                let current = crate::TRANSPOSE_SEMITONES.load(Ordering::SeqCst);
-                let new_value = crate::set_transpose_semitones(current + 1);
-                if crate::is_debug_enabled() { println!("[OSC] Transpose UP: {} -> {}", current, new_value); }
-            }
-        } else {
-            eprintln!("[OSC] /transposeUp without argument ignored");
+    } else if addr == &config.osc.transpose_norm_path {
+        // Handle /transposeNorm - float -1..1 onto the transpose range
+        match args.first().and_then(numeric_value) {
+            Some(position) => run_command(socket, peer_addr, Command::TransposeNormalized(position)),
+            None => log::warn!(target: "osc", "[OSC] {} requires numeric argument (got {:?})", addr, args.first()),
         }
-    } else if addr == &config.osc.transpose_down_path {
-        // Handle /transposeDown - decrement transpose by 1 if argument equals 1
-        if let Some(arg) = args.first() {
-            let should_decrement = match arg {
-                &OscType::Int(v) => v == 1,
-                &OscType::Long(v) => v == 1,
-                &OscType::Float(v) => (v - 1.0).abs() < f32::EPSILON,
-                &OscType::Double(v) => (v - 1.0).abs() < f64::EPSILON,
-                &OscType::Bool(b) => b, // true is equivalent to 1
-                _ => false,
-            };
-            
-            if should_decrement {
-                let current = crate::TRANSPOSE_SEMITONES.load(Ordering::SeqCst);
-                let new_value = crate::set_transpose_semitones(current - 1);
-                if crate::is_debug_enabled() { println!("[OSC] Transpose DOWN: {} -> {}", current, new_value); }
+    } else if addr == &config.osc.transpose_up_path || addr == &config.osc.transpose_down_path {
+        // Handle /transposeUp and /transposeDown - step by 1 on 1/true, stop auto-repeat on 0/false
+        let delta = if addr == &config.osc.transpose_up_path { 1 } else { -1 };
+        match args.first() {
+            Some(arg) if is_trigger(arg) => {
+                run_command(socket, peer_addr, Command::TransposeStep(delta));
+                crate::general::repeat::press(delta);
             }
-        } else {
-            eprintln!("[OSC] /transposeDown without argument ignored");
+            Some(_) => crate::general::repeat::release(delta),
+            None => log::warn!(target: "osc", "[OSC] {} without argument ignored", addr),
+        }
+    } else if addr == &config.osc.octave_up_path || addr == &config.osc.octave_down_path {
+        // Handle /octaveUp and /octaveDown - one octave per 1/true
+        if args.first().is_some_and(is_trigger) {
+            let delta = if addr == &config.osc.octave_up_path { 1 } else { -1 };
+            run_command(socket, peer_addr, Command::OctaveStep(delta));
         }
+    } else {
+        return false;
     }
+    true
 }
@@ -1,31 +1,522 @@
-use std::sync::mpsc::{channel, Receiver, Sender};
-use std::sync::atomic::{AtomicBool, Ordering};
+use crate::general::queue::{self, QueueReceiver, QueueSender};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::sync::{Mutex, OnceLock};
 use std::thread::{self, JoinHandle};
-use std::collections::HashMap;
-use std::net::UdpSocket;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
 use rosc::{OscMessage, OscPacket, OscType, encoder};
 
-// Access global debug flag from crate root
-use crate::is_debug_enabled;
+// Sender logs belong to the OSC debug category
+fn is_debug_enabled() -> bool {
+    crate::is_debug_enabled_for(crate::DebugCategory::Osc)
+}
 
-// MIDI note names for OSC conversion
-const NOTE_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+// How often a hostname target is re-resolved (DNS/DHCP changes on the LAN)
+const DNS_REFRESH_SECS: u64 = 30;
+// Minimum time between socket rebuilds after send failures (avoids a rebuild per note while the target is down)
+const SOCKET_REBUILD_BACKOFF_MS: u64 = 1000;
 
-/// Convert MIDI note number to note name with octave (e.g., "C4", "F#5")
-pub fn midi_note_to_name(note_number: u8) -> String {
-    if note_number > 127 {
-        return "INVALID".to_string();
+/// Point all OSC sender threads at a new target (`host:port`) at runtime
+pub fn set_target(target_addr: String) {
+    if let Ok(mut guard) = crate::state().osc_target.lock() {
+        *guard = Some(target_addr);
     }
-    
-    let note_index = (note_number % 12) as usize;
-    let octave = (note_number / 12) as i32 - 1;
-    
-    format!("{}{}", NOTE_NAMES[note_index], octave)
+    crate::state().osc_target_generation.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Current primary send target: the runtime override if set, otherwise the first configured target
+pub fn current_target() -> String {
+    if let Ok(guard) = crate::state().osc_target.lock() {
+        if let Some(target) = guard.as_ref() {
+            return target.clone();
+        }
+    }
+    targets()[0].addr.clone()
+}
+
+/// One entry of `osc.sending_targets`
+#[derive(Debug, serde::Deserialize, Clone)]
+pub struct OscTargetConfig {
+    /// Name for the console, MQTT and the status; `addr:port` when empty
+    #[serde(default)]
+    pub name: String,
+    pub addr: String,
+    pub port: u16,
+    /// Whether the target receives MIDI at startup
+    #[serde(default = "default_target_enabled")]
+    pub enabled: bool,
+}
+
+fn default_target_enabled() -> bool {
+    true
+}
+
+/// A send target at runtime; every target has its own sender threads and can be switched on its own
+pub struct SendTarget {
+    pub name: String,
+    pub slug: String,
+    /// `host:port` from the config (the primary target follows `set_target` instead)
+    pub addr: String,
+    pub enabled: AtomicBool,
+}
+
+// Send targets from the config; bumped generation when one is switched on or off
+static TARGETS: OnceLock<Vec<SendTarget>> = OnceLock::new();
+static TARGETS_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// All send targets: `osc.sending_targets`, or `sending_addr:sending_port` alone. The first one
+/// is the primary target, which also gets custom controls, the chatbox and discovery overrides.
+pub fn targets() -> &'static [SendTarget] {
+    TARGETS.get_or_init(|| {
+        let osc = &crate::get_config().osc;
+        let entries = if osc.sending_targets.is_empty() {
+            vec![OscTargetConfig { name: String::new(), addr: osc.sending_addr.clone(), port: osc.sending_port, enabled: true }]
+        } else {
+            osc.sending_targets.clone()
+        };
+        entries
+            .into_iter()
+            .map(|entry| {
+                let addr = join_host_port(&entry.addr, entry.port);
+                let name = if entry.name.trim().is_empty() { addr.clone() } else { entry.name.trim().to_string() };
+                SendTarget { slug: crate::custom_controls::slugify(&name), name, addr, enabled: AtomicBool::new(entry.enabled) }
+            })
+            .collect()
+    })
+}
+
+pub fn targets_generation() -> u64 {
+    TARGETS_GENERATION.load(Ordering::SeqCst)
+}
+
+/// Find a target by name, slug or 1-based number
+pub fn find_target(key: &str) -> Option<&'static SendTarget> {
+    let key = key.trim();
+    if let Ok(n) = key.parse::<usize>() {
+        return n.checked_sub(1).and_then(|i| targets().get(i));
+    }
+    targets().iter().find(|t| t.name.eq_ignore_ascii_case(key) || t.slug == key.to_ascii_lowercase())
+}
+
+/// Switch one target on or off; its sender threads pause while it is off
+pub fn set_target_enabled(target: &SendTarget, enabled: bool) {
+    if target.enabled.swap(enabled, Ordering::SeqCst) != enabled {
+        TARGETS_GENERATION.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// One line per target for `status` and `osc targets`
+pub fn describe_targets() -> Vec<String> {
+    targets()
+        .iter()
+        .enumerate()
+        .map(|(i, t)| {
+            let addr = if i == 0 { current_target() } else { t.addr.clone() };
+            let state = if t.enabled.load(Ordering::SeqCst) { "on" } else { "off" };
+            if t.name == addr { format!("{}. {} {}", i + 1, addr, state) } else { format!("{}. {} ({}) {}", i + 1, t.name, addr, state) }
+        })
+        .collect()
+}
+
+// Pause between bulk initialization messages so the receiver doesn't drop a burst
+const INIT_MESSAGE_GAP_MS: u64 = 1;
+
+/// How notes are encoded as avatar parameters
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OscEncoding {
+    /// One Int parameter per key (`C4`, `CSHARP4`, ...)
+    #[default]
+    Notes,
+    /// One Float parameter per octave (`Octave4`) encoding the held note, plus a shared `Velocity`
+    Octave,
+    /// All 128 key states packed into 16 Int bytes (`KeyBits0`..`KeyBits15`) or one blob (`KeyBitmap`)
+    Bitmap,
+}
+
+impl OscEncoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            OscEncoding::Notes => "notes",
+            OscEncoding::Octave => "octave",
+            OscEncoding::Bitmap => "bitmap",
+        }
+    }
+}
+
+// Active encoding (index into OscEncoding), switchable at runtime by profiles
+static ENCODING: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_encoding(encoding: OscEncoding) {
+    ENCODING.store(encoding as u8, Ordering::SeqCst);
+}
+
+pub fn encoding() -> OscEncoding {
+    match ENCODING.load(Ordering::SeqCst) {
+        1 => OscEncoding::Octave,
+        2 => OscEncoding::Bitmap,
+        _ => OscEncoding::Notes,
+    }
+}
+
+/// How black keys are spelled in the OSC note names
+#[derive(Debug, serde::Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NoteAccidentals {
+    /// `CSHARP4`, `DSHARP4`, ...
+    #[default]
+    Sharp,
+    /// `DFLAT4`, `EFLAT4`, ...
+    Flat,
+}
+
+/// OSC argument type of the note on/off parameters (`OscEncoding::Notes`); avatars declare
+/// their parameters as Bool, Int or Float and VRChat ignores values of the wrong type
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NoteArgType {
+    /// `1` / `0`
+    #[default]
+    Int,
+    /// `true` / `false`
+    Bool,
+    /// `1.0` / `0.0`
+    Float,
+}
+
+impl NoteArgType {
+    pub fn name(self) -> &'static str {
+        match self {
+            NoteArgType::Int => "int",
+            NoteArgType::Bool => "bool",
+            NoteArgType::Float => "float",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "int" => Some(NoteArgType::Int),
+            "bool" => Some(NoteArgType::Bool),
+            "float" => Some(NoteArgType::Float),
+            _ => None,
+        }
+    }
+
+    /// Argument for a note being pressed (`true`) or released
+    pub fn arg(self, on: bool) -> OscType {
+        match self {
+            NoteArgType::Int => OscType::Int(on as i32),
+            NoteArgType::Bool => OscType::Bool(on),
+            NoteArgType::Float => OscType::Float(if on { 1.0 } else { 0.0 }),
+        }
+    }
+}
+
+/// What the note parameters carry: the key state, or the velocity for shader driven keys
+#[derive(Debug, serde::Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NoteValueMode {
+    /// On/off in the `note_arg_type`
+    #[default]
+    Bool,
+    /// Float `velocity / 127`, `0.0` on release
+    Velocity,
+}
+
+/// Value mode of a note parameter: its `note_value_modes` entry if there is one, else `note_value_mode`
+pub fn note_value_mode_for(note_path: &str) -> NoteValueMode {
+    let osc = &crate::get_config().osc;
+    osc.note_value_modes.get(note_path).copied().unwrap_or(osc.note_value_mode)
+}
+
+// Active note argument type (index into NoteArgType), switchable at runtime by profiles
+static NOTE_ARG_TYPE: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_note_arg_type(arg_type: NoteArgType) {
+    NOTE_ARG_TYPE.store(arg_type as u8, Ordering::SeqCst);
+}
+
+pub fn note_arg_type() -> NoteArgType {
+    match NOTE_ARG_TYPE.load(Ordering::SeqCst) {
+        1 => NoteArgType::Bool,
+        2 => NoteArgType::Float,
+        _ => NoteArgType::Int,
+    }
+}
+
+// Per-note velocity parameters (`<note>_v`), switchable at runtime since they double the traffic
+static NOTE_VELOCITY: AtomicBool = AtomicBool::new(false);
+
+pub fn set_note_velocity(enabled: bool) {
+    NOTE_VELOCITY.store(enabled, Ordering::SeqCst);
+}
+
+pub fn note_velocity() -> bool {
+    NOTE_VELOCITY.load(Ordering::SeqCst)
+}
+
+/// Velocity parameter of a note parameter (`/avatar/parameters/C4` -> `/avatar/parameters/C4_v`)
+pub fn velocity_parameter(note_path: &str) -> String {
+    format!("{}_v", note_path)
+}
+
+/// Note argument type for a send target: its `note_arg_types` entry if there is one, else the active type
+pub fn note_arg_type_for(target: &str) -> NoteArgType {
+    crate::get_config().osc.note_arg_types.get(target).copied().unwrap_or_else(note_arg_type)
+}
+
+/// A note given as MIDI number (`36`) or name in the `note_names` convention (`"C2"`)
+#[derive(Debug, serde::Deserialize, Clone)]
+#[serde(untagged)]
+pub enum NoteSpec {
+    Number(u8),
+    Name(String),
+}
+
+impl NoteSpec {
+    pub fn resolve(&self) -> Option<u8> {
+        self.resolve_in(crate::get_config().note_names.middle_c_octave)
+    }
+
+    /// Like `resolve`, with middle C in octave `middle_c_octave`
+    pub fn resolve_in(&self, middle_c_octave: i32) -> Option<u8> {
+        match self {
+            NoteSpec::Number(n) => Some(*n).filter(|n| *n <= 127),
+            NoteSpec::Name(name) => crate::general::notes::parse_note(name, middle_c_octave),
+        }
+    }
+}
+
+/// Poly pressure parameter of a note (`/avatar/parameters/Pressure` + C4 -> `/avatar/parameters/Pressure_C4`)
+pub fn poly_pressure_parameter(note: u8) -> String {
+    format!("{}_{}", crate::get_config().osc.pressure_parameter, note_name_for_osc(&midi_note_to_name(note)))
+}
+
+/// Argument type of a CC mapped avatar parameter (`osc.cc_map`)
+#[derive(Debug, serde::Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CcArgType {
+    /// `value / 127`
+    #[default]
+    Float,
+    /// `true` from 64 up (the MIDI on/off threshold, e.g. the sustain pedal)
+    Bool,
+    /// The raw value `0`..`127`
+    Int,
+}
+
+/// One entry of `osc.cc_map`, keyed by CC number
+#[derive(Debug, serde::Deserialize, Clone)]
+pub struct CcMapping {
+    /// Full OSC address, e.g. `/avatar/parameters/ModWheel`
+    pub address: String,
+    #[serde(default, rename = "type")]
+    pub arg_type: CcArgType,
+}
+
+impl CcMapping {
+    pub fn arg(&self, value: u8) -> OscType {
+        match self.arg_type {
+            CcArgType::Float => OscType::Float(value.min(127) as f32 / 127.0),
+            CcArgType::Bool => OscType::Bool(value >= 64),
+            CcArgType::Int => OscType::Int(value.min(127) as i32),
+        }
+    }
+}
+
+/// What happens to notes outside `osc.note_range`
+#[derive(Debug, serde::Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OutOfRangeMode {
+    /// Move by octaves into the range
+    #[default]
+    Fold,
+    /// Don't send them
+    Drop,
+}
+
+/// `osc.note_range`: the notes the avatar has parameters for
+#[derive(Debug, serde::Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct NoteRangeConfig {
+    /// Lowest and highest supported note; unset means no limit on that side
+    pub low: Option<NoteSpec>,
+    pub high: Option<NoteSpec>,
+    pub out_of_range: OutOfRangeMode,
+    /// Set the Bool parameter `OutOfRange` while an out-of-range note is held
+    pub warning_parameter: bool,
+}
+
+pub const OUT_OF_RANGE_PARAMETER: &str = "/avatar/parameters/OutOfRange";
+
+// Resolved `note_range` bounds (names need the loaded `note_names` config)
+static NOTE_RANGE: OnceLock<(u8, u8)> = OnceLock::new();
+
+/// Supported notes as (lowest, highest); the full MIDI range when unset or invalid
+pub fn note_range() -> (u8, u8) {
+    *NOTE_RANGE.get_or_init(|| {
+        let config = &crate::get_config().osc.note_range;
+        let bound = |spec: &Option<NoteSpec>, default: u8, side: &str| match spec {
+            Some(spec) => spec.resolve().unwrap_or_else(|| {
+                log::warn!(target: "config", "[CONFIG] osc.note_range.{}: invalid note {:?}; ignored", side, spec);
+                default
+            }),
+            None => default,
+        };
+        let (low, high) = (bound(&config.low, 0, "low"), bound(&config.high, 127, "high"));
+        if low > high {
+            log::warn!(target: "config", "[CONFIG] osc.note_range: low is above high; range ignored");
+            return (0, 127);
+        }
+        (low, high)
+    })
+}
+
+/// Fit a note into `osc.note_range`: unchanged inside it, otherwise folded by octaves or
+/// dropped (`None`). Ranges narrower than an octave drop notes that can't be folded in.
+pub fn fit_note_range(note: u8) -> Option<u8> {
+    let (low, high) = note_range();
+    if (low..=high).contains(&note) {
+        return Some(note);
+    }
+    match crate::get_config().osc.note_range.out_of_range {
+        OutOfRangeMode::Drop => None,
+        OutOfRangeMode::Fold => {
+            let mut folded = note as i32;
+            while folded < low as i32 {
+                folded += 12;
+            }
+            while folded > high as i32 {
+                folded -= 12;
+            }
+            (folded >= low as i32).then_some(folded as u8)
+        }
+    }
+}
+
+/// Parameter name of an octave in `OscEncoding::Octave` (MIDI octave -1 is `OctaveMinus1`)
+pub fn octave_parameter(octave: i32) -> String {
+    if octave < 0 {
+        format!("/avatar/parameters/OctaveMinus{}", -octave)
+    } else {
+        format!("/avatar/parameters/Octave{}", octave)
+    }
+}
+
+/// Float value of a held note within its octave: C = 1/12 ... B = 12/12, 0 = silent
+pub fn octave_value(note_number: u8) -> f32 {
+    ((note_number % 12) + 1) as f32 / 12.0
+}
+
+/// OSC messages carrying a 128-key bitmap (bit `n % 8` of byte `n / 8` = note `n`),
+/// as 16 Int parameters or a single blob depending on `osc.bitmap_blob`
+pub fn bitmap_messages(bits: &[u8; 16]) -> Vec<OscMessage> {
+    if crate::get_config().osc.bitmap_blob {
+        vec![OscMessage { addr: "/avatar/parameters/KeyBitmap".to_string(), args: vec![OscType::Blob(bits.to_vec())] }]
+    } else {
+        bits.iter()
+            .enumerate()
+            .map(|(i, byte)| OscMessage { addr: format!("/avatar/parameters/KeyBits{}", i), args: vec![OscType::Int(*byte as i32)] })
+            .collect()
+    }
+}
+
+/// Convert MIDI note number to the note name used in OSC parameters (e.g., "C4", "F#5");
+/// follows the `note_names` convention when `apply_to_osc` is set
+pub fn midi_note_to_name(note_number: u8) -> String {
+    crate::general::notes::osc_name(note_number)
 }
 
 /// Convert note name for OSC path (replace # with 'Sharp', e.g., G#3 -> GSharp3)
 pub fn note_name_for_osc(note_name: &str) -> String {
-    note_name.replace('#', "SHARP")
+    let (pitch, octave) = split_note_name(note_name);
+    match pitch.strip_suffix('b').filter(|base| !base.is_empty()) {
+        Some(base) => format!("{}FLAT{}", base, octave),
+        None => format!("{}{}", pitch.replace('#', "SHARP"), octave),
+    }
+}
+
+/// Split a note name into pitch and octave (`"C#-1"` -> `("C#", "-1")`)
+fn split_note_name(note_name: &str) -> (&str, &str) {
+    note_name.split_at(note_name.find(|c: char| c.is_ascii_digit() || c == '-').unwrap_or(note_name.len()))
+}
+
+/// Address of a note parameter, from `osc.note_path_template`: `{note}` is the OSC note name
+/// (`CSHARP4`), `{name}` the pitch alone (`CSHARP`), `{octave}` the octave and `{number}` the MIDI number
+pub fn note_parameter(note: u8) -> String {
+    let osc_name = note_name_for_osc(&midi_note_to_name(note));
+    let (name, octave) = split_note_name(&osc_name);
+    crate::get_config()
+        .osc
+        .note_path_template
+        .replace("{note}", &osc_name)
+        .replace("{name}", name)
+        .replace("{octave}", octave)
+        .replace("{number}", &note.to_string())
+}
+
+/// Per-address rate limit for continuous values like pitch bend and CC driven controls
+/// (`osc.continuous_rate_hz`): the first value goes out at once, later ones at most once per
+/// interval. The latest held-back value is sent when its interval is over, so the final
+/// position always arrives.
+pub struct RateLimiter<T> {
+    last_sent: HashMap<String, Instant>,
+    pending: HashMap<String, T>,
+}
+
+impl<T> Default for RateLimiter<T> {
+    fn default() -> Self {
+        RateLimiter { last_sent: HashMap::new(), pending: HashMap::new() }
+    }
+}
+
+impl<T> RateLimiter<T> {
+    fn interval() -> Option<Duration> {
+        let hz = crate::get_config().osc.continuous_rate_hz;
+        (hz > 0.0).then(|| Duration::from_secs_f32(1.0 / hz))
+    }
+
+    /// The value if it may be sent now; otherwise it replaces the held-back value of `key`
+    pub fn offer(&mut self, key: &str, value: T) -> Option<T> {
+        let Some(interval) = Self::interval() else { return Some(value) };
+        let now = Instant::now();
+        if self.last_sent.get(key).is_some_and(|last| now.duration_since(*last) < interval) {
+            self.pending.insert(key.to_string(), value);
+            return None;
+        }
+        self.last_sent.insert(key.to_string(), now);
+        self.pending.remove(key);
+        Some(value)
+    }
+
+    /// Held-back values whose interval is over (all of them once the limit is switched off)
+    pub fn take_due(&mut self) -> Vec<(String, T)> {
+        let now = Instant::now();
+        let interval = Self::interval().unwrap_or(Duration::ZERO);
+        let due: Vec<String> = self
+            .pending
+            .keys()
+            .filter(|key| self.last_sent.get(*key).is_none_or(|last| now.duration_since(*last) >= interval))
+            .cloned()
+            .collect();
+        due.into_iter()
+            .filter_map(|key| {
+                let value = self.pending.remove(&key)?;
+                self.last_sent.insert(key.clone(), now);
+                Some((key, value))
+            })
+            .collect()
+    }
+
+    /// Time until the next held-back value is due (`None` without any)
+    pub fn next_due_in(&self) -> Option<Duration> {
+        let interval = Self::interval().unwrap_or(Duration::ZERO);
+        self.pending
+            .keys()
+            .map(|key| self.last_sent.get(key).map_or(Duration::ZERO, |last| (*last + interval).saturating_duration_since(Instant::now())))
+            .min()
+    }
 }
 
 /// Structure to hold a MIDI message for OSC processing
@@ -58,33 +549,167 @@ impl MidiMessageForOsc {
     }
 }
 
-/// OSC sender that processes MIDI messages and sends OSC messages
+/// Join host and port into a socket address string, bracketing IPv6 literals
+/// (e.g. `::1` + 9000 -> `[::1]:9000`).
+pub fn join_host_port(host: &str, port: u16) -> String {
+    let host = host.trim();
+    if host.parse::<std::net::Ipv6Addr>().is_ok() {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+/// Resolve a `host:port` target (IPv4, IPv6 or hostname) to a socket address.
+/// IPv4 results are preferred when a hostname resolves to both families.
+fn resolve_target(target: &str) -> std::io::Result<SocketAddr> {
+    let addrs: Vec<SocketAddr> = target.to_socket_addrs()?.collect();
+    addrs
+        .iter()
+        .find(|a| a.is_ipv4())
+        .or_else(|| addrs.first())
+        .copied()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, format!("no address for {}", target)))
+}
+
+/// Pick a local bind address matching the target's family. Loopback targets keep
+/// binding to loopback so packets never leave the machine.
+fn local_bind_addr(target: &SocketAddr) -> SocketAddr {
+    let ip: IpAddr = match target.ip() {
+        IpAddr::V4(v4) if v4.is_loopback() => IpAddr::V4(std::net::Ipv4Addr::LOCALHOST),
+        IpAddr::V4(_) => IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(v6) if v6.is_loopback() => IpAddr::V6(std::net::Ipv6Addr::LOCALHOST),
+        IpAddr::V6(_) => IpAddr::V6(std::net::Ipv6Addr::UNSPECIFIED),
+    };
+    SocketAddr::new(ip, 0)
+}
+
+/// Bind a UDP socket suitable for `target` and connect it, so `send()` can be used
+fn connect_socket(target: &SocketAddr) -> std::io::Result<UdpSocket> {
+    let socket = UdpSocket::bind(local_bind_addr(target))?;
+    socket.connect(target)?;
+    Ok(socket)
+}
+
+/// OSC sender that processes MIDI messages and sends OSC
 pub struct OscSender {
     socket: UdpSocket,
     target_addr: String,
+    resolved_addr: SocketAddr,
+    last_resolved: Instant,
+    target_generation: u64,
+    // Only the primary target follows `set_target`
+    follows_override: bool,
+    // Consecutive send failures and time of the last socket rebuild
+    send_failures: u32,
+    last_rebuild: Option<Instant>,
     key_states: HashMap<String, i32>,
+    // Last arguments sent per address, to skip unchanged values (`osc.suppress_duplicates`)
+    last_sent: HashMap<String, Vec<OscType>>,
+    // Held notes outside `osc.note_range` (as received), for the `OutOfRange` warning parameter
+    out_of_range: HashSet<u8>,
+    // Held notes per octave in press order (octave encoding), the last one is shown
+    octave_notes: HashMap<i32, Vec<u8>>,
+    // Key bitmap (bitmap encoding) and when it was last sent, for the periodic resend
+    key_bits: [u8; 16],
+    last_bitmap_sent: Instant,
+    // Redundant send (`osc.redundant_send`): counter sent with every note state message (0-255)
+    // and the repeats still due, at most one per address
+    sequence: i32,
+    repeats: Vec<(Instant, OscMessage)>,
+    // Notes with a poly pressure value above 0, reset to 0 on their note-off
+    pressed_notes: HashSet<u8>,
+    // Pitch bend, mapped CCs and pressure held back by `osc.continuous_rate_hz`
+    throttle: RateLimiter<OscMessage>,
 }
 
 impl OscSender {
-    pub fn new(target_addr: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        // Explizit an IPv4-Loopback binden, um sicherzustellen, dass wir über 127.0.0.1 senden
-        let socket = UdpSocket::bind("127.0.0.1:0")?;
+    pub fn new(target_addr: &str, follows_override: bool) -> Result<Self, Box<dyn std::error::Error>> {
         // Fallback: if no target provided, default to localhost:9000
         let target = if target_addr.trim().is_empty() {
             "127.0.0.1:9000".to_string()
         } else {
-            target_addr.to_string()
+            target_addr.trim().to_string()
         };
 
-        // Socket mit Ziel verbinden, so dass send() genutzt werden kann
-        socket.connect(&target)?;
+        let resolved_addr = resolve_target(&target)?;
+        let socket = connect_socket(&resolved_addr)?;
 
         Ok(OscSender {
             socket,
             target_addr: target,
+            resolved_addr,
+            last_resolved: Instant::now(),
+            target_generation: crate::state().osc_target_generation.load(Ordering::SeqCst),
+            follows_override,
+            send_failures: 0,
+            last_rebuild: None,
             key_states: HashMap::new(),
+            last_sent: HashMap::new(),
+            out_of_range: HashSet::new(),
+            octave_notes: HashMap::new(),
+            key_bits: [0; 16],
+            last_bitmap_sent: Instant::now(),
+            sequence: 0,
+            repeats: Vec::new(),
+            pressed_notes: HashSet::new(),
+            throttle: RateLimiter::default(),
         })
     }
+
+    /// Switch to a runtime override target if one was set since the last send
+    fn apply_target_override(&mut self) {
+        let generation = crate::state().osc_target_generation.load(Ordering::SeqCst);
+        if !self.follows_override || generation == self.target_generation {
+            return;
+        }
+        self.target_generation = generation;
+        let target = match crate::state().osc_target.lock() {
+            Ok(guard) => guard.clone(),
+            Err(_) => None,
+        };
+        if let Some(target) = target {
+            match resolve_target(&target) {
+                Ok(addr) => {
+                    self.target_addr = target;
+                    self.last_resolved = Instant::now();
+                    self.reconnect(addr);
+                }
+                Err(e) => log::error!(target: "osc", "[OSC] Failed to resolve new target {}: {}", target, e),
+            }
+        }
+    }
+
+    /// Re-resolve hostname targets periodically and reconnect when the address changed.
+    /// IP literal targets never change and are skipped.
+    fn refresh_target(&mut self) {
+        if self.last_resolved.elapsed() < Duration::from_secs(DNS_REFRESH_SECS) {
+            return;
+        }
+        self.last_resolved = Instant::now();
+        if self.target_addr.parse::<SocketAddr>().is_ok() {
+            return;
+        }
+        match resolve_target(&self.target_addr) {
+            Ok(addr) if addr != self.resolved_addr => self.reconnect(addr),
+            Ok(_) => {}
+            Err(e) => log::error!(target: "osc", "[OSC] Failed to re-resolve {}: {} (keeping {})", self.target_addr, e, self.resolved_addr),
+        }
+    }
+
+    /// Rebuild the connected socket for a new resolved address
+    fn reconnect(&mut self, addr: SocketAddr) {
+        match connect_socket(&addr) {
+            Ok(socket) => {
+                log::debug!(target: "osc", "[OSC] Sending to {} via {} (was {})", self.target_addr, addr, self.resolved_addr);
+                self.socket = socket;
+                self.resolved_addr = addr;
+                // The receiver may not have seen any values yet
+                self.last_sent.clear();
+            }
+            Err(e) => log::error!(target: "osc", "[OSC] Failed to reconnect to {} ({}): {}", self.target_addr, addr, e),
+        }
+    }
     
     /// Process and send MIDI message as OSC
     pub fn process_midi_message(&mut self, midi_msg: &MidiMessageForOsc) -> Result<(), Box<dyn std::error::Error>> {
@@ -97,11 +722,35 @@ impl OscSender {
             return Ok(()); // Skip invalid notes
         }
         
+        if matches!(status & 0xF0, 0x80 | 0x90) {
+            let pressed = status & 0xF0 == 0x90 && data2 > 0;
+            let fitted = fit_note_range(data1);
+            if !pressed {
+                if let Some(note) = fitted.filter(|note| self.pressed_notes.remove(note)) {
+                    self.send_pressure(poly_pressure_parameter(note), 0)?;
+                }
+            }
+            if fitted != Some(data1) {
+                self.update_out_of_range(data1, pressed)?;
+                if is_debug_enabled() {
+                    match fitted {
+                        Some(note) => log::debug!(target: "osc", "[OSC] Note {} outside note_range, folded to {}", data1, note),
+                        None => log::debug!(target: "osc", "[OSC] Note {} outside note_range, dropped", data1),
+                    }
+                }
+            }
+            let Some(data1) = fitted else { return Ok(()) };
+            match encoding() {
+                OscEncoding::Octave => return self.process_octave_note(data1, data2, pressed),
+                OscEncoding::Bitmap => return self.process_bitmap_note(data1, pressed),
+                OscEncoding::Notes => {}
+            }
+        }
+
         match status & 0xF0 {
             // Note On (0x90..=0x9F) and Note Off (0x80..=0x8F)
             0x90 => {
                 let note_name = midi_note_to_name(data1);
-                let osc_note_name = note_name_for_osc(&note_name);
 
                 // Velocity 0 on Note On is Note Off per MIDI spec
                 let note_state_int = if data2 > 0 { 1 } else { 0 };
@@ -109,34 +758,79 @@ impl OscSender {
                 // Update key state
                 self.key_states.insert(note_name.clone(), note_state_int);
 
-                // Create and send OSC message
-                let osc_path = format!("/avatar/parameters/{}", osc_note_name);
-                let osc_msg = OscMessage { addr: osc_path, args: vec![OscType::Int(note_state_int)] };
-                self.send_osc_message(osc_msg)?;
+                // Create and send OSC message; the velocity goes first so it is set when the gate opens
+                let osc_path = note_parameter(data1);
+                if note_velocity() {
+                    let velocity = OscMessage { addr: velocity_parameter(&osc_path), args: vec![OscType::Float(data2 as f32 / 127.0)] };
+                    self.send_note_state(velocity)?;
+                }
+                let osc_msg = OscMessage { args: vec![self.note_arg(&osc_path, data2)], addr: osc_path };
+                self.send_note_state(osc_msg)?;
             }
             0x80 => {
                 let note_name = midi_note_to_name(data1);
-                let osc_note_name = note_name_for_osc(&note_name);
                 let note_state_int = 0;
 
                 self.key_states.insert(note_name.clone(), note_state_int);
 
-                let osc_path = format!("/avatar/parameters/{}", osc_note_name);
-                let osc_msg = OscMessage { addr: osc_path, args: vec![OscType::Int(note_state_int)] };
-                self.send_osc_message(osc_msg)?;
+                let osc_path = note_parameter(data1);
+                if note_velocity() {
+                    let velocity = OscMessage { addr: velocity_parameter(&osc_path), args: vec![OscType::Float(0.0)] };
+                    self.send_note_state(velocity)?;
+                }
+                let osc_msg = OscMessage { args: vec![self.note_arg(&osc_path, 0)], addr: osc_path };
+                self.send_note_state(osc_msg)?;
             }
 
             // Pitch Bend (0xE0..=0xEF)
             0xE0 => {
                 let pitch_bend_raw = (data2 as i32 * 128 + data1 as i32) - 8192;
-                let pitch_bend_value = (pitch_bend_raw as f32 / 8192.0).max(-1.0).min(1.0);
+                let pitch_bend_value = (pitch_bend_raw as f32 / 8192.0).clamp(-1.0, 1.0);
                 let pitch_bend_rounded = (pitch_bend_value * 10.0).round() / 10.0;
 
-                if pitch_bend_rounded > 0.0 {
-                    let osc_msg = OscMessage { addr: "/avatar/parameters/PitchUp".to_string(), args: vec![OscType::Float(pitch_bend_rounded)] };
-                    self.send_osc_message(osc_msg)?;
+                let osc_msg = if pitch_bend_rounded > 0.0 {
+                    OscMessage { addr: "/avatar/parameters/PitchUp".to_string(), args: vec![OscType::Float(pitch_bend_rounded)] }
                 } else if pitch_bend_rounded < 0.0 {
-                    let osc_msg = OscMessage { addr: "/avatar/parameters/PitchDown".to_string(), args: vec![OscType::Float(pitch_bend_rounded.abs())] };
+                    OscMessage { addr: "/avatar/parameters/PitchDown".to_string(), args: vec![OscType::Float(pitch_bend_rounded.abs())] }
+                } else {
+                    return Ok(());
+                };
+                if let Some(osc_msg) = self.throttle.offer(&osc_msg.addr.clone(), osc_msg) {
+                    self.send_osc_message(osc_msg)?;
+                }
+            }
+
+            // Channel pressure (0xD0..=0xDF): one float for the whole keyboard
+            0xD0 if crate::get_config().osc.channel_pressure => {
+                self.send_pressure(crate::get_config().osc.pressure_parameter.clone(), data1)?;
+            }
+
+            // Polyphonic aftertouch (0xA0..=0xAF): one float per key, in the note range like the notes
+            0xA0 if crate::get_config().osc.poly_pressure => {
+                let Some(note) = fit_note_range(data1) else { return Ok(()) };
+                if data2 > 0 {
+                    self.pressed_notes.insert(note);
+                } else {
+                    self.pressed_notes.remove(&note);
+                }
+                self.send_pressure(poly_pressure_parameter(note), data2)?;
+            }
+
+            // Program change (0xC0..=0xCF): the patch number, e.g. for instrument skins
+            0xC0 if crate::get_config().osc.program_change => {
+                let osc_msg = OscMessage { addr: crate::get_config().osc.program_parameter.clone(), args: vec![OscType::Int(data1 as i32)] };
+                self.send_osc_message(osc_msg)?;
+            }
+
+            // Control Change (0xB0..=0xBF): mapped CCs become avatar parameters (`osc.cc_map`)
+            0xB0 => {
+                let config = crate::get_config();
+                let Some(mapping) = config.osc.cc_map.get(&data1) else { return Ok(()) };
+                let osc_msg = OscMessage { addr: mapping.address.clone(), args: vec![mapping.arg(data2)] };
+                // Switches go out right away, continuous controllers through the rate limit
+                if mapping.arg_type == CcArgType::Bool {
+                    self.send_osc_message(osc_msg)?;
+                } else if let Some(osc_msg) = self.throttle.offer(&osc_msg.addr.clone(), osc_msg) {
                     self.send_osc_message(osc_msg)?;
                 }
             }
@@ -149,90 +843,456 @@ impl OscSender {
         Ok(())
     }
     
+    /// Pressure as `value / 127` through the rate limit
+    fn send_pressure(&mut self, addr: String, value: u8) -> Result<(), Box<dyn std::error::Error>> {
+        let osc_msg = OscMessage { addr: addr.clone(), args: vec![OscType::Float(value.min(127) as f32 / 127.0)] };
+        match self.throttle.offer(&addr, osc_msg) {
+            Some(osc_msg) => self.send_osc_message(osc_msg),
+            None => Ok(()),
+        }
+    }
+
+    /// Track held out-of-range notes; the warning parameter turns on with the first and off after the last
+    fn update_out_of_range(&mut self, note: u8, pressed: bool) -> Result<(), Box<dyn std::error::Error>> {
+        let was_on = !self.out_of_range.is_empty();
+        if pressed {
+            self.out_of_range.insert(note);
+        } else {
+            self.out_of_range.remove(&note);
+        }
+        let on = !self.out_of_range.is_empty();
+        if on != was_on && crate::get_config().osc.note_range.warning_parameter {
+            let osc_msg = OscMessage { addr: OUT_OF_RANGE_PARAMETER.to_string(), args: vec![OscType::Bool(on)] };
+            self.send_osc_message(osc_msg)?;
+        }
+        Ok(())
+    }
+
+    /// Octave encoding: update the held notes of the note's octave and send the octave value
+    /// (last held note, or 0 when the octave is silent). Velocity goes to the shared `Velocity` float.
+    fn process_octave_note(&mut self, note: u8, velocity: u8, pressed: bool) -> Result<(), Box<dyn std::error::Error>> {
+        let octave = crate::general::notes::osc_octave(note);
+        let held = self.octave_notes.entry(octave).or_default();
+        held.retain(|n| *n != note);
+        if pressed {
+            held.push(note);
+        }
+        let value = held.last().map_or(0.0, |n| octave_value(*n));
+
+        if pressed {
+            let osc_msg = OscMessage { addr: "/avatar/parameters/Velocity".to_string(), args: vec![OscType::Float(velocity as f32 / 127.0)] };
+            self.send_osc_message(osc_msg)?;
+        }
+        let osc_msg = OscMessage { addr: octave_parameter(octave), args: vec![OscType::Float(value)] };
+        self.send_note_state(osc_msg)
+    }
+
+    /// Bitmap encoding: flip the note's bit and send the bitmap if it changed
+    fn process_bitmap_note(&mut self, note: u8, pressed: bool) -> Result<(), Box<dyn std::error::Error>> {
+        let (byte, mask) = ((note / 8) as usize, 1u8 << (note % 8));
+        let before = self.key_bits[byte];
+        if pressed {
+            self.key_bits[byte] |= mask;
+        } else {
+            self.key_bits[byte] &= !mask;
+        }
+        if self.key_bits[byte] == before {
+            return Ok(());
+        }
+        self.send_bitmap(false)
+    }
+
+    /// Send the bitmap; `resend` also sends unchanged bytes (periodic resend for late joiners)
+    fn send_bitmap(&mut self, resend: bool) -> Result<(), Box<dyn std::error::Error>> {
+        self.last_bitmap_sent = Instant::now();
+        for msg in bitmap_messages(&self.key_bits) {
+            if resend {
+                self.send_osc_message_with(msg, true)?;
+            } else {
+                self.send_note_state(msg)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Resend the bitmap every `osc.bitmap_resend_ms` so late joiners get the current state
+    pub fn resend_bitmap_if_due(&mut self) {
+        let interval = crate::get_config().osc.bitmap_resend_ms;
+        if encoding() != OscEncoding::Bitmap || interval == 0 {
+            return;
+        }
+        if self.last_bitmap_sent.elapsed() >= Duration::from_millis(interval) {
+            if let Err(e) = self.send_bitmap(true) {
+                log::debug!(target: "osc", "[OSC] Bitmap resend failed: {}", e);
+            }
+        }
+    }
+
+    /// Argument of a note parameter for a note on with `velocity` (0 = released)
+    fn note_arg(&self, note_path: &str, velocity: u8) -> OscType {
+        match note_value_mode_for(note_path) {
+            NoteValueMode::Bool => note_arg_type_for(&self.target_addr).arg(velocity > 0),
+            NoteValueMode::Velocity => OscType::Float(velocity.min(127) as f32 / 127.0),
+        }
+    }
+
+    /// Send a note state message. With `osc.redundant_send` a changed state is preceded by the next
+    /// sequence counter value and sent once more after `osc.redundant_gap_ms`, so a single dropped
+    /// UDP packet doesn't leave a key stuck. A newer state for the address replaces a pending repeat.
+    fn send_note_state(&mut self, msg: OscMessage) -> Result<(), Box<dyn std::error::Error>> {
+        let osc = &crate::get_config().osc;
+        if !osc.redundant_send || self.is_unchanged(&msg) {
+            return self.send_osc_message(msg);
+        }
+        self.sequence = (self.sequence + 1) % 256;
+        let sequence = OscMessage { addr: osc.sequence_parameter.clone(), args: vec![OscType::Int(self.sequence)] };
+        self.send_osc_message_with(sequence, true)?;
+        self.repeats.retain(|(_, pending)| pending.addr != msg.addr);
+        self.repeats.push((Instant::now() + Duration::from_millis(osc.redundant_gap_ms), msg.clone()));
+        self.send_osc_message(msg)
+    }
+
+    /// Time until the next repeat or held-back pitch bend / CC is due (`None` without any)
+    pub fn next_repeat_in(&self) -> Option<Duration> {
+        let repeat = self.repeats.iter().map(|(due, _)| due.saturating_duration_since(Instant::now())).min();
+        [repeat, self.throttle.next_due_in()].into_iter().flatten().min()
+    }
+
+    /// Send the pitch bend and CC values held back by the rate limit whose interval is over
+    pub fn send_due_throttled(&mut self) {
+        for (_, msg) in self.throttle.take_due() {
+            if let Err(e) = self.send_osc_message(msg) {
+                log::debug!(target: "osc", "[OSC] Rate limited send failed: {}", e);
+            }
+        }
+    }
+
+    /// Send the repeats that are due, each with the current sequence counter value
+    pub fn send_due_repeats(&mut self) {
+        let now = Instant::now();
+        let (due, waiting): (Vec<_>, Vec<_>) = self.repeats.drain(..).partition(|(at, _)| *at <= now);
+        self.repeats = waiting;
+        if due.is_empty() {
+            return;
+        }
+        let sequence = OscMessage { addr: crate::get_config().osc.sequence_parameter.clone(), args: vec![OscType::Int(self.sequence)] };
+        let mut messages = vec![sequence];
+        messages.extend(due.into_iter().map(|(_, msg)| msg));
+        for msg in messages {
+            if let Err(e) = self.send_osc_message_with(msg, true) {
+                log::debug!(target: "osc", "[OSC] Redundant resend failed: {}", e);
+            }
+        }
+    }
+
+    /// Whether `osc.suppress_duplicates` would skip this message
+    fn is_unchanged(&self, msg: &OscMessage) -> bool {
+        crate::get_config().osc.suppress_duplicates && self.last_sent.get(&msg.addr) == Some(&msg.args)
+    }
+
     /// Send OSC message via UDP
-    fn send_osc_message(&self, msg: OscMessage) -> Result<(), Box<dyn std::error::Error>> {
+    /// Send a message unless `osc.suppress_duplicates` is on and the address already has these arguments
+    fn send_osc_message(&mut self, msg: OscMessage) -> Result<(), Box<dyn std::error::Error>> {
+        self.send_osc_message_with(msg, false)
+    }
+
+    /// `force` sends even an unchanged value
+    fn send_osc_message_with(&mut self, msg: OscMessage, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+        self.apply_target_override();
+        self.refresh_target();
+        if !force && self.is_unchanged(&msg) {
+            log::debug!(target: "osc", "[OSC] Skipped unchanged {} to {}", msg.addr, self.target_addr);
+            return Ok(());
+        }
         let packet = OscPacket::Message(msg.clone());
         let msg_buf = encoder::encode(&packet)?;
-        match self.socket.send(&msg_buf) {
+        let result = match self.socket.send(&msg_buf) {
+            Ok(bytes_sent) => Ok(bytes_sent),
+            // A stale connected socket (e.g. VRChat restarted) keeps failing: rebuild it and retry once
+            Err(e) if self.rebuild_socket() => self.socket.send(&msg_buf).map_err(|_| e),
+            Err(e) => Err(e),
+        };
+        match result {
             Ok(bytes_sent) => {
-                if is_debug_enabled() {
-                    println!("[OSC] Sent {} bytes to {}: {}", bytes_sent, self.target_addr, msg.addr);
+                if self.send_failures > 0 {
+                    log::info!(target: "osc", "[OSC] Sending to {} recovered after {} failed attempts", self.target_addr, self.send_failures);
+                    self.send_failures = 0;
                 }
+                log::debug!(target: "osc", "[OSC] Sent {} bytes to {}: {}", bytes_sent, self.target_addr, msg.addr);
+                self.last_sent.insert(msg.addr, msg.args);
                 Ok(())
             }
             Err(e) => {
-                eprintln!("[OSC] Failed to send to {}: {}", self.target_addr, e);
+                // Only report the first failure of a streak to keep the console readable
+                if self.send_failures == 0 || is_debug_enabled() {
+                    log::error!(target: "osc", "[OSC] Failed to send to {}: {}", self.target_addr, e);
+                }
+                self.send_failures += 1;
                 Err(Box::new(e))
             }
         }
     }
+
+    /// Re-resolve the target and bind a fresh connected socket, rate-limited by
+    /// `SOCKET_REBUILD_BACKOFF_MS`. Returns true if a new socket is in place.
+    fn rebuild_socket(&mut self) -> bool {
+        if let Some(last) = self.last_rebuild {
+            if last.elapsed() < Duration::from_millis(SOCKET_REBUILD_BACKOFF_MS) {
+                return false;
+            }
+        }
+        self.last_rebuild = Some(Instant::now());
+        let addr = resolve_target(&self.target_addr).unwrap_or(self.resolved_addr);
+        match connect_socket(&addr) {
+            Ok(socket) => {
+                log::debug!(target: "osc", "[OSC] Rebuilt sender socket for {} ({})", self.target_addr, addr);
+                self.socket = socket;
+                self.resolved_addr = addr;
+                // The receiver may not have seen any values yet
+                self.last_sent.clear();
+                self.last_resolved = Instant::now();
+                true
+            }
+            Err(e) => {
+                log::error!(target: "osc", "[OSC] Failed to rebuild socket for {}: {}", self.target_addr, e);
+                false
+            }
+        }
+    }
 }
 
-/// Spawn OSC sender thread that processes MIDI messages and sends OSC
+// Shared socket for `send_single_osc_message`, kept per target instead of binding per message.
+// A cache private to this module (like the sockets of the sender threads), not shared state,
+// so it is not part of `AppState`.
+static SINGLE_SOCKET: Mutex<Option<(String, UdpSocket)>> = Mutex::new(None);
+
+/// Send one OSC message to `target_addr` outside of the MIDI sender threads
+/// (custom controls, one-off state updates)
+pub fn send_single_osc_message(target_addr: &str, addr: &str, args: Vec<OscType>) -> Result<(), Box<dyn std::error::Error>> {
+    let packet = OscPacket::Message(OscMessage { addr: addr.to_string(), args });
+    let msg_buf = encoder::encode(&packet)?;
+
+    let mut guard = SINGLE_SOCKET.lock().map_err(|_| "OSC socket lock poisoned")?;
+    // Reuse the cached socket for the same target; a failed send drops it and retries on a fresh one
+    if let Some((cached_target, socket)) = guard.as_ref() {
+        if cached_target == target_addr && socket.send(&msg_buf).is_ok() {
+            log::debug!(target: "osc", "[OSC] Sent {} bytes to {}: {}", msg_buf.len(), target_addr, addr);
+            return Ok(());
+        }
+    }
+    *guard = None;
+    let socket = connect_socket(&resolve_target(target_addr)?)?;
+    socket.send(&msg_buf)?;
+    *guard = Some((target_addr.to_string(), socket));
+    log::debug!(target: "osc", "[OSC] Sent {} bytes to {}: {}", msg_buf.len(), target_addr, addr);
+    Ok(())
+}
+
+/// Reset the avatar to a clean state: send 0 for all 128 note parameters (or all octave
+/// parameters, depending on the encoding) and both pitch
+/// parameters, and the `initial` value of every custom control. Returns the number of messages sent.
+pub fn initialize_parameters() -> usize {
+    let target = current_target();
+    let mut sent = 0;
+    let mut send = |addr: &str, args: Vec<OscType>| {
+        match send_single_osc_message(&target, addr, args) {
+            Ok(()) => sent += 1,
+            Err(e) => log::error!(target: "osc", "[OSC] Failed to initialize {}: {}", addr, e),
+        }
+        thread::sleep(Duration::from_millis(INIT_MESSAGE_GAP_MS));
+    };
+    match encoding() {
+        OscEncoding::Notes => {
+            let off = note_arg_type_for(&target).arg(false);
+            for note in 0..=127u8 {
+                let path = note_parameter(note);
+                let arg = match note_value_mode_for(&path) {
+                    NoteValueMode::Bool => off.clone(),
+                    NoteValueMode::Velocity => OscType::Float(0.0),
+                };
+                send(&path, vec![arg]);
+                if note_velocity() {
+                    send(&velocity_parameter(&path), vec![OscType::Float(0.0)]);
+                }
+            }
+        }
+        OscEncoding::Octave => {
+            for octave in crate::general::notes::osc_octave(0)..=crate::general::notes::osc_octave(127) {
+                send(&octave_parameter(octave), vec![OscType::Float(0.0)]);
+            }
+            send("/avatar/parameters/Velocity", vec![OscType::Float(0.0)]);
+        }
+        OscEncoding::Bitmap => {
+            for msg in bitmap_messages(&[0; 16]) {
+                send(&msg.addr, msg.args);
+            }
+        }
+    }
+    send("/avatar/parameters/PitchUp", vec![OscType::Float(0.0)]);
+    send("/avatar/parameters/PitchDown", vec![OscType::Float(0.0)]);
+    if crate::get_config().osc.note_range.warning_parameter {
+        send(OUT_OF_RANGE_PARAMETER, vec![OscType::Bool(false)]);
+    }
+    if crate::get_config().osc.channel_pressure {
+        send(&crate::get_config().osc.pressure_parameter, vec![OscType::Float(0.0)]);
+    }
+    if crate::get_config().osc.poly_pressure {
+        let (low, high) = note_range();
+        for note in low..=high {
+            send(&poly_pressure_parameter(note), vec![OscType::Float(0.0)]);
+        }
+    }
+    for control in crate::custom_controls::controls().iter() {
+        if crate::custom_controls::set_value(&control.slug(), control.initial).is_some() {
+            sent += 1;
+        }
+    }
+    log::debug!(target: "osc", "[OSC] Initialized {} parameters on {}", sent, target);
+    sent
+}
+
+/// Spawn OSC sender thread that processes MIDI messages and sends OSC to one target
+/// (index into `targets()`); the component status shows the primary target and any failure
 pub fn spawn_osc_sender(
-    target_addr: String,
-    midi_receiver: Receiver<Vec<u8>>,
-    enable_flag: &'static AtomicBool,
+    component: &'static str,
+    target: usize,
+    midi_receiver: QueueReceiver<Vec<u8>>,
+    stream_flag: &'static AtomicBool,
 ) -> JoinHandle<()> {
     thread::spawn(move || {
         crate::general::check::mark_osc_sender_started();
-        let mut osc_sender = match OscSender::new(&target_addr) {
+        let send_target = &targets()[target];
+        let target_addr = if target == 0 { current_target() } else { send_target.addr.clone() };
+        let mut osc_sender = match OscSender::new(&target_addr, target == 0) {
             Ok(sender) => sender,
             Err(e) => {
-                eprintln!("Failed to create OSC sender: {}", e);
+                log::error!(target: "osc", "Failed to create OSC sender for {}: {}", send_target.name, e);
+                crate::general::check::report(component, crate::general::check::ComponentState::Broken, format!("{}: {}", target_addr, e));
                 crate::general::check::mark_osc_sender_stopped();
                 return;
             }
         };
+        if target == 0 {
+            let more = match targets().len() - 1 {
+                0 => String::new(),
+                n => format!(" (+{} more)", n),
+            };
+            crate::general::check::report(component, crate::general::check::ComponentState::Up, format!("sending to {}{}", osc_sender.target_addr, more));
+        }
         
         if is_debug_enabled() {
             if let Ok(local_addr) = osc_sender.socket.local_addr() {
-                println!("OSC sender thread started, local {} -> target {}", local_addr, osc_sender.target_addr);
+                log::debug!(target: "osc", "OSC sender thread started, local {} -> target {} ({})", local_addr, osc_sender.target_addr, osc_sender.resolved_addr);
             } else {
-                println!("OSC sender thread started, sending to: {}", target_addr);
+                log::debug!(target: "osc", "OSC sender thread started, sending to: {}", target_addr);
             }
         }
         
         loop {
             // Exit promptly on global shutdown
-            if crate::EXIT_FLAG.load(Ordering::SeqCst) {
+            if crate::state().exit.load(Ordering::SeqCst) {
                 break;
             }
-            // Check if OSC sending is enabled
-            if !enable_flag.load(Ordering::SeqCst) {
+            // Check if OSC sending, this stream and this target are enabled
+            if !crate::state().osc_sending.load(Ordering::SeqCst) || !stream_flag.load(Ordering::SeqCst) || !send_target.enabled.load(Ordering::SeqCst) {
                 std::thread::sleep(std::time::Duration::from_millis(10));
                 continue;
             }
+            // Auto mode without VRChat: discard queued MIDI instead of sending useless UDP traffic
+            if !crate::vrchat::sending_allowed() {
+                if let Err(std::sync::mpsc::RecvTimeoutError::Disconnected) = midi_receiver.recv_timeout(std::time::Duration::from_millis(100)) {
+                    break;
+                }
+                continue;
+            }
             
-            // Try to receive MIDI message with timeout
-            match midi_receiver.recv_timeout(std::time::Duration::from_millis(100)) {
+            // Try to receive MIDI message with timeout (shorter while a repeat or throttled value is pending)
+            let wait = osc_sender.next_repeat_in().map_or(Duration::from_millis(100), |due| due.min(Duration::from_millis(100)));
+            match midi_receiver.recv_timeout(wait) {
                 Ok(raw_bytes) => {
                     if let Some(midi_msg) = MidiMessageForOsc::new(&raw_bytes) {
                         if let Err(e) = osc_sender.process_midi_message(&midi_msg) {
-                            eprintln!("Error processing MIDI message for OSC: {}", e);
+                            log::error!(target: "osc", "Error processing MIDI message for OSC: {}", e);
                         }
                     }
+                    osc_sender.send_due_repeats();
+                    osc_sender.send_due_throttled();
+                    osc_sender.resend_bitmap_if_due();
                 },
                 Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
                     // Continue loop, check enable flag again
+                    osc_sender.send_due_repeats();
+                    osc_sender.send_due_throttled();
+                    osc_sender.resend_bitmap_if_due();
                     continue;
                 },
                 Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
-                    if is_debug_enabled() {
-                        println!("OSC sender: MIDI receiver disconnected, shutting down");
-                    }
+                    log::debug!(target: "osc", "OSC sender: MIDI receiver disconnected, shutting down");
                     break;
                 }
             }
         }
-        if is_debug_enabled() {
-            println!("OSC sender thread terminated");
-        }
+        log::debug!(target: "osc", "OSC sender thread terminated");
         crate::general::check::mark_osc_sender_stopped();
     })
 }
 
-/// Create a channel pair for sending MIDI data to OSC sender
-pub fn create_osc_sender_channel() -> (Sender<Vec<u8>>, Receiver<Vec<u8>>) {
-    channel()
+/// Spawn the raw mirror thread: every incoming MIDI message is sent unchanged as one blob to
+/// `osc.raw_mirror_path` at `osc.raw_mirror_target` (default: the OSC send target)
+pub fn spawn_raw_mirror(midi_receiver: QueueReceiver<Vec<u8>>) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let config = &crate::get_config().osc;
+        // Socket per target; the send target can change at runtime (discovery)
+        let mut socket: Option<(String, UdpSocket)> = None;
+        while !crate::state().exit.load(Ordering::SeqCst) {
+            let raw_bytes = match midi_receiver.recv_timeout(std::time::Duration::from_millis(100)) {
+                Ok(raw_bytes) => raw_bytes,
+                Err(std::sync::mpsc::RecvTimeoutError::Timeout) => continue,
+                Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
+            };
+            let target = config.raw_mirror_target.clone().unwrap_or_else(current_target);
+            if socket.as_ref().is_none_or(|(cached, _)| *cached != target) {
+                socket = match resolve_target(&target).and_then(|addr| connect_socket(&addr)) {
+                    Ok(s) => Some((target.clone(), s)),
+                    Err(e) => {
+                        log::warn!(target: "osc", "[OSC] Raw mirror target {} unavailable: {}", target, e);
+                        None
+                    }
+                };
+            }
+            let packet = OscPacket::Message(OscMessage { addr: config.raw_mirror_path.clone(), args: vec![OscType::Blob(raw_bytes)] });
+            if let (Some((_, s)), Ok(buf)) = (socket.as_ref(), encoder::encode(&packet)) {
+                if s.send(&buf).is_err() {
+                    // Re-resolve on the next message
+                    socket = None;
+                }
+            }
+        }
+    })
+}
+
+/// Create a bounded queue for sending MIDI data to an OSC sender
+pub fn create_osc_sender_channel(name: &str) -> (QueueSender<Vec<u8>>, QueueReceiver<Vec<u8>>) {
+    queue::bounded(name, crate::get_config().queues.capacity)
+}
+
+/// Senders and receivers of one stream, index = send target
+pub type TargetQueues = (Vec<QueueSender<Vec<u8>>>, Vec<QueueReceiver<Vec<u8>>>);
+
+/// One queue per send target for a stream; the extra targets' queues are named `<name>:<slug>`
+pub fn create_osc_target_channels(name: &str) -> TargetQueues {
+    targets()
+        .iter()
+        .enumerate()
+        .map(|(i, target)| if i == 0 { create_osc_sender_channel(name) } else { create_osc_sender_channel(&format!("{}:{}", name, target.slug)) })
+        .unzip()
+}
+
+/// Queue a MIDI message for every enabled send target
+pub fn send_to_targets(senders: &[QueueSender<Vec<u8>>], message: &[u8]) {
+    for (sender, target) in senders.iter().zip(targets()) {
+        if target.enabled.load(Ordering::SeqCst) {
+            sender.send(message.to_vec());
+        }
+    }
 }
@@ -0,0 +1,187 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::{Duration, Instant};
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+use serde_json::{json, Value};
+
+// OSCQuery HTTP servers and plain OSC inputs are advertised under these service types
+const OSCQUERY_SERVICE_TYPE: &str = "_oscjson._tcp.local.";
+const OSC_SERVICE_TYPE: &str = "_osc._udp.local.";
+// How long the advertisement waits for the OSC listener to bind
+const LISTENER_WAIT_SECS: u64 = 10;
+// OSCQuery ACCESS values: container nodes have no value, input paths are write-only
+const ACCESS_NONE: u8 = 0;
+const ACCESS_WRITE: u8 = 2;
+
+/// OSC type tag of a custom control argument
+fn type_tag(kind: crate::custom_controls::ControlKind) -> &'static str {
+    match kind {
+        crate::custom_controls::ControlKind::Bool => "T",
+        crate::custom_controls::ControlKind::Int => "i",
+        crate::custom_controls::ControlKind::Float => "f",
+    }
+}
+
+/// Every OSC path the listener handles: (path, type tag, description)
+fn input_paths() -> Vec<(String, String, String)> {
+    let osc = &crate::get_config().osc;
+    let mut paths: Vec<(String, String, String)> = [
+        (&osc.transpose_path, "i", "Transpose in semitones"),
+        (&osc.transpose_up_path, "i", "1 = one semitone up"),
+        (&osc.transpose_down_path, "i", "1 = one semitone down"),
+        (&osc.transpose_norm_path, "f", "Transpose as -1..1 across the configured range"),
+        (&osc.octave_up_path, "i", "1 = one octave up"),
+        (&osc.octave_down_path, "i", "1 = one octave down"),
+        (&osc.command_path, "s", "Console command, e.g. \"profile Live\""),
+        (&osc.setlist_next_path, "i", "1 = next setlist song"),
+        (&osc.setlist_prev_path, "i", "1 = previous setlist song"),
+        (&osc.sequence_start_path, "i", "1 = start the transpose sequence"),
+        (&osc.sequence_stop_path, "i", "1 = stop the transpose sequence"),
+    ]
+    .into_iter()
+    .map(|(path, tag, description)| (path.clone(), tag.to_string(), description.to_string()))
+    .collect();
+    for control in crate::custom_controls::controls().iter() {
+        let tags = type_tag(control.kind).repeat(control.args.len().max(1));
+        paths.push((format!("{}/{}", osc.custom_control_path, control.slug()), tags, format!("Custom control {}", control.name)));
+    }
+    // VRChat only sends avatar changes and parameters to services that list /avatar
+    paths.push(("/avatar/change".to_string(), "s".to_string(), "VRChat avatar change (VRChat detection)".to_string()));
+    paths
+}
+
+/// OSCQuery node tree of the input paths, with a container node for every path prefix
+fn node_tree() -> Value {
+    let mut root = json!({ "FULL_PATH": "/", "ACCESS": ACCESS_NONE, "CONTENTS": {} });
+    for (path, tag, description) in input_paths() {
+        let mut node = &mut root;
+        let mut full_path = String::new();
+        for segment in path.split('/').filter(|s| !s.is_empty()) {
+            full_path.push('/');
+            full_path.push_str(segment);
+            node = &mut node["CONTENTS"][segment];
+            if node.is_null() {
+                *node = json!({ "FULL_PATH": full_path, "ACCESS": ACCESS_NONE });
+            }
+        }
+        node["TYPE"] = tag.into();
+        node["ACCESS"] = ACCESS_WRITE.into();
+        node["DESCRIPTION"] = description.into();
+    }
+    root
+}
+
+/// Node at `path` (`/` is the root), if it exists
+fn find_node(tree: &Value, path: &str) -> Option<Value> {
+    path.split('/').filter(|s| !s.is_empty()).try_fold(tree, |node, segment| node.get("CONTENTS")?.get(segment)).cloned()
+}
+
+/// Name of this instance in the OSCQuery host info and the mDNS advertisement
+fn service_name() -> String {
+    crate::general::instance::with_suffix(env!("CARGO_PKG_NAME"))
+}
+
+fn host_info(stream: &TcpStream, osc_port: u16) -> Value {
+    let osc_ip = match stream.local_addr() {
+        Ok(addr) => addr.ip().to_string(),
+        Err(_) => "127.0.0.1".to_string(),
+    };
+    json!({
+        "NAME": service_name(),
+        "OSC_IP": osc_ip,
+        "OSC_PORT": osc_port,
+        "OSC_TRANSPORT": "UDP",
+        "EXTENSIONS": { "ACCESS": true, "DESCRIPTION": true, "VALUE": false },
+    })
+}
+
+fn respond(mut stream: TcpStream, osc_port: u16) {
+    stream.set_read_timeout(Some(Duration::from_millis(500))).ok();
+    let mut request = [0u8; 1024];
+    let size = stream.read(&mut request).unwrap_or(0);
+    let request = String::from_utf8_lossy(&request[..size]);
+    let target = request.split_whitespace().nth(1).unwrap_or("/");
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let (status, body) = if !request.starts_with("GET ") {
+        ("405 Method Not Allowed", "{\"error\":\"GET only\"}".to_string())
+    } else if query == "HOST_INFO" {
+        ("200 OK", host_info(&stream, osc_port).to_string())
+    } else {
+        match find_node(&node_tree(), path) {
+            Some(node) => ("200 OK", node.to_string()),
+            None => ("404 Not Found", "{\"error\":\"no such path\"}".to_string()),
+        }
+    };
+    let response = format!(
+        "HTTP/1.0 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes());
+}
+
+/// Advertise the OSCQuery server and the OSC listener via mDNS. The daemon must stay alive
+/// for the advertisement to be answered.
+fn advertise(http_port: u16, osc_port: u16) -> Result<ServiceDaemon, mdns_sd::Error> {
+    let daemon = ServiceDaemon::new()?;
+    let name = service_name();
+    let host = format!("{}.local.", crate::custom_controls::slugify(&name));
+    for (service_type, port) in [(OSCQUERY_SERVICE_TYPE, http_port), (OSC_SERVICE_TYPE, osc_port)] {
+        let info = ServiceInfo::new(service_type, &name, &host, "", port, None::<std::collections::HashMap<String, String>>)?;
+        daemon.register(info.enable_addr_auto())?;
+    }
+    Ok(daemon)
+}
+
+/// Start the OSCQuery server on `osc.listening_host:osc.oscquery_port` (`0` = any free port) and,
+/// once the OSC listener is bound, advertise both via mDNS so VRChat and OSC tools find the listener
+pub fn spawn_server() -> std::io::Result<thread::JoinHandle<()>> {
+    let osc = &crate::get_config().osc;
+    let listener = TcpListener::bind(crate::osc_sender::join_host_port(&osc.listening_host, osc.oscquery_port))?;
+    let http_port = listener.local_addr()?.port();
+    // Non-blocking accept so the thread notices the exit flag
+    listener.set_nonblocking(true)?;
+    Ok(thread::spawn(move || {
+        let deadline = Instant::now() + Duration::from_secs(LISTENER_WAIT_SECS);
+        let osc_port = loop {
+            match crate::osc_listener::listening_port() {
+                Some(port) => break Some(port),
+                None if Instant::now() >= deadline || crate::state().exit.load(Ordering::SeqCst) => break None,
+                None => thread::sleep(Duration::from_millis(100)),
+            }
+        };
+        let Some(osc_port) = osc_port else {
+            log::warn!(target: "osc", "[OSCQUERY] OSC listener not running; nothing to advertise");
+            return;
+        };
+        let daemon = match advertise(http_port, osc_port) {
+            Ok(daemon) => {
+                log::info!(target: "osc", "OSCQuery: advertising OSC port {} (HTTP on port {})", osc_port, http_port);
+                Some(daemon)
+            }
+            Err(e) => {
+                log::error!(target: "osc", "[OSCQUERY] mDNS advertisement failed: {}", e);
+                None
+            }
+        };
+        while !crate::state().exit.load(Ordering::SeqCst) {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let _ = stream.set_nonblocking(false);
+                    respond(stream, osc_port);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(100)),
+                Err(e) => {
+                    log::error!(target: "osc", "[OSCQUERY] Accept failed: {}", e);
+                    thread::sleep(Duration::from_millis(100));
+                }
+            }
+        }
+        if let Some(daemon) = daemon {
+            let _ = daemon.shutdown();
+        }
+    }))
+}
@@ -0,0 +1,78 @@
+use crate::general::commands::{self, Command, Source};
+
+fn to_i32(n: i64) -> Result<i32, String> {
+    i32::try_from(n).map_err(|_| format!("{} is out of range", n))
+}
+
+/// A number body (`5`, `-2`) or `{"value": n}`; `Some(Err)` when it doesn't fit an `i32`
+fn int_value(body: &serde_json::Value) -> Option<Result<i32, String>> {
+    body.as_i64().or_else(|| body.get("value").and_then(serde_json::Value::as_i64)).map(to_i32)
+}
+
+/// `{"step": n}`
+fn step_value(body: &serde_json::Value) -> Option<Result<i32, String>> {
+    body.get("step").and_then(serde_json::Value::as_i64).map(to_i32)
+}
+
+/// `true`/`false`, `on`/`off`, `1`/`0` or `{"value": bool}`
+fn bool_value(text: &str, body: &serde_json::Value) -> Option<bool> {
+    match text.trim_matches('"').to_ascii_lowercase().as_str() {
+        "true" | "on" | "1" => Some(true),
+        "false" | "off" | "0" => Some(false),
+        _ => body.get("value").and_then(serde_json::Value::as_bool),
+    }
+}
+
+/// The command of a `POST` to `path`, mirroring the console commands; `None` for unknown paths
+fn parse(path: &str, text: &str) -> Option<Result<Command, String>> {
+    let body: serde_json::Value = serde_json::from_str(text).unwrap_or(serde_json::Value::Null);
+    let result = match path {
+        "/transpose" => int_value(&body)
+            .map(|n| n.map(Command::Transpose))
+            .or_else(|| step_value(&body).map(|n| n.map(Command::TransposeStep)))
+            .unwrap_or_else(|| Err("Expected a semitone number, {\"value\": n} or {\"step\": n}".to_string())),
+        "/octave" => int_value(&body)
+            .map(|n| n.map(Command::Octave))
+            .or_else(|| step_value(&body).map(|n| n.map(Command::OctaveStep)))
+            .unwrap_or_else(|| Err("Expected an octave number, {\"value\": n} or {\"step\": n}".to_string())),
+        "/osc/enabled" => bool_value(text, &body).map(Command::OscSending).ok_or_else(|| "Expected true or false".to_string()),
+        "/panic" => Ok(Command::Panic),
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// Run a REST control request: `POST /transpose`, `/octave`, `/osc/enabled` or `/panic`.
+/// Returns the HTTP status and the JSON reply.
+pub fn post(path: &str, body: &str) -> (&'static str, serde_json::Value) {
+    let command = match parse(path, body) {
+        Some(Ok(command)) => command,
+        None => return ("404 Not Found", serde_json::json!({ "ok": false, "error": "not found" })),
+        Some(Err(e)) => return ("400 Bad Request", serde_json::json!({ "ok": false, "error": e })),
+    };
+    let result = commands::execute(command, Source::Web);
+    if let Err(e) = &result {
+        log::warn!(target: "app", "[REST] {}", e);
+    }
+    let status = if result.is_ok() { "200 OK" } else { "400 Bad Request" };
+    (status, crate::remote::event_api::outcome_json(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_outside_i32_are_rejected() {
+        assert_eq!(parse("/transpose", "5"), Some(Ok(Command::Transpose(5))));
+        assert_eq!(parse("/transpose", "{\"step\": -1}"), Some(Ok(Command::TransposeStep(-1))));
+        assert!(matches!(parse("/transpose", "4294967301"), Some(Err(_))));
+        assert!(matches!(parse("/octave", "{\"step\": -4294967296}"), Some(Err(_))));
+    }
+
+    #[test]
+    fn console_commands_are_not_exposed() {
+        assert_eq!(parse("/command", "in 1s exit"), None);
+        assert_eq!(post("/command", "{\"command\": \"profile Live\"}").0, "404 Not Found");
+    }
+}
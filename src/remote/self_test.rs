@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::net::UdpSocket;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+use rosc::{decoder, encoder, OscMessage, OscPacket, OscType};
+use rumqttc::{Client, Event, Incoming, MqttOptions, QoS};
+
+/// Command line flag selecting the control surface self-test
+pub const SEND_TEST_FLAG: &str = "--send-test";
+
+// How long a step waits for the running instance to confirm it
+const RESPONSE_TIMEOUT_MS: u64 = 2000;
+const TEST_CLIENT_ID: &str = "midi_transposer_selftest";
+
+/// Whether `--send-test` was given; the optional next argument is the OSC target (`host:port`)
+pub fn send_test_target(args: &[String]) -> Option<Option<String>> {
+    let idx = args.iter().position(|a| a == SEND_TEST_FLAG)?;
+    Some(args.get(idx + 1).filter(|a| !a.starts_with("--")).cloned())
+}
+
+/// OSC control port of the running instance, from its `listening_host`/`listening_port`
+fn default_osc_target() -> String {
+    let osc = &crate::get_config().osc;
+    let host = match osc.listening_host.as_str() {
+        "0.0.0.0" | "::" | "" => "127.0.0.1",
+        host => host,
+    };
+    crate::osc_sender::join_host_port(host, osc.listening_port)
+}
+
+/// State topics of the running instance as seen over MQTT (latest payload per topic)
+struct StateWatcher {
+    client: Client,
+    updates: Receiver<(String, String)>,
+    latest: HashMap<String, String>,
+}
+
+impl StateWatcher {
+    fn connect(topics: &[String]) -> Result<Self, String> {
+        let config = &crate::get_config().mqtt;
+        let mut options = MqttOptions::new(TEST_CLIENT_ID, config.broker_host.as_str(), config.broker_port);
+        options.set_keep_alive(Duration::from_secs(5));
+        options.set_credentials(&config.username, &config.password);
+        let (client, mut connection) = Client::new(options, 32);
+        let (tx, updates) = mpsc::channel();
+        let (connected_tx, connected) = mpsc::channel();
+        thread::spawn(move || {
+            for event in connection.iter() {
+                match event {
+                    Ok(Event::Incoming(Incoming::ConnAck(_))) => {
+                        let _ = connected_tx.send(Ok(()));
+                    }
+                    Ok(Event::Incoming(Incoming::Publish(p))) => {
+                        let payload = String::from_utf8_lossy(&p.payload).trim().to_string();
+                        if tx.send((p.topic, payload)).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        let _ = connected_tx.send(Err(e.to_string()));
+                        break;
+                    }
+                }
+            }
+        });
+        connected
+            .recv_timeout(Duration::from_millis(RESPONSE_TIMEOUT_MS))
+            .map_err(|_| format!("no answer from broker {}:{}", config.broker_host, config.broker_port))??;
+        for topic in topics {
+            client.subscribe(topic, QoS::AtLeastOnce).map_err(|e| e.to_string())?;
+        }
+        let mut watcher = StateWatcher { client, updates, latest: HashMap::new() };
+        // Collect the retained states the instance published on connect
+        watcher.drain_until(Instant::now() + Duration::from_millis(500), |_| false);
+        Ok(watcher)
+    }
+
+    fn drain_until(&mut self, deadline: Instant, done: impl Fn(&HashMap<String, String>) -> bool) -> bool {
+        loop {
+            if done(&self.latest) {
+                return true;
+            }
+            let left = deadline.saturating_duration_since(Instant::now());
+            match self.updates.recv_timeout(left) {
+                Ok((topic, payload)) => {
+                    self.latest.insert(topic, payload);
+                }
+                Err(_) => return done(&self.latest),
+            }
+        }
+    }
+
+    /// Wait until `topic` reports `expected`; returns the last seen value on timeout
+    fn expect(&mut self, topic: &str, expected: &str) -> Result<(), String> {
+        let deadline = Instant::now() + Duration::from_millis(RESPONSE_TIMEOUT_MS);
+        if self.drain_until(deadline, |latest| latest.get(topic).is_some_and(|v| v == expected)) {
+            Ok(())
+        } else {
+            Err(format!("expected {}, got {}", expected, self.latest.get(topic).map(String::as_str).unwrap_or("nothing")))
+        }
+    }
+
+    fn publish(&self, topic: &str, payload: &str) -> Result<(), String> {
+        self.client.publish(topic, QoS::AtLeastOnce, false, payload).map_err(|e| e.to_string())
+    }
+}
+
+/// Counts passed/failed steps and prints one line per step
+#[derive(Default)]
+struct Report {
+    passed: usize,
+    failed: usize,
+    unverified: usize,
+}
+
+impl Report {
+    fn step(&mut self, name: &str, result: Result<(), String>) {
+        match result {
+            Ok(()) => {
+                self.passed += 1;
+                crate::io::console::print_line(format!("  PASS  {}", name));
+            }
+            Err(e) => {
+                self.failed += 1;
+                crate::io::console::print_line(format!("  FAIL  {}: {}", name, e));
+            }
+        }
+    }
+
+    fn sent(&mut self, name: &str, result: Result<(), String>) {
+        match result {
+            Ok(()) => {
+                self.unverified += 1;
+                crate::io::console::print_line(format!("  SENT  {} (not verified without MQTT)", name));
+            }
+            Err(e) => self.step(name, Err(e)),
+        }
+    }
+}
+
+fn send_osc(socket: &UdpSocket, target: &str, addr: &str, arg: OscType) -> Result<(), String> {
+    let packet = OscPacket::Message(OscMessage { addr: addr.to_string(), args: vec![arg] });
+    let buf = encoder::encode(&packet).map_err(|e| e.to_string())?;
+    socket.send_to(&buf, target).map(|_| ()).map_err(|e| e.to_string())
+}
+
+/// Wait for the `<transpose_path>/clamped` reply to an out-of-range request
+fn expect_clamped_reply(socket: &UdpSocket, requested: i32, applied: i32) -> Result<(), String> {
+    let expected_addr = format!("{}/clamped", crate::get_config().osc.transpose_path);
+    socket.set_read_timeout(Some(Duration::from_millis(RESPONSE_TIMEOUT_MS))).map_err(|e| e.to_string())?;
+    let mut buf = [0u8; decoder::MTU];
+    let deadline = Instant::now() + Duration::from_millis(RESPONSE_TIMEOUT_MS);
+    while Instant::now() < deadline {
+        let size = socket.recv(&mut buf).map_err(|_| "no clamp reply".to_string())?;
+        if let Ok((_, OscPacket::Message(msg))) = decoder::decode_udp(&buf[..size]) {
+            // Earlier steps may have been clamped too (narrow transpose range); skip their replies
+            if msg.addr == expected_addr && matches!(msg.args.as_slice(), [OscType::Int(r), OscType::Int(a)] if *r == requested && *a == applied) {
+                return Ok(());
+            }
+        }
+    }
+    Err("no clamp reply".to_string())
+}
+
+/// Exercise the OSC and MQTT control surface of a running instance and verify its responses.
+/// Transpose and the "send original" switch are restored afterwards. Fails if any step failed.
+pub fn run_send_test(osc_target: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let config = crate::get_config();
+    let target = osc_target.unwrap_or_else(default_osc_target);
+    let base = crate::general::instance::with_suffix(&config.mqtt.base_topic);
+    let transpose_state = format!("{}/state/transpose", base);
+    let send_original_state = format!("{}/state/osc/sendOriginal", base);
+
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    let mut watcher = if config.mqtt.enabled {
+        match StateWatcher::connect(&[transpose_state.clone(), send_original_state.clone()]) {
+            Ok(w) => Some(w),
+            Err(e) => {
+                crate::io::console::print_line(format!("MQTT unavailable ({}); OSC steps are sent but only the clamp reply is verified", e));
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let initial_transpose = watcher.as_ref().and_then(|w| w.latest.get(&transpose_state).cloned());
+    if watcher.is_some() && initial_transpose.is_none() {
+        crate::io::console::print_line(format!("No transpose state on {} - is the transposer running with MQTT enabled?", transpose_state));
+    }
+
+    crate::io::console::print_line(format!("Self-test against OSC {} {}", target, if watcher.is_some() { format!("and MQTT {}", base) } else { String::new() }));
+    let mut report = Report::default();
+    let osc = &config.osc;
+    let osc_steps: [(&str, &str, OscType, i32); 6] = [
+        ("OSC set +3", osc.transpose_path.as_str(), OscType::Float(3.0), 3),
+        ("OSC up", osc.transpose_up_path.as_str(), OscType::Int(1), 4),
+        ("OSC up", osc.transpose_up_path.as_str(), OscType::Int(1), 5),
+        ("OSC down", osc.transpose_down_path.as_str(), OscType::Int(1), 4),
+        ("OSC set 0", osc.transpose_path.as_str(), OscType::Int(0), 0),
+        ("OSC down", osc.transpose_down_path.as_str(), OscType::Bool(true), -1),
+    ];
+    for (name, addr, arg, expected) in osc_steps {
+        let mut sent = send_osc(&socket, &target, addr, arg);
+        if addr != osc.transpose_path {
+            // Release the button right away so a configured auto-repeat doesn't step further
+            sent = sent.and_then(|_| send_osc(&socket, &target, addr, OscType::Int(0)));
+        }
+        match watcher.as_mut() {
+            Some(w) => report.step(name, sent.and_then(|_| w.expect(&transpose_state, &expected.to_string()))),
+            None => {
+                report.sent(name, sent);
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+    }
+
+    // Out-of-range request: the listener answers with a clamp reply
+    let max = config.transpose.max as i32;
+    let clamped = send_osc(&socket, &target, &osc.transpose_path, OscType::Int(max + 1))
+        .and_then(|_| expect_clamped_reply(&socket, max + 1, max));
+    report.step("OSC clamp reply", clamped);
+
+    if let Some(w) = watcher.as_mut() {
+        let set = format!("{}/transpose", base);
+        report.step("MQTT set -2", w.publish(&set, "-2").and_then(|_| w.expect(&transpose_state, "-2")));
+        report.step("MQTT up", w.publish(&format!("{}/transposeUp", base), "PRESS").and_then(|_| w.expect(&transpose_state, "-1")));
+        report.step("MQTT down", w.publish(&format!("{}/transposeDown", base), "PRESS").and_then(|_| w.expect(&transpose_state, "-2")));
+
+        let toggle = format!("{}/osc/sendOriginal", base);
+        let was_on = w.latest.get(&send_original_state).is_some_and(|v| v == "1");
+        let (flipped, restored) = if was_on { ("0", "1") } else { ("1", "0") };
+        report.step("MQTT sendOriginal toggle", w.publish(&toggle, flipped).and_then(|_| w.expect(&send_original_state, flipped)));
+        report.step("MQTT sendOriginal restore", w.publish(&toggle, restored).and_then(|_| w.expect(&send_original_state, restored)));
+
+        if let Some(value) = &initial_transpose {
+            report.step("MQTT restore transpose", w.publish(&set, value).and_then(|_| w.expect(&transpose_state, value)));
+        }
+        let _ = w.client.disconnect();
+    } else {
+        // Without MQTT the previous value is unknown; leave the instance untransposed
+        let _ = send_osc(&socket, &target, &osc.transpose_path, OscType::Int(0));
+    }
+
+    crate::io::console::print_line(format!("Self-test: {} passed, {} failed, {} unverified", report.passed, report.failed, report.unverified));
+    if report.failed > 0 {
+        return Err(format!("{} step(s) failed", report.failed).into());
+    }
+    Ok(())
+}
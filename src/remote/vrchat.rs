@@ -0,0 +1,248 @@
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use mdns_sd::{ServiceDaemon, ServiceEvent};
+use termcolor::Color;
+
+// How often the process list is scanned for VRChat
+const PROCESS_POLL_SECS: u64 = 5;
+// Executable name of the VRChat client (also under Proton on Linux)
+const VRCHAT_PROCESS: &str = "VRChat.exe";
+// VRChat advertises its OSCQuery HTTP server under this service type
+const OSCQUERY_SERVICE_TYPE: &str = "_oscjson._tcp.local.";
+// How long the connectivity check browses for OSCQuery services / waits for an ICMP error
+const OSCQUERY_BROWSE_MS: u64 = 1500;
+const ICMP_WAIT_MS: u64 = 300;
+
+static PROCESS_RUNNING: AtomicBool = AtomicBool::new(false);
+// Milliseconds since START of the last /avatar/* message, 0 = never
+static LAST_AVATAR_MESSAGE_MS: AtomicU64 = AtomicU64::new(0);
+static START: OnceLock<Instant> = OnceLock::new();
+
+fn elapsed_ms() -> u64 {
+    START.get_or_init(Instant::now).elapsed().as_millis() as u64 + 1
+}
+
+/// Record an incoming `/avatar/change` (or other `/avatar/...`) message from VRChat
+pub fn mark_avatar_message() {
+    LAST_AVATAR_MESSAGE_MS.store(elapsed_ms(), Ordering::SeqCst);
+}
+
+/// Whether an avatar message arrived within the configured activity window
+fn recent_avatar_message() -> bool {
+    let last = LAST_AVATAR_MESSAGE_MS.load(Ordering::SeqCst);
+    if last == 0 {
+        return false;
+    }
+    let window_ms = crate::get_config().osc.vrchat_activity_timeout_secs * 1000;
+    elapsed_ms().saturating_sub(last) <= window_ms
+}
+
+/// Human-readable detection state for `status`
+pub fn detection_state() -> &'static str {
+    if PROCESS_RUNNING.load(Ordering::SeqCst) {
+        "detected (process running)"
+    } else if recent_avatar_message() {
+        "detected (recent /avatar message)"
+    } else {
+        "not detected"
+    }
+}
+
+pub fn is_detected() -> bool {
+    PROCESS_RUNNING.load(Ordering::SeqCst) || recent_avatar_message()
+}
+
+/// False only when auto mode is on and VRChat isn't detected
+pub fn sending_allowed() -> bool {
+    !crate::state().osc_sending_auto.load(Ordering::SeqCst) || is_detected()
+}
+
+#[cfg(target_os = "windows")]
+fn vrchat_process_running() -> bool {
+    Command::new("tasklist")
+        .args(["/FI", &format!("IMAGENAME eq {}", VRCHAT_PROCESS), "/NH"])
+        .output()
+        .map(|out| String::from_utf8_lossy(&out.stdout).contains(VRCHAT_PROCESS))
+        .unwrap_or(false)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn vrchat_process_running() -> bool {
+    // Proton/Wine processes show the .exe name in their command line
+    Command::new("pgrep")
+        .args(["-f", VRCHAT_PROCESS])
+        .output()
+        .map(|out| out.status.success())
+        .unwrap_or(false)
+}
+
+/// Spawn the background thread that polls for the VRChat process
+pub fn spawn_process_watcher() -> thread::JoinHandle<()> {
+    START.get_or_init(Instant::now);
+    thread::spawn(move || {
+        let mut last_poll: Option<Instant> = None;
+        while !crate::state().exit.load(Ordering::SeqCst) {
+            // Only spend time on process scans while auto mode actually needs them
+            if !crate::state().osc_sending_auto.load(Ordering::SeqCst) {
+                last_poll = None;
+                thread::sleep(Duration::from_millis(200));
+                continue;
+            }
+            if last_poll.is_none_or(|t| t.elapsed() >= Duration::from_secs(PROCESS_POLL_SECS)) {
+                last_poll = Some(Instant::now());
+                let running = vrchat_process_running();
+                if running != PROCESS_RUNNING.swap(running, Ordering::SeqCst) && crate::is_debug_enabled_for(crate::DebugCategory::Osc) {
+                    log::debug!(target: "osc", "[VRCHAT] Process {}", if running { "started" } else { "stopped" });
+                }
+            }
+            thread::sleep(Duration::from_millis(200));
+        }
+    })
+}
+
+/// Result of a VRChat connectivity check (`check vrchat`)
+#[derive(Debug, Clone)]
+pub struct ConnectivityReport {
+    pub target: String,
+    /// UDP to the send target didn't bounce with an ICMP "port unreachable"
+    pub udp_ok: bool,
+    pub udp_detail: String,
+    /// OSC input port reported by VRChat's OSCQuery server, if one answered
+    pub oscquery_port: Option<u16>,
+}
+
+impl ConnectivityReport {
+    pub fn receiving(&self) -> bool {
+        self.udp_ok && self.oscquery_port.is_some_and(|port| self.target.ends_with(&format!(":{}", port)))
+    }
+
+    pub fn summary(&self) -> String {
+        if self.receiving() {
+            return format!("VRChat receiving OSC on {}", self.target);
+        }
+        if !self.udp_ok {
+            return format!("VRChat not receiving OSC: {} ({})", self.target, self.udp_detail);
+        }
+        match self.oscquery_port {
+            Some(port) => format!("VRChat not receiving OSC: it listens on port {}, sending to {}", port, self.target),
+            None => format!("VRChat not receiving OSC: no VRChat OSCQuery service found (sending to {})", self.target),
+        }
+    }
+}
+
+// Last check result for `status`
+static LAST_REPORT: Mutex<Option<ConnectivityReport>> = Mutex::new(None);
+
+/// Summary of the last connectivity check, if one ran
+pub fn last_check_summary() -> Option<String> {
+    LAST_REPORT.lock().ok().and_then(|g| g.as_ref().map(|r| r.summary()))
+}
+
+/// Send a harmless OSC message to the target and wait briefly for an ICMP error,
+/// which the OS reports on the connected socket as a refused/reset connection
+fn probe_udp(target: &str) -> Result<(), String> {
+    let addr: SocketAddr = target
+        .to_socket_addrs()
+        .map_err(|e| e.to_string())?
+        .next()
+        .ok_or_else(|| "address did not resolve".to_string())?;
+    let bind: SocketAddr = if addr.is_ipv4() { "0.0.0.0:0".parse().unwrap_or(addr) } else { "[::]:0".parse().unwrap_or(addr) };
+    let socket = UdpSocket::bind(bind).map_err(|e| e.to_string())?;
+    socket.connect(addr).map_err(|e| e.to_string())?;
+    let packet = rosc::OscPacket::Message(rosc::OscMessage { addr: "/transposer/ping".to_string(), args: vec![] });
+    let buf = rosc::encoder::encode(&packet).map_err(|e| e.to_string())?;
+    socket.send(&buf).map_err(|e| e.to_string())?;
+    socket.set_read_timeout(Some(Duration::from_millis(ICMP_WAIT_MS))).map_err(|e| e.to_string())?;
+    let mut reply = [0u8; 64];
+    match socket.recv(&mut reply) {
+        Err(e) if matches!(e.kind(), std::io::ErrorKind::ConnectionRefused | std::io::ErrorKind::ConnectionReset) => {
+            Err("port unreachable (ICMP)".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Ask a VRChat OSCQuery server for its OSC input port (`GET /?HOST_INFO`)
+fn query_host_info(addr: SocketAddr) -> Option<u16> {
+    let mut stream = TcpStream::connect_timeout(&addr, Duration::from_millis(500)).ok()?;
+    stream.set_read_timeout(Some(Duration::from_millis(500))).ok()?;
+    let request = format!("GET /?HOST_INFO HTTP/1.0\r\nHost: {}\r\n\r\n", addr);
+    stream.write_all(request.as_bytes()).ok()?;
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response);
+    let body = response.split("\r\n\r\n").nth(1)?;
+    let info: serde_json::Value = serde_json::from_str(body).ok()?;
+    info.get("OSC_PORT")?.as_u64().and_then(|p| u16::try_from(p).ok())
+}
+
+/// Browse for VRChat's OSCQuery service and return the OSC port it reports
+fn find_vrchat_oscquery() -> Option<u16> {
+    let daemon = ServiceDaemon::new().ok()?;
+    let receiver = daemon.browse(OSCQUERY_SERVICE_TYPE).ok()?;
+    let deadline = Instant::now() + Duration::from_millis(OSCQUERY_BROWSE_MS);
+    let mut port = None;
+    while let Ok(event) = receiver.recv_deadline(deadline) {
+        if let ServiceEvent::ServiceResolved(info) = event {
+            if !info.get_fullname().starts_with("VRChat-Client") {
+                continue;
+            }
+            let addrs = info.get_addresses();
+            if let Some(ip) = addrs.iter().find(|a| a.is_ipv4()).or_else(|| addrs.iter().next()) {
+                port = query_host_info(SocketAddr::new(*ip, info.get_port()));
+                if port.is_some() {
+                    break;
+                }
+            }
+        }
+    }
+    let _ = daemon.stop_browse(OSCQUERY_SERVICE_TYPE);
+    let _ = daemon.shutdown();
+    port
+}
+
+/// Run the connectivity check against the current send target and remember the result
+pub fn check_connectivity() -> ConnectivityReport {
+    let target = crate::osc_sender::current_target();
+    let (udp_ok, udp_detail) = match probe_udp(&target) {
+        Ok(()) => (true, "no ICMP error".to_string()),
+        Err(detail) => (false, detail),
+    };
+    let report = ConnectivityReport { target, udp_ok, udp_detail, oscquery_port: find_vrchat_oscquery() };
+    if let Ok(mut guard) = LAST_REPORT.lock() {
+        *guard = Some(report.clone());
+    }
+    report
+}
+
+/// Print a check result as a colored banner (green = receiving, red = not receiving)
+pub fn print_connectivity_banner(report: &ConnectivityReport) {
+    let color = if report.receiving() { Color::Green } else { Color::Red };
+    crate::io::console::print_colored(&[(Some(color), &report.summary())]);
+}
+
+/// Spawn the background thread that repeats the check every `osc.vrchat_check_interval_secs`
+/// while OSC sending is enabled; the banner is printed whenever the result changes
+pub fn spawn_connectivity_checker() -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let interval = crate::get_config().osc.vrchat_check_interval_secs;
+        let mut last_check: Option<Instant> = None;
+        let mut last_receiving: Option<bool> = None;
+        while !crate::state().exit.load(Ordering::SeqCst) {
+            let due = last_check.is_none_or(|t| t.elapsed() >= Duration::from_secs(interval));
+            if interval > 0 && due && crate::state().osc_sending.load(Ordering::SeqCst) {
+                last_check = Some(Instant::now());
+                let report = check_connectivity();
+                if last_receiving != Some(report.receiving()) {
+                    print_connectivity_banner(&report);
+                    last_receiving = Some(report.receiving());
+                }
+            }
+            thread::sleep(Duration::from_millis(200));
+        }
+    })
+}
@@ -0,0 +1,291 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+use crate::general::commands::{self, Outcome, Source};
+use crate::remote::websocket::{Received, WebSocket};
+
+/// Dashboard page; talks to `/ws` with console commands and gets the state pushed back
+const PAGE: &str = include_str!("web.html");
+// How often an open WebSocket is checked for state changes
+const PUSH_INTERVAL: Duration = Duration::from_millis(100);
+// Connections served at once (each has its own thread); more get 503 until one closes
+const MAX_CLIENTS: usize = 32;
+static CLIENTS: AtomicUsize = AtomicUsize::new(0);
+
+/// One slot of `MAX_CLIENTS`, given back when the connection's thread ends
+struct ClientSlot;
+
+impl ClientSlot {
+    fn take() -> Option<Self> {
+        CLIENTS.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| (n < MAX_CLIENTS).then_some(n + 1)).ok().map(|_| ClientSlot)
+    }
+}
+
+impl Drop for ClientSlot {
+    fn drop(&mut self) {
+        CLIENTS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Everything the dashboard shows
+pub fn state_json() -> serde_json::Value {
+    let config = crate::get_config();
+    let latency = crate::general::latency::snapshot();
+    serde_json::json!({
+        "transpose": crate::state().transpose.load(Ordering::SeqCst),
+        "min": config.transpose.min,
+        "max": config.transpose.max,
+        "octave": crate::state().octave.load(Ordering::SeqCst),
+        "osc_sending": crate::state().osc_sending.load(Ordering::SeqCst),
+        "osc_target": crate::osc_sender::current_target(),
+        "mqtt_enabled": crate::state().mqtt_enabled.load(Ordering::SeqCst),
+        "components": crate::general::check::components_json(),
+        "held_notes": crate::general::stats::held_notes().into_iter().map(crate::general::notes::display_name).collect::<Vec<_>>(),
+        "messages": latency.count,
+        "latency_ms": (latency.avg_us() / 10.0).round() / 100.0,
+    })
+}
+
+/// Run a console command sent by the dashboard; the reply is shown as a status line
+fn run_command(text: &str) -> serde_json::Value {
+    let result = match commands::parse(text) {
+        Some(Ok(command)) => commands::execute(command, Source::Web),
+        Some(Err(usage)) => Err(usage),
+        None => Err(format!("Unknown command '{}'", text.trim())),
+    };
+    match result {
+        Ok(Outcome::Transpose { requested, applied }) if requested != applied => {
+            serde_json::json!({ "message": format!("Transpose {:+} (clamped from {:+})", applied, requested) })
+        }
+        Ok(Outcome::Transpose { applied, .. }) => serde_json::json!({ "message": format!("Transpose {:+}", applied) }),
+        Ok(Outcome::Done(message)) => serde_json::json!({ "message": message }),
+        Ok(Outcome::Silent) => serde_json::json!({ "message": text.trim() }),
+        Err(e) => {
+            log::warn!(target: "app", "[WEB] {}", e);
+            serde_json::json!({ "error": e })
+        }
+    }
+}
+
+/// Push the state whenever it changes and run the commands the page sends, until it closes
+fn serve_dashboard(mut socket: WebSocket) {
+    let mut last_state = String::new();
+    while !crate::state().exit.load(Ordering::SeqCst) {
+        let state = state_json().to_string();
+        if state != last_state {
+            if socket.send_text(&state).is_err() {
+                return;
+            }
+            last_state = state;
+        }
+        for received in socket.poll() {
+            match received {
+                Received::Text(text) => {
+                    if socket.send_text(&run_command(&text).to_string()).is_err() {
+                        return;
+                    }
+                }
+                Received::Closed => return,
+            }
+        }
+    }
+    socket.close();
+}
+
+/// Decode `%XX` escapes and `+` in a query string value
+fn url_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => match text.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 2;
+                }
+                None => decoded.push(b'%'),
+            },
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Compare without returning early, so the response time doesn't tell how much of a guessed token matched
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Whether a request carries the `web_token` (if one is configured): as `Authorization: Bearer <token>`
+/// or, for browsers and WebSockets, as `?token=<token>` (URL-encoded)
+fn authorized(target: &str, authorization: Option<&str>) -> bool {
+    let config = crate::get_config();
+    let Some(token) = config.web_token.as_deref().filter(|t| !t.is_empty()) else { return true };
+    let bearer = authorization.and_then(|value| value.strip_prefix("Bearer ")).map(str::trim);
+    let query = target.split_once('?').and_then(|(_, query)| query.split('&').find_map(|pair| pair.strip_prefix("token="))).map(url_decode);
+    bearer.is_some_and(|bearer| constant_time_eq(bearer, token)) || query.is_some_and(|query| constant_time_eq(&query, token))
+}
+
+/// Whether a browser request comes from a page of this server: the `Origin` (if sent) has to
+/// name the same host and port as `Host`, so other sites can't drive the API from a visitor's browser
+fn same_origin(origin: Option<&str>, host: Option<&str>) -> bool {
+    let Some(origin) = origin else { return true };
+    let origin_host = origin.split_once("://").map(|(_, rest)| rest.trim_end_matches('/'));
+    matches!((origin_host, host), (Some(origin_host), Some(host)) if origin_host.eq_ignore_ascii_case(host.trim()))
+}
+
+fn handle(mut stream: TcpStream) {
+    stream.set_read_timeout(Some(Duration::from_millis(500))).ok();
+    let mut request = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < 8192 {
+        match stream.read(&mut chunk) {
+            Ok(0) | Err(_) => break,
+            Ok(size) => request.extend_from_slice(&chunk[..size]),
+        }
+    }
+    let header_end = request.windows(4).position(|w| w == b"\r\n\r\n").map_or(request.len(), |i| i + 4);
+    let mut body = request.split_off(header_end);
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.split_whitespace();
+    let (method, target) = (request_line.next().unwrap_or(""), request_line.next().unwrap_or(""));
+    let path = target.split('?').next().unwrap_or("");
+    let header = |name: &str| {
+        request.lines().find_map(|line| line.split_once(':').filter(|(key, _)| key.trim().eq_ignore_ascii_case(name)).map(|(_, value)| value.trim()))
+    };
+    // The page itself is public; it passes its `?token=` on to the WebSocket
+    if !matches!(path, "/" | "/index.html") && !authorized(target, header("Authorization")) {
+        respond(&mut stream, "401 Unauthorized", "application/json", "{\"ok\":false,\"error\":\"missing or wrong token\"}");
+        return;
+    }
+    // Commands and WebSockets only from this server's own page (or non-browser clients)
+    let upgrade = header("Sec-WebSocket-Key").is_some();
+    if (method == "POST" || upgrade) && !same_origin(header("Origin"), header("Host")) {
+        log::warn!(target: "app", "[WEB] Rejected cross-origin request from {}", header("Origin").unwrap_or(""));
+        respond(&mut stream, "403 Forbidden", "application/json", "{\"ok\":false,\"error\":\"cross-origin request\"}");
+        return;
+    }
+    if method == "POST" {
+        let length = header("Content-Length").and_then(|l| l.parse::<usize>().ok()).unwrap_or(0).min(8192);
+        while body.len() < length {
+            match stream.read(&mut chunk) {
+                Ok(0) | Err(_) => break,
+                Ok(size) => body.extend_from_slice(&chunk[..size]),
+            }
+        }
+        let (status, reply) = crate::remote::rest::post(path, String::from_utf8_lossy(&body).trim());
+        respond(&mut stream, status, "application/json", &reply.to_string());
+        return;
+    }
+    if method != "GET" {
+        respond(&mut stream, "405 Method Not Allowed", "text/plain", "GET or POST only\n");
+        return;
+    }
+    match (path, header("Sec-WebSocket-Key")) {
+        ("/ws", Some(key)) => match WebSocket::accept(stream, key, PUSH_INTERVAL) {
+            Ok(socket) => {
+                log::debug!(target: "app", "[WEB] Dashboard connected");
+                serve_dashboard(socket);
+            }
+            Err(e) => log::debug!(target: "app", "[WEB] WebSocket handshake failed: {}", e),
+        },
+        ("/api", Some(key)) => match WebSocket::accept(stream, key, crate::remote::event_api::TICK) {
+            Ok(socket) => {
+                log::debug!(target: "app", "[WEB] API client connected");
+                crate::remote::event_api::serve(socket);
+            }
+            Err(e) => log::debug!(target: "app", "[WEB] WebSocket handshake failed: {}", e),
+        },
+        ("/" | "/index.html", _) => respond(&mut stream, "200 OK", "text/html; charset=utf-8", PAGE),
+        ("/state", _) => respond(&mut stream, "200 OK", "application/json", &state_json().to_string()),
+        _ => respond(&mut stream, "404 Not Found", "text/plain", "not found\n"),
+    }
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &str) {
+    let response = format!(
+        "HTTP/1.0 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes());
+}
+
+/// Serve the live control dashboard on `addr` until exit: the page at `GET /`, the state
+/// at `GET /state`, its WebSocket at `/ws`, the event API WebSocket at `/api` and the
+/// REST control endpoints (`POST`, see `rest`); one thread per connection, at most
+/// `MAX_CLIENTS` at once. Refuses to start on a non-loopback address without a `web_token`.
+pub fn spawn_web_server(addr: &str) -> std::io::Result<thread::JoinHandle<()>> {
+    let listener = TcpListener::bind(addr)?;
+    let has_token = crate::get_config().web_token.as_deref().is_some_and(|t| !t.is_empty());
+    if !has_token && !listener.local_addr()?.ip().is_loopback() {
+        return Err(std::io::Error::new(ErrorKind::PermissionDenied, "web_token is required when web_ui is reachable from the network"));
+    }
+    // Non-blocking accept so the thread notices the exit flag
+    listener.set_nonblocking(true)?;
+    Ok(thread::spawn(move || {
+        while !crate::state().exit.load(Ordering::SeqCst) {
+            match listener.accept() {
+                Ok((mut stream, _)) => {
+                    let _ = stream.set_nonblocking(false);
+                    match ClientSlot::take() {
+                        Some(slot) => {
+                            thread::spawn(move || {
+                                let _slot = slot;
+                                handle(stream)
+                            });
+                        }
+                        None => {
+                            log::warn!(target: "app", "[WEB] {} connections open, refusing another", MAX_CLIENTS);
+                            respond(&mut stream, "503 Service Unavailable", "text/plain", "Too many connections\n");
+                        }
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(100)),
+                Err(e) => {
+                    log::error!(target: "app", "[WEB] Accept failed: {}", e);
+                    thread::sleep(Duration::from_millis(100));
+                }
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_tokens_are_url_decoded() {
+        assert_eq!(url_decode("a%2Bb%3D%3D"), "a+b==");
+        assert_eq!(url_decode("two+words"), "two words");
+        assert_eq!(url_decode("100%"), "100%");
+        assert_eq!(url_decode("%zz"), "%zz");
+    }
+
+    #[test]
+    fn tokens_compare_by_value_and_length() {
+        assert!(constant_time_eq("secret", "secret"));
+        assert!(!constant_time_eq("secret", "secreT"));
+        assert!(!constant_time_eq("secret", "secret2"));
+        assert!(!constant_time_eq("", "secret"));
+    }
+
+    #[test]
+    fn cross_origin_requests_are_rejected() {
+        assert!(same_origin(None, Some("pc:9080")));
+        assert!(same_origin(Some("http://pc:9080"), Some("pc:9080")));
+        assert!(same_origin(Some("https://PC:9080/"), Some("pc:9080")));
+        assert!(!same_origin(Some("http://evil.example"), Some("pc:9080")));
+        assert!(!same_origin(Some("http://pc:9081"), Some("pc:9080")));
+        assert!(!same_origin(Some("null"), Some("pc:9080")));
+        assert!(!same_origin(Some("http://pc:9080"), None));
+    }
+}
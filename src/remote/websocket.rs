@@ -0,0 +1,167 @@
+use std::io::{ErrorKind, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+use tungstenite::protocol::frame::coding::CloseCode;
+use tungstenite::protocol::{CloseFrame, Role, WebSocketConfig};
+use tungstenite::{Error, Message};
+
+// Largest client message (and frame) accepted; a bigger one closes the connection with 1009
+const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
+/// `Sec-WebSocket-Accept` for a client's `Sec-WebSocket-Key`
+pub fn accept_key(key: &str) -> String {
+    tungstenite::handshake::derive_accept_key(key.trim().as_bytes())
+}
+
+/// Server side of an upgraded connection: text messages out, complete client messages in.
+/// Framing, masking, fragmentation and pings are handled by tungstenite.
+pub struct WebSocket {
+    socket: tungstenite::WebSocket<TcpStream>,
+}
+
+/// What `WebSocket::poll` received
+pub enum Received {
+    Text(String),
+    Closed,
+}
+
+impl WebSocket {
+    /// Finish the handshake for a request carrying `Sec-WebSocket-Key`; `poll` waits at most `tick`
+    pub fn accept(mut stream: TcpStream, key: &str, tick: Duration) -> std::io::Result<Self> {
+        let response = format!(
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+            accept_key(key)
+        );
+        stream.write_all(response.as_bytes())?;
+        stream.set_read_timeout(Some(tick))?;
+        let config = WebSocketConfig::default().max_message_size(Some(MAX_MESSAGE_SIZE)).max_frame_size(Some(MAX_MESSAGE_SIZE));
+        Ok(WebSocket { socket: tungstenite::WebSocket::from_raw_socket(stream, Role::Server, Some(config)) })
+    }
+
+    pub fn send_text(&mut self, text: &str) -> std::io::Result<()> {
+        self.socket.send(Message::text(text)).map_err(|e| match e {
+            Error::Io(e) => e,
+            e => std::io::Error::new(ErrorKind::ConnectionAborted, e),
+        })
+    }
+
+    /// Wait up to one tick for client messages: text messages in order (pings are answered);
+    /// ends with `Closed` when the client closed, broke the protocol or the connection broke
+    pub fn poll(&mut self) -> Vec<Received> {
+        let mut received = Vec::new();
+        loop {
+            match self.socket.read() {
+                Ok(Message::Text(text)) => received.push(Received::Text(text.to_string())),
+                Ok(Message::Close(_)) => {
+                    // tungstenite queued the reply; send it before the connection is dropped
+                    let _ = self.socket.flush();
+                    received.push(Received::Closed);
+                    return received;
+                }
+                Ok(_) => {}
+                Err(Error::Io(e)) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => return received,
+                Err(e) => {
+                    let code = match e {
+                        Error::Capacity(_) => Some(CloseCode::Size),
+                        Error::Protocol(_) => Some(CloseCode::Protocol),
+                        Error::Utf8 => Some(CloseCode::Invalid),
+                        _ => None,
+                    };
+                    if let Some(code) = code {
+                        log::debug!(target: "app", "[WEB] Closing WebSocket: {}", e);
+                        let _ = self.socket.close(Some(CloseFrame { code, reason: "".into() }));
+                        let _ = self.socket.flush();
+                    }
+                    received.push(Received::Closed);
+                    return received;
+                }
+            }
+        }
+    }
+
+    pub fn close(&mut self) {
+        let _ = self.socket.close(None);
+        let _ = self.socket.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    #[test]
+    fn accept_key_matches_rfc_6455_example() {
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    /// A server socket and the raw client end of it, past the handshake
+    fn connected() -> (WebSocket, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let socket = WebSocket::accept(stream, "dGhlIHNhbXBsZSBub25jZQ==", Duration::from_millis(50)).unwrap();
+        let mut response = Vec::new();
+        while !response.ends_with(b"\r\n\r\n") {
+            let mut byte = [0u8];
+            client.read_exact(&mut byte).unwrap();
+            response.push(byte[0]);
+        }
+        (socket, client)
+    }
+
+    /// Masked client frame header with `opcode` for a payload of `len` bytes (mask 0)
+    fn client_header(fin: bool, opcode: u8, len: usize) -> Vec<u8> {
+        let mut header = vec![if fin { 0x80 } else { 0 } | opcode];
+        match len {
+            0..=125 => header.push(0x80 | len as u8),
+            126..=0xFFFF => {
+                header.push(0x80 | 126);
+                header.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            _ => {
+                header.push(0x80 | 127);
+                header.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        header.extend_from_slice(&[0; 4]);
+        header
+    }
+
+    /// Status code of the close frame the server sent
+    fn close_code(client: &mut TcpStream) -> u16 {
+        let mut frame = [0u8; 4];
+        client.read_exact(&mut frame).unwrap();
+        assert_eq!(frame[0], 0x88);
+        u16::from_be_bytes([frame[2], frame[3]])
+    }
+
+    #[test]
+    fn fragmented_text_is_joined() {
+        let (mut socket, mut client) = connected();
+        let mut frames = client_header(false, 1, 3);
+        frames.extend_from_slice(b"abc");
+        frames.extend(client_header(true, 0, 3));
+        frames.extend_from_slice(b"def");
+        client.write_all(&frames).unwrap();
+        let received = socket.poll();
+        assert!(matches!(received.as_slice(), [Received::Text(text)] if text == "abcdef"));
+    }
+
+    #[test]
+    fn oversized_message_closes_with_1009() {
+        let (mut socket, mut client) = connected();
+        client.write_all(&client_header(true, 1, MAX_MESSAGE_SIZE + 1)).unwrap();
+        assert!(matches!(socket.poll().as_slice(), [Received::Closed]));
+        assert_eq!(close_code(&mut client), 1009);
+    }
+
+    #[test]
+    fn unmasked_client_frame_is_rejected() {
+        let (mut socket, mut client) = connected();
+        client.write_all(&[0x81, 2, b'h', b'i']).unwrap();
+        assert!(matches!(socket.poll().as_slice(), [Received::Closed]));
+        assert_eq!(close_code(&mut client), 1002);
+    }
+}